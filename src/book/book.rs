@@ -1,33 +1,153 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::{self, Display, Formatter};
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 use super::summary::{parse_summary, Link, SectionNumber, Summary, SummaryItem};
 use crate::config::BuildConfig;
 use crate::errors::*;
+use crate::utils;
 
-/// Load a book into memory from its `src/` directory.
+/// Load a book into memory from its `src/` directory, using its `SUMMARY.md`.
+///
+/// Books with another summary file can be loaded with [`read_summary`] and
+/// [`load_book_from_summary`].
 pub fn load_book<P: AsRef<Path>>(src_dir: P, cfg: &BuildConfig) -> Result<Book> {
     let src_dir = src_dir.as_ref();
-    let summary_md = src_dir.join("SUMMARY.md");
+    let summary = read_summary(src_dir, Path::new("SUMMARY.md"))?;
+
+    load_book_from_summary(&summary, src_dir, cfg)
+}
 
+/// Reads and parses the summary of the given `src/` directory from
+/// `summary_file`, which is relative to it like `book.summary`.
+pub fn read_summary<P: AsRef<Path>>(src_dir: P, summary_file: &Path) -> Result<Summary> {
     let mut summary_content = String::new();
-    File::open(summary_md)
-        .with_context(|| "Couldn't open SUMMARY.md")?
+    File::open(src_dir.as_ref().join(summary_file))
+        .with_context(|| format!("Couldn't open {}", summary_file.display()))?
         .read_to_string(&mut summary_content)?;
 
-    let summary = parse_summary(&summary_content).with_context(|| "Summary parsing failed")?;
+    parse_summary(&summary_content).with_context(|| "Summary parsing failed")
+}
+
+/// Load a book into memory from an already parsed `Summary`, taking the
+/// chapter locations relative to `src_dir`.
+///
+/// This is what [`load_book`] does once it has parsed the `SUMMARY.md`, so
+/// tools can build a `Summary` in memory instead of writing it to disk.
+pub fn load_book_from_summary<P: AsRef<Path>>(
+    summary: &Summary,
+    src_dir: P,
+    cfg: &BuildConfig,
+) -> Result<Book> {
+    let src_dir = src_dir.as_ref();
 
     if cfg.create_missing {
-        create_missing(&src_dir, &summary).with_context(|| "Unable to create missing chapters")?;
+        create_missing(src_dir, summary).with_context(|| "Unable to create missing chapters")?;
+    }
+
+    load_book_from_disk(summary, src_dir)
+}
+
+/// Create the `Summary` describing the structure of a `Book`, the reverse of
+/// [`load_book_from_summary`].
+///
+/// The unnumbered chapters before the first numbered chapter or part title
+/// become the prefix chapters, those after the last numbered chapter and its
+/// separators the suffix chapters.
+pub fn summary_from_book(book: &Book) -> Summary {
+    let is_numbered = |item: &BookItem| match *item {
+        BookItem::Chapter(ref ch) => ch.number.is_some(),
+        BookItem::PartTitle(_) => true,
+        BookItem::Separator => false,
+    };
+    let start = book
+        .sections
+        .iter()
+        .position(is_numbered)
+        .unwrap_or(book.sections.len());
+    let mut end = book
+        .sections
+        .iter()
+        .rposition(is_numbered)
+        .map_or(start, |last| last + 1);
+    // Separators right after the numbered chapters are still part of them
+    while let Some(BookItem::Separator) = book.sections.get(end) {
+        end += 1;
+    }
+
+    Summary {
+        title: None,
+        prefix_chapters: summary_items(&book.sections[..start]),
+        numbered_chapters: summary_items(&book.sections[start..end]),
+        suffix_chapters: summary_items(&book.sections[end..]),
     }
+}
+
+fn summary_items(items: &[BookItem]) -> Vec<SummaryItem> {
+    items
+        .iter()
+        .map(|item| match *item {
+            BookItem::Chapter(ref ch) => SummaryItem::Link(Link {
+                name: ch.name.clone(),
+                location: ch.path.clone(),
+                number: ch.number.clone(),
+                nested_items: summary_items(&ch.sub_items),
+            }),
+            BookItem::Separator => SummaryItem::Separator,
+            BookItem::PartTitle(ref title) => SummaryItem::PartTitle(title.clone()),
+        })
+        .collect()
+}
+
+/// Load a book into memory from the contents of a `SUMMARY.md` and the
+/// sources of its chapters, keyed by their location in the summary.
+///
+/// Draft chapters don't need a source. A chapter which is missing from
+/// `chapters` is an error, unless `cfg.create_missing` is set, in which case
+/// it gets the same stub as [`create_missing_chapters`] would write to disk.
+pub fn load_book_from_memory(
+    summary_md: &str,
+    chapters: &HashMap<PathBuf, String>,
+    cfg: &BuildConfig,
+) -> Result<Book> {
+    let summary = parse_summary(summary_md).with_context(|| "Summary parsing failed")?;
+    let chapters = chapters
+        .iter()
+        .map(|(location, content)| (without_cur_dir(location), content.clone()))
+        .collect();
+
+    load_book_from_sources(&summary, &Sources::Memory(&chapters, cfg.create_missing))
+}
+
+/// Drops the `./` components of `path`, which the `SUMMARY.md` may or may not
+/// use for the same chapter.
+fn without_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| *c != Component::CurDir)
+        .collect()
+}
 
-    load_book_from_disk(&summary, src_dir)
+/// Create a stub file for every chapter listed in the `SUMMARY.md` of the
+/// given `src/` directory which doesn't exist yet, returning how many stubs
+/// were created. Existing files are never overwritten.
+pub fn create_missing_chapters<P: AsRef<Path>>(src_dir: P) -> Result<usize> {
+    let src_dir = src_dir.as_ref();
+    let summary = read_summary(src_dir, Path::new("SUMMARY.md"))?;
+
+    create_missing_chapters_from_summary(&summary, src_dir)
+}
+
+/// Like [`create_missing_chapters`], but for an already parsed `Summary`.
+pub fn create_missing_chapters_from_summary<P: AsRef<Path>>(
+    summary: &Summary,
+    src_dir: P,
+) -> Result<usize> {
+    create_missing(src_dir.as_ref(), summary).with_context(|| "Unable to create missing chapters")
 }
 
-fn create_missing(src_dir: &Path, summary: &Summary) -> Result<()> {
+fn create_missing(src_dir: &Path, summary: &Summary) -> Result<usize> {
     let mut items: Vec<_> = summary
         .prefix_chapters
         .iter()
@@ -35,6 +155,8 @@ fn create_missing(src_dir: &Path, summary: &Summary) -> Result<()> {
         .chain(summary.suffix_chapters.iter())
         .collect();
 
+    let mut created = 0;
+
     while !items.is_empty() {
         let next = items.pop().expect("already checked");
 
@@ -51,6 +173,7 @@ fn create_missing(src_dir: &Path, summary: &Summary) -> Result<()> {
 
                     let mut f = File::create(&filename)?;
                     writeln!(f, "# {}", link.name)?;
+                    created += 1;
                 }
             }
 
@@ -58,7 +181,7 @@ fn create_missing(src_dir: &Path, summary: &Summary) -> Result<()> {
         }
     }
 
-    Ok(())
+    Ok(created)
 }
 
 /// A dumb tree structure representing a book.
@@ -110,6 +233,46 @@ impl Book {
         self.sections.push(item.into());
         self
     }
+
+    /// Remove every chapter which isn't at or below `path`, relative to the
+    /// source directory. The chapters above the remaining ones are kept, so
+    /// they can still be navigated.
+    ///
+    /// Separators, and the titles of parts without any remaining chapters,
+    /// are removed as well. Returns the paths of the removed chapters.
+    pub fn retain_subtree<P: AsRef<Path>>(&mut self, path: P) -> Vec<PathBuf> {
+        let mut removed = Vec::new();
+        let sections = std::mem::replace(&mut self.sections, Vec::new());
+        self.sections = retain_subtree(sections, path.as_ref(), &mut removed);
+        removed
+    }
+}
+
+fn retain_subtree(items: Vec<BookItem>, path: &Path, removed: &mut Vec<PathBuf>) -> Vec<BookItem> {
+    let mut retained = Vec::new();
+    let mut part_title = None;
+
+    for item in items {
+        match item {
+            BookItem::Chapter(mut ch) => {
+                if !ch.path.as_ref().map_or(false, |p| p.starts_with(path)) {
+                    let sub_items = std::mem::replace(&mut ch.sub_items, Vec::new());
+                    ch.sub_items = retain_subtree(sub_items, path, removed);
+                    if ch.sub_items.is_empty() {
+                        removed.extend(ch.path);
+                        continue;
+                    }
+                }
+
+                retained.extend(part_title.take());
+                retained.push(BookItem::Chapter(ch));
+            }
+            BookItem::PartTitle(_) => part_title = Some(item),
+            BookItem::Separator => {}
+        }
+    }
+
+    retained
 }
 
 pub fn for_each_mut<'a, F, I>(func: &mut F, items: I)
@@ -128,6 +291,7 @@ where
 
 /// Enum representing any type of item which can be added to a book.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)] // Boxing the chapters would break the public API
 pub enum BookItem {
     /// A nested chapter.
     Chapter(Chapter),
@@ -157,8 +321,52 @@ pub struct Chapter {
     pub sub_items: Vec<BookItem>,
     /// The chapter's location, relative to the `SUMMARY.md` file.
     pub path: Option<PathBuf>,
+    /// The location of the chapter's source file, which stays the same when
+    /// a preprocessor changes the `path`, e.g. `README.md` for a chapter
+    /// rendered to `index.html`.
+    #[serde(default)]
+    pub source_path: Option<PathBuf>,
     /// An ordered list of the names of each chapter above this one, in the hierarchy.
     pub parent_names: Vec<String>,
+    /// The title of the chapter's page, if it should differ from the chapter's
+    /// name (e.g. when set with `{{#title}}`).
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Keywords describing the chapter, set with `tags` in its front matter.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A hash of the chapter's source markdown, see [`Chapter::content_hash`].
+    #[serde(default)]
+    pub source_hash: Option<String>,
+    /// Whether to use curly quotes in this chapter, set with `curly-quotes` in
+    /// its front matter. Overrides `output.html.curly-quotes` when set.
+    #[serde(default)]
+    pub curly_quotes: Option<bool>,
+    /// Where the lines of `content` came from, ordered by line, see
+    /// [`Chapter::source_of`].
+    ///
+    /// Loading a chapter maps its content to its source file, past any front
+    /// matter, and the `links` preprocessor maps the lines it includes to the
+    /// files they came from. Preprocessors which add or remove lines after it
+    /// don't update the mapping, so it is only approximate after them.
+    #[serde(default)]
+    pub source_lines: Vec<SourceLine>,
+}
+
+/// Where a run of lines of a chapter's content came from, see
+/// [`Chapter::source_lines`].
+///
+/// The run goes up to the next `SourceLine` of the chapter, and its lines
+/// follow the lines of `file` one to one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceLine {
+    /// The first line of the run in the chapter's content, numbered from 1.
+    pub line: usize,
+    /// The file the lines came from, relative to the book's source directory,
+    /// or its full path if it's outside of it.
+    pub file: PathBuf,
+    /// The line of `file` the run starts at, numbered from 1.
+    pub source_line: usize,
 }
 
 impl Chapter {
@@ -169,10 +377,18 @@ impl Chapter {
         path: P,
         parent_names: Vec<String>,
     ) -> Chapter {
+        let path = path.into();
         Chapter {
             name: name.to_string(),
+            source_hash: Some(hash_source(&content)),
             content,
-            path: Some(path.into()),
+            source_lines: vec![SourceLine {
+                line: 1,
+                file: path.clone(),
+                source_line: 1,
+            }],
+            source_path: Some(path.clone()),
+            path: Some(path),
             parent_names,
             ..Default::default()
         }
@@ -190,6 +406,29 @@ impl Chapter {
         }
     }
 
+    /// A stable hash of the chapter's source markdown, as it was when the
+    /// book was loaded, e.g. for caching pages or generating ETags.
+    ///
+    /// Draft chapters don't have one.
+    pub fn content_hash(&self) -> Option<&str> {
+        self.source_hash.as_ref().map(String::as_str)
+    }
+
+    /// The file and line, numbered from 1, the line `line` of the chapter's
+    /// content came from, if it is known.
+    ///
+    /// This lets checkers and renderers point at the line to fix when they
+    /// find a problem in the expanded content, even if it was included from
+    /// another file.
+    pub fn source_of(&self, line: usize) -> Option<(&Path, usize)> {
+        let run = self
+            .source_lines
+            .iter()
+            .rev()
+            .find(|run| run.line <= line)?;
+        Some((&run.file, run.source_line + line - run.line))
+    }
+
     /// Check if the chapter is a draft chapter, meaning it has no path to a source markdown file
     pub fn is_draft_chapter(&self) -> bool {
         match self.path {
@@ -205,8 +444,58 @@ impl Chapter {
 /// `SUMMARY.md` give the chapter locations relative to it.
 pub(crate) fn load_book_from_disk<P: AsRef<Path>>(summary: &Summary, src_dir: P) -> Result<Book> {
     debug!("Loading the book from disk");
-    let src_dir = src_dir.as_ref();
+    load_book_from_sources(summary, &Sources::Disk(src_dir.as_ref()))
+}
+
+/// Where the sources of a book's chapters are read from.
+enum Sources<'a> {
+    /// The book's source directory.
+    Disk(&'a Path),
+    /// The sources keyed by the chapters' locations, and whether missing
+    /// chapters get a stub.
+    Memory(&'a HashMap<PathBuf, String>, bool),
+}
+
+impl Sources<'_> {
+    /// Reads the source of the chapter `link` points to at `location`,
+    /// returning it along with the chapter's path relative to the book's
+    /// source directory.
+    fn read(&self, link: &Link, location: &Path) -> Result<(String, PathBuf)> {
+        match *self {
+            Sources::Disk(src_dir) => {
+                let full_path = if location.is_absolute() {
+                    location.to_path_buf()
+                } else {
+                    src_dir.join(location)
+                };
+
+                let mut f = File::open(&full_path)
+                    .with_context(|| format!("Chapter file not found, {}", location.display()))?;
+
+                let mut content = String::new();
+                f.read_to_string(&mut content).with_context(|| {
+                    format!("Unable to read \"{}\" ({})", link.name, full_path.display())
+                })?;
+
+                let stripped = full_path
+                    .strip_prefix(src_dir)
+                    .expect("Chapters are always inside a book");
+                Ok((content, stripped.to_path_buf()))
+            }
+            Sources::Memory(chapters, create_missing) => {
+                let location = without_cur_dir(location);
+                let content = match chapters.get(&location) {
+                    Some(content) => content.clone(),
+                    None if create_missing => format!("# {}\n", link.name),
+                    None => bail!("Chapter source not found, {}", location.display()),
+                };
+                Ok((content, location))
+            }
+        }
+    }
+}
 
+fn load_book_from_sources(summary: &Summary, sources: &Sources<'_>) -> Result<Book> {
     let prefix = summary.prefix_chapters.iter();
     let numbered = summary.numbered_chapters.iter();
     let suffix = summary.suffix_chapters.iter();
@@ -216,7 +505,7 @@ pub(crate) fn load_book_from_disk<P: AsRef<Path>>(summary: &Summary, src_dir: P)
     let mut chapters = Vec::new();
 
     for summary_item in summary_items {
-        let chapter = load_summary_item(summary_item, src_dir, Vec::new())?;
+        let chapter = load_summary_item(summary_item, sources, Vec::new())?;
         chapters.push(chapter);
     }
 
@@ -226,49 +515,64 @@ pub(crate) fn load_book_from_disk<P: AsRef<Path>>(summary: &Summary, src_dir: P)
     })
 }
 
-fn load_summary_item<P: AsRef<Path> + Clone>(
+fn load_summary_item(
     item: &SummaryItem,
-    src_dir: P,
+    sources: &Sources<'_>,
     parent_names: Vec<String>,
 ) -> Result<BookItem> {
     match item {
         SummaryItem::Separator => Ok(BookItem::Separator),
         SummaryItem::Link(ref link) => {
-            load_chapter(link, src_dir, parent_names).map(BookItem::Chapter)
+            load_chapter(link, sources, parent_names).map(BookItem::Chapter)
         }
         SummaryItem::PartTitle(title) => Ok(BookItem::PartTitle(title.clone())),
     }
 }
 
-fn load_chapter<P: AsRef<Path>>(
-    link: &Link,
-    src_dir: P,
-    parent_names: Vec<String>,
-) -> Result<Chapter> {
-    let src_dir = src_dir.as_ref();
+/// Hashes the source of a chapter, see [`utils::stable_hash`].
+fn hash_source(source: &str) -> String {
+    utils::stable_hash(&[source.as_bytes()])
+}
 
+fn load_chapter(link: &Link, sources: &Sources<'_>, parent_names: Vec<String>) -> Result<Chapter> {
     let mut ch = if let Some(ref link_location) = link.location {
         debug!("Loading {} ({})", link.name, link_location.display());
 
-        let location = if link_location.is_absolute() {
-            link_location.clone()
-        } else {
-            src_dir.join(link_location)
-        };
-
-        let mut f = File::open(&location)
-            .with_context(|| format!("Chapter file not found, {}", link_location.display()))?;
-
-        let mut content = String::new();
-        f.read_to_string(&mut content).with_context(|| {
-            format!("Unable to read \"{}\" ({})", link.name, location.display())
-        })?;
-
-        let stripped = location
-            .strip_prefix(&src_dir)
-            .expect("Chapters are always inside a book");
+        let (mut content, stripped) = sources.read(link, link_location)?;
+
+        let source_hash = hash_source(&content);
+        let mut tags = Vec::new();
+        let mut curly_quotes = None;
+        let mut front_matter_lines = 0;
+        if let Some((front_matter, rest)) = split_front_matter(&content) {
+            for (key, value) in front_matter {
+                match key {
+                    "tags" => tags = parse_front_matter_list(value),
+                    "curly-quotes" => match value.parse() {
+                        Ok(value) => curly_quotes = Some(value),
+                        Err(_) => warn!(
+                            "Ignoring \"curly-quotes: {}\" in {}, it must be true or false",
+                            value,
+                            link_location.display()
+                        ),
+                    },
+                    _ => warn!(
+                        "Ignoring unknown front matter key \"{}\" in {}",
+                        key,
+                        link_location.display()
+                    ),
+                }
+            }
+            front_matter_lines = content[..content.len() - rest.len()].matches('\n').count();
+            content = rest.to_string();
+        }
 
-        Chapter::new(&link.name, content, stripped, parent_names.clone())
+        let mut ch = Chapter::new(&link.name, content, stripped, parent_names.clone());
+        ch.tags = tags;
+        ch.curly_quotes = curly_quotes;
+        ch.source_hash = Some(source_hash);
+        ch.source_lines[0].source_line += front_matter_lines;
+        ch
     } else {
         Chapter::new_draft(&link.name, parent_names.clone())
     };
@@ -281,7 +585,7 @@ fn load_chapter<P: AsRef<Path>>(
     let sub_items = link
         .nested_items
         .iter()
-        .map(|i| load_summary_item(i, src_dir, sub_item_parents.clone()))
+        .map(|i| load_summary_item(i, sources, sub_item_parents.clone()))
         .collect::<Result<Vec<_>>>()?;
 
     ch.sub_items = sub_items;
@@ -289,6 +593,70 @@ fn load_chapter<P: AsRef<Path>>(
     Ok(ch)
 }
 
+/// Splits the front matter from the start of a chapter, returning its
+/// `key: value` pairs and the rest of the chapter.
+///
+/// The front matter is enclosed by lines containing only `---`, e.g.
+///
+/// ```text
+/// ---
+/// tags: [async, tokio]
+/// ---
+/// ```
+///
+/// As a chapter can also start with a horizontal rule, the block is only
+/// treated as front matter if every line in it is a `key: value` pair.
+pub(crate) fn split_front_matter(content: &str) -> Option<(Vec<(&str, &str)>, &str)> {
+    let mut lines = content.split('\n');
+    if lines.next()?.trim_end() != "---" {
+        return None;
+    }
+
+    let mut pairs = Vec::new();
+    let mut offset = content.find('\n')? + 1;
+    for line in lines {
+        offset = std::cmp::min(offset + line.len() + 1, content.len());
+        let line = line.trim();
+        if line == "---" {
+            return Some((pairs, &content[offset..]));
+        }
+        if line.is_empty() {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next()?.trim();
+        let value = parts.next()?.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return None;
+        }
+        pairs.push((key, value));
+    }
+
+    None
+}
+
+/// Parses a list in front matter, either written as `[a, b]` or as `a, b`.
+fn parse_front_matter_list(value: &str) -> Vec<String> {
+    let value = value.trim();
+    let value = if value.starts_with('[') && value.ends_with(']') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    };
+
+    value
+        .split(',')
+        .map(|item| item.trim().trim_matches(|c| c == '"' || c == '\''))
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 /// A depth-first iterator over the items in a book.
 ///
 /// # Note
@@ -389,15 +757,103 @@ And here is some \
             Vec::new(),
         );
 
-        let got = load_chapter(&link, temp_dir.path(), Vec::new()).unwrap();
+        let got = load_chapter(&link, &Sources::Disk(temp_dir.path()), Vec::new()).unwrap();
         assert_eq!(got, should_be);
     }
 
+    #[test]
+    fn front_matter_tags_are_loaded_and_stripped() {
+        let temp_dir = TempFileBuilder::new().prefix("book").tempdir().unwrap();
+        let content = "---\ntags: [async, \"tokio\"]\n---\n# Chapter 1\n";
+        fs::write(temp_dir.path().join("chapter_1.md"), content).unwrap();
+        let link = Link::new("Chapter 1", "chapter_1.md");
+
+        let got = load_chapter(&link, &Sources::Disk(temp_dir.path()), Vec::new()).unwrap();
+        assert_eq!(got.content, "# Chapter 1\n");
+        assert_eq!(got.tags, vec!["async", "tokio"]);
+        assert_eq!(got.source_of(1), Some((Path::new("chapter_1.md"), 4)));
+    }
+
+    #[test]
+    fn front_matter_can_override_curly_quotes() {
+        let temp_dir = TempFileBuilder::new().prefix("book").tempdir().unwrap();
+        let link = Link::new("Chapter 1", "chapter_1.md");
+
+        fs::write(temp_dir.path().join("chapter_1.md"), "# Chapter 1\n").unwrap();
+        let got = load_chapter(&link, &Sources::Disk(temp_dir.path()), Vec::new()).unwrap();
+        assert_eq!(got.curly_quotes, None);
+
+        let content = "---\ncurly-quotes: false\n---\n# Chapter 1\n";
+        fs::write(temp_dir.path().join("chapter_1.md"), content).unwrap();
+        let got = load_chapter(&link, &Sources::Disk(temp_dir.path()), Vec::new()).unwrap();
+        assert_eq!(got.curly_quotes, Some(false));
+
+        let content = "---\ncurly-quotes: sometimes\n---\n# Chapter 1\n";
+        fs::write(temp_dir.path().join("chapter_1.md"), content).unwrap();
+        let got = load_chapter(&link, &Sources::Disk(temp_dir.path()), Vec::new()).unwrap();
+        assert_eq!(got.curly_quotes, None);
+    }
+
+    #[test]
+    fn content_hashes_cover_the_whole_source_file() {
+        assert_eq!(hash_source(""), "cbf29ce484222325");
+        assert_eq!(hash_source("a"), "af63dc4c8601ec8c");
+
+        let temp_dir = TempFileBuilder::new().prefix("book").tempdir().unwrap();
+        let content = "---\ntags: [async]\n---\n# Chapter 1\n";
+        fs::write(temp_dir.path().join("chapter_1.md"), content).unwrap();
+        let link = Link::new("Chapter 1", "chapter_1.md");
+
+        let got = load_chapter(&link, &Sources::Disk(temp_dir.path()), Vec::new()).unwrap();
+        assert_eq!(got.content_hash(), Some(hash_source(content).as_str()));
+        assert_eq!(Chapter::new_draft("Draft", Vec::new()).content_hash(), None);
+    }
+
+    #[test]
+    fn horizontal_rules_arent_front_matter() {
+        let content = "---\nSome text\n---\n";
+        assert!(split_front_matter(content).is_none());
+        assert!(split_front_matter("---\ntags: a\n").is_none());
+        assert!(split_front_matter("# Heading\n---\ntags: a\n---\n").is_none());
+
+        let (pairs, rest) = split_front_matter("---\r\ntags: a, b\r\n---").unwrap();
+        assert_eq!(pairs, vec![("tags", "a, b")]);
+        assert_eq!(rest, "");
+        assert_eq!(parse_front_matter_list("a, b"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn create_missing_chapters_only_creates_new_files() {
+        let temp = TempFileBuilder::new().prefix("book").tempdir().unwrap();
+        let summary = "# Summary\n\n\
+                       - [Existing](./existing.md)\n\
+                       - [New Chapter](./new.md)\n    \
+                       - [Nested](./nested/deeper.md)\n\
+                       - [Draft]()\n";
+        fs::write(temp.path().join("SUMMARY.md"), summary).unwrap();
+        fs::write(temp.path().join("existing.md"), "Keep me").unwrap();
+
+        let created = create_missing_chapters(temp.path()).unwrap();
+        assert_eq!(created, 2);
+
+        assert_eq!(
+            fs::read_to_string(temp.path().join("existing.md")).unwrap(),
+            "Keep me"
+        );
+        assert_eq!(
+            fs::read_to_string(temp.path().join("new.md")).unwrap(),
+            "# New Chapter\n"
+        );
+        assert!(temp.path().join("nested/deeper.md").exists());
+
+        assert_eq!(create_missing_chapters(temp.path()).unwrap(), 0);
+    }
+
     #[test]
     fn cant_load_a_nonexistent_chapter() {
         let link = Link::new("Chapter 1", "/foo/bar/baz.md");
 
-        let got = load_chapter(&link, "", Vec::new());
+        let got = load_chapter(&link, &Sources::Disk(Path::new("")), Vec::new());
         assert!(got.is_err());
     }
 
@@ -410,23 +866,48 @@ And here is some \
             content: String::from("Hello World!"),
             number: Some(SectionNumber(vec![1, 2])),
             path: Some(PathBuf::from("second.md")),
+            source_path: Some(PathBuf::from("second.md")),
             parent_names: vec![String::from("Chapter 1")],
             sub_items: Vec::new(),
+            title: None,
+            tags: Vec::new(),
+            source_hash: Some(hash_source("Hello World!")),
+            curly_quotes: None,
+            source_lines: vec![SourceLine {
+                line: 1,
+                file: PathBuf::from("second.md"),
+                source_line: 1,
+            }],
         };
         let should_be = BookItem::Chapter(Chapter {
             name: String::from("Chapter 1"),
             content: String::from(DUMMY_SRC),
             number: None,
             path: Some(PathBuf::from("chapter_1.md")),
+            source_path: Some(PathBuf::from("chapter_1.md")),
             parent_names: Vec::new(),
             sub_items: vec![
                 BookItem::Chapter(nested.clone()),
                 BookItem::Separator,
                 BookItem::Chapter(nested.clone()),
             ],
+            title: None,
+            tags: Vec::new(),
+            source_hash: Some(hash_source(DUMMY_SRC)),
+            curly_quotes: None,
+            source_lines: vec![SourceLine {
+                line: 1,
+                file: PathBuf::from("chapter_1.md"),
+                source_line: 1,
+            }],
         });
 
-        let got = load_summary_item(&SummaryItem::Link(root), temp.path(), Vec::new()).unwrap();
+        let got = load_summary_item(
+            &SummaryItem::Link(root),
+            &Sources::Disk(temp.path()),
+            Vec::new(),
+        )
+        .unwrap();
         assert_eq!(got, should_be);
     }
 
@@ -442,6 +923,13 @@ And here is some \
                 name: String::from("Chapter 1"),
                 content: String::from(DUMMY_SRC),
                 path: Some(PathBuf::from("chapter_1.md")),
+                source_path: Some(PathBuf::from("chapter_1.md")),
+                source_hash: Some(hash_source(DUMMY_SRC)),
+                source_lines: vec![SourceLine {
+                    line: 1,
+                    file: PathBuf::from("chapter_1.md"),
+                    source_line: 1,
+                }],
                 ..Default::default()
             })],
             ..Default::default()
@@ -452,6 +940,86 @@ And here is some \
         assert_eq!(got, should_be);
     }
 
+    #[test]
+    fn books_can_be_loaded_from_a_summary_and_back() {
+        let temp = TempFileBuilder::new().prefix("book").tempdir().unwrap();
+        let summary = parse_summary(
+            "[Intro](intro.md)\n\n\
+             # Guide\n\n\
+             - [Setup](guide/setup.md)\n    \
+             - [Linux](guide/linux.md)\n\
+             - [Draft]()\n\n\
+             ---\n\n\
+             [Credits](credits.md)\n",
+        )
+        .unwrap();
+        let cfg = BuildConfig {
+            create_missing: true,
+            ..Default::default()
+        };
+
+        let book = load_book_from_summary(&summary, temp.path(), &cfg).unwrap();
+
+        assert!(temp.path().join("guide/linux.md").exists());
+        let names: Vec<_> = book
+            .iter()
+            .filter_map(|item| match *item {
+                BookItem::Chapter(ref ch) => Some(ch.name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["Intro", "Setup", "Linux", "Draft", "Credits"]);
+        assert_eq!(summary_from_book(&book), summary);
+    }
+
+    #[test]
+    fn books_can_be_loaded_from_memory() {
+        let summary_md =
+            "# Summary\n\n- [First](./first.md)\n    - [Nested](first/nested.md)\n- [Draft]()\n";
+        let mut chapters = HashMap::new();
+        chapters.insert(PathBuf::from("first.md"), String::from("# First\n"));
+        let cfg = BuildConfig {
+            create_missing: false,
+            ..Default::default()
+        };
+
+        let err = load_book_from_memory(summary_md, &chapters, &cfg).unwrap_err();
+        assert_eq!(err.to_string(), "Chapter source not found, first/nested.md");
+
+        chapters.insert(PathBuf::from("./first/nested.md"), String::from("Nested"));
+        let book = load_book_from_memory(summary_md, &chapters, &cfg).unwrap();
+        let contents: Vec<_> = book
+            .iter()
+            .filter_map(|item| match *item {
+                BookItem::Chapter(ref ch) => Some((ch.path.clone(), ch.content.as_str())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                (Some(PathBuf::from("first.md")), "# First\n"),
+                (Some(PathBuf::from("first/nested.md")), "Nested"),
+                (None, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_chapters_in_memory_can_get_a_stub() {
+        let chapters = HashMap::new();
+        let cfg = BuildConfig {
+            create_missing: true,
+            ..Default::default()
+        };
+
+        let book = load_book_from_memory("- [First](first.md)\n", &chapters, &cfg).unwrap();
+        match book.sections[0] {
+            BookItem::Chapter(ref ch) => assert_eq!(ch.content, "# First\n"),
+            ref other => panic!("Expected a chapter, got {:?}", other),
+        }
+    }
+
     #[test]
     fn book_iter_iterates_over_sequential_items() {
         let book = Book {
@@ -482,6 +1050,7 @@ And here is some \
                     content: String::from(DUMMY_SRC),
                     number: None,
                     path: Some(PathBuf::from("Chapter_1/index.md")),
+                    source_path: Some(PathBuf::from("Chapter_1/index.md")),
                     parent_names: Vec::new(),
                     sub_items: vec![
                         BookItem::Chapter(Chapter::new(
@@ -498,6 +1067,11 @@ And here is some \
                             Vec::new(),
                         )),
                     ],
+                    title: None,
+                    tags: Vec::new(),
+                    source_hash: None,
+                    curly_quotes: None,
+                    source_lines: Vec::new(),
                 }),
                 BookItem::Separator,
             ],
@@ -534,6 +1108,7 @@ And here is some \
                     content: String::from(DUMMY_SRC),
                     number: None,
                     path: Some(PathBuf::from("Chapter_1/index.md")),
+                    source_path: Some(PathBuf::from("Chapter_1/index.md")),
                     parent_names: Vec::new(),
                     sub_items: vec![
                         BookItem::Chapter(Chapter::new(
@@ -550,6 +1125,11 @@ And here is some \
                             Vec::new(),
                         )),
                     ],
+                    title: None,
+                    tags: Vec::new(),
+                    source_hash: None,
+                    curly_quotes: None,
+                    source_lines: Vec::new(),
                 }),
                 BookItem::Separator,
             ],
@@ -599,4 +1179,59 @@ And here is some \
         let got = load_book_from_disk(&summary, temp.path());
         assert!(got.is_err());
     }
+
+    #[test]
+    fn only_the_subtree_and_its_ancestors_are_retained() {
+        let chapter = |name: &str, path: &str, sub_items: Vec<BookItem>| {
+            let mut ch = Chapter::new(name, String::new(), path, Vec::new());
+            ch.sub_items = sub_items;
+            BookItem::Chapter(ch)
+        };
+        let mut book = Book::new();
+        book.sections = vec![
+            chapter("Intro", "intro.md", vec![]),
+            BookItem::Separator,
+            BookItem::PartTitle(String::from("Guide")),
+            chapter(
+                "Guide",
+                "guide.md",
+                vec![
+                    chapter(
+                        "Setup",
+                        "guide/setup.md",
+                        vec![chapter("Linux", "guide/setup/linux.md", vec![])],
+                    ),
+                    chapter("Usage", "usage.md", vec![]),
+                ],
+            ),
+            BookItem::PartTitle(String::from("Reference")),
+            chapter("Reference", "reference.md", vec![]),
+        ];
+
+        let removed = book.retain_subtree("guide/");
+
+        assert_eq!(
+            removed,
+            vec![
+                PathBuf::from("intro.md"),
+                PathBuf::from("usage.md"),
+                PathBuf::from("reference.md"),
+            ]
+        );
+        assert_eq!(
+            book.sections,
+            vec![
+                BookItem::PartTitle(String::from("Guide")),
+                chapter(
+                    "Guide",
+                    "guide.md",
+                    vec![chapter(
+                        "Setup",
+                        "guide/setup.md",
+                        vec![chapter("Linux", "guide/setup/linux.md", vec![])],
+                    )],
+                ),
+            ]
+        );
+    }
 }
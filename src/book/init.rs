@@ -1,6 +1,6 @@
 use std::fs::{self, File};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::MDBook;
 use crate::config::Config;
@@ -14,6 +14,7 @@ pub struct BookBuilder {
     create_gitignore: bool,
     config: Config,
     copy_theme: bool,
+    template: Option<Template>,
 }
 
 impl BookBuilder {
@@ -25,6 +26,7 @@ impl BookBuilder {
             create_gitignore: false,
             config: Config::default(),
             copy_theme: false,
+            template: None,
         }
     }
 
@@ -46,6 +48,17 @@ impl BookBuilder {
         self
     }
 
+    /// Create the book's contents from a template instead of the default
+    /// stub chapter. If the template contains a `book.toml`, it replaces the
+    /// current `Config`.
+    pub fn with_template(&mut self, template: Template) -> Result<&mut BookBuilder> {
+        if let Some(config) = template.config()? {
+            self.config = config;
+        }
+        self.template = Some(template);
+        Ok(self)
+    }
+
     /// Should we create a `.gitignore` file?
     pub fn create_gitignore(&mut self, create: bool) -> &mut BookBuilder {
         self.create_gitignore = create;
@@ -55,7 +68,8 @@ impl BookBuilder {
     /// Generate the actual book. This will:
     ///
     /// - Create the directory structure.
-    /// - Stub out some dummy chapters and the `SUMMARY.md`.
+    /// - Stub out some dummy chapters and the `SUMMARY.md`, or copy them from
+    ///   the template (if applicable).
     /// - Create a `.gitignore` (if applicable)
     /// - Create a themes directory and populate it (if applicable)
     /// - Generate a `book.toml` file,
@@ -171,13 +185,20 @@ impl BookBuilder {
     }
 
     fn create_stub_files(&self) -> Result<()> {
+        if let Some(ref template) = self.template {
+            debug!("Creating book contents from the {} template", template);
+            return template.copy_to(&self.root);
+        }
+
         debug!("Creating example book contents");
         let src_dir = self.root.join(&self.config.book.src);
 
-        let summary = src_dir.join("SUMMARY.md");
+        let summary = src_dir.join(&self.config.book.summary);
         if !summary.exists() {
             trace!("No summary found creating stub summary and chapter_1.md.");
-            let mut f = File::create(&summary).with_context(|| "Unable to create SUMMARY.md")?;
+            let mut f = File::create(&summary).with_context(|| {
+                format!("Unable to create {}", self.config.book.summary.display())
+            })?;
             writeln!(f, "# Summary")?;
             writeln!(f)?;
             writeln!(f, "- [Chapter 1](./chapter_1.md)")?;
@@ -205,3 +226,184 @@ impl BookBuilder {
         Ok(())
     }
 }
+
+/// The source of the initial contents of a book created by the
+/// [`BookBuilder`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Template {
+    /// One of the templates bundled with mdBook, see
+    /// [`Template::builtin_names`].
+    Builtin(&'static str),
+    /// A directory laid out like a book, whose files are copied into the new
+    /// book.
+    Directory(PathBuf),
+}
+
+type TemplateFiles = &'static [(&'static str, &'static [u8])];
+
+macro_rules! template_files {
+    ($name:literal: $($file:literal),* $(,)?) => {
+        &[$(($file, include_bytes!(concat!("templates/", $name, "/", $file)))),*]
+    };
+}
+
+static BUILTIN_TEMPLATES: &[(&str, TemplateFiles)] = &[
+    (
+        "api-docs",
+        template_files!("api-docs":
+            "book.toml",
+            "src/SUMMARY.md",
+            "src/overview.md",
+            "src/quick_start.md",
+            "src/authentication.md",
+            "src/reference/endpoints.md",
+            "src/reference/types.md",
+            "src/reference/errors.md",
+            "src/changelog.md",
+        ),
+    ),
+    (
+        "blog",
+        template_files!("blog":
+            "book.toml",
+            "src/SUMMARY.md",
+            "src/about.md",
+            "src/posts/hello_world.md",
+        ),
+    ),
+    (
+        "tutorial",
+        template_files!("tutorial":
+            "book.toml",
+            "src/SUMMARY.md",
+            "src/introduction.md",
+            "src/getting_started.md",
+            "src/installation.md",
+            "src/hello_world.md",
+            "src/exercises.md",
+            "src/next_steps.md",
+        ),
+    ),
+];
+
+impl Template {
+    /// The names of the templates bundled with mdBook.
+    pub fn builtin_names() -> Vec<&'static str> {
+        BUILTIN_TEMPLATES.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Look up a template by the name of a bundled template or the path to a
+    /// template directory.
+    pub fn find(name_or_path: &str) -> Result<Template> {
+        let builtin = BUILTIN_TEMPLATES
+            .iter()
+            .find(|(name, _)| *name == name_or_path);
+        if let Some((name, _)) = builtin {
+            return Ok(Template::Builtin(name));
+        }
+
+        let path = PathBuf::from(name_or_path);
+        if path.is_dir() {
+            Ok(Template::Directory(path))
+        } else {
+            bail!(
+                "\"{}\" is neither a directory nor a builtin template (available: {})",
+                name_or_path,
+                Template::builtin_names().join(", ")
+            )
+        }
+    }
+
+    /// The configuration in the template's `book.toml`, if it has one.
+    pub fn config(&self) -> Result<Option<Config>> {
+        match self {
+            Template::Builtin(_) => match self.builtin_file("book.toml") {
+                Some(contents) => {
+                    let contents = std::str::from_utf8(contents)?;
+                    Ok(Some(contents.parse()?))
+                }
+                None => Ok(None),
+            },
+            Template::Directory(dir) => {
+                let book_toml = dir.join("book.toml");
+                if book_toml.exists() {
+                    Ok(Some(Config::from_disk(book_toml)?))
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+
+    fn builtin_files(&self) -> TemplateFiles {
+        match self {
+            Template::Builtin(name) => BUILTIN_TEMPLATES
+                .iter()
+                .find(|(builtin, _)| builtin == name)
+                .map(|(_, files)| *files)
+                .unwrap_or(&[]),
+            Template::Directory(_) => &[],
+        }
+    }
+
+    fn builtin_file(&self, path: &str) -> Option<&'static [u8]> {
+        self.builtin_files()
+            .iter()
+            .find(|(file, _)| *file == path)
+            .map(|(_, contents)| *contents)
+    }
+
+    /// Copy the template's files (except `book.toml`, which is generated from
+    /// the config) into `root`, leaving files which already exist untouched.
+    fn copy_to(&self, root: &Path) -> Result<()> {
+        match self {
+            Template::Builtin(_) => {
+                for (file, contents) in self.builtin_files() {
+                    if *file != "book.toml" {
+                        write_if_missing(&root.join(file), contents)?;
+                    }
+                }
+                Ok(())
+            }
+            Template::Directory(dir) => copy_template_dir(dir, dir, root),
+        }
+    }
+}
+
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Template::Builtin(name) => write!(f, "{}", name),
+            Template::Directory(dir) => write!(f, "{}", dir.display()),
+        }
+    }
+}
+
+fn copy_template_dir(template_root: &Path, dir: &Path, root: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let relative = path.strip_prefix(template_root)?;
+
+        if path.is_dir() {
+            copy_template_dir(template_root, &path, root)?;
+        } else if relative != Path::new("book.toml") {
+            write_if_missing(&root.join(relative), &fs::read(&path)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_if_missing(path: &Path, contents: &[u8]) -> Result<()> {
+    if path.exists() {
+        trace!("{} already exists, not overwriting it", path.display());
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    File::create(path)
+        .with_context(|| format!("Unable to create {}", path.display()))?
+        .write_all(contents)?;
+    Ok(())
+}
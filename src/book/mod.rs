@@ -9,13 +9,24 @@
 mod book;
 mod init;
 mod summary;
+mod test_report;
 
-pub use self::book::{load_book, Book, BookItem, BookItems, Chapter};
-pub use self::init::BookBuilder;
+pub(crate) use self::book::split_front_matter;
+pub use self::book::{
+    create_missing_chapters, create_missing_chapters_from_summary, load_book,
+    load_book_from_memory, load_book_from_summary, read_summary, summary_from_book, Book, BookItem,
+    BookItems, Chapter, SourceLine,
+};
+pub use self::init::{BookBuilder, Template};
 pub use self::summary::{parse_summary, Link, SectionNumber, Summary, SummaryItem};
+pub use self::test_report::{BlockReport, ChapterReport, TestOutcome, TestReport};
 
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use std::collections::HashMap;
+use std::env;
 use std::io::Write;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
 use std::process::Command;
 use std::string::ToString;
 use tempfile::Builder as TempFileBuilder;
@@ -23,9 +34,13 @@ use toml::Value;
 
 use crate::errors::*;
 use crate::preprocess::{
-    CmdPreprocessor, IndexPreprocessor, LinkPreprocessor, Preprocessor, PreprocessorContext,
+    AnchorsPreprocessor, CmdPreprocessor, DetailsPreprocessor, EquationsPreprocessor,
+    FiguresPreprocessor, GlossaryPreprocessor, IndexPreprocessor, LinkPreprocessor, Preprocessor,
+    PreprocessorContext, SplitHeadingsPreprocessor, VariablesPreprocessor, WikilinksPreprocessor,
+};
+use crate::renderer::{
+    CmdRenderer, HtmlHandlebars, JsonRenderer, MarkdownRenderer, RenderContext, Renderer,
 };
-use crate::renderer::{CmdRenderer, HtmlHandlebars, MarkdownRenderer, RenderContext, Renderer};
 use crate::utils;
 
 use crate::config::{Config, RustEdition};
@@ -83,7 +98,8 @@ impl MDBook {
         let root = book_root.into();
 
         let src_dir = root.join(&config.book.src);
-        let book = book::load_book(&src_dir, &config.build)?;
+        let summary = book::read_summary(&src_dir, &config.book.summary)?;
+        let book = book::load_book_from_summary(&summary, &src_dir, &config.build)?;
 
         let renderers = determine_renderers(&config);
         let preprocessors = determine_preprocessors(&config)?;
@@ -120,6 +136,35 @@ impl MDBook {
         })
     }
 
+    /// Load a book entirely from memory, from the contents of its `SUMMARY.md`
+    /// and the sources of its chapters keyed by their location in the summary.
+    ///
+    /// The book is still built into, and may include files from, the
+    /// directories of `config` below `book_root`. A chapter missing from
+    /// `chapters` is an error, unless `build.create-missing` is set, in which
+    /// case it only gets a title. Draft chapters need no source.
+    pub fn from_summary_str<P: Into<PathBuf>>(
+        book_root: P,
+        summary_md: &str,
+        chapters: HashMap<PathBuf, String>,
+        config: Config,
+    ) -> Result<MDBook> {
+        let root = book_root.into();
+
+        let book = book::load_book_from_memory(summary_md, &chapters, &config.build)?;
+
+        let renderers = determine_renderers(&config);
+        let preprocessors = determine_preprocessors(&config)?;
+
+        Ok(MDBook {
+            root,
+            config,
+            book,
+            renderers,
+            preprocessors,
+        })
+    }
+
     /// Returns a flat depth-first iterator over the elements of the book,
     /// it returns an [BookItem enum](bookitem.html):
     /// `(section: String, bookitem: &BookItem)`
@@ -170,11 +215,162 @@ impl MDBook {
     }
 
     /// Tells the renderer to build our book and put it in the build directory.
+    ///
+    /// All the renderers run, even when one of them fails, so the others
+    /// still write their output. Failing renderers are an error, unless they
+    /// are marked with `output.<name>.optional`.
     pub fn build(&self) -> Result<()> {
+        let renderers: Vec<_> = self.renderers.iter().map(|r| &**r).collect();
+        self.run_renderers(&renderers)
+    }
+
+    /// Build the book with only the renderer called `name`, e.g. to skip slow
+    /// backends while working on a book.
+    ///
+    /// This is [`build_renderers`](#method.build_renderers) with one name.
+    pub fn build_renderer(&self, name: &str) -> Result<()> {
+        self.build_renderers(&[name])
+    }
+
+    /// Build the book with only the renderers called `names`.
+    ///
+    /// Nothing is built if the book has no renderer with one of the names.
+    /// Otherwise this works like [`MDBook::build`], with the renderers in the
+    /// order of the book's.
+    ///
+    /// The preprocessors still run once for each renderer, not once for all
+    /// of them, because which of them run depends on the renderer through
+    /// `preprocessor.<name>.renderers` and their `supports_renderer`.
+    pub fn build_renderers(&self, names: &[&str]) -> Result<()> {
+        for name in names {
+            if !self.renderers.iter().any(|r| r.name() == *name) {
+                let names: Vec<_> = self.renderers.iter().map(|r| r.name()).collect();
+                bail!(
+                    "The book has no `{}` renderer, its renderers are: {}",
+                    name,
+                    names.join(", ")
+                );
+            }
+        }
+
+        let renderers: Vec<_> = self
+            .renderers
+            .iter()
+            .filter(|r| names.contains(&r.name()))
+            .map(|r| &**r)
+            .collect();
+        self.run_renderers(&renderers)
+    }
+
+    /// Runs all the `renderers`, and fails if any of them did which isn't
+    /// optional.
+    fn run_renderers(&self, renderers: &[&dyn Renderer]) -> Result<()> {
         info!("Book building has started");
 
+        self.check_renderers()?;
+
+        let mut built = Vec::new();
+        let mut failures = Vec::new();
+        for renderer in renderers {
+            let name = renderer.name();
+            match self.execute_build_process(*renderer) {
+                Ok(()) => built.push(name),
+                Err(e) if self.renderer_is_optional(name) => {
+                    warn!("The optional \"{}\" backend failed: {}", name, e);
+                    for cause in e.chain().skip(1) {
+                        warn!("\tCaused By: {}", cause);
+                    }
+                }
+                Err(e) => {
+                    failures.push((name, e.context(format!("The \"{}\" backend failed", name))))
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+        if !built.is_empty() {
+            info!("Still built the {} backends", built.join(", "));
+        }
+
+        if failures.len() == 1 {
+            let (_, e) = failures.pop().expect("There is one failure");
+            return Err(e);
+        }
+        for (_, e) in &failures {
+            utils::log_backtrace(e);
+        }
+        let names: Vec<_> = failures.iter().map(|(name, _)| *name).collect();
+        bail!("The {} backends failed", names.join(", "))
+    }
+
+    /// Whether the failure of the renderer called `name` doesn't fail the
+    /// build, set with `output.<name>.optional`.
+    fn renderer_is_optional(&self, name: &str) -> bool {
+        match self.config.get(&format!("output.{}.optional", name)) {
+            Some(Value::Boolean(optional)) => *optional,
+            _ => false,
+        }
+    }
+
+    /// Only build the chapters at or below `path`, relative to the source
+    /// directory, along with the chapters above them so they can still be
+    /// navigated. This is handy for reviewing one part of a huge book.
+    ///
+    /// Links from the remaining chapters to the removed ones are left alone,
+    /// but reported because they are dead in the built book.
+    pub fn only<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path: PathBuf = path
+            .as_ref()
+            .components()
+            .filter(|c| c != &Component::CurDir)
+            .collect();
+        let removed = self.book.retain_subtree(&path);
+
+        if self.book.sections.is_empty() {
+            bail!("The book has no chapters in `{}`", path.display());
+        }
+
+        for item in self.book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                for link in links_to(ch, &removed) {
+                    warn!(
+                        "\"{}\" links to `{}`, which isn't part of this build",
+                        ch.name, link
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Make sure that no two renderers share a name or write into each
+    /// other's output directory, so they can't overwrite each other's output.
+    fn check_renderers(&self) -> Result<()> {
+        let mut seen: Vec<(&str, PathBuf)> = Vec::new();
+
         for renderer in &self.renderers {
-            self.execute_build_process(&**renderer)?;
+            let name = renderer.name();
+            let build_dir = self.build_dir_for(name);
+
+            for (other_name, other_dir) in &seen {
+                if *other_name == name {
+                    bail!("More than one renderer is named `{}`", name);
+                }
+                if build_dir.starts_with(other_dir) || other_dir.starts_with(&build_dir) {
+                    bail!(
+                        "The output directories of the `{}` and `{}` renderers overlap ({} and {})",
+                        other_name,
+                        name,
+                        other_dir.display(),
+                        build_dir.display()
+                    );
+                }
+            }
+
+            seen.push((name, build_dir));
         }
 
         Ok(())
@@ -182,12 +378,35 @@ impl MDBook {
 
     /// Run the entire build process for a particular `Renderer`.
     pub fn execute_build_process(&self, renderer: &dyn Renderer) -> Result<()> {
+        let preprocessed_book = self.preprocess_for(renderer.name())?;
+
+        info!("Running the {} backend", renderer.name());
+        self.render(&preprocessed_book, renderer)?;
+
+        Ok(())
+    }
+
+    /// Run the preprocessors on the book without rendering it, and return the
+    /// preprocessed book, e.g. to hand the markdown with its includes expanded
+    /// to another tool.
+    ///
+    /// The preprocessors run as they do for the `markdown` renderer, which
+    /// leaves out the ones only meant for HTML, like `index`, so the chapters
+    /// keep the paths of their markdown files. Use
+    /// [`preprocess_for`](#method.preprocess_for) to run them as they do for
+    /// another renderer.
+    pub fn preprocess(&self) -> Result<Book> {
+        self.preprocess_for("markdown")
+    }
+
+    /// Like [`preprocess`](#method.preprocess), but runs the preprocessors as
+    /// they do for the renderer called `renderer`. It is checked against
+    /// `preprocessor.<name>.renderers` and passed to the preprocessors'
+    /// `supports_renderer`, and is the `renderer` of their context.
+    pub fn preprocess_for(&self, renderer: &str) -> Result<Book> {
         let mut preprocessed_book = self.book.clone();
-        let preprocess_ctx = PreprocessorContext::new(
-            self.root.clone(),
-            self.config.clone(),
-            renderer.name().to_string(),
-        );
+        let preprocess_ctx =
+            PreprocessorContext::new(self.root.clone(), self.config.clone(), renderer.to_string());
 
         for preprocessor in &self.preprocessors {
             if preprocessor_should_run(&**preprocessor, renderer, &self.config) {
@@ -196,10 +415,7 @@ impl MDBook {
             }
         }
 
-        info!("Running the {} backend", renderer.name());
-        self.render(&preprocessed_book, renderer)?;
-
-        Ok(())
+        Ok(preprocessed_book)
     }
 
     fn render(&self, preprocessed_book: &Book, renderer: &dyn Renderer) -> Result<()> {
@@ -226,6 +442,25 @@ impl MDBook {
         self
     }
 
+    /// Replace the renderer with the same name as `renderer`, keeping its
+    /// place among the other renderers, or add it if there is none.
+    ///
+    /// For example, an [`HtmlHandlebars`] with additional helpers can take
+    /// the place of the HTML renderer configured in `book.toml`.
+    ///
+    /// [`HtmlHandlebars`]: ../renderer/struct.HtmlHandlebars.html
+    pub fn replace_renderer<R: Renderer + 'static>(&mut self, renderer: R) -> &mut Self {
+        match self
+            .renderers
+            .iter()
+            .position(|r| r.name() == renderer.name())
+        {
+            Some(i) => self.renderers[i] = Box::new(renderer),
+            None => self.renderers.push(Box::new(renderer)),
+        }
+        self
+    }
+
     /// Register a [`Preprocessor`](../preprocess/trait.Preprocessor.html) to be used when rendering the book.
     pub fn with_preprocessor<P: Preprocessor + 'static>(&mut self, preprocessor: P) -> &mut Self {
         self.preprocessors.push(Box::new(preprocessor));
@@ -234,6 +469,22 @@ impl MDBook {
 
     /// Run `rustdoc` tests on the book, linking against the provided libraries.
     pub fn test(&mut self, library_paths: Vec<&str>) -> Result<()> {
+        self.test_all(library_paths, false)?.check()
+    }
+
+    /// Like [`test`](#method.test), but only compiles the code blocks without
+    /// running any of them, as if they all had the `no_run` attribute.
+    pub fn test_no_run(&mut self, library_paths: Vec<&str>) -> Result<()> {
+        self.test_all(library_paths, true)?.check()
+    }
+
+    /// Run `rustdoc` tests on the book, linking against the provided libraries,
+    /// and return the outcome of every tested code block.
+    ///
+    /// Failing code blocks don't make this return an error, see
+    /// [`TestReport::check`](struct.TestReport.html#method.check) for that.
+    /// With `no_run` the code blocks are only compiled.
+    pub fn test_all(&mut self, library_paths: Vec<&str>, no_run: bool) -> Result<TestReport> {
         let library_args: Vec<&str> = (0..library_paths.len())
             .map(|_| "-L")
             .zip(library_paths.into_iter())
@@ -250,12 +501,17 @@ impl MDBook {
         // Index Preprocessor is disabled so that chapter paths continue to point to the
         // actual markdown files.
 
+        let mut report = TestReport::default();
         for item in book.iter() {
             if let BookItem::Chapter(ref ch) = *item {
                 let chapter_path = match ch.path {
                     Some(ref path) if !path.as_os_str().is_empty() => path,
                     _ => continue,
                 };
+                // Chapters written in HTML have no code blocks for rustdoc
+                if chapter_path.extension().map_or(false, |ext| ext == "html") {
+                    continue;
+                }
 
                 let path = self.source_dir().join(&chapter_path);
                 info!("Testing file: {:?}", path);
@@ -263,9 +519,10 @@ impl MDBook {
                 // write preprocessed file to tempdir
                 let path = temp_dir.path().join(&chapter_path);
                 let mut tmpf = utils::fs::create_file(&path)?;
-                tmpf.write_all(ch.content.as_bytes())?;
+                let (content, blocks) = prepare_for_rustdoc(&ch.content, no_run);
+                tmpf.write_all(content.as_bytes())?;
 
-                let mut cmd = Command::new("rustdoc");
+                let mut cmd = Command::new(self.rustdoc());
                 cmd.arg(&path).arg("--test").args(&library_args);
 
                 if let Some(edition) = self.config.rust.edition {
@@ -281,17 +538,33 @@ impl MDBook {
 
                 let output = cmd.output()?;
 
-                if !output.status.success() {
-                    bail!(
-                        "rustdoc returned an error:\n\
-                        \n--- stdout\n{}\n--- stderr\n{}",
-                        String::from_utf8_lossy(&output.stdout),
-                        String::from_utf8_lossy(&output.stderr)
-                    );
-                }
+                let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+                report.chapters.push(ChapterReport {
+                    name: ch.name.clone(),
+                    path: chapter_path.clone(),
+                    blocks: test_report::parse_rustdoc_output(&stdout, &blocks),
+                    success: output.status.success(),
+                    stdout,
+                    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                });
             }
         }
-        Ok(())
+
+        Ok(report)
+    }
+
+    /// The `rustdoc` binary to test the book with: `rust.rustdoc`, relative to
+    /// the book's root, the `MDBOOK_RUSTDOC` environment variable, or else
+    /// the `rustdoc` on the `PATH`.
+    fn rustdoc(&self) -> PathBuf {
+        match self.config.rust.rustdoc {
+            // A bare name like `rustdoc-nightly` is looked up on the `PATH`
+            Some(ref path) if path.components().count() > 1 => self.root.join(path),
+            Some(ref path) => path.clone(),
+            None => env::var_os("MDBOOK_RUSTDOC")
+                .filter(|path| !path.is_empty())
+                .map_or_else(|| PathBuf::from("rustdoc"), PathBuf::from),
+        }
     }
 
     /// The logic for determining where a backend should put its build
@@ -342,6 +615,129 @@ impl MDBook {
     }
 }
 
+/// The links of a chapter which point to any of the `pages`.
+fn links_to(ch: &Chapter, pages: &[PathBuf]) -> Vec<String> {
+    let dir = match ch.path {
+        Some(ref path) => path.parent().unwrap_or_else(|| Path::new("")),
+        None => return Vec::new(),
+    };
+
+    let mut links = Vec::new();
+    for event in utils::new_cmark_parser(&ch.content) {
+        let dest = match event {
+            Event::Start(Tag::Link(_, dest, _)) => dest,
+            _ => continue,
+        };
+        if dest.starts_with('#') || dest.contains("://") {
+            continue;
+        }
+
+        // Resolve the link relative to the chapter, ignoring the anchor
+        let file = dest.split('#').next().unwrap();
+        let mut target = PathBuf::new();
+        for component in dir.join(file).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    target.pop();
+                }
+                other => target.push(other),
+            }
+        }
+
+        if pages.contains(&target) {
+            links.push(dest.to_string());
+        }
+    }
+    links
+}
+
+/// A code block of a chapter, as it is passed to rustdoc.
+#[derive(Debug, PartialEq)]
+struct TestedBlock {
+    /// The line the code block starts on, which rustdoc names its test after.
+    line: usize,
+    /// The attributes of the code block, like `rust,should_panic`.
+    info: String,
+}
+
+/// Prepares the `content` of a chapter for rustdoc, returning it along with
+/// its code blocks.
+///
+/// The attributes of fenced code blocks are separated by commas, just like
+/// in the HTML output, so rustdoc reads `rust, compile_fail` the same way as
+/// `rust,compile_fail`. Code blocks with the `mdbook-test-skip` attribute
+/// are turned into plain text, so rustdoc doesn't test them. With `no_run`,
+/// every code block gets the `no_run` attribute, which means indented code
+/// blocks are fenced.
+fn prepare_for_rustdoc(content: &str, no_run: bool) -> (String, Vec<TestedBlock>) {
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    for (event, range) in utils::new_cmark_parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let mut attributes: Vec<_> = info
+                    .split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|attribute| !attribute.is_empty())
+                    .collect();
+                if utils::skips_tests(&info) {
+                    attributes = vec!["text"];
+                } else if no_run && !attributes.contains(&"no_run") {
+                    attributes.push("no_run");
+                }
+                let cleaned = attributes.join(",");
+                if cleaned == *info {
+                    continue;
+                }
+
+                let line = content[range.start..].lines().next().unwrap_or("");
+                let fence_start = line.find(|c| c == '`' || c == '~').unwrap_or(0);
+                let fence_char = line[fence_start..].chars().next().unwrap_or('`');
+                let fence_len = line[fence_start..]
+                    .chars()
+                    .take_while(|&c| c == fence_char)
+                    .count();
+                let fence_end = range.start + fence_start + fence_len;
+                edits.push((fence_end..range.start + line.len(), cleaned));
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)) if no_run => {
+                // The fences go where the code's indentation starts
+                let line_start = content[..range.start].rfind('\n').map_or(0, |i| i + 1);
+                let prefix = &content[line_start..range.start];
+                let prefix = &prefix[..prefix.len().saturating_sub(4)];
+                edits.push((line_start..line_start, format!("{}```no_run\n", prefix)));
+                let newline = if content[..range.end].ends_with('\n') {
+                    ""
+                } else {
+                    "\n"
+                };
+                edits.push((range.end..range.end, format!("{}{}```\n", newline, prefix)));
+            }
+            _ => {}
+        }
+    }
+
+    let mut result = content.to_string();
+    for (range, replacement) in edits.into_iter().rev() {
+        result.replace_range(range, &replacement);
+    }
+
+    let blocks = utils::new_cmark_parser(&result)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            let info = match event {
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => info.to_string(),
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)) => String::new(),
+                _ => return None,
+            };
+            Some(TestedBlock {
+                line: result[..range.start].matches('\n').count() + 1,
+                info,
+            })
+        })
+        .collect();
+    (result, blocks)
+}
+
 /// Look at the `Config` and try to figure out what renderers to use.
 fn determine_renderers(config: &Config) -> Vec<Box<dyn Renderer>> {
     let mut renderers = Vec::new();
@@ -352,6 +748,8 @@ fn determine_renderers(config: &Config) -> Vec<Box<dyn Renderer>> {
                 Box::new(HtmlHandlebars::new()) as Box<dyn Renderer>
             } else if key == "markdown" {
                 Box::new(MarkdownRenderer::new()) as Box<dyn Renderer>
+            } else if key == "json" {
+                Box::new(JsonRenderer::new()) as Box<dyn Renderer>
             } else {
                 interpret_custom_renderer(key, table)
             }
@@ -391,10 +789,19 @@ fn determine_preprocessors(config: &Config) -> Result<Vec<Box<dyn Preprocessor>>
             match key.as_ref() {
                 "links" => preprocessors.push(Box::new(LinkPreprocessor::new())),
                 "index" => preprocessors.push(Box::new(IndexPreprocessor::new())),
+                "split-headings" => preprocessors.push(Box::new(SplitHeadingsPreprocessor::new())),
+                "wikilinks" => preprocessors.push(Box::new(WikilinksPreprocessor::new())),
+                "glossary" => preprocessors.push(Box::new(GlossaryPreprocessor::new())),
+                "figures" => preprocessors.push(Box::new(FiguresPreprocessor::new())),
+                "equations" => preprocessors.push(Box::new(EquationsPreprocessor::new())),
+                "variables" => preprocessors.push(Box::new(VariablesPreprocessor::new())),
+                "details" => preprocessors.push(Box::new(DetailsPreprocessor::new())),
+                "anchors" => preprocessors.push(Box::new(AnchorsPreprocessor::new())),
                 name => preprocessors.push(interpret_custom_preprocessor(
                     name,
                     &preprocessor_table[name],
-                )),
+                    config,
+                )?),
             }
         }
     }
@@ -402,14 +809,24 @@ fn determine_preprocessors(config: &Config) -> Result<Vec<Box<dyn Preprocessor>>
     Ok(preprocessors)
 }
 
-fn interpret_custom_preprocessor(key: &str, table: &Value) -> Box<CmdPreprocessor> {
+fn interpret_custom_preprocessor(
+    key: &str,
+    table: &Value,
+    config: &Config,
+) -> Result<Box<CmdPreprocessor>> {
     let command = table
         .get("command")
         .and_then(Value::as_str)
         .map(ToString::to_string)
         .unwrap_or_else(|| format!("mdbook-{}", key));
 
-    Box::new(CmdPreprocessor::new(key.to_string(), command))
+    let mut preprocessor = CmdPreprocessor::new(key.to_string(), command);
+    let timeout = utils::process::timeout(config, &format!("preprocessor.{}.timeout", key))?;
+    if let Some(timeout) = timeout {
+        preprocessor = preprocessor.with_timeout(timeout);
+    }
+
+    Ok(Box::new(preprocessor))
 }
 
 fn interpret_custom_renderer(key: &str, table: &Value) -> Box<CmdRenderer> {
@@ -433,16 +850,15 @@ fn interpret_custom_renderer(key: &str, table: &Value) -> Box<CmdRenderer> {
 /// default preprocessors always run if they support the renderer.
 fn preprocessor_should_run(
     preprocessor: &dyn Preprocessor,
-    renderer: &dyn Renderer,
+    renderer_name: &str,
     cfg: &Config,
 ) -> bool {
     // default preprocessors should be run by default (if supported)
     if cfg.build.use_default_preprocessors && is_default_preprocessor(preprocessor) {
-        return preprocessor.supports_renderer(renderer.name());
+        return preprocessor.supports_renderer(renderer_name);
     }
 
     let key = format!("preprocessor.{}.renderers", preprocessor.name());
-    let renderer_name = renderer.name();
 
     if let Some(Value::Array(ref explicit_renderers)) = cfg.get(&key) {
         return explicit_renderers
@@ -557,7 +973,8 @@ mod tests {
 
         // make sure the `preprocessor.random` table exists
         let random = cfg.get_preprocessor("random").unwrap();
-        let random = interpret_custom_preprocessor("random", &Value::Table(random.clone()));
+        let random =
+            interpret_custom_preprocessor("random", &Value::Table(random.clone()), &cfg).unwrap();
 
         assert_eq!(random.cmd(), "python random.py");
     }
@@ -583,10 +1000,76 @@ mod tests {
         let html_renderer = HtmlHandlebars::default();
         let pre = LinkPreprocessor::new();
 
-        let should_run = preprocessor_should_run(&pre, &html_renderer, &cfg);
+        let should_run = preprocessor_should_run(&pre, html_renderer.name(), &cfg);
         assert!(should_run);
     }
 
+    #[test]
+    fn code_blocks_are_prepared_for_rustdoc() {
+        let content = "```rust,mdbook-test-skip\nlet x = y;\n```\n\n\
+                       - item\n\n  ~~~~ rust mdbook-test-skip\n  z\n  ~~~~\n\n\
+                       ```rust, should_panic\npanic!();\n```\n\n    indented\n";
+        let (content, blocks) = prepare_for_rustdoc(content, false);
+        assert_eq!(
+            content,
+            "```text\nlet x = y;\n```\n\n\
+             - item\n\n  ~~~~text\n  z\n  ~~~~\n\n\
+             ```rust,should_panic\npanic!();\n```\n\n    indented\n"
+        );
+        let block = |line, info: &str| TestedBlock {
+            line,
+            info: info.to_string(),
+        };
+        assert_eq!(
+            blocks,
+            vec![
+                block(1, "text"),
+                block(7, "text"),
+                block(11, "rust,should_panic"),
+                block(15, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_run_is_forced_on_every_code_block() {
+        let content = "```rust,should_panic\npanic!();\n```\n\n\
+                       ```no_run\nloop {}\n```\n\n\
+                       > quote\n>\n>     indented();\n\n\
+                       - item\n\n      listed();\n";
+        let (content, blocks) = prepare_for_rustdoc(content, true);
+        assert_eq!(
+            content,
+            "```rust,should_panic,no_run\npanic!();\n```\n\n\
+             ```no_run\nloop {}\n```\n\n\
+             > quote\n>\n> ```no_run\n>     indented();\n> ```\n\n\
+             - item\n\n  ```no_run\n      listed();\n  ```\n"
+        );
+        let infos: Vec<_> = blocks.iter().map(|block| block.info.as_str()).collect();
+        assert_eq!(
+            infos,
+            vec!["rust,should_panic,no_run", "no_run", "no_run", "no_run"]
+        );
+        assert_eq!(blocks[2].line, 11);
+    }
+
+    #[test]
+    fn the_index_preprocessor_only_runs_for_html() {
+        let cfg = Config::default();
+        let pre = IndexPreprocessor::new();
+
+        assert!(preprocessor_should_run(
+            &pre,
+            HtmlHandlebars::new().name(),
+            &cfg
+        ));
+        assert!(!preprocessor_should_run(
+            &pre,
+            MarkdownRenderer::new().name(),
+            &cfg
+        ));
+    }
+
     struct BoolPreprocessor(bool);
     impl Preprocessor for BoolPreprocessor {
         fn name(&self) -> &str {
@@ -608,11 +1091,11 @@ mod tests {
         let html = HtmlHandlebars::new();
 
         let should_be = true;
-        let got = preprocessor_should_run(&BoolPreprocessor(should_be), &html, &cfg);
+        let got = preprocessor_should_run(&BoolPreprocessor(should_be), html.name(), &cfg);
         assert_eq!(got, should_be);
 
         let should_be = false;
-        let got = preprocessor_should_run(&BoolPreprocessor(should_be), &html, &cfg);
+        let got = preprocessor_should_run(&BoolPreprocessor(should_be), html.name(), &cfg);
         assert_eq!(got, should_be);
     }
 }
@@ -0,0 +1,274 @@
+use regex::Regex;
+use std::path::PathBuf;
+
+use super::TestedBlock;
+use crate::errors::*;
+
+/// The results of testing the code blocks of a book with rustdoc, as
+/// returned by [`MDBook::test_all`](struct.MDBook.html#method.test_all).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestReport {
+    /// The tested chapters, in the order of the book.
+    pub chapters: Vec<ChapterReport>,
+}
+
+impl TestReport {
+    /// Whether the tests of all chapters passed.
+    pub fn passed(&self) -> bool {
+        self.chapters.iter().all(ChapterReport::passed)
+    }
+
+    /// Returns an error describing every failed code block, if any of the
+    /// chapters failed.
+    pub fn check(&self) -> Result<()> {
+        let failures: Vec<_> = self
+            .chapters
+            .iter()
+            .filter(|chapter| !chapter.passed())
+            .map(ChapterReport::failure_message)
+            .collect();
+        if !failures.is_empty() {
+            bail!(failures.join("\n\n"));
+        }
+        Ok(())
+    }
+}
+
+/// The results of testing the code blocks of a single chapter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterReport {
+    /// The chapter's name.
+    pub name: String,
+    /// The chapter's location, relative to the `SUMMARY.md` file.
+    pub path: PathBuf,
+    /// The code blocks rustdoc tested, in the order of the chapter.
+    pub blocks: Vec<BlockReport>,
+    /// Whether rustdoc exited successfully. It can fail without any failed
+    /// code blocks, e.g. when it can't read the chapter.
+    pub success: bool,
+    /// What rustdoc wrote to its stdout.
+    pub stdout: String,
+    /// What rustdoc wrote to its stderr.
+    pub stderr: String,
+}
+
+impl ChapterReport {
+    /// Whether the tests of the chapter passed.
+    pub fn passed(&self) -> bool {
+        self.success && self.blocks.iter().all(|block| !block.outcome.is_failure())
+    }
+
+    fn failure_message(&self) -> String {
+        let mut message = format!("rustdoc returned an error for {}:\n", self.path.display());
+        for block in self
+            .blocks
+            .iter()
+            .filter(|block| block.outcome.is_failure())
+        {
+            let reason = match block.outcome {
+                TestOutcome::Failed { ref output } => output
+                    .lines()
+                    .map(str::trim)
+                    .find(|line| !line.is_empty())
+                    .unwrap_or_default(),
+                _ => "",
+            };
+            message.push_str(&format!(
+                "\n  code block {} (line {}, `{}`) failed: {}",
+                block
+                    .index
+                    .map_or_else(|| String::from("?"), |index| index.to_string()),
+                block.line,
+                block.attributes,
+                reason
+            ));
+        }
+        message.push_str(&format!(
+            "\n\n--- stdout\n{}\n--- stderr\n{}",
+            self.stdout, self.stderr
+        ));
+        message
+    }
+}
+
+/// The result of testing a single code block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockReport {
+    /// The number of the code block in its chapter, starting at 1, if it
+    /// could be matched with rustdoc's output.
+    pub index: Option<usize>,
+    /// The line of the chapter, as passed to rustdoc, the code block starts on.
+    pub line: usize,
+    /// The attributes of the code block, like `rust,should_panic`.
+    pub attributes: String,
+    /// How the test of the code block went.
+    pub outcome: TestOutcome,
+}
+
+/// How the test of a code block went.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestOutcome {
+    /// The code block was only compiled, because of `no_run` or
+    /// `compile_fail`, and did what it was supposed to.
+    Compiled,
+    /// The code block was compiled and run successfully.
+    Ran,
+    /// The code block wasn't tested, because of `ignore`.
+    Ignored,
+    /// The test of the code block failed.
+    Failed {
+        /// What rustdoc wrote about the failure, e.g. the compiler errors.
+        output: String,
+    },
+}
+
+impl TestOutcome {
+    /// Whether the test of the code block failed.
+    pub fn is_failure(&self) -> bool {
+        match *self {
+            TestOutcome::Failed { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Reads the results of the tests of the `blocks` of a chapter from rustdoc's
+/// `stdout`.
+pub(super) fn parse_rustdoc_output(stdout: &str, blocks: &[TestedBlock]) -> Vec<BlockReport> {
+    lazy_static! {
+        static ref RESULT: Regex =
+            Regex::new(r"^test .* \(line (\d+)\)( - [a-z ]+)? \.\.\. (ok|FAILED|ignored)$")
+                .unwrap();
+        static ref SECTION: Regex =
+            Regex::new(r"^---- .* \(line (\d+)\)(?: - [a-z ]+)? stdout ----$").unwrap();
+    }
+
+    let mut reports: Vec<BlockReport> = stdout
+        .lines()
+        .filter_map(|line| RESULT.captures(line))
+        .filter_map(|caps| {
+            let line: usize = caps[1].parse().ok()?;
+            let outcome = match (&caps[3], caps.get(2)) {
+                ("ok", Some(_)) => TestOutcome::Compiled,
+                ("ok", None) => TestOutcome::Ran,
+                ("ignored", _) => TestOutcome::Ignored,
+                _ => TestOutcome::Failed {
+                    output: String::new(),
+                },
+            };
+            let index = blocks.iter().position(|block| block.line == line);
+            Some(BlockReport {
+                index: index.map(|i| i + 1),
+                line,
+                attributes: index.map_or_else(String::new, |i| blocks[i].info.clone()),
+                outcome,
+            })
+        })
+        .collect();
+
+    let mut current = None;
+    for line in stdout.lines() {
+        if let Some(caps) = SECTION.captures(line) {
+            current = caps[1].parse::<usize>().ok();
+        } else if line.starts_with("failures:") {
+            current = None;
+        } else if let Some(test_line) = current {
+            let report = reports.iter_mut().find(|report| report.line == test_line);
+            if let Some(BlockReport {
+                outcome: TestOutcome::Failed { ref mut output },
+                ..
+            }) = report
+            {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+    for report in &mut reports {
+        if let TestOutcome::Failed { ref mut output } = report.outcome {
+            *output = output.trim().to_string();
+        }
+    }
+
+    reports.sort_by_key(|report| report.line);
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcomes_are_read_from_rustdoc_output() {
+        let stdout = "\n\
+running 4 tests
+test /tmp/x/intro.md - Intro (line 11) - compile fail ... FAILED
+test /tmp/x/intro.md - Intro (line 3) ... ok
+test /tmp/x/intro.md - Intro (line 15) - compile ... ok
+test /tmp/x/intro.md - Intro (line 7) ... FAILED
+test /tmp/x/intro.md - Intro (line 19) ... ignored
+
+failures:
+
+---- /tmp/x/intro.md - Intro (line 11) stdout ----
+Test compiled successfully, but it's marked `compile_fail`.
+---- /tmp/x/intro.md - Intro (line 7) stdout ----
+
+error[E0308]: mismatched types
+ --> /tmp/x/intro.md:8:14
+
+failures:
+    /tmp/x/intro.md - Intro (line 11)
+    /tmp/x/intro.md - Intro (line 7)
+
+test result: FAILED. 2 passed; 2 failed; 1 ignored; 0 measured; 0 filtered out
+";
+        let blocks: Vec<_> = [
+            (3, "rust"),
+            (7, ""),
+            (11, "rust,compile_fail"),
+            (19, "ignore"),
+        ]
+        .iter()
+        .map(|&(line, info)| TestedBlock {
+            line,
+            info: info.to_string(),
+        })
+        .collect();
+
+        let report = |index, line, attributes: &str, outcome| BlockReport {
+            index,
+            line,
+            attributes: attributes.to_string(),
+            outcome,
+        };
+        assert_eq!(
+            parse_rustdoc_output(stdout, &blocks),
+            vec![
+                report(Some(1), 3, "rust", TestOutcome::Ran),
+                report(
+                    Some(2),
+                    7,
+                    "",
+                    TestOutcome::Failed {
+                        output: String::from(
+                            "error[E0308]: mismatched types\n --> /tmp/x/intro.md:8:14"
+                        )
+                    }
+                ),
+                report(
+                    Some(3),
+                    11,
+                    "rust,compile_fail",
+                    TestOutcome::Failed {
+                        output: String::from(
+                            "Test compiled successfully, but it's marked `compile_fail`."
+                        )
+                    }
+                ),
+                report(None, 15, "", TestOutcome::Compiled),
+                report(Some(4), 19, "ignore", TestOutcome::Ignored),
+            ]
+        );
+    }
+}
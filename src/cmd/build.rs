@@ -1,5 +1,5 @@
 use crate::{get_book_dir, open};
-use clap::{App, ArgMatches, SubCommand};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use mdbook::errors::Result;
 use mdbook::MDBook;
 
@@ -17,6 +17,23 @@ pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
              (Defaults to the Current Directory when omitted)'",
         )
         .arg_from_usage("-o, --open 'Opens the compiled book in a web browser'")
+        .arg_from_usage(
+            "--only=[path] 'Only builds the chapters at or below this path{n}\
+             Relative to the book's source directory, e.g. `guide/`.'",
+        )
+        .arg(
+            Arg::with_name("renderer")
+                .short("r")
+                .long("renderer")
+                .value_name("renderer")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Only builds the book with this renderer, can be given more than once{n}\
+                     (Defaults to all the renderers of the book when omitted)",
+                ),
+        )
 }
 
 // Build command implementation
@@ -28,7 +45,14 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
         book.config.build.build_dir = dest_dir.into();
     }
 
-    book.build()?;
+    if let Some(path) = args.value_of("only") {
+        book.only(path)?;
+    }
+
+    match args.values_of("renderer") {
+        Some(renderers) => book.build_renderers(&renderers.collect::<Vec<_>>())?,
+        None => book.build()?,
+    }
 
     if args.is_present("open") {
         // FIXME: What's the right behaviour if we don't use the HTML renderer?
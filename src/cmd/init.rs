@@ -1,10 +1,12 @@
 use crate::get_book_dir;
 use clap::{App, ArgMatches, SubCommand};
-use mdbook::config;
+use mdbook::book::{self, Template};
+use mdbook::config::Config;
 use mdbook::errors::Result;
 use mdbook::MDBook;
 use std::io;
 use std::io::Write;
+use std::path::Path;
 use std::process::Command;
 
 // Create clap subcommand arguments
@@ -18,13 +20,28 @@ pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
         )
         .arg_from_usage("--theme 'Copies the default theme into your source folder'")
         .arg_from_usage("--force 'Skips confirmation prompts'")
+        .arg_from_usage(
+            "--template [template] 'Creates the book from a template, either one of \
+             api-docs, blog or tutorial or the path to a directory'",
+        )
+        .arg_from_usage(
+            "--from-summary 'Only creates stub files for the chapters of an existing \
+             SUMMARY.md that don't exist yet'",
+        )
 }
 
 // Init command implementation
 pub fn execute(args: &ArgMatches) -> Result<()> {
     let book_dir = get_book_dir(args);
+    if args.is_present("from-summary") {
+        return create_stubs_from_summary(&book_dir);
+    }
+
     let mut builder = MDBook::init(&book_dir);
-    let mut config = config::Config::default();
+    if let Some(template) = args.value_of("template") {
+        builder.with_template(Template::find(template)?)?;
+    }
+    let mut config = builder.config().clone();
 
     // If flag `--theme` is present, copy theme to src
     if args.is_present("theme") {
@@ -33,10 +50,7 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
         if !args.is_present("force") {
             // Print warning
             println!();
-            println!(
-                "Copying the default theme to {}",
-                builder.config().book.src.display()
-            );
+            println!("Copying the default theme to {}", config.book.src.display());
             println!("This could potentially overwrite files already present in that directory.");
             print!("\nAre you sure you want to continue? (y/n) ");
 
@@ -55,13 +69,15 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
         builder.create_gitignore(true);
     }
 
-    config.book.title = request_book_title();
+    if let Some(title) = request_book_title() {
+        config.book.title = Some(title);
+    }
 
     if let Some(author) = get_author_name() {
         debug!("Obtained user name from gitconfig: {:?}", author);
         config.book.authors.push(author);
-        builder.with_config(config);
     }
+    builder.with_config(config);
 
     builder.build()?;
     println!("\nAll done, no errors...");
@@ -69,6 +85,27 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Creates the chapters listed in the book's existing summary, `SUMMARY.md`
+/// unless `book.summary` says otherwise.
+fn create_stubs_from_summary(book_dir: &Path) -> Result<()> {
+    let config_location = book_dir.join("book.toml");
+    let config = if config_location.exists() {
+        Config::from_disk(&config_location)?
+    } else {
+        Config::default()
+    };
+
+    let src_dir = book_dir.join(&config.book.src);
+    let summary = book::read_summary(&src_dir, &config.book.summary)?;
+    let created = book::create_missing_chapters_from_summary(&summary, &src_dir)?;
+    match created {
+        1 => println!("Created 1 chapter stub"),
+        n => println!("Created {} chapter stubs", n),
+    }
+
+    Ok(())
+}
+
 /// Obtains author name from git config file by running the `git config` command.
 fn get_author_name() -> Option<String> {
     let output = Command::new("git")
@@ -3,6 +3,7 @@
 pub mod build;
 pub mod clean;
 pub mod init;
+pub mod render;
 #[cfg(feature = "serve")]
 pub mod serve;
 pub mod test;
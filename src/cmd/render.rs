@@ -0,0 +1,53 @@
+use anyhow::Context;
+use clap::{App, ArgMatches, SubCommand};
+use mdbook::utils;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// Create clap subcommand arguments
+pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("render")
+        .about("Renders a single markdown file to an HTML fragment on stdout")
+        .arg_from_usage(
+            "--curly-quotes 'Converts straight quotes to curly quotes{n}\
+             Works like output.html.curly-quotes in book.toml.'",
+        )
+        .arg_from_usage(
+            "-p, --path=[path] 'Path of the file relative to the book's source directory{n}\
+             Relative links are rewritten to be relative to the book's root, like on the print page.'",
+        )
+        .arg_from_usage(
+            "[input] 'Markdown file to render{n}\
+             (Reads from stdin when omitted or `-`)'",
+        )
+}
+
+// Render command implementation
+pub fn execute(args: &ArgMatches) -> mdbook::errors::Result<()> {
+    let markdown = match args.value_of("input") {
+        None | Some("-") => {
+            let mut markdown = String::new();
+            io::stdin()
+                .read_to_string(&mut markdown)
+                .with_context(|| "Unable to read markdown from stdin")?;
+            markdown
+        }
+        Some(input) => fs::read_to_string(input)
+            .with_context(|| format!("Unable to read markdown from {}", input))?,
+    };
+
+    let curly_quotes = args.is_present("curly-quotes");
+    let html = match args.value_of("path") {
+        Some(path) => {
+            utils::render_markdown_with_path(&markdown, curly_quotes, Some(Path::new(path)))
+        }
+        None => utils::render_markdown(&markdown, curly_quotes),
+    };
+
+    io::stdout()
+        .write_all(html.as_bytes())
+        .with_context(|| "Unable to write the rendered HTML to stdout")?;
+
+    Ok(())
+}
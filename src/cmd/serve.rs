@@ -1,16 +1,20 @@
 #[cfg(feature = "watch")]
 use super::watch;
 use crate::{get_book_dir, open};
+use anyhow::{bail, Context};
 use clap::{App, Arg, ArgMatches, SubCommand};
 use futures_util::sink::SinkExt;
 use futures_util::StreamExt;
 use mdbook::errors::*;
+use mdbook::renderer::HtmlHandlebars;
 use mdbook::utils;
 use mdbook::utils::fs::get_404_output_file;
 use mdbook::MDBook;
-use std::net::{SocketAddr, ToSocketAddrs};
-use std::path::PathBuf;
+use std::fs;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::path::{Path, PathBuf};
 use tokio::sync::broadcast;
+use warp::filters::BoxedFilter;
 use warp::ws::Message;
 use warp::Filter;
 
@@ -46,23 +50,78 @@ pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .default_value("3000")
                 .empty_values(false)
-                .help("Port to use for HTTP connections"),
+                .help("Port to use for HTTP connections, or 0 for any free one"),
+        )
+        .arg_from_usage("--auto-port 'Listens on any free port, like --port 0'")
+        .arg(
+            Arg::with_name("port-file")
+                .long("port-file")
+                .takes_value(true)
+                .empty_values(false)
+                .help(
+                    "File to write the address the server listens on to, \
+                     like 127.0.0.1:3000",
+                ),
+        )
+        .arg(
+            Arg::with_name("static-dir")
+                .long("static-dir")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("/prefix:dir")
+                .help(
+                    "Also serves the files of a directory outside of the book at a URL prefix, \
+                     like /assets:../shared/assets (Can be given more than once)",
+                ),
         )
         .arg_from_usage("-o, --open 'Opens the book server in a web browser'")
 }
 
+/// Whether the book is rendered to HTML, explicitly or by default.
+fn uses_html_renderer(book: &MDBook) -> bool {
+    match book.config.get("output").and_then(toml::Value::as_table) {
+        Some(outputs) if !outputs.is_empty() => outputs.contains_key("html"),
+        _ => true,
+    }
+}
+
 // Serve command implementation
 pub fn execute(args: &ArgMatches) -> Result<()> {
     let book_dir = get_book_dir(args);
     let mut book = MDBook::load(&book_dir)?;
 
-    let port = args.value_of("port").unwrap();
+    let port = if args.is_present("auto-port") {
+        "0"
+    } else {
+        args.value_of("port").unwrap()
+    };
     let hostname = args.value_of("hostname").unwrap();
     let open_browser = args.is_present("open");
+    let static_dirs = args
+        .values_of("static-dir")
+        .into_iter()
+        .flatten()
+        .map(parse_static_dir)
+        .collect::<Result<Vec<_>>>()?;
 
     let address = format!("{}:{}", hostname, port);
+    let sockaddr: SocketAddr = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no address found for {}", address))?;
+
+    // Listen before building the book, so the livereload URL has the port the
+    // system picked for port 0.
+    let listener =
+        TcpListener::bind(sockaddr).with_context(|| format!("Unable to listen on {}", address))?;
+    let local_addr = listener.local_addr()?;
+    let address = format!("{}:{}", hostname, local_addr.port());
 
     let livereload_url = format!("ws://{}/{}", address, LIVE_RELOAD_ENDPOINT);
+    // Every rebuild uses the same HTML renderer, so it only updates the search
+    // index with the chapters which changed.
+    let html = HtmlHandlebars::new();
     let update_config = |book: &mut MDBook| {
         book.config
             .set("output.html.livereload-url", &livereload_url)
@@ -72,14 +131,13 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
         }
         // Override site-url for local serving of the 404 file
         book.config.set("output.html.site-url", "/").unwrap();
+        if uses_html_renderer(book) {
+            book.replace_renderer(html.clone());
+        }
     };
     update_config(&mut book);
     book.build()?;
 
-    let sockaddr: SocketAddr = address
-        .to_socket_addrs()?
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("no address found for {}", address))?;
     let build_dir = book.build_dir_for("html");
     let input_404 = book
         .config
@@ -94,11 +152,15 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
 
     let reload_tx = tx.clone();
     let thread_handle = std::thread::spawn(move || {
-        serve(build_dir, sockaddr, reload_tx, &file_404);
+        serve(build_dir, static_dirs, listener, reload_tx, &file_404);
     });
 
     let serving_url = format!("http://{}", address);
     info!("Serving on: {}", serving_url);
+    if let Some(port_file) = args.value_of("port-file") {
+        fs::write(port_file, format!("{}\n", local_addr))
+            .with_context(|| format!("Unable to write the address to {}", port_file))?;
+    }
 
     if open_browser {
         open(serving_url);
@@ -128,10 +190,39 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
     Ok(())
 }
 
+/// Splits a `--static-dir`, like `/assets:../shared/assets`, into the URL
+/// prefix and the directory served there.
+fn parse_static_dir(value: &str) -> Result<(String, PathBuf)> {
+    let mut parts = value.splitn(2, ':');
+    let (prefix, dir) = match (parts.next(), parts.next()) {
+        (Some(prefix), Some(dir)) if prefix.starts_with('/') && !dir.is_empty() => {
+            (prefix, Path::new(dir))
+        }
+        _ => bail!("`--static-dir {}` must be of the form /prefix:dir", value),
+    };
+    if !dir.is_dir() {
+        bail!("The static directory {} doesn't exist", dir.display());
+    }
+
+    Ok((prefix.trim_matches('/').to_string(), dir.to_path_buf()))
+}
+
+/// A warp Filter serving the files of `dir` below the URL `prefix`.
+fn static_dir_route(prefix: &str, dir: PathBuf) -> BoxedFilter<(warp::fs::File,)> {
+    let route = prefix
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .fold(warp::any().boxed(), |route, segment| {
+            route.and(warp::path(segment.to_string())).boxed()
+        });
+    route.and(warp::fs::dir(dir)).boxed()
+}
+
 #[tokio::main]
 async fn serve(
     build_dir: PathBuf,
-    address: SocketAddr,
+    static_dirs: Vec<(String, PathBuf)>,
+    listener: TcpListener,
     reload_tx: broadcast::Sender<Message>,
     file_404: &str,
 ) {
@@ -155,11 +246,19 @@ async fn serve(
                 }
             })
         });
-    // A warp Filter that serves from the filesystem.
-    let book_route = warp::fs::dir(build_dir.clone());
+    // A warp Filter that serves from the filesystem, trying the static
+    // directories in order before the book.
+    let book_route = static_dirs.into_iter().rev().fold(
+        warp::fs::dir(build_dir.clone()).boxed(),
+        |route, (prefix, dir)| static_dir_route(&prefix, dir).or(route).unify().boxed(),
+    );
     // The fallback route for 404 errors
     let fallback_route = warp::fs::file(build_dir.join(file_404))
         .map(|reply| warp::reply::with_status(reply, warp::http::StatusCode::NOT_FOUND));
     let routes = livereload.or(book_route).or(fallback_route);
-    warp::serve(routes).run(address).await;
+    let mut listener = listener
+        .set_nonblocking(true)
+        .and_then(|_| tokio::net::TcpListener::from_std(listener))
+        .expect("The listener can be used by tokio");
+    warp::serve(routes).run_incoming(listener.incoming()).await;
 }
@@ -1,7 +1,10 @@
 use crate::get_book_dir;
 use clap::{App, Arg, ArgMatches, SubCommand};
+use mdbook::book::TestOutcome;
 use mdbook::errors::Result;
 use mdbook::MDBook;
+use std::env;
+use std::path::Path;
 
 // Create clap subcommand arguments
 pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
@@ -16,6 +19,14 @@ pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
             "[dir] 'Root directory for the book{n}\
              (Defaults to the Current Directory when omitted)'",
         )
+        .arg_from_usage(
+            "--rustdoc=[rustdoc] 'The rustdoc binary to test the code samples with{n}\
+             (Defaults to rust.rustdoc from book.toml, MDBOOK_RUSTDOC or the rustdoc on the PATH)'",
+        )
+        .arg_from_usage(
+            "--no-run 'Only compiles the code samples without running any of them{n}\
+             (as if they were all marked `no_run`)'",
+        )
         .arg(Arg::with_name("library-path")
             .short("L")
             .long("library-path")
@@ -39,8 +50,39 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
     if let Some(dest_dir) = args.value_of("dest-dir") {
         book.config.build.build_dir = dest_dir.into();
     }
+    if let Some(rustdoc) = args.value_of_os("rustdoc") {
+        let rustdoc = Path::new(rustdoc);
+        // Paths on the command line are relative to the current directory
+        book.config.rust.rustdoc = Some(if rustdoc.components().count() > 1 {
+            env::current_dir()?.join(rustdoc)
+        } else {
+            rustdoc.to_path_buf()
+        });
+    }
 
-    book.test(library_paths)?;
+    let report = book.test_all(library_paths, args.is_present("no-run"))?;
+    for chapter in &report.chapters {
+        let count = |outcome: fn(&TestOutcome) -> bool| {
+            chapter
+                .blocks
+                .iter()
+                .filter(|b| outcome(&b.outcome))
+                .count()
+        };
+        let summary = format!(
+            "{}: {} ran, {} compiled, {} ignored, {} failed",
+            chapter.path.display(),
+            count(|o| *o == TestOutcome::Ran),
+            count(|o| *o == TestOutcome::Compiled),
+            count(|o| *o == TestOutcome::Ignored),
+            count(TestOutcome::is_failure),
+        );
+        if chapter.passed() {
+            info!("{}", summary);
+        } else {
+            error!("{}", summary);
+        }
+    }
 
-    Ok(())
+    report.check()
 }
@@ -2,9 +2,12 @@ use crate::{get_book_dir, open};
 use clap::{App, ArgMatches, SubCommand};
 use mdbook::errors::Result;
 use mdbook::utils;
+use mdbook::utils::fs::GlobPattern;
 use mdbook::MDBook;
 use notify::Watcher;
+use shlex::Shlex;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 use std::sync::mpsc::channel;
 use std::thread::sleep;
 use std::time::Duration;
@@ -23,6 +26,10 @@ pub fn make_subcommand<'a, 'b>() -> App<'a, 'b> {
              (Defaults to the Current Directory when omitted)'",
         )
         .arg_from_usage("-o, --open 'Open the compiled book in a web browser'")
+        .arg_from_usage(
+            "-x, --exec=[command] 'Command to run after each successful rebuild{n}\
+             It is run in the book's root directory with MDBOOK_ROOT and MDBOOK_BUILD_DIR set.'",
+        )
 }
 
 // Watch command implementation
@@ -46,23 +53,74 @@ pub fn execute(args: &ArgMatches) -> Result<()> {
         info!("Files changed: {:?}\nBuilding book...\n", paths);
         let result = MDBook::load(&book_dir).and_then(|mut b| {
             update_config(&mut b);
-            b.build()
+            b.build()?;
+            Ok(b)
         });
 
-        if let Err(e) = result {
-            error!("Unable to build the book");
-            utils::log_backtrace(&e);
+        match result {
+            Ok(book) => {
+                if let Some(command) = args.value_of("exec") {
+                    run_exec_command(command, &book);
+                }
+            }
+            Err(e) => {
+                error!("Unable to build the book");
+                utils::log_backtrace(&e);
+            }
         }
     });
 
     Ok(())
 }
 
-fn remove_ignored_files(book_root: &PathBuf, paths: &[PathBuf]) -> Vec<PathBuf> {
+/// Runs the `--exec` command after a rebuild. Failures are only logged so that
+/// watching continues.
+fn run_exec_command(command: &str, book: &MDBook) {
+    let mut words = Shlex::new(command);
+    let executable = match words.next() {
+        Some(e) => e,
+        None => {
+            warn!("The command to run after rebuilding is empty");
+            return;
+        }
+    };
+
+    info!("Running `{}`", command);
+    let status = Command::new(executable)
+        .args(words)
+        .current_dir(&book.root)
+        .env("MDBOOK_ROOT", &book.root)
+        .env(
+            "MDBOOK_BUILD_DIR",
+            book.root.join(&book.config.build.build_dir),
+        )
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => error!("`{}` failed with {}", command, status),
+        Err(e) => error!("Unable to run `{}`: {}", command, e),
+    }
+}
+
+fn remove_ignored_files(
+    book_root: &PathBuf,
+    watch_ignore: &[String],
+    paths: &[PathBuf],
+) -> Vec<PathBuf> {
     if paths.is_empty() {
         return vec![];
     }
 
+    let paths = remove_gitignored_files(book_root, paths);
+    if watch_ignore.is_empty() {
+        paths
+    } else {
+        filter_watch_ignored_files(book_root, watch_ignore, paths)
+    }
+}
+
+fn remove_gitignored_files(book_root: &PathBuf, paths: &[PathBuf]) -> Vec<PathBuf> {
     match find_gitignore(book_root) {
         Some(gitignore_path) => {
             match gitignore::File::new(gitignore_path.as_path()) {
@@ -105,6 +163,85 @@ fn filter_ignored_files(exclusion_checker: gitignore::File, paths: &[PathBuf]) -
         .collect()
 }
 
+/// Removes the paths matching one of the `build.watch-ignore` patterns, or
+/// lying in a directory which does.
+fn filter_watch_ignored_files(
+    book_root: &Path,
+    watch_ignore: &[String],
+    paths: Vec<PathBuf>,
+) -> Vec<PathBuf> {
+    let root = book_root
+        .canonicalize()
+        .unwrap_or_else(|_| book_root.to_path_buf());
+
+    let patterns: Vec<_> = watch_ignore
+        .iter()
+        .filter_map(|raw| match GlobPattern::new(raw) {
+            Ok(pattern) => Some(pattern),
+            Err(error) => {
+                warn!("Invalid watch-ignore pattern: {}", error);
+                None
+            }
+        })
+        .collect();
+
+    paths
+        .into_iter()
+        .filter(|path| {
+            let ignored = match path.strip_prefix(&root) {
+                Ok(relative) => {
+                    let is_dir = path.is_dir();
+                    patterns
+                        .iter()
+                        .any(|pattern| pattern.matches(relative, is_dir))
+                }
+                Err(_) => false,
+            };
+            if ignored {
+                debug!("Ignoring change to {:?} (build.watch-ignore)", path);
+            }
+            !ignored
+        })
+        .collect()
+}
+
+/// The directories besides the source and theme directories in which changes
+/// trigger a rebuild, from `build.extra-watch-dirs`.
+fn extra_watch_dirs(book: &MDBook) -> Vec<PathBuf> {
+    book.config
+        .build
+        .extra_watch_dirs
+        .iter()
+        .map(|dir| book.root.join(dir))
+        .collect()
+}
+
+/// The book's own output mustn't trigger another build, unless
+/// `build.watch-build-dir` is set. The events may name the build directory
+/// through symlinks or not, so both of its paths are returned.
+fn ignored_build_dirs(book: &MDBook) -> Vec<PathBuf> {
+    if book.config.build.watch_build_dir {
+        return Vec::new();
+    }
+    let build_dir = book.root.join(&book.config.build.build_dir);
+    let canonical = build_dir.canonicalize().ok();
+    std::iter::once(build_dir).chain(canonical).collect()
+}
+
+/// Removes the paths inside one of the `build_dirs`.
+fn remove_build_dir_files(build_dirs: &[PathBuf], paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths
+        .into_iter()
+        .filter(|path| {
+            let output = build_dirs.iter().any(|dir| path.starts_with(dir));
+            if output {
+                debug!("Ignoring change to {:?} in the build directory", path);
+            }
+            !output
+        })
+        .collect()
+}
+
 /// Calls the closure when a book source file is changed, blocking indefinitely.
 pub fn trigger_on_change<F>(book: &MDBook, closure: F)
 where
@@ -135,6 +272,17 @@ where
     // Add the book.toml file to the watcher if it exists
     let _ = watcher.watch(book.root.join("book.toml"), NonRecursive);
 
+    for dir in extra_watch_dirs(book) {
+        if let Err(e) = watcher.watch(&dir, Recursive) {
+            warn!(
+                "Unable to watch the extra directory {:?}:\n    {:?}",
+                dir, e
+            );
+        }
+    }
+
+    let build_dirs = ignored_build_dirs(book);
+
     info!("Listening for changes...");
 
     loop {
@@ -155,10 +303,91 @@ where
             })
             .collect::<Vec<_>>();
 
-        let paths = remove_ignored_files(&book.root, &paths[..]);
+        let paths = remove_build_dir_files(&build_dirs, paths);
+        let paths = remove_ignored_files(&book.root, &book.config.build.watch_ignore, &paths[..]);
 
         if !paths.is_empty() {
             closure(paths, &book.root);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::Builder as TempFileBuilder;
+
+    #[test]
+    fn watch_ignore_patterns_drop_matching_events() {
+        let temp = TempFileBuilder::new().prefix("watch").tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("src/generated")).unwrap();
+        let files = [
+            "src/chapter_1.md",
+            "src/notes.tmp",
+            "src/generated/api.md",
+            "src/images.md",
+        ];
+        for file in &files {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let paths: Vec<_> = files.iter().map(|file| root.join(file)).collect();
+
+        let watch_ignore = vec![String::from("*.tmp"), String::from("src/generated/")];
+        let got = remove_ignored_files(&root, &watch_ignore, &paths);
+        assert_eq!(
+            got,
+            vec![root.join("src/chapter_1.md"), root.join("src/images.md")]
+        );
+
+        // Nothing is left to rebuild if all events are ignored
+        let got = remove_ignored_files(&root, &watch_ignore, &paths[1..3]);
+        assert!(got.is_empty());
+    }
+
+    fn book_with_config(root: &Path, book_toml: &str) -> MDBook {
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/SUMMARY.md"), "- [Intro](intro.md)\n").unwrap();
+        fs::write(root.join("src/intro.md"), "# Intro\n").unwrap();
+        fs::write(root.join("book.toml"), book_toml).unwrap();
+        MDBook::load(root).unwrap()
+    }
+
+    #[test]
+    fn extra_watch_dirs_are_relative_to_the_book_root() {
+        let temp = TempFileBuilder::new().prefix("watch").tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let book = book_with_config(
+            &root,
+            "[build]\nextra-watch-dirs = [\"api\", \"../shared\"]\n",
+        );
+
+        assert_eq!(
+            extra_watch_dirs(&book),
+            vec![root.join("api"), root.join("../shared")]
+        );
+    }
+
+    #[test]
+    fn changes_in_the_build_dir_are_removed() {
+        let temp = TempFileBuilder::new().prefix("watch").tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let mut book = book_with_config(&root, "[build]\nbuild-dir = \"output\"\n");
+
+        let build_dirs = ignored_build_dirs(&book);
+        assert_eq!(build_dirs, vec![root.join("output")]);
+        let paths = vec![
+            root.join("output/html/intro.html"),
+            root.join("src/intro.md"),
+            root.join("outputs.md"),
+        ];
+        assert_eq!(
+            remove_build_dir_files(&build_dirs, paths.clone()),
+            vec![root.join("src/intro.md"), root.join("outputs.md")]
+        );
+
+        book.config.build.watch_build_dir = true;
+        assert!(ignored_build_dirs(&book).is_empty());
+    }
+}
@@ -50,7 +50,7 @@
 #![deny(missing_docs)]
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
 use std::fs::File;
 use std::io::Read;
@@ -169,13 +169,19 @@ impl Config {
         self.rest.read_mut(key)
     }
 
-    /// Convenience method for getting the html renderer's configuration.
+    /// Convenience method for getting the html renderer's configuration, the
+    /// `[output.html]` table.
     ///
-    /// # Note
+    /// This is `None` if the table is missing, or if it is invalid, which is
+    /// logged.
     ///
-    /// This is for compatibility only. It will be removed completely once the
-    /// HTML renderer is refactored to be less coupled to `mdbook` internals.
-    #[doc(hidden)]
+    /// ```rust
+    /// # use mdbook::Config;
+    /// # use std::str::FromStr;
+    /// let config = Config::from_str("[output.html]\ncurly-quotes = true\n")?;
+    /// assert!(config.html_config().unwrap().curly_quotes);
+    /// # Ok::<(), mdbook::errors::Error>(())
+    /// ```
     pub fn html_config(&self) -> Option<HtmlConfig> {
         match self
             .get_deserialized_opt("output.html")
@@ -245,11 +251,63 @@ impl Config {
     }
 
     /// Get the table associated with a particular preprocessor.
+    ///
+    /// ```rust
+    /// # use mdbook::Config;
+    /// # use std::str::FromStr;
+    /// let config = Config::from_str("[preprocessor.emoji]\nstyle = \"github\"\n")?;
+    /// let table = config.get_preprocessor("emoji").unwrap();
+    /// assert_eq!(table["style"].as_str(), Some("github"));
+    /// assert!(config.get_preprocessor("links").is_none());
+    /// # Ok::<(), mdbook::errors::Error>(())
+    /// ```
     pub fn get_preprocessor<I: AsRef<str>>(&self, index: I) -> Option<&Table> {
         let key = format!("preprocessor.{}", index.as_ref());
         self.get(&key).and_then(Value::as_table)
     }
 
+    /// Fetch the option `key` of a particular renderer, like
+    /// `output.html.curly-quotes`, and deserialize it. `None` if it isn't set.
+    pub fn get_renderer_option<'de, T: Deserialize<'de>>(
+        &self,
+        renderer: &str,
+        key: &str,
+    ) -> Result<Option<T>> {
+        self.get_deserialized_opt(format!("output.{}.{}", renderer, key))
+    }
+
+    /// Fetch the option `key` of a particular preprocessor, like
+    /// `preprocessor.emoji.style`, and deserialize it. `None` if it isn't set.
+    ///
+    /// ```rust
+    /// # use mdbook::Config;
+    /// # use std::str::FromStr;
+    /// let config = Config::from_str(
+    ///     "[preprocessor.emoji]\nstyle = \"github\"\nskip = [\"code\", \"headings\"]\n",
+    /// )?;
+    ///
+    /// let skip: Vec<String> = config
+    ///     .get_preprocessor_option("emoji", "skip")?
+    ///     .unwrap_or_default();
+    /// assert_eq!(skip, ["code", "headings"]);
+    ///
+    /// let size: u32 = config
+    ///     .get_preprocessor_option("emoji", "size")?
+    ///     .unwrap_or(16);
+    /// assert_eq!(size, 16);
+    ///
+    /// // Values of the wrong type are an error.
+    /// assert!(config.get_preprocessor_option::<bool>("emoji", "style").is_err());
+    /// # Ok::<(), mdbook::errors::Error>(())
+    /// ```
+    pub fn get_preprocessor_option<'de, T: Deserialize<'de>>(
+        &self,
+        preprocessor: &str,
+        key: &str,
+    ) -> Result<Option<T>> {
+        self.get_deserialized_opt(format!("preprocessor.{}.{}", preprocessor, key))
+    }
+
     fn from_legacy(mut table: Value) -> Config {
         let mut cfg = Config::default();
 
@@ -404,10 +462,16 @@ pub struct BookConfig {
     pub description: Option<String>,
     /// Location of the book source relative to the book's root directory.
     pub src: PathBuf,
+    /// Location of the summary relative to the book source, for books which
+    /// can't use `SUMMARY.md`.
+    #[serde(skip_serializing_if = "is_default_summary")]
+    pub summary: PathBuf,
     /// Does this book support more than one language?
     pub multilingual: bool,
     /// The main language of the book.
     pub language: Option<String>,
+    /// The version of the book, or of what it documents.
+    pub version: Option<String>,
 }
 
 impl Default for BookConfig {
@@ -417,12 +481,20 @@ impl Default for BookConfig {
             authors: Vec::new(),
             description: None,
             src: PathBuf::from("src"),
+            summary: PathBuf::from("SUMMARY.md"),
             multilingual: false,
             language: Some(String::from("en")),
+            version: None,
         }
     }
 }
 
+/// Whether `summary` is the default summary file, which `mdbook init` then
+/// leaves out of the `book.toml`.
+fn is_default_summary(summary: &Path) -> bool {
+    summary == Path::new("SUMMARY.md")
+}
+
 /// Configuration for the build procedure.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
@@ -435,6 +507,17 @@ pub struct BuildConfig {
     /// Should the default preprocessors always be used when they are
     /// compatible with the renderer?
     pub use_default_preprocessors: bool,
+    /// Patterns (in `.gitignore` syntax, relative to the book's root) of
+    /// files which don't trigger a rebuild when watching the book.
+    pub watch_ignore: Vec<String>,
+    /// Directories (relative to the book's root) in which changes trigger a
+    /// rebuild when watching the book, besides the source and theme
+    /// directories.
+    pub extra_watch_dirs: Vec<PathBuf>,
+    /// Let changes in the build directory trigger a rebuild when watching the
+    /// book. They are ignored by default, so writing the book's output can't
+    /// start another build.
+    pub watch_build_dir: bool,
 }
 
 impl Default for BuildConfig {
@@ -443,6 +526,9 @@ impl Default for BuildConfig {
             build_dir: PathBuf::from("book"),
             create_missing: true,
             use_default_preprocessors: true,
+            watch_ignore: Vec::new(),
+            extra_watch_dirs: Vec::new(),
+            watch_build_dir: false,
         }
     }
 }
@@ -453,6 +539,9 @@ impl Default for BuildConfig {
 pub struct RustConfig {
     /// Rust edition used in playground
     pub edition: Option<RustEdition>,
+    /// The `rustdoc` binary `mdbook test` uses, instead of the one on the
+    /// `PATH`.
+    pub rustdoc: Option<PathBuf>,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
@@ -479,6 +568,25 @@ pub struct HtmlConfig {
     pub preferred_dark_theme: Option<String>,
     /// Use "smart quotes" instead of the usual `"` character.
     pub curly_quotes: bool,
+    /// The language of fenced code blocks which don't have one, e.g. `bash`
+    /// for a book full of shell commands.
+    pub default_language: Option<String>,
+    /// Turn paragraphs like `Term\n: Definition` into definition lists.
+    pub definition_lists: bool,
+    /// The URL to put in front of local images, resolved relative to the
+    /// source directory, e.g. to serve them from a CDN.
+    pub asset_url_prefix: Option<String>,
+    /// The extensions of files, like `pdf`, which links download instead of
+    /// opening them.
+    pub download_extensions: Vec<String>,
+    /// Whether the links to downloads open in a new tab.
+    pub download_new_tab: bool,
+    /// Give the generated HTML files LF line endings, no trailing whitespace
+    /// outside of preformatted text, and a single final newline.
+    pub normalize_output: bool,
+    /// Make the links between the book's pages absolute, starting at the
+    /// `site_url`, instead of relative to the page.
+    pub absolute_links: bool,
     /// Should mathjax be enabled?
     pub mathjax_support: bool,
     /// Whether to fonts.css and respective font files to the output directory.
@@ -487,16 +595,62 @@ pub struct HtmlConfig {
     pub google_analytics: Option<String>,
     /// Additional CSS stylesheets to include in the rendered page's `<head>`.
     pub additional_css: Vec<PathBuf>,
+    /// Inline the theme's and the additional stylesheets into a single
+    /// `<style>` element on every page, instead of linking to them.
+    pub inline_css: bool,
+    /// CSS custom properties, like `--sidebar-bg`, overriding the ones of the
+    /// theme.
+    pub theme_vars: BTreeMap<String, String>,
+    /// The maximum width of the content column, like `"80ch"`, overriding
+    /// the theme's `--content-max-width`.
+    pub max_content_width: Option<String>,
     /// Additional JS scripts to include at the bottom of the rendered page's
-    /// `<body>`.
-    pub additional_js: Vec<PathBuf>,
+    /// `<body>`, or in its `<head>`.
+    pub additional_js: Vec<AdditionalJs>,
     /// Fold settings.
     pub fold: Fold,
     /// Playground settings.
     #[serde(alias = "playpen")]
     pub playground: Playground,
+    /// Code block settings.
+    pub code: Code,
+    /// Print page settings.
+    pub print: Print,
     /// Don't render section labels.
     pub no_section_label: bool,
+    /// Add a "Skip to content" link and ARIA landmark roles for assistive
+    /// technology.
+    pub a11y: bool,
+    /// Show breadcrumbs leading from the book's home page to the current
+    /// chapter at the top of every page.
+    pub breadcrumbs: bool,
+    /// The format of the dates the `format_date` helper renders into the
+    /// templates, either strftime-style, like `%B %-d, %Y`, or one of
+    /// `iso8601`, `rfc2822` and `rfc3339`. Default: `iso8601`.
+    pub date_format: String,
+    /// Bind the theme's keyboard shortcuts, like `s` for the search.
+    pub keyboard_shortcuts: bool,
+    /// The keys of the keyboard shortcuts.
+    pub shortcut_keys: ShortcutKeys,
+    /// Give every heading a button copying the URL of its anchor.
+    pub heading_permalinks: bool,
+    /// Prefix the ids of the headings on the print page with their chapter's
+    /// path, so no two chapters share an id, and point the links between the
+    /// chapters at those headings.
+    pub anchor_prefix: bool,
+    /// Give every page's template the title, path, section number and level
+    /// of all the chapters of the book.
+    pub expose_chapters: bool,
+    /// Give every page's template the headings of its chapter.
+    pub page_toc: bool,
+    /// Embed the headings of every page's chapter as JSON, for theme scripts.
+    pub page_outline_data: bool,
+    /// Give every page's template the latest commits changing the book's
+    /// chapters. If `None`, they aren't looked up.
+    pub recent_changes: Option<RecentChanges>,
+    /// The direction of the book's text. If `None`, it is derived from
+    /// `book.language`.
+    pub text_direction: Option<TextDirection>,
     /// Search settings. If `None`, the default will be used.
     pub search: Option<Search>,
     /// Git repository url. If `None`, the git button will not be shown.
@@ -508,6 +662,18 @@ pub struct HtmlConfig {
     pub input_404: Option<String>,
     /// Absolute url to site, used to emit correct paths for the 404 page, which might be accessed in a deeply nested directory
     pub site_url: Option<String>,
+    /// Emit a `<base>` tag pointing at the book's root on every page, and make
+    /// asset and navigation links relative to it instead of to the page.
+    pub use_base_tag: bool,
+    /// Emit a `sitemap.xml` listing the absolute URLs of all pages, based on `site_url`.
+    pub sitemap: bool,
+    /// Globs of files, relative to the book's root, like `robots.txt` or
+    /// `.well-known/**`, which are copied into the output directory as they
+    /// are.
+    pub extra_files: Vec<String>,
+    /// Custom favicon files to use instead of the theme's. If `None`, the
+    /// theme's `favicon.png` and `favicon.svg` are used.
+    pub favicon: Option<Favicon>,
     /// This is used as a bit of a workaround for the `mdbook serve` command.
     /// Basically, because you set the websocket port from the command line, the
     /// `mdbook serve` command needs a way to let the HTML renderer know where
@@ -519,6 +685,11 @@ pub struct HtmlConfig {
     /// The mapping from old pages to new pages/URLs to use when generating
     /// redirects.
     pub redirect: HashMap<String, String>,
+    /// Patterns redirecting the old pages in `redirect_paths` matching them,
+    /// tried in order.
+    pub redirect_patterns: Vec<RedirectPattern>,
+    /// The old pages to generate redirects for with `redirect_patterns`.
+    pub redirect_paths: Vec<String>,
 }
 
 impl Default for HtmlConfig {
@@ -528,21 +699,51 @@ impl Default for HtmlConfig {
             default_theme: None,
             preferred_dark_theme: None,
             curly_quotes: false,
+            default_language: None,
+            definition_lists: false,
+            asset_url_prefix: None,
+            download_extensions: Vec::new(),
+            download_new_tab: false,
+            normalize_output: false,
+            absolute_links: false,
             mathjax_support: false,
             copy_fonts: true,
             google_analytics: None,
             additional_css: Vec::new(),
+            inline_css: false,
+            theme_vars: BTreeMap::new(),
+            max_content_width: None,
             additional_js: Vec::new(),
             fold: Fold::default(),
             playground: Playground::default(),
+            code: Code::default(),
+            print: Print::default(),
             no_section_label: false,
+            a11y: false,
+            breadcrumbs: false,
+            date_format: String::from("iso8601"),
+            keyboard_shortcuts: true,
+            shortcut_keys: ShortcutKeys::default(),
+            heading_permalinks: false,
+            anchor_prefix: false,
+            expose_chapters: false,
+            page_toc: false,
+            page_outline_data: false,
+            recent_changes: None,
+            text_direction: None,
             search: None,
             git_repository_url: None,
             git_repository_icon: None,
             input_404: None,
             site_url: None,
+            use_base_tag: false,
+            sitemap: false,
+            extra_files: Vec::new(),
+            favicon: None,
             livereload_url: None,
             redirect: HashMap::new(),
+            redirect_patterns: Vec::new(),
+            redirect_paths: Vec::new(),
         }
     }
 }
@@ -558,6 +759,158 @@ impl HtmlConfig {
     }
 }
 
+/// An entry of `output.html.additional-js`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AdditionalJs {
+    /// The path of a script, loaded with a plain `<script>` tag.
+    Path(PathBuf),
+    /// A script with attributes for its `<script>` tag.
+    Script(Script),
+}
+
+impl AdditionalJs {
+    /// The path of the script.
+    pub fn path(&self) -> &Path {
+        match *self {
+            AdditionalJs::Path(ref path) => path,
+            AdditionalJs::Script(ref script) => &script.path,
+        }
+    }
+}
+
+impl From<PathBuf> for AdditionalJs {
+    fn from(path: PathBuf) -> AdditionalJs {
+        AdditionalJs::Path(path)
+    }
+}
+
+/// A script in `output.html.additional-js` with attributes for its `<script>`
+/// tag.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Script {
+    /// The path of the script.
+    pub path: PathBuf,
+    /// The `type` attribute, e.g. `module`. Default: `text/javascript`.
+    #[serde(default, rename = "type")]
+    pub script_type: Option<String>,
+    /// Add the `defer` attribute. Default: `false`.
+    #[serde(default)]
+    pub defer: bool,
+    /// Add the `async` attribute. Default: `false`.
+    #[serde(default, rename = "async")]
+    pub is_async: bool,
+    /// Where to put the `<script>` tag. Default: `body-end`.
+    #[serde(default)]
+    pub placement: ScriptPlacement,
+}
+
+/// Where the `<script>` tag of an additional script goes.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptPlacement {
+    /// In the page's `<head>`.
+    Head,
+    /// At the end of the page's `<body>`.
+    BodyEnd,
+}
+
+impl Default for ScriptPlacement {
+    fn default() -> ScriptPlacement {
+        ScriptPlacement::BodyEnd
+    }
+}
+
+/// The direction text is written in.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TextDirection {
+    /// Left to right, like English.
+    #[serde(rename = "ltr")]
+    LeftToRight,
+    /// Right to left, like Arabic or Hebrew.
+    #[serde(rename = "rtl")]
+    RightToLeft,
+}
+
+impl TextDirection {
+    /// The direction of a language, given as a language code like `ar` or
+    /// `he-IL`.
+    pub fn from_language(language: &str) -> TextDirection {
+        const RTL_LANGUAGES: &[&str] = &[
+            "ar", "arc", "ckb", "dv", "fa", "he", "ks", "ps", "sd", "ug", "ur", "yi",
+        ];
+
+        let code = language
+            .split(|c| c == '-' || c == '_')
+            .next()
+            .unwrap_or("");
+        if RTL_LANGUAGES.contains(&code.to_lowercase().as_str()) {
+            TextDirection::RightToLeft
+        } else {
+            TextDirection::LeftToRight
+        }
+    }
+
+    /// The value of the HTML `dir` attribute for this direction.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TextDirection::LeftToRight => "ltr",
+            TextDirection::RightToLeft => "rtl",
+        }
+    }
+}
+
+/// Favicon settings for the HTML renderer.
+///
+/// Paths are relative to the book's root directory.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Favicon {
+    /// A single icon, used for every size.
+    Single(PathBuf),
+    /// Icons keyed by their size (e.g. `"32x32"`), or by `"apple-touch-icon"`
+    /// for the icon used when the page is added to a mobile home screen.
+    Sizes(BTreeMap<String, PathBuf>),
+}
+
+impl Favicon {
+    /// All configured icons, as `(size or "apple-touch-icon", path)` pairs.
+    /// The size is `None` for a single icon.
+    pub fn icons(&self) -> Vec<(Option<&str>, &Path)> {
+        match *self {
+            Favicon::Single(ref path) => vec![(None, path.as_path())],
+            Favicon::Sizes(ref sizes) => sizes
+                .iter()
+                .map(|(size, path)| (Some(size.as_str()), path.as_path()))
+                .collect(),
+        }
+    }
+}
+
+/// The keys of the theme's keyboard shortcuts, named like the `key` of a
+/// `KeyboardEvent`, e.g. `ArrowLeft`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ShortcutKeys {
+    /// Open the search. Default: `s`.
+    pub search: String,
+    /// Go to the previous chapter. Default: `ArrowLeft`.
+    pub previous: String,
+    /// Go to the next chapter. Default: `ArrowRight`.
+    pub next: String,
+}
+
+impl Default for ShortcutKeys {
+    fn default() -> ShortcutKeys {
+        ShortcutKeys {
+            search: String::from("s"),
+            previous: String::from("ArrowLeft"),
+            next: String::from("ArrowRight"),
+        }
+    }
+}
+
 /// Configuration for how to fold chapters of sidebar.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
@@ -570,6 +923,32 @@ pub struct Fold {
     pub level: u8,
 }
 
+/// Configuration for the latest changes to the book's chapters, read from git.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct RecentChanges {
+    /// How many commits to give the templates. Default: `10`.
+    pub count: usize,
+}
+
+impl Default for RecentChanges {
+    fn default() -> RecentChanges {
+        RecentChanges { count: 10 }
+    }
+}
+
+/// An entry of `output.html.redirect-patterns`, redirecting the old pages
+/// matching the regex `from` to `to`, in which `$1` or `${name}` are replaced
+/// with what the groups of `from` matched, like `/new/$1` for `^/old/(.*)$`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RedirectPattern {
+    /// The regex matched against the old page's path.
+    pub from: String,
+    /// Where the old pages matching `from` are redirected to.
+    pub to: String,
+}
+
 /// Configuration for tweaking how the the HTML renderer handles the playground.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(default, rename_all = "kebab-case")]
@@ -583,6 +962,9 @@ pub struct Playground {
     pub copy_js: bool,
     /// Display line numbers on playground snippets. Default: `false`.
     pub line_numbers: bool,
+    /// Base URL of the playground instance used to run code snippets.
+    /// Default: `https://play.rust-lang.org`.
+    pub runner_url: String,
 }
 
 impl Default for Playground {
@@ -592,6 +974,83 @@ impl Default for Playground {
             copyable: true,
             copy_js: true,
             line_numbers: false,
+            runner_url: String::from("https://play.rust-lang.org"),
+        }
+    }
+}
+
+/// Configuration for the `print.html` page, which contains every chapter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Print {
+    /// Render the print page and show the print button. Default: `true`.
+    pub enable: bool,
+    /// Patterns (in `.gitignore` syntax, relative to the source directory) of
+    /// the chapters to leave out of the print page. Default: empty.
+    pub exclude: Vec<String>,
+    /// Class of an empty `<div>` inserted between chapters on the print page,
+    /// e.g. `page-break` to start every chapter on a new page. Default: `None`.
+    pub page_break_class: Option<String>,
+}
+
+impl Default for Print {
+    fn default() -> Print {
+        Print {
+            enable: true,
+            exclude: Vec::new(),
+            page_break_class: None,
+        }
+    }
+}
+
+impl Print {
+    /// Whether the chapter at `path` should be part of the print page.
+    ///
+    /// Invalid patterns in `exclude` don't match any chapter.
+    pub fn includes(&self, path: &Path) -> bool {
+        self.enable
+            && !self.exclude.iter().any(|pattern| {
+                crate::utils::fs::GlobPattern::new(pattern)
+                    .map(|pattern| pattern.matches(path, false))
+                    .unwrap_or(false)
+            })
+    }
+}
+
+/// Configuration for how the HTML renderer handles code blocks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Code {
+    /// Display the copy-to-clipboard button on code blocks. Default: `true`.
+    pub copy_button: bool,
+    /// The tooltip and accessible label of the copy button.
+    /// Default: `Copy to clipboard`.
+    pub copy_button_label: String,
+    /// The tooltip shown after a code block was copied. Default: `Copied!`.
+    pub copy_success_label: String,
+    /// Display line numbers alongside code blocks. Default: `false`.
+    pub line_numbers: bool,
+    /// Line prefixes which mark lines as hidden in code blocks of the given
+    /// languages, like `#` does for Rust. Default: empty.
+    pub hidelines: HashMap<String, String>,
+    /// Soft-wrap long lines in code blocks instead of scrolling them.
+    /// Default: `false`.
+    pub wrap: bool,
+    /// Display a button on code blocks which toggles wrapping their lines.
+    /// Default: `false`.
+    pub wrap_button: bool,
+}
+
+impl Default for Code {
+    fn default() -> Code {
+        Code {
+            copy_button: true,
+            copy_button_label: String::from("Copy to clipboard"),
+            copy_success_label: String::from("Copied!"),
+            line_numbers: false,
+            hidelines: HashMap::new(),
+            wrap: false,
+            wrap_button: false,
         }
     }
 }
@@ -627,6 +1086,19 @@ pub struct Search {
     /// Copy JavaScript files for the search functionality to the output directory?
     /// Default: `true`.
     pub copy_js: bool,
+    /// The language whose stemmer and stop words are used for the search index, as an
+    /// ISO 639-1 code, like `de`. Languages without a stemmer fall back to English.
+    /// Default: `"en"`.
+    pub lang: String,
+    /// Additional words which are left out of the search index and ignored in search
+    /// queries. Default: `[]`.
+    pub stopwords: Vec<String>,
+    /// Store the positions of the indexed terms, so search result teasers can highlight the
+    /// words which matched the search exactly. Default: `false`.
+    pub teaser_highlight: bool,
+    /// Rank results from earlier chapters slightly higher, so they come first when
+    /// they match about as well as later ones. Default: `false`.
+    pub prefer_order: bool,
 }
 
 impl Default for Search {
@@ -643,6 +1115,36 @@ impl Default for Search {
             expand: true,
             heading_split_level: 3,
             copy_js: true,
+            lang: String::from("en"),
+            stopwords: Vec::new(),
+            teaser_highlight: false,
+            prefer_order: false,
+        }
+    }
+}
+
+/// Configuration for the Markdown renderer, which decides whether it writes a
+/// normalized version of the book's sources or a faithful copy of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct MarkdownConfig {
+    /// Expand the `{{#include}}` and other links of the `links` preprocessor,
+    /// instead of leaving them as they are written. Default: `true`.
+    pub expand_includes: bool,
+    /// Point the links to `.md` files at the `.html` pages the HTML renderer
+    /// makes of them. Default: `false`.
+    pub rewrite_links: bool,
+    /// Keep the front matter of the chapters, which is removed when the book
+    /// is loaded. Default: `false`.
+    pub front_matter: bool,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> MarkdownConfig {
+        MarkdownConfig {
+            expand_includes: true,
+            rewrite_links: false,
+            front_matter: false,
         }
     }
 }
@@ -721,19 +1223,25 @@ mod tests {
             description: Some(String::from("A completely useless book")),
             multilingual: true,
             src: PathBuf::from("source"),
+            summary: PathBuf::from("SUMMARY.md"),
             language: Some(String::from("ja")),
+            version: None,
         };
         let build_should_be = BuildConfig {
             build_dir: PathBuf::from("outputs"),
             create_missing: false,
             use_default_preprocessors: true,
+            watch_ignore: Vec::new(),
+            extra_watch_dirs: Vec::new(),
+            watch_build_dir: false,
         };
-        let rust_should_be = RustConfig { edition: None };
+        let rust_should_be = RustConfig::default();
         let playground_should_be = Playground {
             editable: true,
             copyable: true,
             copy_js: true,
             line_numbers: false,
+            runner_url: String::from("https://play.rust-lang.org"),
         };
         let html_should_be = HtmlConfig {
             curly_quotes: true,
@@ -783,6 +1291,7 @@ mod tests {
             )),
             authors: vec![String::from("Mathieu David")],
             src: PathBuf::from("./source"),
+            summary: PathBuf::from("SUMMARY.md"),
             ..Default::default()
         };
 
@@ -791,6 +1300,7 @@ mod tests {
 
         let rust_should_be = RustConfig {
             edition: Some(RustEdition::E2015),
+            ..Default::default()
         };
         let got = Config::from_str(src).unwrap();
         assert_eq!(got.rust, rust_should_be);
@@ -810,6 +1320,7 @@ mod tests {
 
         let rust_should_be = RustConfig {
             edition: Some(RustEdition::E2018),
+            ..Default::default()
         };
 
         let got = Config::from_str(src).unwrap();
@@ -893,6 +1404,7 @@ mod tests {
             )),
             authors: vec![String::from("Mathieu David")],
             src: PathBuf::from("./source"),
+            summary: PathBuf::from("SUMMARY.md"),
             ..Default::default()
         };
 
@@ -900,6 +1412,9 @@ mod tests {
             build_dir: PathBuf::from("my-book"),
             create_missing: true,
             use_default_preprocessors: true,
+            watch_ignore: Vec::new(),
+            extra_watch_dirs: Vec::new(),
+            watch_build_dir: false,
         };
 
         let html_should_be = HtmlConfig {
@@ -907,7 +1422,7 @@ mod tests {
             curly_quotes: true,
             google_analytics: Some(String::from("123456")),
             additional_css: vec![PathBuf::from("custom.css"), PathBuf::from("custom2.css")],
-            additional_js: vec![PathBuf::from("custom.js")],
+            additional_js: vec![PathBuf::from("custom.js").into()],
             ..Default::default()
         };
 
@@ -1035,4 +1550,25 @@ mod tests {
         assert_eq!(html_config.input_404, Some("missing.md".to_string()));
         assert_eq!(&get_404_output_file(&html_config.input_404), "missing.html");
     }
+
+    #[test]
+    fn text_direction_from_language() {
+        let inputs = vec![
+            ("en", TextDirection::LeftToRight),
+            ("ar", TextDirection::RightToLeft),
+            ("he-IL", TextDirection::RightToLeft),
+            ("FA_ir", TextDirection::RightToLeft),
+            ("de-CH", TextDirection::LeftToRight),
+            ("", TextDirection::LeftToRight),
+        ];
+
+        for (language, direction) in inputs {
+            assert_eq!(
+                TextDirection::from_language(language),
+                direction,
+                "{}",
+                language
+            );
+        }
+    }
 }
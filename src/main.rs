@@ -4,22 +4,23 @@ extern crate clap;
 extern crate log;
 
 use chrono::Local;
-use clap::{App, AppSettings, ArgMatches};
-use env_logger::Builder;
-use log::LevelFilter;
+use clap::{App, AppSettings, Arg, ArgMatches};
+use env_logger::{Builder, Logger};
+use log::{LevelFilter, Log, Metadata, Record};
 use mdbook::utils;
+use std::cmp;
 use std::env;
 use std::ffi::OsStr;
+use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 mod cmd;
 
 const VERSION: &str = concat!("v", crate_version!());
 
 fn main() {
-    init_logger();
-
     // Create a list of valid arguments and sub-commands
     let app = App::new(crate_name!())
         .about(crate_description!())
@@ -32,9 +33,45 @@ fn main() {
             "For more information about a specific command, try `mdbook <command> --help`\n\
              The source code for mdBook is available at: https://github.com/rust-lang/mdBook",
         )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true)
+                .help("Logs more, debug messages with -v and trace messages with -vv"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .multiple(true)
+                .global(true)
+                .help("Logs less, warnings with -q, only errors with -qq and nothing with -qqq"),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .long("log-level")
+                .value_name("filters")
+                .takes_value(true)
+                .global(true)
+                .help(
+                    "Sets the log level, or filters in the format of RUST_LOG like `mdbook=debug`{n}\
+                     (Overrides -v, -q and the RUST_LOG environment variable)",
+                ),
+        )
+        .arg(
+            Arg::with_name("log-file")
+                .long("log-file")
+                .value_name("file")
+                .takes_value(true)
+                .global(true)
+                .help("Also writes the warnings and errors to this file, replacing it"),
+        )
         .subcommand(cmd::init::make_subcommand())
         .subcommand(cmd::build::make_subcommand())
         .subcommand(cmd::test::make_subcommand())
+        .subcommand(cmd::render::make_subcommand())
         .subcommand(cmd::clean::make_subcommand());
 
     #[cfg(feature = "watch")]
@@ -42,8 +79,11 @@ fn main() {
     #[cfg(feature = "serve")]
     let app = app.subcommand(cmd::serve::make_subcommand());
 
+    let matches = app.get_matches();
+    init_logger(&matches);
+
     // Check which subcomamnd the user ran...
-    let res = match app.get_matches().subcommand() {
+    let res = match matches.subcommand() {
         ("init", Some(sub_matches)) => cmd::init::execute(sub_matches),
         ("build", Some(sub_matches)) => cmd::build::execute(sub_matches),
         ("clean", Some(sub_matches)) => cmd::clean::execute(sub_matches),
@@ -52,6 +92,7 @@ fn main() {
         #[cfg(feature = "serve")]
         ("serve", Some(sub_matches)) => cmd::serve::execute(sub_matches),
         ("test", Some(sub_matches)) => cmd::test::execute(sub_matches),
+        ("render", Some(sub_matches)) => cmd::render::execute(sub_matches),
         (_, _) => unreachable!(),
     };
 
@@ -62,21 +103,31 @@ fn main() {
     }
 }
 
-fn init_logger() {
+/// Sets up the logger, at the level given by `--log-level`, by `-v` and `-q`
+/// relative to the default of info, or by `RUST_LOG`, in that order.
+fn init_logger(matches: &ArgMatches) {
     let mut builder = Builder::new();
 
-    builder.format(|formatter, record| {
-        writeln!(
-            formatter,
-            "{} [{}] ({}): {}",
-            Local::now().format("%Y-%m-%d %H:%M:%S"),
-            record.level(),
-            record.target(),
-            record.args()
-        )
-    });
+    builder.format(|formatter, record| writeln!(formatter, "{}", format_record(record)));
+
+    // Global arguments may be given before or after the subcommand
+    let args = matches.subcommand().1.unwrap_or(matches);
+    let verbose = cmp::max(
+        matches.occurrences_of("verbose"),
+        args.occurrences_of("verbose"),
+    );
+    let quiet = cmp::max(
+        matches.occurrences_of("quiet"),
+        args.occurrences_of("quiet"),
+    );
 
-    if let Ok(var) = env::var("RUST_LOG") {
+    if let Some(filters) = args.value_of("log-level") {
+        builder.parse_filters(filters);
+    } else if verbose > 0 || quiet > 0 {
+        let level = level_filter(verbose, quiet);
+        builder.filter(None, level);
+        builder.filter(Some("html5ever"), cmp::min(level, LevelFilter::Error));
+    } else if let Ok(var) = env::var("RUST_LOG") {
         builder.parse_filters(&var);
     } else {
         // if no RUST_LOG provided, default to logging at the Info level
@@ -85,7 +136,85 @@ fn init_logger() {
         builder.filter(Some("html5ever"), LevelFilter::Error);
     }
 
-    builder.init();
+    let (file, file_error) = match args.value_of("log-file") {
+        Some(path) => match File::create(path) {
+            Ok(file) => (Some(Mutex::new(file)), None),
+            Err(e) => (None, Some((path, e))),
+        },
+        None => (None, None),
+    };
+
+    let logger = TeeLogger {
+        logger: builder.build(),
+        file,
+    };
+    log::set_max_level(logger.logger.filter());
+    log::set_boxed_logger(Box::new(logger)).expect("The logger is only set up once");
+
+    if let Some((path, e)) = file_error {
+        error!("Unable to create the log file {}: {}", path, e);
+        std::process::exit(101);
+    }
+}
+
+fn format_record(record: &Record<'_>) -> String {
+    format!(
+        "{} [{}] ({}): {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+/// Logs to stderr, and the warnings and errors to the `--log-file` as well.
+struct TeeLogger {
+    logger: Logger,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for TeeLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.logger.matches(record) {
+            return;
+        }
+        self.logger.log(record);
+
+        if let Some(ref file) = self.file {
+            if record.level() <= log::Level::Warn {
+                let mut file = file.lock().expect("Logging doesn't panic");
+                // There's nowhere left to report failing to log
+                let _ = writeln!(file, "{}", format_record(record));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+        if let Some(ref file) = self.file {
+            let _ = file.lock().expect("Logging doesn't panic").flush();
+        }
+    }
+}
+
+/// The level for `-v` given `verbose` times and `-q` given `quiet` times.
+fn level_filter(verbose: u64, quiet: u64) -> LevelFilter {
+    const LEVELS: [LevelFilter; 6] = [
+        LevelFilter::Off,
+        LevelFilter::Error,
+        LevelFilter::Warn,
+        LevelFilter::Info,
+        LevelFilter::Debug,
+        LevelFilter::Trace,
+    ];
+    let info = 3;
+
+    let level = (info + verbose).saturating_sub(quiet);
+    LEVELS[cmp::min(level, 5) as usize]
 }
 
 fn get_book_dir(args: &ArgMatches) -> PathBuf {
@@ -0,0 +1,279 @@
+use pulldown_cmark::{html, Event, Tag};
+use regex::Regex;
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::utils;
+
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+
+/// A preprocessor for checking that the anchors of links between chapters,
+/// like `[x](other.md#some-heading)`, match a heading of the chapter they
+/// link to.
+///
+/// The ids of the headings are the ones the HTML renderer gives them, and
+/// ids set in HTML, like `<a id="top">`, count too. Links whose anchor only
+/// differs by case, spaces or punctuation, like `#Some-Heading`, warn with
+/// the id they should use, or are changed to it with
+/// `preprocessor.anchors.normalize = true`. Other links matching no heading
+/// warn with the most similar id, if any.
+#[derive(Default)]
+pub struct AnchorsPreprocessor;
+
+impl AnchorsPreprocessor {
+    pub(crate) const NAME: &'static str = "anchors";
+
+    /// Create a new `AnchorsPreprocessor`.
+    pub fn new() -> Self {
+        AnchorsPreprocessor
+    }
+}
+
+impl Preprocessor for AnchorsPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let normalize: bool = ctx
+            .config
+            .get_preprocessor_option(Self::NAME, "normalize")
+            .with_context(|| "`preprocessor.anchors.normalize` must be a boolean")?
+            .unwrap_or(false);
+
+        let mut anchors = HashMap::new();
+        for item in book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                if let Some(ref path) = ch.path {
+                    anchors.insert(path.clone(), chapter_anchors(&ch.content));
+                }
+            }
+        }
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if let Some(ref path) = ch.path {
+                    let (content, problems) = check_links(&ch.content, path, &anchors, normalize);
+                    for problem in problems {
+                        warn!("{}", problem);
+                    }
+                    ch.content = content;
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// The ids of the headings of a chapter's `content`, and the ids set in its
+/// HTML.
+fn chapter_anchors(content: &str) -> BTreeSet<String> {
+    lazy_static! {
+        static ref HTML_ID: Regex = Regex::new(r#"\sid="([^"]+)""#).unwrap();
+    }
+
+    let mut anchors = BTreeSet::new();
+    let mut id_counter = HashMap::new();
+    let mut heading = None;
+    for event in utils::new_cmark_parser(content) {
+        match event {
+            Event::Start(Tag::Heading(_)) => heading = Some(Vec::new()),
+            Event::End(Tag::Heading(_)) => {
+                let mut text = String::new();
+                html::push_html(&mut text, heading.take().unwrap_or_default().into_iter());
+
+                // Duplicate ids are numbered like the HTML renderer does.
+                let id = utils::id_from_content(&text);
+                let count = id_counter.entry(id.clone()).or_insert(0);
+                anchors.insert(match *count {
+                    0 => id,
+                    count => format!("{}-{}", id, count),
+                });
+                *count += 1;
+            }
+            event => {
+                if let Some(ref mut events) = heading {
+                    events.push(event);
+                }
+            }
+        }
+    }
+
+    anchors.extend(
+        HTML_ID
+            .captures_iter(content)
+            .map(|caps| caps[1].to_string()),
+    );
+    anchors
+}
+
+/// Checks the anchors of the links in the `content` of the chapter at
+/// `page`, returning the content, with the anchors normalized if
+/// `normalize` is set, and the problems found.
+fn check_links(
+    content: &str,
+    page: &Path,
+    anchors: &HashMap<PathBuf, BTreeSet<String>>,
+    normalize: bool,
+) -> (String, Vec<String>) {
+    lazy_static! {
+        static ref SCHEME_LINK: Regex = Regex::new(r"^[a-z][a-z0-9+.-]*:").unwrap();
+    }
+
+    let mut problems = Vec::new();
+    let mut fixed = String::with_capacity(content.len());
+    let mut last = 0;
+    for (event, range) in utils::new_cmark_parser(content).into_offset_iter() {
+        let dest = match event {
+            Event::Start(Tag::Link(_, dest, _)) => dest,
+            _ => continue,
+        };
+        let hash = match dest.find('#') {
+            Some(hash) if !SCHEME_LINK.is_match(&dest) && !dest.starts_with("//") => hash,
+            _ => continue,
+        };
+        let (target, anchor) = (&dest[..hash], &dest[hash + 1..]);
+        let target = if target.is_empty() {
+            page.to_path_buf()
+        } else {
+            PathBuf::from(utils::resolve_in_book(Some(page), target))
+        };
+        let ids = match anchors.get(&target) {
+            Some(ids) if !ids.contains(anchor) => ids,
+            _ => continue,
+        };
+
+        let normalized = utils::normalize_id(&anchor.replace("%20", " "));
+        if !ids.contains(&normalized) {
+            problems.push(match closest_id(&normalized, ids) {
+                Some(id) => format!(
+                    "The link to `{}` in {} matches no heading of {}, did you mean `#{}`?",
+                    dest,
+                    page.display(),
+                    target.display(),
+                    id
+                ),
+                None => format!(
+                    "The link to `{}` in {} matches no heading of {}",
+                    dest,
+                    page.display(),
+                    target.display()
+                ),
+            });
+            continue;
+        }
+
+        // The anchor of a reference link is in its definition instead.
+        let link = &content[range.clone()];
+        match link.rfind(&format!("#{})", anchor)) {
+            Some(i) if normalize => {
+                let start = range.start + i + 1;
+                fixed.push_str(&content[last..start]);
+                fixed.push_str(&normalized);
+                last = start + anchor.len();
+            }
+            _ => problems.push(format!(
+                "The link to `{}` in {} should use the anchor `#{}`",
+                dest,
+                page.display(),
+                normalized
+            )),
+        }
+    }
+    fixed.push_str(&content[last..]);
+    (fixed, problems)
+}
+
+/// The id most similar to `anchor`, if any is similar enough.
+fn closest_id<'a>(anchor: &str, ids: &'a BTreeSet<String>) -> Option<&'a str> {
+    let max_distance = std::cmp::max(1, anchor.chars().count() / 3);
+    ids.iter()
+        .map(|id| (edit_distance(anchor, id), id))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, id)| id.as_str())
+}
+
+/// The Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substituted = previous + if ca == cb { 0 } else { 1 };
+            previous = row[j + 1];
+            row[j + 1] = std::cmp::min(substituted, std::cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchors() -> HashMap<PathBuf, BTreeSet<String>> {
+        let mut anchors = HashMap::new();
+        anchors.insert(
+            PathBuf::from("guide/setup.md"),
+            chapter_anchors(
+                "# Setup\n\n## Installing `mdbook`\n\n## Usage\n\n## Usage\n\n<a id=\"top\"></a>\n",
+            ),
+        );
+        anchors.insert(PathBuf::from("intro.md"), chapter_anchors("# Intro\n"));
+        anchors
+    }
+
+    #[test]
+    fn anchors_are_the_ids_of_the_headings() {
+        let ids: Vec<_> = anchors()[Path::new("guide/setup.md")]
+            .iter()
+            .cloned()
+            .collect();
+        assert_eq!(
+            ids,
+            ["installing-mdbook", "setup", "top", "usage", "usage-1"]
+        );
+    }
+
+    #[test]
+    fn mismatched_anchors_warn_with_suggestions() {
+        let content = "[a](setup.md#usage-1) [b](#top) [c](../intro.md#Intro) \
+                       [d](setup.md#instaling-mdbook) [e](setup.md#nothing-like-it) \
+                       [f](https://example.com/setup.md#Usage) [g](missing.md#x)\n";
+        let (fixed, problems) =
+            check_links(content, Path::new("guide/setup.md"), &anchors(), false);
+        assert_eq!(fixed, content);
+        assert_eq!(
+            problems,
+            [
+                "The link to `../intro.md#Intro` in guide/setup.md should use the anchor `#intro`",
+                "The link to `setup.md#instaling-mdbook` in guide/setup.md matches no heading \
+                 of guide/setup.md, did you mean `#installing-mdbook`?",
+                "The link to `setup.md#nothing-like-it` in guide/setup.md matches no heading \
+                 of guide/setup.md",
+            ]
+        );
+    }
+
+    #[test]
+    fn mismatched_anchors_can_be_normalized() {
+        let content = "[Usage](guide/setup.md#Usage) and [#Setup](guide/setup.md#Setup) \
+                       and [ref][r]\n\n[r]: guide/setup.md#USAGE\n";
+        let (fixed, problems) = check_links(content, Path::new("intro.md"), &anchors(), true);
+        assert_eq!(
+            fixed,
+            "[Usage](guide/setup.md#usage) and [#Setup](guide/setup.md#setup) \
+             and [ref][r]\n\n[r]: guide/setup.md#USAGE\n"
+        );
+        assert_eq!(
+            problems,
+            ["The link to `guide/setup.md#USAGE` in intro.md should use the anchor `#usage`"]
+        );
+    }
+}
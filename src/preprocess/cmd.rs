@@ -1,9 +1,11 @@
 use super::{Preprocessor, PreprocessorContext};
 use crate::book::Book;
 use crate::errors::*;
+use crate::utils::process;
 use shlex::Shlex;
 use std::io::{self, Read, Write};
-use std::process::{Child, Command, Stdio};
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
 /// A custom preprocessor which will shell out to a 3rd-party program.
 ///
@@ -12,7 +14,8 @@ use std::process::{Child, Command, Stdio};
 /// When the `supports_renderer()` method is executed, `CmdPreprocessor` will
 /// execute the shell command `$cmd supports $renderer`. If the renderer is
 /// supported, custom preprocessors should exit with a exit code of `0`,
-/// any other exit code be considered as unsupported.
+/// any other exit code be considered as unsupported. A command which runs for
+/// longer than its timeout is killed and considered as unsupported too.
 ///
 /// The `run()` method is implemented by passing a `(PreprocessorContext, Book)`
 /// tuple to the spawned command (`$cmd`) as JSON via `stdin`. Preprocessors
@@ -21,8 +24,9 @@ use std::process::{Child, Command, Stdio};
 /// to parse the input provided by `mdbook`.
 ///
 /// Exiting with a non-zero exit code while preprocessing is considered an
-/// error. `stderr` is passed directly through to the user, so it can be used
-/// for logging or emitting warnings if desired.
+/// error. `stderr` is logged at the debug level once the command has
+/// finished, or shown as part of the error if it failed, so it can be used
+/// for logging if desired.
 ///
 /// # Examples
 ///
@@ -32,12 +36,25 @@ use std::process::{Child, Command, Stdio};
 pub struct CmdPreprocessor {
     name: String,
     cmd: String,
+    timeout: Option<Duration>,
 }
 
 impl CmdPreprocessor {
     /// Create a new `CmdPreprocessor`.
     pub fn new(name: String, cmd: String) -> CmdPreprocessor {
-        CmdPreprocessor { name, cmd }
+        CmdPreprocessor {
+            name,
+            cmd,
+            timeout: None,
+        }
+    }
+
+    /// Kill the command of the `supports` check once it has run for longer
+    /// than `timeout`. `run()` is limited by `preprocessor.<name>.timeout`
+    /// instead, as mdBook also sets this from it.
+    pub fn with_timeout(mut self, timeout: Duration) -> CmdPreprocessor {
+        self.timeout = Some(timeout);
+        self
     }
 
     /// A convenience function custom preprocessors can use to parse the input
@@ -46,16 +63,6 @@ impl CmdPreprocessor {
         serde_json::from_reader(reader).with_context(|| "Unable to parse the input")
     }
 
-    fn write_input_to_child(&self, child: &mut Child, book: &Book, ctx: &PreprocessorContext) {
-        let stdin = child.stdin.take().expect("Child has stdin");
-
-        if let Err(e) = self.write_input(stdin, &book, &ctx) {
-            // Looks like the backend hung up before we could finish
-            // sending it the render context. Log the error and keep going
-            warn!("Error writing the RenderContext to the backend, {}", e);
-        }
-    }
-
     fn write_input<W: Write>(
         &self,
         writer: W,
@@ -94,11 +101,13 @@ impl Preprocessor for CmdPreprocessor {
 
     fn run(&self, ctx: &PreprocessorContext, book: Book) -> Result<Book> {
         let mut cmd = self.command()?;
+        let timeout =
+            process::timeout(&ctx.config, &format!("preprocessor.{}.timeout", self.name))?;
 
         let mut child = cmd
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
+            .stderr(Stdio::piped())
             .spawn()
             .with_context(|| {
                 format!(
@@ -107,20 +116,40 @@ impl Preprocessor for CmdPreprocessor {
                 )
             })?;
 
-        self.write_input_to_child(&mut child, &book, ctx);
-
-        let output = child
-            .wait_with_output()
-            .with_context(|| "Error waiting for the preprocessor to complete")?;
-
-        trace!("{} exited with output: {:?}", self.cmd, output);
-        ensure!(
-            output.status.success(),
-            "The preprocessor exited unsuccessfully"
-        );
+        let mut input = Vec::new();
+        self.write_input(&mut input, &book, ctx)?;
+        process::write_stdin(&mut child, input);
+
+        let output = process::read_to_end(child.stdout.take().expect("Child has stdout"));
+        let stderr = process::read_to_end(child.stderr.take().expect("Child has stderr"));
+
+        let status = match process::wait_timeout(&mut child, timeout)
+            .with_context(|| "Error waiting for the preprocessor to complete")?
+        {
+            Some(status) => status,
+            None => bail!(
+                "The \"{}\" preprocessor (`{}`) didn't finish within {} seconds and was killed",
+                self.name,
+                self.cmd,
+                timeout.unwrap_or_default().as_secs_f64()
+            ),
+        };
+        let output =
+            process::collect(output).with_context(|| "Unable to read the preprocessor's output")?;
+        let stderr =
+            process::collect(stderr).with_context(|| "Unable to read the preprocessor's stderr")?;
+
+        trace!("{} exited with status: {:?}", self.cmd, status);
+        if !status.success() {
+            return Err(process::failure(
+                &format!("The \"{}\" preprocessor", self.name),
+                "The preprocessor exited unsuccessfully".to_string(),
+                &stderr,
+            ));
+        }
+        process::log_stderr(&format!("The \"{}\" preprocessor", self.name), &stderr);
 
-        serde_json::from_slice(&output.stdout)
-            .with_context(|| "Unable to parse the preprocessed book")
+        serde_json::from_slice(&output).with_context(|| "Unable to parse the preprocessed book")
     }
 
     fn supports_renderer(&self, renderer: &str) -> bool {
@@ -148,8 +177,23 @@ impl Preprocessor for CmdPreprocessor {
             .stdin(Stdio::null())
             .stdout(Stdio::inherit())
             .stderr(Stdio::inherit())
-            .status()
-            .map(|status| status.code() == Some(0));
+            .spawn()
+            .and_then(|mut child| process::wait_timeout(&mut child, self.timeout));
+
+        let outcome = match outcome {
+            Ok(Some(status)) => Ok(status.code() == Some(0)),
+            Ok(None) => {
+                warn!(
+                    "The \"{}\" preprocessor didn't finish checking whether it supports \"{}\" \
+                     within {} seconds and was killed",
+                    self.name,
+                    renderer,
+                    self.timeout.unwrap_or_default().as_secs_f64()
+                );
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        };
 
         if let Err(ref e) = outcome {
             if e.kind() == io::ErrorKind::NotFound {
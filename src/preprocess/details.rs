@@ -0,0 +1,195 @@
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use std::ops::Range;
+
+use crate::errors::*;
+use crate::utils;
+
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+
+const DEFAULT_SUMMARY: &str = "Details";
+
+/// A preprocessor for turning fenced `details` blocks into collapsible
+/// `<details>` elements.
+///
+/// The first line of the block is the summary, which is always shown, and the
+/// rest is the markdown revealed by expanding it:
+///
+/// `````markdown
+/// ````details
+/// How do I build the book?
+/// Run:
+///
+/// ```bash
+/// mdbook build
+/// ```
+/// ````
+/// `````
+///
+/// The body is left as markdown between blank lines, so it is rendered like
+/// the rest of the chapter, code blocks and nested `details` blocks included.
+/// Only blocks which aren't inside a list or quote are turned into
+/// `<details>`.
+#[derive(Default)]
+pub struct DetailsPreprocessor;
+
+impl DetailsPreprocessor {
+    pub(crate) const NAME: &'static str = "details";
+
+    /// Create a new `DetailsPreprocessor`.
+    pub fn new() -> Self {
+        DetailsPreprocessor
+    }
+}
+
+impl Preprocessor for DetailsPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, _ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                ch.content = render_details(&ch.content);
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// Replaces the `details` blocks of `content` with `<details>` elements.
+fn render_details(content: &str) -> String {
+    let mut rendered = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut depth = 0;
+    // The range and body of the `details` block currently being read.
+    let mut block: Option<(Range<usize>, String)> = None;
+
+    for (event, range) in utils::new_cmark_parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info)))
+                if depth == 0 && info.trim() == "details" =>
+            {
+                block = Some((range, String::new()));
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::Text(ref text) => {
+                if let Some((_, ref mut body)) = block {
+                    body.push_str(text);
+                }
+            }
+            Event::End(_) => {
+                depth -= 1;
+                if let (0, Some((range, body))) = (depth, block.take()) {
+                    rendered.push_str(&content[last..range.start]);
+                    rendered.push_str(&details_element(&body));
+                    last = range.end;
+                }
+            }
+            _ => {}
+        }
+    }
+    rendered.push_str(&content[last..]);
+    rendered
+}
+
+/// The `<details>` element for the `body` of a `details` block.
+fn details_element(body: &str) -> String {
+    let mut lines = body.splitn(2, '\n');
+    let summary = match lines.next().map(str::trim) {
+        Some(summary) if !summary.is_empty() => summary,
+        _ => DEFAULT_SUMMARY,
+    };
+    let inner = render_details(lines.next().unwrap_or(""));
+
+    // The blank lines end the HTML blocks, so the body in between is
+    // rendered as markdown. The line break after the block's closing fence
+    // is kept, which ends the last one.
+    format!(
+        "<details>\n<summary>{}</summary>\n\n{}\n\n</details>\n",
+        inline_markdown(summary),
+        inner.trim_end()
+    )
+}
+
+/// Renders a single line of markdown without wrapping it in a paragraph.
+fn inline_markdown(line: &str) -> String {
+    let html = utils::render_markdown(line, false);
+    let html = html.trim();
+    if html.starts_with("<p>") && html.ends_with("</p>") {
+        html[3..html.len() - 4].to_string()
+    } else {
+        html.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn details_blocks_become_details_elements() {
+        let content = "# Answers\n\
+                       \n\
+                       ````details\n\
+                       How do I *build* it?\n\
+                       Run:\n\
+                       \n\
+                       ```bash\n\
+                       mdbook build\n\
+                       ```\n\
+                       ````\n\
+                       After.\n";
+        assert_eq!(
+            render_details(content),
+            "# Answers\n\
+             \n\
+             <details>\n\
+             <summary>How do I <em>build</em> it?</summary>\n\
+             \n\
+             Run:\n\
+             \n\
+             ```bash\n\
+             mdbook build\n\
+             ```\n\
+             \n\
+             </details>\n\
+             \n\
+             After.\n"
+        );
+    }
+
+    #[test]
+    fn details_blocks_can_be_nested() {
+        let content = "`````details\n\
+                       \n\
+                       ````details\n\
+                       Inner\n\
+                       Text\n\
+                       ````\n\
+                       `````\n";
+        assert_eq!(
+            render_details(content),
+            "<details>\n\
+             <summary>Details</summary>\n\
+             \n\
+             <details>\n\
+             <summary>Inner</summary>\n\
+             \n\
+             Text\n\
+             \n\
+             </details>\n\
+             \n\
+             </details>\n\
+             \n"
+        );
+    }
+
+    #[test]
+    fn other_code_blocks_are_left_alone() {
+        let content = "```rust\nfn main() {}\n```\n\n> ```details\n> Quoted\n> ```\n";
+        assert_eq!(render_details(content), content);
+    }
+}
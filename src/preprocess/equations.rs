@@ -0,0 +1,184 @@
+use regex::{Captures, Regex};
+use std::path::Path;
+
+use crate::errors::*;
+use crate::utils::{code_ranges, escape_html};
+
+use super::numbering::{numbered_per_chapter, Numbering};
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+
+/// A preprocessor for numbering display equations and resolving references
+/// to them.
+///
+/// A display equation followed by an id, like `\\[ E = mc^2 \\]{#eq:energy}`,
+/// gets a number, shown right-aligned next to it, and `{{#eqref eq:energy}}`
+/// becomes a link to it, like `(3)`. The number is added as HTML around the
+/// equation, never inside the math, so MathJax only sees the equation itself.
+/// `preprocessor.equations.numbering` works like it does for figures.
+/// References to unknown equations are left as they are with a warning, and
+/// equations and references in code are left alone.
+#[derive(Default)]
+pub struct EquationsPreprocessor;
+
+impl EquationsPreprocessor {
+    pub(crate) const NAME: &'static str = "equations";
+
+    /// Create a new `EquationsPreprocessor`.
+    pub fn new() -> Self {
+        EquationsPreprocessor
+    }
+}
+
+impl Preprocessor for EquationsPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let per_chapter = numbered_per_chapter(ctx, Self::NAME)?;
+
+        let mut equations = Numbering::new("equation", per_chapter);
+        for item in book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                equations.add_chapter(ch, equation_ids(&ch.content));
+            }
+        }
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if let Some(ref path) = ch.path {
+                    ch.content = render_equations(&ch.content, path, &equations);
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// Finds the display equations with an id outside of code in `content`.
+fn find_equations(content: &str) -> impl Iterator<Item = Captures<'_>> {
+    lazy_static! {
+        // The id is optional so that an equation without one is never taken
+        // to end at the `\\]` of a later equation.
+        static ref EQUATION: Regex =
+            Regex::new(r"(?s)\\\\\[.*?\\\\\](?:\{#(eq:[^}\s]+)\})?").unwrap();
+    }
+
+    let code = code_ranges(content);
+    EQUATION.captures_iter(content).filter(move |caps| {
+        let start = caps.get(0).unwrap().start();
+        caps.get(1).is_some() && !code.iter().any(|range| range.contains(&start))
+    })
+}
+
+fn equation_ids(content: &str) -> Vec<String> {
+    find_equations(content)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// Numbers the equations in the `content` of the chapter at `page` and turns
+/// the references to them into links.
+fn render_equations(content: &str, page: &Path, equations: &Numbering) -> String {
+    lazy_static! {
+        static ref REFERENCE: Regex = Regex::new(r"\{\{#eqref\s+(eq:[^}\s]+)\s*\}\}").unwrap();
+    }
+
+    let mut rendered = String::with_capacity(content.len());
+    let mut last = 0;
+    let numbers = equations.chapter_numbers(page);
+    for (i, caps) in find_equations(content).enumerate() {
+        let whole = caps.get(0).unwrap();
+        let id = caps.get(1).unwrap();
+        let number = numbers
+            .get(i)
+            .map_or_else(String::new, |number| format!("({})", number));
+
+        // The blank lines keep the equation itself markdown, so its
+        // backslashes are unescaped as usual.
+        rendered.push_str(&content[last..whole.start()]);
+        rendered.push_str(&format!(
+            "<div class=\"equation\" id=\"{}\">\n<span class=\"equation-number\">{}</span>\n\n{}\n\n</div>\n",
+            escape_html(id.as_str()),
+            number,
+            &content[whole.start()..id.start() - 2],
+        ));
+        last = whole.end();
+    }
+    rendered.push_str(&content[last..]);
+
+    equations.link_references(&rendered, page, &REFERENCE, |number| {
+        format!("({})", number)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::Chapter;
+
+    const CONTENT: &str = "# Energy\n\
+                           \\\\[ E = mc^2 \\\\]{#eq:energy}\n\
+                           \n\
+                           Unnumbered: \\\\[ x \\\\]\n\
+                           \n\
+                           \\\\[\n\
+                           \\begin{cases} a \\\\\\\\ b \\end{cases}\n\
+                           \\\\]{#eq:cases}\n\
+                           \n\
+                           `\\\\[ y \\\\]{#eq:code}`\n\
+                           \n\
+                           From {{#eqref eq:energy}} and {{#eqref eq:cases}}, but not {{#eqref eq:none}}.\n";
+
+    #[test]
+    fn only_equations_with_an_id_are_numbered() {
+        assert_eq!(equation_ids(CONTENT), ["eq:energy", "eq:cases"]);
+    }
+
+    #[test]
+    fn equations_get_numbers_and_references_become_links() {
+        let mut equations = Numbering::new("equation", false);
+        let other = Chapter::new(
+            "Intro",
+            String::from("\\\\[ 1 + 1 = 2 \\\\]{#eq:sum}\n"),
+            "intro.md",
+            vec![],
+        );
+        let energy = Chapter::new("Energy", CONTENT.to_string(), "physics/energy.md", vec![]);
+        for ch in &[other, energy] {
+            equations.add_chapter(ch, equation_ids(&ch.content));
+        }
+
+        let content = format!("{}See {{{{#eqref eq:sum}}}}.\n", CONTENT);
+        assert_eq!(
+            render_equations(&content, Path::new("physics/energy.md"), &equations),
+            "# Energy\n\
+             <div class=\"equation\" id=\"eq:energy\">\n\
+             <span class=\"equation-number\">(2)</span>\n\
+             \n\
+             \\\\[ E = mc^2 \\\\]\n\
+             \n\
+             </div>\n\
+             \n\
+             \n\
+             Unnumbered: \\\\[ x \\\\]\n\
+             \n\
+             <div class=\"equation\" id=\"eq:cases\">\n\
+             <span class=\"equation-number\">(3)</span>\n\
+             \n\
+             \\\\[\n\
+             \\begin{cases} a \\\\\\\\ b \\end{cases}\n\
+             \\\\]\n\
+             \n\
+             </div>\n\
+             \n\
+             \n\
+             `\\\\[ y \\\\]{#eq:code}`\n\
+             \n\
+             From [(2)](#eq:energy) and [(3)](#eq:cases), but not {{#eqref eq:none}}.\n\
+             See [(1)](../intro.md#eq:sum).\n"
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use regex::{Captures, Regex};
+use std::path::Path;
+
+use crate::errors::*;
+use crate::utils::{code_ranges, escape_html};
+
+use super::numbering::{numbered_per_chapter, Numbering};
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+
+const DEFAULT_LABEL: &str = "Figure";
+
+/// A preprocessor for numbering figures and resolving references to them.
+///
+/// An image on a line of its own followed by an id, like
+/// `![A caption](diagram.png){#fig:overview}`, becomes a `<figure>` with a
+/// numbered caption, and `{{#ref fig:overview}}` becomes a link to it, like
+/// `Figure 3`. With `preprocessor.figures.numbering = "chapter"` the numbers
+/// restart in every chapter and are prefixed with its section number,
+/// otherwise they run through the whole book. `preprocessor.figures.label`
+/// replaces the word `Figure`. References to unknown figures are left as they
+/// are with a warning, and figures and references in code are left alone.
+#[derive(Default)]
+pub struct FiguresPreprocessor;
+
+impl FiguresPreprocessor {
+    pub(crate) const NAME: &'static str = "figures";
+
+    /// Create a new `FiguresPreprocessor`.
+    pub fn new() -> Self {
+        FiguresPreprocessor
+    }
+}
+
+impl Preprocessor for FiguresPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let per_chapter = numbered_per_chapter(ctx, Self::NAME)?;
+        let label: String = ctx
+            .config
+            .get_preprocessor_option(Self::NAME, "label")
+            .with_context(|| "`preprocessor.figures.label` must be a string")?
+            .unwrap_or_else(|| DEFAULT_LABEL.to_string());
+
+        let mut figures = Numbering::new("figure", per_chapter);
+        for item in book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                figures.add_chapter(ch, figure_ids(&ch.content));
+            }
+        }
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if let Some(ref path) = ch.path {
+                    ch.content = render_figures(&ch.content, path, &figures, &label);
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// Finds the figures outside of code in `content`.
+fn find_figures(content: &str) -> impl Iterator<Item = Captures<'_>> {
+    lazy_static! {
+        static ref FIGURE: Regex = Regex::new(
+            r#"(?m)^ {0,3}!\[([^\]\n]*)\]\(([^)\s]+)(?:\s+"([^"\n]*)")?\)\{#(fig:[^}\s]+)\}[ \t]*$"#
+        )
+        .unwrap();
+    }
+
+    let code = code_ranges(content);
+    FIGURE.captures_iter(content).filter(move |caps| {
+        let start = caps.get(0).unwrap().start();
+        !code.iter().any(|range| range.contains(&start))
+    })
+}
+
+fn figure_ids(content: &str) -> Vec<String> {
+    find_figures(content)
+        .map(|caps| caps[4].to_string())
+        .collect()
+}
+
+/// Turns the figures in the `content` of the chapter at `page` into
+/// `<figure>`s and the references to them into links.
+fn render_figures(content: &str, page: &Path, figures: &Numbering, label: &str) -> String {
+    lazy_static! {
+        static ref REFERENCE: Regex = Regex::new(r"\{\{#ref\s+(fig:[^}\s]+)\s*\}\}").unwrap();
+    }
+
+    let mut rendered = String::with_capacity(content.len());
+    let mut last = 0;
+    let numbers = figures.chapter_numbers(page);
+    for (i, caps) in find_figures(content).enumerate() {
+        let whole = caps.get(0).unwrap();
+        let caption = escape_html(&caps[1]);
+        let title = caps.get(3).map_or_else(String::new, |t| {
+            format!(" title=\"{}\"", escape_html(t.as_str()))
+        });
+        let number = numbers
+            .get(i)
+            .map_or_else(String::new, |number| format!("{} {}: ", label, number));
+
+        rendered.push_str(&content[last..whole.start()]);
+        rendered.push_str(&format!(
+            "<figure id=\"{}\">\n<img src=\"{}\" alt=\"{}\"{}>\n<figcaption>{}{}</figcaption>\n</figure>\n",
+            escape_html(&caps[4]),
+            escape_html(&caps[2]),
+            caption,
+            title,
+            number,
+            caption,
+        ));
+        last = whole.end();
+    }
+    rendered.push_str(&content[last..]);
+
+    figures.link_references(&rendered, page, &REFERENCE, |number| {
+        format!("{} {}", label, number)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::Chapter;
+
+    fn chapters() -> Vec<Chapter> {
+        vec![
+            Chapter::new(
+                "Intro",
+                String::from(
+                    "# Intro\n\
+                     ![The overview](img/overview.png){#fig:overview}\n\
+                     See {{#ref fig:layers}} and {{#ref fig:overview}}.\n\
+                     \n\
+                     ```\n\
+                     ![Not a figure](x.png){#fig:code}\n\
+                     {{#ref fig:overview}}\n\
+                     ```\n",
+                ),
+                "intro.md",
+                vec![],
+            ),
+            Chapter::new(
+                "Design",
+                String::from(
+                    "![Layers & \"stacks\"](../img/layers.svg \"The layers\"){#fig:layers}\n\
+                     \n\
+                     ![Flow](flow.png){#fig:overview}\n\
+                     As {{#ref fig:overview}} shows, {{#ref fig:missing}} is missing.\n",
+                ),
+                "guide/design.md",
+                vec![],
+            ),
+        ]
+    }
+
+    #[test]
+    fn figures_outside_of_code_are_found() {
+        let chapters = chapters();
+        assert_eq!(figure_ids(&chapters[0].content), ["fig:overview"]);
+        assert_eq!(
+            figure_ids(&chapters[1].content),
+            ["fig:layers", "fig:overview"]
+        );
+    }
+
+    #[test]
+    fn figures_get_captions_and_references_become_links() {
+        let chapters = chapters();
+        let mut figures = Numbering::new("figure", false);
+        for ch in &chapters {
+            figures.add_chapter(ch, figure_ids(&ch.content));
+        }
+
+        assert_eq!(
+            render_figures(
+                &chapters[0].content,
+                Path::new("intro.md"),
+                &figures,
+                "Figure"
+            ),
+            "# Intro\n\
+             <figure id=\"fig:overview\">\n\
+             <img src=\"img/overview.png\" alt=\"The overview\">\n\
+             <figcaption>Figure 1: The overview</figcaption>\n\
+             </figure>\n\
+             \n\
+             See [Figure 2](guide/design.md#fig:layers) and [Figure 1](#fig:overview).\n\
+             \n\
+             ```\n\
+             ![Not a figure](x.png){#fig:code}\n\
+             {{#ref fig:overview}}\n\
+             ```\n"
+        );
+        // The duplicate id keeps its number, but references go to the first.
+        assert_eq!(
+            render_figures(
+                &chapters[1].content,
+                Path::new("guide/design.md"),
+                &figures,
+                "Fig."
+            ),
+            "<figure id=\"fig:layers\">\n\
+             <img src=\"../img/layers.svg\" alt=\"Layers &amp; &quot;stacks&quot;\" title=\"The layers\">\n\
+             <figcaption>Fig. 2: Layers &amp; &quot;stacks&quot;</figcaption>\n\
+             </figure>\n\
+             \n\
+             \n\
+             <figure id=\"fig:overview\">\n\
+             <img src=\"flow.png\" alt=\"Flow\">\n\
+             <figcaption>Fig. 3: Flow</figcaption>\n\
+             </figure>\n\
+             \n\
+             As [Fig. 1](../intro.md#fig:overview) shows, {{#ref fig:missing}} is missing.\n"
+        );
+    }
+}
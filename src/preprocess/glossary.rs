@@ -0,0 +1,277 @@
+use regex::Regex;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::utils;
+
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+use pulldown_cmark::{Event, Tag};
+
+const DEFAULT_FILE: &str = "glossary.md";
+
+/// A preprocessor for linking the terms of a glossary to their definitions.
+///
+/// The glossary is the chapter at `preprocessor.glossary.file`, relative to
+/// the source directory (`glossary.md` by default), and every heading below
+/// its title is a term. The first use of each term in every other chapter is
+/// turned into a link to the term's heading. Terms are matched ignoring case,
+/// and only as whole words. Code, links, images and headings are left alone.
+#[derive(Default)]
+pub struct GlossaryPreprocessor;
+
+impl GlossaryPreprocessor {
+    pub(crate) const NAME: &'static str = "glossary";
+
+    /// Create a new `GlossaryPreprocessor`.
+    pub fn new() -> Self {
+        GlossaryPreprocessor
+    }
+}
+
+impl Preprocessor for GlossaryPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let file: PathBuf = ctx
+            .config
+            .get_preprocessor_option(Self::NAME, "file")
+            .with_context(|| "`preprocessor.glossary.file` must be a path")?
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_FILE));
+
+        let mut glossary = None;
+        for item in book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                if ch.path.as_ref() == Some(&file) {
+                    glossary = Glossary::new(&file, &ch.content);
+                }
+            }
+        }
+        let glossary = match glossary {
+            Some(glossary) => glossary,
+            None => {
+                warn!(
+                    "The glossary {} isn't a chapter of the book or defines no terms",
+                    file.display()
+                );
+                return Ok(book);
+            }
+        };
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if let Some(ref path) = ch.path {
+                    if *path != glossary.path {
+                        ch.content = link_terms(&ch.content, path, &glossary);
+                    }
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// The terms of a glossary and the anchors of their definitions.
+#[derive(Debug)]
+struct Glossary {
+    path: PathBuf,
+    /// The anchors, keyed by the lowercase terms.
+    anchors: HashMap<String, String>,
+    /// Matches any of the terms, preferring the longest.
+    pattern: Regex,
+}
+
+impl Glossary {
+    /// Reads the terms from the headings of the glossary `content`, returning
+    /// `None` if there are none.
+    fn new(path: &Path, content: &str) -> Option<Glossary> {
+        let mut terms = Vec::new();
+        let mut anchors = HashMap::new();
+        let mut current: Option<String> = None;
+
+        for event in utils::new_cmark_parser(content) {
+            match event {
+                Event::Start(Tag::Heading(level)) if level > 1 => current = Some(String::new()),
+                Event::Text(text) | Event::Code(text) => {
+                    if let Some(ref mut heading) = current {
+                        heading.push_str(&text);
+                    }
+                }
+                Event::End(Tag::Heading(_)) => {
+                    if let Some(term) = current.take() {
+                        let term = term.trim();
+                        let key = term.to_lowercase();
+                        if !term.is_empty() && !anchors.contains_key(&key) {
+                            anchors.insert(key, utils::id_from_content(term));
+                            terms.push(term.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if terms.is_empty() {
+            return None;
+        }
+
+        // Only insist on a word boundary where the term itself starts or ends
+        // with a word character, so terms like `C++` still match.
+        let boundary = |c: Option<char>| match c {
+            Some(c) if c.is_alphanumeric() || c == '_' => r"\b",
+            _ => "",
+        };
+        terms.sort_by_key(|term| Reverse(term.chars().count()));
+        let alternatives: Vec<_> = terms
+            .iter()
+            .map(|term| {
+                format!(
+                    "{}{}{}",
+                    boundary(term.chars().next()),
+                    regex::escape(term),
+                    boundary(term.chars().last())
+                )
+            })
+            .collect();
+        let pattern = Regex::new(&format!("(?i){}", alternatives.join("|")))
+            .expect("The escaped terms always form a valid regex");
+
+        Some(Glossary {
+            path: path.to_path_buf(),
+            anchors,
+            pattern,
+        })
+    }
+}
+
+/// Whether terms within `tag` are left alone.
+fn is_skipped(tag: &Tag<'_>) -> bool {
+    match *tag {
+        Tag::Heading(_) | Tag::Link(..) | Tag::Image(..) | Tag::CodeBlock(_) => true,
+        _ => false,
+    }
+}
+
+/// Links the first use of each term of the `glossary` in the `content` of
+/// the chapter at `page`.
+fn link_terms(content: &str, page: &Path, glossary: &Glossary) -> String {
+    let link = utils::fs::relative_link(page, &glossary.path);
+    let mut linked = HashSet::new();
+    let mut edits = Vec::new();
+    let mut skipping = 0;
+
+    for (event, range) in utils::new_cmark_parser(content).into_offset_iter() {
+        match event {
+            Event::Start(ref tag) if is_skipped(tag) => skipping += 1,
+            Event::End(ref tag) if is_skipped(tag) => skipping -= 1,
+            // Text which doesn't appear verbatim in the source, like escapes
+            // and entities, can't be linked in place.
+            Event::Text(ref text) if skipping == 0 && content[range.clone()] == **text => {
+                for m in glossary.pattern.find_iter(text) {
+                    let key = m.as_str().to_lowercase();
+                    if let Some(anchor) = glossary.anchors.get(&key) {
+                        if linked.insert(key) {
+                            let start = range.start + m.start();
+                            edits.push((start..range.start + m.end(), anchor));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut linked_content = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, anchor) in edits {
+        linked_content.push_str(&content[last..range.start]);
+        linked_content.push_str(&format!(
+            "[{}]({}#{})",
+            &content[range.clone()],
+            link,
+            anchor
+        ));
+        last = range.end;
+    }
+    linked_content.push_str(&content[last..]);
+    linked_content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glossary() -> Glossary {
+        let content = "# Glossary\n\
+                       \n\
+                       ## Crate\n\
+                       A compilation unit.\n\
+                       \n\
+                       ## Crate root\n\
+                       The source file a crate is compiled from.\n\
+                       \n\
+                       ## C++\n\
+                       Another language.\n";
+        Glossary::new(Path::new("reference/glossary.md"), content).unwrap()
+    }
+
+    #[test]
+    fn terms_are_read_from_the_headings() {
+        let glossary = glossary();
+        assert_eq!(glossary.anchors.len(), 3);
+        assert_eq!(glossary.anchors["crate"], "crate");
+        assert_eq!(glossary.anchors["crate root"], "crate-root");
+        assert_eq!(glossary.anchors["c++"], "c");
+
+        assert!(Glossary::new(Path::new("glossary.md"), "# Glossary\n\nNothing.\n").is_none());
+    }
+
+    #[test]
+    fn only_the_first_use_of_a_term_is_linked() {
+        let content = "Every crate has crates it depends on.\n\nThat crate is a crate.\n";
+        assert_eq!(
+            link_terms(content, Path::new("guide/intro.md"), &glossary()),
+            "Every [crate](../reference/glossary.md#crate) has crates it depends on.\n\n\
+             That crate is a crate.\n"
+        );
+    }
+
+    #[test]
+    fn terms_are_matched_ignoring_case_and_preferring_longer_ones() {
+        let content = "The Crate Root of a CRATE, written in c++ or Rust.";
+        assert_eq!(
+            link_terms(content, Path::new("reference/index.md"), &glossary()),
+            "The [Crate Root](glossary.md#crate-root) of a [CRATE](glossary.md#crate), \
+             written in [c++](glossary.md#c) or Rust."
+        );
+    }
+
+    #[test]
+    fn terms_in_code_links_and_headings_are_left_alone() {
+        let content = "# A crate\n\
+                       \n\
+                       `crate` and [the crate](crates.md) and ![crate](crate.png)\n\
+                       \n\
+                       ```\n\
+                       crate\n\
+                       ```\n\
+                       \n\
+                       A subcrate, crate_name or &amp;crate is a crate.\n";
+        assert_eq!(
+            link_terms(content, Path::new("intro.md"), &glossary()),
+            "# A crate\n\
+             \n\
+             `crate` and [the crate](crates.md) and ![crate](crate.png)\n\
+             \n\
+             ```\n\
+             crate\n\
+             ```\n\
+             \n\
+             A subcrate, crate_name or &amp;[crate](reference/glossary.md#crate) is a crate.\n"
+        );
+    }
+}
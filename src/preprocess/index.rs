@@ -44,6 +44,12 @@ impl Preprocessor for IndexPreprocessor {
 
         Ok(book)
     }
+
+    /// Only the HTML renderer turns `index.md` into the `index.html` page of a
+    /// directory, other renderers get the chapters' original paths.
+    fn supports_renderer(&self, renderer: &str) -> bool {
+        renderer == "html"
+    }
 }
 
 fn warn_readme_name_conflict<P: AsRef<Path>>(readme_path: P, index_path: P) {
@@ -1,7 +1,9 @@
+use crate::config::MarkdownConfig;
 use crate::errors::*;
+use crate::utils::table::data_table;
 use crate::utils::{
-    take_anchored_lines, take_lines, take_rustdoc_include_anchored_lines,
-    take_rustdoc_include_lines,
+    anchor_start_line, dedent, take_anchored_lines, take_lines,
+    take_rustdoc_include_anchored_lines, take_rustdoc_include_lines,
 };
 use regex::{CaptureMatches, Captures, Regex};
 use std::fs;
@@ -9,20 +11,28 @@ use std::ops::{Bound, Range, RangeBounds, RangeFrom, RangeFull, RangeTo};
 use std::path::{Path, PathBuf};
 
 use super::{Preprocessor, PreprocessorContext};
-use crate::book::{Book, BookItem};
+use crate::book::{Book, BookItem, SourceLine};
 
 const ESCAPE_CHAR: char = '\\';
-const MAX_LINK_NESTED_DEPTH: usize = 10;
+const DEFAULT_MAX_LINK_NESTED_DEPTH: usize = 10;
 
 /// A preprocessor for expanding helpers in a chapter. Supported helpers are:
 ///
 /// - `{{# include}}` - Insert an external file of any type. Include the whole file, only particular
-///.  lines, or only between the specified anchors.
+///   lines, or only between the specified anchors. With the `dedent` option, the indentation
+///   the lines have in common is removed. With the `table` option, a CSV file or a JSON array of
+///   objects is turned into a markdown table.
 /// - `{{# rustdoc_include}}` - Insert an external Rust file, showing the particular lines
 ///.  specified or the lines between specified anchors, and include the rest of the file behind `#`.
 ///   This hides the lines from initial display but shows them when the reader expands the code
 ///   block and provides them to Rustdoc for testing.
 /// - `{{# playground}}` - Insert runnable Rust files
+/// - `{{# title}}` - Override the title of the chapter's page, stored in
+///   [`Chapter::title`](crate::book::Chapter::title).
+/// - `{{# version}}` - Insert `book.version`, or the `package.version` of a `Cargo.toml` file.
+///
+/// The lines of the included files are recorded in
+/// [`Chapter::source_lines`](crate::book::Chapter::source_lines).
 #[derive(Default)]
 pub struct LinkPreprocessor;
 
@@ -41,9 +51,33 @@ impl Preprocessor for LinkPreprocessor {
     }
 
     fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        if ctx.renderer == "markdown" {
+            let markdown: MarkdownConfig = ctx
+                .config
+                .get_deserialized_opt("output.markdown")
+                .with_context(|| "Parsing configuration [output.markdown]")?
+                .unwrap_or_default();
+            if !markdown.expand_includes {
+                return Ok(book);
+            }
+        }
+
         let src_dir = ctx.root.join(&ctx.config.book.src);
+        let max_depth = match ctx.config.get("preprocessor.links.max-depth") {
+            Some(value) => value
+                .as_integer()
+                .filter(|depth| *depth >= 0)
+                .map(|depth| depth as usize)
+                .with_context(|| "`preprocessor.links.max-depth` must be a non-negative integer")?,
+            None => DEFAULT_MAX_LINK_NESTED_DEPTH,
+        };
+        let book_version = ctx.config.book.version.as_ref();
+        let mut error = None;
 
         book.for_each_mut(|section: &mut BookItem| {
+            if error.is_some() {
+                return;
+            }
             if let BookItem::Chapter(ref mut ch) = *section {
                 if let Some(ref chapter_path) = ch.path {
                     let base = chapter_path
@@ -51,17 +85,119 @@ impl Preprocessor for LinkPreprocessor {
                         .map(|dir| src_dir.join(dir))
                         .expect("All book items have a parent");
 
-                    let content = replace_all(&ch.content, base, chapter_path, 0);
-                    ch.content = content;
+                    let mut includes = IncludeStack::new(&src_dir, max_depth);
+                    includes.push(src_dir.join(chapter_path));
+                    let mut title = None;
+                    let version = book_version.map(String::as_str);
+                    let source = ch.source_path.as_ref().unwrap_or(chapter_path);
+                    let first_line = ch.source_of(1).map_or(1, |(_, line)| line);
+                    match expand_all(
+                        &ch.content,
+                        base,
+                        chapter_path,
+                        (source, first_line),
+                        &mut includes,
+                        &mut title,
+                        version,
+                    ) {
+                        Ok((content, source_lines)) => {
+                            ch.content = content;
+                            ch.source_lines = source_lines;
+                        }
+                        Err(e) => error = Some(e),
+                    }
+                    if title.is_some() {
+                        ch.title = title;
+                    }
                 }
             }
         });
 
-        Ok(book)
+        match error {
+            Some(e) => Err(e),
+            None => Ok(book),
+        }
     }
 }
 
-fn replace_all<P1, P2>(s: &str, path: P1, source: P2, depth: usize) -> String
+/// The files which are currently being included into a chapter, starting with
+/// the chapter itself.
+struct IncludeStack<'a> {
+    src_dir: &'a Path,
+    max_depth: usize,
+    files: Vec<PathBuf>,
+}
+
+impl<'a> IncludeStack<'a> {
+    fn new(src_dir: &'a Path, max_depth: usize) -> Self {
+        IncludeStack {
+            src_dir,
+            max_depth,
+            files: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, file: PathBuf) {
+        self.files.push(fs::canonicalize(&file).unwrap_or(file));
+    }
+
+    fn pop(&mut self) {
+        self.files.pop();
+    }
+
+    /// Checks that `file` can be included by the file at the top of the stack.
+    fn check(&self, file: &Path) -> Result<()> {
+        let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+
+        if let Some(start) = self.files.iter().position(|f| *f == file) {
+            let cycle = self.display(&self.files[start..], &file);
+            bail!("Cyclic include detected: {}", cycle);
+        }
+        if self.files.len() > self.max_depth {
+            let chain = self.display(&self.files, &file);
+            bail!(
+                "Maximum include depth of {} exceeded: {}",
+                self.max_depth,
+                chain
+            );
+        }
+
+        Ok(())
+    }
+
+    fn display(&self, files: &[PathBuf], last: &Path) -> String {
+        files
+            .iter()
+            .map(|f| f.as_path())
+            .chain(std::iter::once(last))
+            .map(|f| self.relative(f).display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    }
+
+    /// The path of `file` relative to the source directory, or its full path
+    /// if it's outside of it.
+    fn relative(&self, file: &Path) -> PathBuf {
+        let src_dir = fs::canonicalize(self.src_dir).unwrap_or_else(|_| self.src_dir.into());
+        let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        match file.strip_prefix(&src_dir) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => file,
+        }
+    }
+}
+
+/// Expands the links in `s`, returning the result and where its lines came
+/// from, with the lines of `s` starting at the line `origin` of a file.
+fn expand_all<P1, P2>(
+    s: &str,
+    path: P1,
+    source: P2,
+    origin: (&Path, usize),
+    includes: &mut IncludeStack<'_>,
+    title: &mut Option<String>,
+    book_version: Option<&str>,
+) -> Result<(String, Vec<SourceLine>)>
 where
     P1: AsRef<Path>,
     P2: AsRef<Path>,
@@ -71,25 +207,92 @@ where
     // we therefore have to store the difference to correct this
     let path = path.as_ref();
     let source = source.as_ref();
+    let (file, first_line) = origin;
     let mut previous_end_index = 0;
     let mut replaced = String::new();
+    let mut source_lines = vec![SourceLine {
+        line: 1,
+        file: file.to_path_buf(),
+        source_line: first_line,
+    }];
 
     for link in find_links(s) {
         replaced.push_str(&s[previous_end_index..link.start_index]);
 
+        if let LinkType::Title(ref new_title) = link.link_type {
+            *title = Some(new_title.to_string());
+            previous_end_index = link.end_index;
+            continue;
+        }
+        if let LinkType::Version(ref manifest) = link.link_type {
+            match find_version(manifest.as_ref().map(|m| path.join(m)), book_version) {
+                Ok(version) => replaced.push_str(&version),
+                Err(e) => {
+                    warn!("Unable to insert the version for {}: {}", link.link_text, e);
+                    replaced.push_str(link.link_text);
+                }
+            }
+            previous_end_index = link.end_index;
+            continue;
+        }
+
         match link.render_with_path(&path) {
-            Ok(new_content) => {
-                if depth < MAX_LINK_NESTED_DEPTH {
-                    if let Some(rel_path) = link.link_type.relative_path(path) {
-                        replaced.push_str(&replace_all(&new_content, rel_path, source, depth + 1));
-                    } else {
-                        replaced.push_str(&new_content);
+            Ok(rendered) => {
+                if let Some(target) = link.link_type.target_file(path) {
+                    includes.check(&target).with_context(|| {
+                        format!("Unable to expand links in {}", source.display())
+                    })?;
+                    let rel_path = target
+                        .parent()
+                        .expect("Included file should not be /")
+                        .to_path_buf();
+                    let target_file = includes.relative(&target);
+                    includes.push(target);
+                    let expanded = expand_all(
+                        &rendered.content,
+                        rel_path,
+                        source,
+                        (&target_file, rendered.first_line),
+                        includes,
+                        title,
+                        book_version,
+                    );
+                    includes.pop();
+                    let (expanded, expanded_lines) = expanded?;
+
+                    if !expanded.is_empty() {
+                        let start = line_count(&replaced) + rendered.header_lines;
+                        for run in expanded_lines {
+                            push_source_line(
+                                &mut source_lines,
+                                SourceLine {
+                                    line: run.line + start - 1,
+                                    ..run
+                                },
+                            );
+                        }
                     }
-                } else {
-                    error!(
-                        "Stack depth exceeded in {}. Check for cyclic includes",
-                        source.display()
+                    replaced.push_str(&expanded);
+
+                    // The rest of the link's line comes from this file again,
+                    // but if the included lines didn't end it, only the lines
+                    // after it can be told apart from them.
+                    let end_line = first_line + s[..link.end_index].matches('\n').count();
+                    let (line, source_line) = if replaced.is_empty() || replaced.ends_with('\n') {
+                        (line_count(&replaced), end_line)
+                    } else {
+                        (line_count(&replaced) + 1, end_line + 1)
+                    };
+                    push_source_line(
+                        &mut source_lines,
+                        SourceLine {
+                            line,
+                            file: file.to_path_buf(),
+                            source_line,
+                        },
                     );
+                } else {
+                    replaced.push_str(&rendered.content);
                 }
                 previous_end_index = link.end_index;
             }
@@ -107,21 +310,123 @@ where
     }
 
     replaced.push_str(&s[previous_end_index..]);
-    replaced
+    Ok((replaced, source_lines))
+}
+
+/// The number of the line the end of `s` is on, counting from 1.
+fn line_count(s: &str) -> usize {
+    s.matches('\n').count() + 1
+}
+
+/// Adds `run` to the end of `source_lines`, replacing the last run if it
+/// starts at the same line.
+fn push_source_line(source_lines: &mut Vec<SourceLine>, run: SourceLine) {
+    if source_lines
+        .last()
+        .map_or(false, |last| last.line == run.line)
+    {
+        source_lines.pop();
+    }
+    source_lines.push(run);
 }
 
 #[derive(PartialEq, Debug, Clone)]
 enum LinkType<'a> {
     Escaped,
-    Include(PathBuf, RangeOrAnchor),
+    Include(PathBuf, RangeOrAnchor, Vec<&'a str>),
     Playground(PathBuf, Vec<&'a str>),
+    Title(&'a str),
     RustdocInclude(PathBuf, RangeOrAnchor),
+    Version(Option<PathBuf>),
 }
 
 #[derive(PartialEq, Debug, Clone)]
 enum RangeOrAnchor {
     Range(LineRange),
     Anchor(String),
+    Item(RustItem),
+}
+
+/// A named item of a Rust file, like `fn:parse_summary`.
+#[derive(PartialEq, Debug, Clone)]
+struct RustItem {
+    kind: String,
+    name: String,
+}
+
+impl RustItem {
+    const KINDS: &'static [&'static str] = &[
+        "fn", "struct", "enum", "union", "trait", "mod", "const", "static", "type", "macro",
+    ];
+
+    /// Finds the lines of the item in `source`.
+    #[cfg(feature = "rust-items")]
+    fn lines(&self, source: &str) -> Result<Range<usize>> {
+        use syn::spanned::Spanned;
+        use syn::{ImplItem, Item, TraitItem};
+
+        fn find(items: &[Item], kind: &str, name: &str) -> Option<proc_macro2::Span> {
+            for item in items {
+                let found = match (kind, item) {
+                    ("fn", Item::Fn(item)) => item.sig.ident == name,
+                    ("struct", Item::Struct(item)) => item.ident == name,
+                    ("enum", Item::Enum(item)) => item.ident == name,
+                    ("union", Item::Union(item)) => item.ident == name,
+                    ("trait", Item::Trait(item)) => item.ident == name,
+                    ("mod", Item::Mod(item)) => item.ident == name,
+                    ("const", Item::Const(item)) => item.ident == name,
+                    ("static", Item::Static(item)) => item.ident == name,
+                    ("type", Item::Type(item)) => item.ident == name,
+                    ("macro", Item::Macro(item)) => {
+                        item.ident.as_ref().map_or(false, |ident| ident == name)
+                    }
+                    _ => false,
+                };
+                if found {
+                    return Some(item.span());
+                }
+
+                // Methods can be found in `impl` blocks and traits.
+                let nested = match item {
+                    Item::Mod(item) => item
+                        .content
+                        .as_ref()
+                        .and_then(|(_, items)| find(items, kind, name)),
+                    Item::Impl(item) if kind == "fn" => item.items.iter().find_map(|i| match i {
+                        ImplItem::Method(method) if method.sig.ident == name => Some(i.span()),
+                        _ => None,
+                    }),
+                    Item::Trait(item) if kind == "fn" => item.items.iter().find_map(|i| match i {
+                        TraitItem::Method(method) if method.sig.ident == name => Some(i.span()),
+                        _ => None,
+                    }),
+                    _ => None,
+                };
+                if nested.is_some() {
+                    return nested;
+                }
+            }
+            None
+        }
+
+        let file = syn::parse_file(source).with_context(|| "Unable to parse the Rust file")?;
+        match find(&file.items, &self.kind, &self.name) {
+            // Lines are numbered from 1
+            Some(span) => Ok(span.start().line - 1..span.end().line),
+            None => bail!("Unable to find `{} {}`", self.kind, self.name),
+        }
+    }
+
+    #[cfg(not(feature = "rust-items"))]
+    fn lines(&self, _source: &str) -> Result<Range<usize>> {
+        bail!(
+            "Unable to include `{} {}`, because mdBook was compiled without the `rust-items` \
+             feature, install it with `cargo install mdbook --features rust-items` to include \
+             Rust items by their name",
+            self.kind,
+            self.name
+        )
+    }
 }
 
 // A range of lines specified with some include directive.
@@ -178,23 +483,17 @@ impl From<RangeFull> for LineRange {
 }
 
 impl<'a> LinkType<'a> {
-    fn relative_path<P: AsRef<Path>>(self, base: P) -> Option<PathBuf> {
+    /// The file inserted by this link, if any.
+    fn target_file<P: AsRef<Path>>(&self, base: P) -> Option<PathBuf> {
         let base = base.as_ref();
         match self {
-            LinkType::Escaped => None,
-            LinkType::Include(p, _) => Some(return_relative_path(base, &p)),
-            LinkType::Playground(p, _) => Some(return_relative_path(base, &p)),
-            LinkType::RustdocInclude(p, _) => Some(return_relative_path(base, &p)),
+            LinkType::Escaped | LinkType::Title(_) | LinkType::Version(_) => None,
+            LinkType::Include(p, _, _) => Some(base.join(p)),
+            LinkType::Playground(p, _) => Some(base.join(p)),
+            LinkType::RustdocInclude(p, _) => Some(base.join(p)),
         }
     }
 }
-fn return_relative_path<P: AsRef<Path>>(base: P, relative: P) -> PathBuf {
-    base.as_ref()
-        .join(relative)
-        .parent()
-        .expect("Included file should not be /")
-        .to_path_buf()
-}
 
 fn parse_range_or_anchor(parts: Option<&str>) -> RangeOrAnchor {
     let mut parts = parts.unwrap_or("").splitn(3, ':').fuse();
@@ -206,6 +505,14 @@ fn parse_range_or_anchor(parts: Option<&str>) -> RangeOrAnchor {
     } else if let Some("") = next_element {
         None
     } else if let Some(anchor) = next_element {
+        if let Some(name) = parts.next() {
+            if RustItem::KINDS.contains(&anchor) {
+                return RangeOrAnchor::Item(RustItem {
+                    kind: String::from(anchor),
+                    name: String::from(name),
+                });
+            }
+        }
         return RangeOrAnchor::Anchor(String::from(anchor));
     } else {
         None
@@ -226,13 +533,13 @@ fn parse_range_or_anchor(parts: Option<&str>) -> RangeOrAnchor {
     }
 }
 
-fn parse_include_path(path: &str) -> LinkType<'static> {
+fn parse_include_path<'a>(path: &str, props: Vec<&'a str>) -> LinkType<'a> {
     let mut parts = path.splitn(2, ':');
 
     let path = parts.next().unwrap().into();
     let range_or_anchor = parse_range_or_anchor(parts.next());
 
-    LinkType::Include(path, range_or_anchor)
+    LinkType::Include(path, range_or_anchor, props)
 }
 
 fn parse_rustdoc_include_path(path: &str) -> LinkType<'static> {
@@ -244,6 +551,30 @@ fn parse_rustdoc_include_path(path: &str) -> LinkType<'static> {
     LinkType::RustdocInclude(path, range_or_anchor)
 }
 
+/// Finds the version for `{{#version}}`, the `package.version` of the
+/// `Cargo.toml` at `manifest` or else `book.version`.
+fn find_version(manifest: Option<PathBuf>, book_version: Option<&str>) -> Result<String> {
+    let manifest = match manifest {
+        Some(manifest) => manifest,
+        None => {
+            return book_version
+                .map(String::from)
+                .with_context(|| "`book.version` isn't set in book.toml");
+        }
+    };
+
+    let contents = fs::read_to_string(&manifest)
+        .with_context(|| format!("Could not read {}", manifest.display()))?;
+    let value: toml::Value = toml::from_str(&contents)
+        .with_context(|| format!("Could not parse {}", manifest.display()))?;
+    value
+        .get("package")
+        .and_then(|package| package.get("version"))
+        .and_then(toml::Value::as_str)
+        .map(String::from)
+        .with_context(|| format!("{} has no `package.version`", manifest.display()))
+}
+
 #[derive(PartialEq, Debug, Clone)]
 struct Link<'a> {
     start_index: usize,
@@ -254,14 +585,15 @@ struct Link<'a> {
 
 impl<'a> Link<'a> {
     fn from_capture(cap: Captures<'a>) -> Option<Link<'a>> {
-        let link_type = match (cap.get(0), cap.get(1), cap.get(2)) {
-            (_, Some(typ), Some(rest)) => {
+        let link_type = match (cap.get(0), cap.get(1), cap.get(2), cap.get(3)) {
+            (_, Some(title), _, _) => Some(LinkType::Title(title.as_str().trim())),
+            (_, _, Some(typ), Some(rest)) => {
                 let mut path_props = rest.as_str().split_whitespace();
                 let file_arg = path_props.next();
                 let props: Vec<&str> = path_props.collect();
 
                 match (typ.as_str(), file_arg) {
-                    ("include", Some(pth)) => Some(parse_include_path(pth)),
+                    ("include", Some(pth)) => Some(parse_include_path(pth, props)),
                     ("playground", Some(pth)) => Some(LinkType::Playground(pth.into(), props)),
                     ("playpen", Some(pth)) => {
                         warn!(
@@ -272,10 +604,12 @@ impl<'a> Link<'a> {
                         Some(LinkType::Playground(pth.into(), props))
                     }
                     ("rustdoc_include", Some(pth)) => Some(parse_rustdoc_include_path(pth)),
+                    ("version", Some(pth)) => Some(LinkType::Version(Some(pth.into()))),
                     _ => None,
                 }
             }
-            (Some(mat), None, None) if mat.as_str().starts_with(ESCAPE_CHAR) => {
+            (_, _, Some(typ), None) if typ.as_str() == "version" => Some(LinkType::Version(None)),
+            (Some(mat), None, None, None) if mat.as_str().starts_with(ESCAPE_CHAR) => {
                 Some(LinkType::Escaped)
             }
             _ => None,
@@ -291,46 +625,101 @@ impl<'a> Link<'a> {
         })
     }
 
-    fn render_with_path<P: AsRef<Path>>(&self, base: P) -> Result<String> {
+    fn item_lines(&self, item: &RustItem, source: &str, target: &Path) -> Result<Range<usize>> {
+        item.lines(source).with_context(|| {
+            format!(
+                "Could not include the item for link {} ({})",
+                self.link_text,
+                target.display(),
+            )
+        })
+    }
+
+    fn render_with_path<P: AsRef<Path>>(&self, base: P) -> Result<Rendered> {
         let base = base.as_ref();
         match self.link_type {
             // omit the escape char
-            LinkType::Escaped => Ok((&self.link_text[1..]).to_owned()),
-            LinkType::Include(ref pat, ref range_or_anchor) => {
+            LinkType::Escaped => Ok(Rendered::new(self.link_text[1..].to_owned(), 1)),
+            // the title is stored in the chapter instead
+            LinkType::Title(_) => Ok(Rendered::new(String::new(), 1)),
+            // the version is inserted by `replace_all`
+            LinkType::Version(_) => Ok(Rendered::new(self.link_text.to_owned(), 1)),
+            LinkType::Include(ref pat, ref range_or_anchor, ref props) => {
                 let target = base.join(pat);
 
-                fs::read_to_string(&target)
-                    .map(|s| match range_or_anchor {
-                        RangeOrAnchor::Range(range) => take_lines(&s, range.clone()),
-                        RangeOrAnchor::Anchor(anchor) => take_anchored_lines(&s, anchor),
-                    })
-                    .with_context(|| {
+                let s = fs::read_to_string(&target).with_context(|| {
+                    format!(
+                        "Could not read file for link {} ({})",
+                        self.link_text,
+                        target.display(),
+                    )
+                })?;
+                let (lines, first_line) = match range_or_anchor {
+                    RangeOrAnchor::Range(range) => {
+                        let first_line = match range.start_bound() {
+                            Bound::Included(&start) => start + 1,
+                            Bound::Excluded(&start) => start + 2,
+                            Bound::Unbounded => 1,
+                        };
+                        (take_lines(&s, range.clone()), first_line)
+                    }
+                    RangeOrAnchor::Anchor(anchor) => {
+                        let first_line = anchor_start_line(&s, anchor).map_or(1, |line| line + 2);
+                        (take_anchored_lines(&s, anchor), first_line)
+                    }
+                    RangeOrAnchor::Item(item) => {
+                        let range = self.item_lines(item, &s, &target)?;
+                        let first_line = range.start + 1;
+                        (take_lines(&s, range), first_line)
+                    }
+                };
+
+                let mut dedented = false;
+                let mut table = false;
+                for prop in props {
+                    match *prop {
+                        "dedent" => dedented = true,
+                        "table" => table = true,
+                        other => warn!("Unknown option `{}` for link {}", other, self.link_text),
+                    }
+                }
+                let lines = if dedented { dedent(&lines) } else { lines };
+                let lines = if table {
+                    data_table(&lines, &target).with_context(|| {
                         format!(
-                            "Could not read file for link {} ({})",
-                            self.link_text,
+                            "Could not include {} as a table for link {}",
                             target.display(),
+                            self.link_text,
                         )
-                    })
+                    })?
+                } else {
+                    lines
+                };
+                Ok(Rendered::new(lines, first_line))
             }
             LinkType::RustdocInclude(ref pat, ref range_or_anchor) => {
                 let target = base.join(pat);
 
-                fs::read_to_string(&target)
-                    .map(|s| match range_or_anchor {
-                        RangeOrAnchor::Range(range) => {
-                            take_rustdoc_include_lines(&s, range.clone())
-                        }
-                        RangeOrAnchor::Anchor(anchor) => {
-                            take_rustdoc_include_anchored_lines(&s, anchor)
-                        }
-                    })
-                    .with_context(|| {
-                        format!(
-                            "Could not read file for link {} ({})",
-                            self.link_text,
-                            target.display(),
-                        )
-                    })
+                let s = fs::read_to_string(&target).with_context(|| {
+                    format!(
+                        "Could not read file for link {} ({})",
+                        self.link_text,
+                        target.display(),
+                    )
+                })?;
+                // The other lines are kept behind `#`, so the whole file is
+                // included.
+                let lines = match range_or_anchor {
+                    RangeOrAnchor::Range(range) => take_rustdoc_include_lines(&s, range.clone()),
+                    RangeOrAnchor::Anchor(anchor) => {
+                        take_rustdoc_include_anchored_lines(&s, anchor)
+                    }
+                    RangeOrAnchor::Item(item) => {
+                        let range = self.item_lines(item, &s, &target)?;
+                        take_rustdoc_include_lines(&s, range)
+                    }
+                };
+                Ok(Rendered::new(lines, 1))
             }
             LinkType::Playground(ref pat, ref attrs) => {
                 let target = base.join(pat);
@@ -343,17 +732,36 @@ impl<'a> Link<'a> {
                     )
                 })?;
                 let ftype = if !attrs.is_empty() { "rust," } else { "rust" };
-                Ok(format!(
-                    "```{}{}\n{}\n```\n",
-                    ftype,
-                    attrs.join(","),
-                    contents
-                ))
+                Ok(Rendered {
+                    content: format!("```{}{}\n{}\n```\n", ftype, attrs.join(","), contents),
+                    first_line: 1,
+                    header_lines: 1,
+                })
             }
         }
     }
 }
 
+/// What a link is replaced with.
+struct Rendered {
+    content: String,
+    /// The line of the included file the content starts at.
+    first_line: usize,
+    /// The lines of the content before the included ones, like the opening
+    /// fence of a playground.
+    header_lines: usize,
+}
+
+impl Rendered {
+    fn new(content: String, first_line: usize) -> Self {
+        Rendered {
+            content,
+            first_line,
+            header_lines: 0,
+        }
+    }
+}
+
 struct LinkIter<'a>(CaptureMatches<'a, 'a>);
 
 impl<'a> Iterator for LinkIter<'a> {
@@ -370,16 +778,20 @@ impl<'a> Iterator for LinkIter<'a> {
 
 fn find_links(contents: &str) -> LinkIter<'_> {
     // lazily compute following regex
-    // r"\\\{\{#.*\}\}|\{\{#([a-zA-Z0-9]+)\s*([a-zA-Z0-9_.\-:/\\\s]+)\}\}")?;
+    // r"\\\{\{#.*\}\}|\{\{#title\s+([^}]+)\}\}|\{\{#([a-zA-Z0-9]+)(?:\s+([a-zA-Z0-9_.\-:/\\\s]+))?\}\}")?;
     lazy_static! {
         static ref RE: Regex = Regex::new(
             r"(?x)                       # insignificant whitespace mode
             \\\{\{\#.*\}\}               # match escaped link
             |                            # or
+            \{\{\s*\#title\s+            # title link opening parens, type and whitespace
+            ([^}]+)                      # the title, which can be any text
+            \s*\}\}                      # whitespace and link closing parens
+            |                            # or
             \{\{\s*                      # link opening parens and whitespace
             \#([a-zA-Z0-9_]+)            # link type
-            \s+                          # separating whitespace
-            ([a-zA-Z0-9\s_.\-:/\\\+]+)   # link target path and space separated properties
+            (?:\s+                       # separating whitespace
+            ([a-zA-Z0-9\s_.\-:/\\\+]+))? # link target path and space separated properties
             \s*\}\}                      # whitespace and link closing parens"
         )
         .unwrap();
@@ -390,6 +802,25 @@ fn find_links(contents: &str) -> LinkIter<'_> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::book::Chapter;
+
+    /// Expands the links in `s`, starting at the first line of `source`.
+    fn replace_all<P1, P2>(
+        s: &str,
+        path: P1,
+        source: P2,
+        includes: &mut IncludeStack<'_>,
+        title: &mut Option<String>,
+        book_version: Option<&str>,
+    ) -> Result<String>
+    where
+        P1: AsRef<Path>,
+        P2: AsRef<Path>,
+    {
+        let source = source.as_ref();
+        expand_all(s, path, source, (source, 1), includes, title, book_version)
+            .map(|(replaced, _)| replaced)
+    }
 
     #[test]
     fn test_replace_all_escaped() {
@@ -403,7 +834,116 @@ mod tests {
         ```hbs
         {{#include file.rs}} << an escaped link!
         ```";
-        assert_eq!(replace_all(start, "", "", 0), end);
+        let mut includes = IncludeStack::new(Path::new(""), DEFAULT_MAX_LINK_NESTED_DEPTH);
+        let mut title = None;
+        assert_eq!(
+            replace_all(start, "", "", &mut includes, &mut title, None).unwrap(),
+            end
+        );
+        assert_eq!(title, None);
+    }
+
+    #[test]
+    fn test_replace_all_title() {
+        let start = "{{#title My Page: A Subtitle }}\n# Heading\n";
+        let mut includes = IncludeStack::new(Path::new(""), DEFAULT_MAX_LINK_NESTED_DEPTH);
+        let mut title = None;
+        assert_eq!(
+            replace_all(start, "", "", &mut includes, &mut title, None).unwrap(),
+            "\n# Heading\n"
+        );
+        assert_eq!(title, Some(String::from("My Page: A Subtitle")));
+    }
+
+    #[test]
+    fn test_replace_all_version() {
+        let start = "mdBook {{#version Cargo.toml}}, book {{ #version }}, \
+                     {{#version missing/Cargo.toml}}";
+        let mut includes = IncludeStack::new(Path::new(""), DEFAULT_MAX_LINK_NESTED_DEPTH);
+        let mut title = None;
+        assert_eq!(
+            replace_all(start, "", "", &mut includes, &mut title, Some("1.2.0")).unwrap(),
+            format!(
+                "mdBook {}, book 1.2.0, {{{{#version missing/Cargo.toml}}}}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        // Without a `book.version` the link is left as it is.
+        assert_eq!(
+            replace_all("{{#version}}", "", "", &mut includes, &mut title, None).unwrap(),
+            "{{#version}}"
+        );
+    }
+
+    #[test]
+    fn included_lines_are_mapped_to_their_files() {
+        let temp = tempfile::Builder::new().prefix("book").tempdir().unwrap();
+        fs::write(
+            temp.path().join("part.md"),
+            "P1\n{{#include code.rs:2:3}}\nP3\n",
+        )
+        .unwrap();
+        fs::write(temp.path().join("code.rs"), "c1\nc2\nc3\nc4\n").unwrap();
+
+        let start = "# Title\n\n{{#include part.md}}\nAfter\n";
+        let mut includes = IncludeStack::new(temp.path(), DEFAULT_MAX_LINK_NESTED_DEPTH);
+        let mut title = None;
+        let (content, source_lines) = expand_all(
+            start,
+            temp.path(),
+            "chapter.md",
+            (Path::new("chapter.md"), 1),
+            &mut includes,
+            &mut title,
+            None,
+        )
+        .unwrap();
+        // Whole files are included without their last line break.
+        assert_eq!(content, "# Title\n\nP1\nc2\nc3\nP3\nAfter\n");
+
+        let source_lines: Vec<_> = source_lines
+            .iter()
+            .map(|run| (run.line, run.file.to_str().unwrap(), run.source_line))
+            .collect();
+        assert_eq!(
+            source_lines,
+            [
+                (1, "chapter.md", 1),
+                (3, "part.md", 1),
+                (4, "code.rs", 2),
+                (6, "part.md", 3),
+                (7, "chapter.md", 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn playground_lines_start_after_the_fence() {
+        let temp = tempfile::Builder::new().prefix("book").tempdir().unwrap();
+        fs::write(temp.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let mut includes = IncludeStack::new(temp.path(), DEFAULT_MAX_LINK_NESTED_DEPTH);
+        let mut title = None;
+        let (content, source_lines) = expand_all(
+            "Run:\n{{#playground main.rs}}\nDone\n",
+            temp.path(),
+            "chapter.md",
+            (Path::new("chapter.md"), 5),
+            &mut includes,
+            &mut title,
+            None,
+        )
+        .unwrap();
+        assert_eq!(content, "Run:\n```rust\nfn main() {}\n```\n\nDone\n");
+
+        let chapter = Chapter {
+            source_lines,
+            ..Default::default()
+        };
+        assert_eq!(chapter.source_of(1), Some((Path::new("chapter.md"), 5)));
+        assert_eq!(chapter.source_of(2), Some((Path::new("chapter.md"), 6)));
+        assert_eq!(chapter.source_of(3), Some((Path::new("main.rs"), 1)));
+        assert_eq!(chapter.source_of(6), Some((Path::new("chapter.md"), 7)));
     }
 
     #[test]
@@ -460,6 +1000,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_find_links_only_titles_take_any_text() {
+        let s = "{{#title Why? A \"Guide\", (2nd edition)}} {{#include \"file.rs\"}} \
+                 {{#playground file.rs?}}";
+        let res = find_links(s).collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            vec![Link {
+                start_index: 0,
+                end_index: 40,
+                link_type: LinkType::Title("Why? A \"Guide\", (2nd edition)"),
+                link_text: "{{#title Why? A \"Guide\", (2nd edition)}}",
+            }]
+        );
+    }
+
     #[test]
     fn test_find_links_with_special_characters() {
         let s = "Some random text with {{#playground foo-bar\\baz/_c++.rs}}...";
@@ -490,7 +1046,8 @@ mod tests {
                 end_index: 48,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(9..20))
+                    RangeOrAnchor::Range(LineRange::from(9..20)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs:10:20}}",
             }]
@@ -509,7 +1066,8 @@ mod tests {
                 end_index: 45,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(9..10))
+                    RangeOrAnchor::Range(LineRange::from(9..10)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs:10}}",
             }]
@@ -528,7 +1086,8 @@ mod tests {
                 end_index: 46,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(9..))
+                    RangeOrAnchor::Range(LineRange::from(9..)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs:10:}}",
             }]
@@ -547,7 +1106,8 @@ mod tests {
                 end_index: 46,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(..20))
+                    RangeOrAnchor::Range(LineRange::from(..20)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs::20}}",
             }]
@@ -566,7 +1126,8 @@ mod tests {
                 end_index: 44,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(..))
+                    RangeOrAnchor::Range(LineRange::from(..)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs::}}",
             }]
@@ -585,7 +1146,8 @@ mod tests {
                 end_index: 42,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(..))
+                    RangeOrAnchor::Range(LineRange::from(..)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs}}",
             }]
@@ -604,13 +1166,56 @@ mod tests {
                 end_index: 49,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Anchor(String::from("anchor"))
+                    RangeOrAnchor::Anchor(String::from("anchor")),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs:anchor}}",
             }]
         );
     }
 
+    #[test]
+    fn test_find_links_version() {
+        let s = "{{#version}} and {{#version ../Cargo.toml}}";
+        let res = find_links(s).collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            vec![
+                Link {
+                    start_index: 0,
+                    end_index: 12,
+                    link_type: LinkType::Version(None),
+                    link_text: "{{#version}}",
+                },
+                Link {
+                    start_index: 17,
+                    end_index: 43,
+                    link_type: LinkType::Version(Some(PathBuf::from("../Cargo.toml"))),
+                    link_text: "{{#version ../Cargo.toml}}",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_links_with_anchor_and_dedent() {
+        let s = "{{#include file.rs:anchor dedent}}";
+        let res = find_links(s).collect::<Vec<_>>();
+        assert_eq!(
+            res,
+            vec![Link {
+                start_index: 0,
+                end_index: 34,
+                link_type: LinkType::Include(
+                    PathBuf::from("file.rs"),
+                    RangeOrAnchor::Anchor(String::from("anchor")),
+                    vec!["dedent"]
+                ),
+                link_text: "{{#include file.rs:anchor dedent}}",
+            }]
+        );
+    }
+
     #[test]
     fn test_find_links_escaped_link() {
         let s = "Some random text with escaped playground \\{{#playground file.rs editable}} ...";
@@ -676,7 +1281,8 @@ mod tests {
                 end_index: 61,
                 link_type: LinkType::Include(
                     PathBuf::from("file.rs"),
-                    RangeOrAnchor::Range(LineRange::from(..))
+                    RangeOrAnchor::Range(LineRange::from(..)),
+                    vec![]
                 ),
                 link_text: "{{#include file.rs}}",
             }
@@ -706,181 +1312,261 @@ mod tests {
 
     #[test]
     fn parse_without_colon_includes_all() {
-        let link_type = parse_include_path("arbitrary");
+        let link_type = parse_include_path("arbitrary", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(RangeFull))
+                RangeOrAnchor::Range(LineRange::from(RangeFull)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_nothing_after_colon_includes_all() {
-        let link_type = parse_include_path("arbitrary:");
+        let link_type = parse_include_path("arbitrary:", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(RangeFull))
+                RangeOrAnchor::Range(LineRange::from(RangeFull)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_two_colons_includes_all() {
-        let link_type = parse_include_path("arbitrary::");
+        let link_type = parse_include_path("arbitrary::", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(RangeFull))
+                RangeOrAnchor::Range(LineRange::from(RangeFull)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_garbage_after_two_colons_includes_all() {
-        let link_type = parse_include_path("arbitrary::NaN");
+        let link_type = parse_include_path("arbitrary::NaN", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(RangeFull))
+                RangeOrAnchor::Range(LineRange::from(RangeFull)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_one_number_after_colon_only_that_line() {
-        let link_type = parse_include_path("arbitrary:5");
+        let link_type = parse_include_path("arbitrary:5", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(4..5))
+                RangeOrAnchor::Range(LineRange::from(4..5)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_one_based_start_becomes_zero_based() {
-        let link_type = parse_include_path("arbitrary:1");
+        let link_type = parse_include_path("arbitrary:1", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(0..1))
+                RangeOrAnchor::Range(LineRange::from(0..1)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_zero_based_start_stays_zero_based_but_is_probably_an_error() {
-        let link_type = parse_include_path("arbitrary:0");
+        let link_type = parse_include_path("arbitrary:0", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(0..1))
+                RangeOrAnchor::Range(LineRange::from(0..1)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_start_only_range() {
-        let link_type = parse_include_path("arbitrary:5:");
+        let link_type = parse_include_path("arbitrary:5:", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(4..))
+                RangeOrAnchor::Range(LineRange::from(4..)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_start_with_garbage_interpreted_as_start_only_range() {
-        let link_type = parse_include_path("arbitrary:5:NaN");
+        let link_type = parse_include_path("arbitrary:5:NaN", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(4..))
+                RangeOrAnchor::Range(LineRange::from(4..)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_end_only_range() {
-        let link_type = parse_include_path("arbitrary::5");
+        let link_type = parse_include_path("arbitrary::5", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(..5))
+                RangeOrAnchor::Range(LineRange::from(..5)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_start_and_end_range() {
-        let link_type = parse_include_path("arbitrary:5:10");
+        let link_type = parse_include_path("arbitrary:5:10", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(4..10))
+                RangeOrAnchor::Range(LineRange::from(4..10)),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_negative_interpreted_as_anchor() {
-        let link_type = parse_include_path("arbitrary:-5");
+        let link_type = parse_include_path("arbitrary:-5", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Anchor("-5".to_string())
+                RangeOrAnchor::Anchor("-5".to_string()),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_floating_point_interpreted_as_anchor() {
-        let link_type = parse_include_path("arbitrary:-5.7");
+        let link_type = parse_include_path("arbitrary:-5.7", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Anchor("-5.7".to_string())
+                RangeOrAnchor::Anchor("-5.7".to_string()),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_anchor_followed_by_colon() {
-        let link_type = parse_include_path("arbitrary:some-anchor:this-gets-ignored");
+        let link_type = parse_include_path("arbitrary:some-anchor:this-gets-ignored", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Anchor("some-anchor".to_string())
+                RangeOrAnchor::Anchor("some-anchor".to_string()),
+                vec![]
             )
         );
     }
 
     #[test]
     fn parse_with_more_than_three_colons_ignores_everything_after_third_colon() {
-        let link_type = parse_include_path("arbitrary:5:10:17:anything:");
+        let link_type = parse_include_path("arbitrary:5:10:17:anything:", vec![]);
         assert_eq!(
             link_type,
             LinkType::Include(
                 PathBuf::from("arbitrary"),
-                RangeOrAnchor::Range(LineRange::from(4..10))
+                RangeOrAnchor::Range(LineRange::from(4..10)),
+                vec![]
             )
         );
     }
+
+    #[test]
+    fn parse_with_rust_item() {
+        let link_type = parse_include_path("src/lib.rs:fn:parse_summary", vec![]);
+        assert_eq!(
+            link_type,
+            LinkType::Include(
+                PathBuf::from("src/lib.rs"),
+                RangeOrAnchor::Item(RustItem {
+                    kind: "fn".to_string(),
+                    name: "parse_summary".to_string(),
+                }),
+                vec![]
+            )
+        );
+
+        // Without a name, or with an unknown kind, it's still an anchor
+        let link_type = parse_include_path("src/lib.rs:fn", vec![]);
+        assert_eq!(
+            link_type,
+            LinkType::Include(
+                PathBuf::from("src/lib.rs"),
+                RangeOrAnchor::Anchor("fn".to_string()),
+                vec![]
+            )
+        );
+        let link_type = parse_include_path("src/lib.rs:function:parse_summary", vec![]);
+        assert_eq!(
+            link_type,
+            LinkType::Include(
+                PathBuf::from("src/lib.rs"),
+                RangeOrAnchor::Anchor("function".to_string()),
+                vec![]
+            )
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "rust-items")]
+    fn rust_items_are_found_by_name() {
+        let source = "use std::fmt;\n\
+                      \n\
+                      /// A summary.\n\
+                      #[derive(Debug)]\n\
+                      pub struct Summary {\n    \
+                          title: String,\n\
+                      }\n\
+                      \n\
+                      mod parser {\n    \
+                          impl super::Summary {\n        \
+                              fn parse(&self) {}\n    \
+                          }\n\
+                      }\n";
+        let item = |kind: &str, name: &str| RustItem {
+            kind: kind.to_string(),
+            name: name.to_string(),
+        };
+
+        assert_eq!(item("struct", "Summary").lines(source).unwrap(), 2..7);
+        assert_eq!(item("mod", "parser").lines(source).unwrap(), 8..13);
+        assert_eq!(item("fn", "parse").lines(source).unwrap(), 10..11);
+
+        let err = item("fn", "Summary").lines(source).unwrap_err();
+        assert_eq!(err.to_string(), "Unable to find `fn Summary`");
+    }
 }
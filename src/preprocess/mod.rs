@@ -1,18 +1,37 @@
 //! Book preprocessing.
 
+pub use self::anchors::AnchorsPreprocessor;
 pub use self::cmd::CmdPreprocessor;
+pub use self::details::DetailsPreprocessor;
+pub use self::equations::EquationsPreprocessor;
+pub use self::figures::FiguresPreprocessor;
+pub use self::glossary::GlossaryPreprocessor;
 pub use self::index::IndexPreprocessor;
 pub use self::links::LinkPreprocessor;
+pub use self::split_headings::SplitHeadingsPreprocessor;
+pub use self::variables::VariablesPreprocessor;
+pub use self::wikilinks::WikilinksPreprocessor;
 
+mod anchors;
 mod cmd;
+mod details;
+mod equations;
+mod figures;
+mod glossary;
 mod index;
 mod links;
+mod numbering;
+mod split_headings;
+mod variables;
+mod wikilinks;
 
 use crate::book::Book;
 use crate::config::Config;
 use crate::errors::*;
 
+use serde::de::DeserializeOwned;
 use std::path::PathBuf;
+use toml::Value;
 
 /// Extra information for a `Preprocessor` to give them more context when
 /// processing a book.
@@ -41,6 +60,53 @@ impl PreprocessorContext {
             __non_exhaustive: (),
         }
     }
+
+    /// Deserializes the table of the book's configuration at `key`, like
+    /// `preprocessor.emoji`, into a `T`.
+    ///
+    /// A missing table is read like an empty one, so a `T` with
+    /// `#[serde(default)]` gets its defaults. Values of the wrong type are an
+    /// error naming their key:
+    ///
+    /// ```rust
+    /// # use mdbook::preprocess::CmdPreprocessor;
+    /// use serde_derive::Deserialize;
+    ///
+    /// #[derive(Debug, Default, Deserialize)]
+    /// #[serde(default)]
+    /// struct EmojiConfig {
+    ///     style: String,
+    ///     size: u32,
+    /// }
+    ///
+    /// let input = r#"[{
+    ///     "root": "/path/to/book",
+    ///     "config": {"book": {}, "preprocessor": {"emoji": {"size": "big"}}},
+    ///     "renderer": "html",
+    ///     "mdbook_version": "0.4.0"
+    /// }, {"sections": [], "__non_exhaustive": null}]"#;
+    /// let (ctx, _book) = CmdPreprocessor::parse_input(input.as_bytes())?;
+    ///
+    /// let err = ctx.config_for::<EmojiConfig>("preprocessor.emoji").unwrap_err();
+    /// assert_eq!(
+    ///     format!("{:#}", err),
+    ///     "Invalid configuration in [preprocessor.emoji]: \
+    ///      invalid type: string \"big\", expected u32 for key `size`"
+    /// );
+    ///
+    /// let config: EmojiConfig = ctx.config_for("preprocessor.unused")?;
+    /// assert_eq!(config.size, 0);
+    /// # Ok::<(), mdbook::errors::Error>(())
+    /// ```
+    pub fn config_for<T: DeserializeOwned>(&self, key: &str) -> Result<T> {
+        let value = match self.config.get(key) {
+            Some(value) => value.clone(),
+            None => Value::Table(Default::default()),
+        };
+        value
+            .try_into()
+            .with_context(|| format!("Invalid configuration in [{}]", key))
+    }
 }
 
 /// An operation which is run immediately after loading a book into memory and
@@ -56,6 +122,11 @@ pub trait Preprocessor {
     /// A hint to `MDBook` whether this preprocessor is compatible with a
     /// particular renderer.
     ///
+    /// `MDBook` asks this before every build for a renderer, and only runs the
+    /// preprocessor if it returns `true`. This is the equivalent of the
+    /// `supports` command of a [`CmdPreprocessor`], and is overridden by the
+    /// `preprocessor.<name>.renderers` option, if set.
+    ///
     /// By default, always returns `true`.
     fn supports_renderer(&self, _renderer: &str) -> bool {
         true
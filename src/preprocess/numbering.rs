@@ -0,0 +1,204 @@
+//! Numbering the figures or equations of a book and resolving references to
+//! them.
+
+use regex::{Captures, Regex};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::utils;
+
+use super::PreprocessorContext;
+use crate::book::Chapter;
+
+/// Reads the `numbering` option of the preprocessor `name`, returning whether
+/// the numbers restart in every chapter.
+pub(super) fn numbered_per_chapter(ctx: &PreprocessorContext, name: &str) -> Result<bool> {
+    let key = format!("preprocessor.{}.numbering", name);
+    match ctx.config.get(&key).map(|value| value.as_str()) {
+        None | Some(Some("book")) => Ok(false),
+        Some(Some("chapter")) => Ok(true),
+        Some(_) => bail!("`{}` must be \"book\" or \"chapter\"", key),
+    }
+}
+
+/// The numbers of the figures or equations of a book.
+#[derive(Debug)]
+pub(super) struct Numbering {
+    /// What is numbered, like `figure`, for warnings.
+    kind: &'static str,
+    per_chapter: bool,
+    /// The number and chapter of everything, keyed by its id.
+    targets: HashMap<String, (String, PathBuf)>,
+    /// The numbers of every chapter, in order.
+    numbers: HashMap<PathBuf, Vec<String>>,
+    /// How many have been numbered so far.
+    count: usize,
+}
+
+impl Numbering {
+    pub(super) fn new(kind: &'static str, per_chapter: bool) -> Self {
+        Numbering {
+            kind,
+            per_chapter,
+            targets: HashMap::new(),
+            numbers: HashMap::new(),
+            count: 0,
+        }
+    }
+
+    /// Numbers the figures or equations with the `ids` of a chapter, in order.
+    pub(super) fn add_chapter<I>(&mut self, ch: &Chapter, ids: I)
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let path = match ch.path {
+            Some(ref path) => path,
+            None => return,
+        };
+        if self.per_chapter {
+            self.count = 0;
+        }
+        let prefix = match ch.number {
+            Some(ref number) if self.per_chapter => number.to_string(),
+            _ => String::new(),
+        };
+
+        for id in ids {
+            self.count += 1;
+            let number = format!("{}{}", prefix, self.count);
+            self.numbers
+                .entry(path.clone())
+                .or_default()
+                .push(number.clone());
+            match self.targets.entry(id) {
+                Entry::Occupied(entry) => warn!(
+                    "The {} id `{}` in {} is already used, references go to the first {}",
+                    self.kind,
+                    entry.key(),
+                    path.display(),
+                    self.kind
+                ),
+                Entry::Vacant(entry) => {
+                    entry.insert((number, path.clone()));
+                }
+            }
+        }
+    }
+
+    /// The number and chapter of the figure or equation with the `id`.
+    pub(super) fn target(&self, id: &str) -> Option<&(String, PathBuf)> {
+        self.targets.get(id)
+    }
+
+    /// The numbers of the chapter at `page`, in order.
+    pub(super) fn chapter_numbers(&self, page: &Path) -> &[String] {
+        self.numbers.get(page).map_or(&[][..], Vec::as_slice)
+    }
+
+    /// Turns the references matched by `reference`, whose first group is the
+    /// id, into links in the `content` of the chapter at `page`. `text` gives
+    /// the text of a link from the number. References in code are left alone.
+    pub(super) fn link_references<F>(
+        &self,
+        content: &str,
+        page: &Path,
+        reference: &Regex,
+        text: F,
+    ) -> String
+    where
+        F: Fn(&str) -> String,
+    {
+        let code = utils::code_ranges(content);
+        reference
+            .replace_all(content, |caps: &Captures<'_>| {
+                let whole = caps.get(0).unwrap();
+                if code.iter().any(|range| range.contains(&whole.start())) {
+                    return whole.as_str().to_string();
+                }
+
+                match self.target(&caps[1]) {
+                    Some((number, path)) => {
+                        let link = if path == page {
+                            String::new()
+                        } else {
+                            utils::fs::relative_link(page, path)
+                        };
+                        format!("[{}]({}#{})", text(number), link, &caps[1])
+                    }
+                    None => {
+                        warn!(
+                            "The reference `{}` in {} doesn't match any {}",
+                            whole.as_str(),
+                            page.display(),
+                            self.kind
+                        );
+                        whole.as_str().to_string()
+                    }
+                }
+            })
+            .into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::book::SectionNumber;
+
+    fn numbering(per_chapter: bool) -> Numbering {
+        let mut numbering = Numbering::new("figure", per_chapter);
+        let mut intro = Chapter::new("Intro", String::new(), "intro.md", vec![]);
+        intro.number = Some(SectionNumber(vec![1]));
+        numbering.add_chapter(&intro, vec![String::from("fig:a")]);
+        let draft = Chapter::new_draft("Draft", vec![]);
+        numbering.add_chapter(&draft, vec![String::from("fig:draft")]);
+        let mut guide = Chapter::new("Guide", String::new(), "guide/index.md", vec![]);
+        guide.number = Some(SectionNumber(vec![2, 1]));
+        let ids = vec!["fig:b", "fig:a", "fig:c"];
+        numbering.add_chapter(&guide, ids.into_iter().map(String::from));
+        numbering
+    }
+
+    #[test]
+    fn numbers_run_through_the_book() {
+        let numbering = numbering(false);
+        assert_eq!(numbering.chapter_numbers(Path::new("intro.md")), ["1"]);
+        assert_eq!(
+            numbering.chapter_numbers(Path::new("guide/index.md")),
+            ["2", "3", "4"]
+        );
+        assert_eq!(
+            numbering.target("fig:a"),
+            Some(&(String::from("1"), PathBuf::from("intro.md")))
+        );
+        assert_eq!(numbering.target("fig:c").unwrap().0, "4");
+        assert_eq!(numbering.target("fig:draft"), None);
+    }
+
+    #[test]
+    fn numbers_can_restart_in_every_chapter() {
+        let numbering = numbering(true);
+        assert_eq!(numbering.chapter_numbers(Path::new("intro.md")), ["1.1"]);
+        assert_eq!(
+            numbering.chapter_numbers(Path::new("guide/index.md")),
+            ["2.1.1", "2.1.2", "2.1.3"]
+        );
+    }
+
+    #[test]
+    fn references_become_links() {
+        let reference = Regex::new(r"\{\{#ref (fig:[a-z]+)\}\}").unwrap();
+        let content = "{{#ref fig:a}}, {{#ref fig:b}}, {{#ref fig:x}} and `{{#ref fig:a}}`";
+        assert_eq!(
+            numbering(false).link_references(
+                content,
+                Path::new("guide/index.md"),
+                &reference,
+                |number| format!("Figure {}", number)
+            ),
+            "[Figure 1](../intro.md#fig:a), [Figure 2](#fig:b), {{#ref fig:x}} and `{{#ref fig:a}}`"
+        );
+    }
+}
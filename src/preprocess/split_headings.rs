@@ -0,0 +1,376 @@
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::utils;
+
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem, Chapter, SectionNumber};
+use pulldown_cmark::{Event, Tag};
+
+const DEFAULT_LEVEL: u32 = 2;
+
+/// A preprocessor for splitting large chapters into virtual sub-chapters,
+/// one for each heading of a certain level.
+///
+/// Only the chapters listed in `preprocessor.split-headings.files` are split,
+/// at the headings of `preprocessor.split-headings.level` (`##` by default).
+/// Everything before the first such heading stays in the chapter itself, and
+/// links to anchors of the file are rewritten to point to the page the
+/// anchor ends up on. The sub-chapters are one directory deeper than the
+/// chapter, so their relative links and images are rewritten as well.
+#[derive(Default)]
+pub struct SplitHeadingsPreprocessor;
+
+impl SplitHeadingsPreprocessor {
+    pub(crate) const NAME: &'static str = "split-headings";
+
+    /// Create a new `SplitHeadingsPreprocessor`.
+    pub fn new() -> Self {
+        SplitHeadingsPreprocessor
+    }
+}
+
+impl Preprocessor for SplitHeadingsPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let files: Vec<PathBuf> = ctx
+            .config
+            .get_preprocessor_option(Self::NAME, "files")
+            .with_context(|| "`preprocessor.split-headings.files` must be a list of paths")?
+            .unwrap_or_default();
+        let level = match ctx.config.get("preprocessor.split-headings.level") {
+            Some(value) => value
+                .as_integer()
+                .filter(|level| (1..=6).contains(level))
+                .map(|level| level as u32)
+                .with_context(|| {
+                    "`preprocessor.split-headings.level` must be a heading level from 1 to 6"
+                })?,
+            None => DEFAULT_LEVEL,
+        };
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if ch.path.as_ref().map_or(false, |path| files.contains(path)) {
+                    split_chapter(ch, level);
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+#[derive(Debug)]
+struct Heading {
+    level: u32,
+    /// Where the heading starts in the chapter's content.
+    start: usize,
+    text: String,
+}
+
+/// Finds all the headings of a chapter.
+fn headings(content: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut current: Option<Heading> = None;
+
+    for (event, range) in utils::new_cmark_parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading(level)) => {
+                current = Some(Heading {
+                    level,
+                    start: range.start,
+                    text: String::new(),
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(ref mut heading) = current {
+                    heading.text.push_str(&text);
+                }
+            }
+            Event::End(Tag::Heading(_)) => headings.extend(current.take()),
+            _ => {}
+        }
+    }
+
+    headings
+}
+
+/// Moves every section of `ch` starting with a heading of `level` into a
+/// sub-chapter of its own, in front of the chapter's existing sub-chapters.
+fn split_chapter(ch: &mut Chapter, level: u32) {
+    let path = ch
+        .path
+        .clone()
+        .expect("Only chapters with a path are split");
+    let headings = headings(&ch.content);
+
+    let split_points: Vec<_> = headings.iter().filter(|h| h.level == level).collect();
+    if split_points.is_empty() {
+        return;
+    }
+
+    // The names and pages of the sub-chapters, along with where they start.
+    let dir = path.with_extension("");
+    let mut slugs = HashMap::new();
+    let mut pages = Vec::new();
+    for heading in &split_points {
+        let mut slug = utils::id_from_content(&heading.text);
+        if slug.is_empty() {
+            slug = String::from("section");
+        }
+        let count = slugs.entry(slug.clone()).or_insert(0);
+        if *count > 0 {
+            slug = format!("{}-{}", slug, count);
+        }
+        *count += 1;
+
+        pages.push((heading.start, heading.text.clone(), dir.join(slug + ".md")));
+    }
+
+    // Every anchor of the file belongs to the page its heading ends up on.
+    let mut anchors = HashMap::new();
+    for heading in &headings {
+        let page = pages
+            .iter()
+            .rev()
+            .find(|&&(start, _, _)| start <= heading.start)
+            .map_or(&path, |(_, _, page)| page);
+        anchors
+            .entry(utils::id_from_content(&heading.text))
+            .or_insert_with(|| page.clone());
+    }
+
+    let content = std::mem::replace(&mut ch.content, String::new());
+    ch.content = rewrite_anchor_links(&content[..pages[0].0], &path, &anchors);
+
+    let mut parent_names = ch.parent_names.clone();
+    parent_names.push(ch.name.clone());
+
+    let mut sub_items = Vec::new();
+    for (i, &(start, ref name, ref page)) in pages.iter().enumerate() {
+        let end = pages.get(i + 1).map_or(content.len(), |next| next.0);
+        sub_items.push(BookItem::Chapter(Chapter {
+            name: name.clone(),
+            content: rewrite_anchor_links(
+                &rewrite_relative_links(&content[start..end], &path, page),
+                page,
+                &anchors,
+            ),
+            path: Some(page.clone()),
+            parent_names: parent_names.clone(),
+            ..Default::default()
+        }));
+    }
+    sub_items.append(&mut ch.sub_items);
+    ch.sub_items = sub_items;
+
+    if let Some(ref number) = ch.number {
+        renumber(&mut ch.sub_items, number);
+    }
+}
+
+/// Gives the chapters in `items` consecutive section numbers below `parent`.
+fn renumber(items: &mut [BookItem], parent: &SectionNumber) {
+    let mut next = 1;
+    for item in items {
+        if let BookItem::Chapter(ref mut ch) = *item {
+            let mut number = parent.clone();
+            number.0.push(next);
+            next += 1;
+            renumber(&mut ch.sub_items, &number);
+            ch.number = Some(number);
+        }
+    }
+}
+
+/// Points links to anchors, like `[see below](#details)`, to the page the
+/// anchor is on, unless that is `page` itself.
+fn rewrite_anchor_links(content: &str, page: &Path, anchors: &HashMap<String, PathBuf>) -> String {
+    lazy_static! {
+        static ref INLINE_LINK: Regex = Regex::new(r"(\]\()#([^)\s]+)").unwrap();
+        static ref LINK_DEFINITION: Regex =
+            Regex::new(r"(?m)^( {0,3}\[[^\]]+\]:[ \t]*)#(\S+)").unwrap();
+    }
+
+    let replace = |caps: &Captures<'_>| match anchors.get(&caps[2]) {
+        Some(target) if target != page => {
+            format!(
+                "{}{}#{}",
+                &caps[1],
+                utils::fs::relative_link(page, target),
+                &caps[2]
+            )
+        }
+        _ => caps[0].to_string(),
+    };
+
+    let content = INLINE_LINK.replace_all(content, &replace);
+    LINK_DEFINITION.replace_all(&content, &replace).into_owned()
+}
+
+/// Points the relative links and images of `content`, which moved from the
+/// chapter at `original` to `page`, to their targets again. Links in inline
+/// HTML and link reference definitions are rewritten too, but not the ones in
+/// code.
+fn rewrite_relative_links(content: &str, original: &Path, page: &Path) -> String {
+    lazy_static! {
+        static ref LINK: Regex = Regex::new(
+            r#"(?m)(\]\(|^ {0,3}\[[^\]]+\]:[ \t]*|\b(?:src|href)\s*=\s*["'])([^\s()<>"'#?]+)"#
+        )
+        .unwrap();
+    }
+
+    let code = utils::code_ranges(content);
+    LINK.replace_all(content, |caps: &Captures<'_>| {
+        let link = &caps[2];
+        let start = caps.get(0).map_or(0, |m| m.start());
+        let keep = code.iter().any(|range| range.contains(&start))
+            || link.starts_with('/')
+            || link.contains(':')
+            || utils::leads_outside_of_book(original, link);
+        let target = utils::resolve_in_book(Some(original), link);
+        if keep || target.is_empty() {
+            return caps[0].to_string();
+        }
+
+        let mut new_link = utils::fs::relative_link(page, Path::new(&target));
+        if link.ends_with('/') {
+            new_link.push('/');
+        }
+        format!("{}{}", &caps[1], new_link)
+    })
+    .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reference_chapter() -> Chapter {
+        let content = "# Reference\n\
+                       See [the syntax](#syntax) and [the options][opts].\n\
+                       \n\
+                       [opts]: #options\n\
+                       \n\
+                       ## Syntax\n\
+                       Some syntax, also see [below](#options).\n\
+                       \n\
+                       ### Details\n\
+                       Back to the [top](#reference).\n\
+                       \n\
+                       ## `Options`\n\
+                       Explained in [details](#details), see [`Options`].\n\
+                       \n\
+                       [`Options`]: #options\n";
+        let mut ch = Chapter::new("Reference", content.to_string(), "ref/index.md", vec![]);
+        ch.number = Some(SectionNumber(vec![2]));
+        ch.sub_items.push(BookItem::Chapter(Chapter::new(
+            "Appendix",
+            String::new(),
+            "ref/appendix.md",
+            vec![String::from("Reference")],
+        )));
+        ch
+    }
+
+    fn sub_chapter(ch: &Chapter, index: usize) -> &Chapter {
+        match ch.sub_items[index] {
+            BookItem::Chapter(ref ch) => ch,
+            ref other => panic!("Expected a chapter, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn chapters_are_split_at_the_headings_of_the_level() {
+        let mut ch = reference_chapter();
+        split_chapter(&mut ch, 2);
+
+        assert_eq!(ch.sub_items.len(), 3);
+        let syntax = sub_chapter(&ch, 0);
+        assert_eq!(syntax.name, "Syntax");
+        assert_eq!(syntax.path, Some(PathBuf::from("ref/index/syntax.md")));
+        assert_eq!(syntax.number, Some(SectionNumber(vec![2, 1])));
+        assert_eq!(syntax.parent_names, vec![String::from("Reference")]);
+        assert!(syntax.content.starts_with("## Syntax\n"));
+        assert!(syntax.content.contains("### Details\n"));
+
+        let options = sub_chapter(&ch, 1);
+        assert_eq!(options.name, "Options");
+        assert_eq!(options.path, Some(PathBuf::from("ref/index/options.md")));
+        assert_eq!(options.number, Some(SectionNumber(vec![2, 2])));
+
+        let appendix = sub_chapter(&ch, 2);
+        assert_eq!(appendix.name, "Appendix");
+        assert_eq!(appendix.number, Some(SectionNumber(vec![2, 3])));
+    }
+
+    #[test]
+    fn anchor_links_point_to_the_new_pages() {
+        let mut ch = reference_chapter();
+        split_chapter(&mut ch, 2);
+
+        assert_eq!(
+            ch.content,
+            "# Reference\n\
+             See [the syntax](index/syntax.md#syntax) and [the options][opts].\n\
+             \n\
+             [opts]: index/options.md#options\n\
+             \n"
+        );
+        assert!(sub_chapter(&ch, 0)
+            .content
+            .contains("also see [below](options.md#options)"));
+        assert!(sub_chapter(&ch, 0)
+            .content
+            .contains("Back to the [top](../index.md#reference)"));
+        assert!(sub_chapter(&ch, 1)
+            .content
+            .contains("Explained in [details](syntax.md#details)"));
+        assert!(sub_chapter(&ch, 1)
+            .content
+            .ends_with("[`Options`]: #options\n"));
+    }
+
+    #[test]
+    fn relative_links_point_to_their_targets_from_the_new_pages() {
+        let content = "# Reference\n\
+                       ![pic](img/a.png) and [other](other.md)\n\
+                       \n\
+                       ## Syntax\n\
+                       ![pic](img/a.png \"A picture\") and [other](other.md#usage)\n\
+                       [top][top], <img src=\"img/b.png\">, [web](https://example.com/a.md)\n\
+                       [root](/index.md) and `[code](other.md)`\n\
+                       \n\
+                       [top]: ../top.md\n";
+        let mut ch = Chapter::new("Reference", content.to_string(), "ref/index.md", vec![]);
+        split_chapter(&mut ch, 2);
+
+        assert!(ch
+            .content
+            .contains("![pic](img/a.png) and [other](other.md)"));
+        assert_eq!(
+            sub_chapter(&ch, 0).content,
+            "## Syntax\n\
+             ![pic](../img/a.png \"A picture\") and [other](../other.md#usage)\n\
+             [top][top], <img src=\"../img/b.png\">, [web](https://example.com/a.md)\n\
+             [root](/index.md) and `[code](other.md)`\n\
+             \n\
+             [top]: ../../top.md\n"
+        );
+    }
+
+    #[test]
+    fn chapters_without_headings_of_the_level_stay_intact() {
+        let mut ch = reference_chapter();
+        let original = ch.clone();
+        split_chapter(&mut ch, 4);
+
+        assert_eq!(ch, original);
+    }
+}
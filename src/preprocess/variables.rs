@@ -0,0 +1,139 @@
+use regex::{Captures, Regex};
+use std::path::Path;
+use toml::Value;
+
+use crate::errors::*;
+use crate::utils::code_ranges;
+
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+
+/// A preprocessor for substituting the variables of the `[variables]` table
+/// of `book.toml` into the chapters.
+///
+/// `{{#var product_name}}` is replaced with the value of
+/// `variables.product_name`, and dots reach into nested tables, like
+/// `{{#var versions.stable}}`. Variables which aren't defined, or which are
+/// arrays or tables, are left as they are with a warning. Variables in code
+/// are left alone.
+#[derive(Default)]
+pub struct VariablesPreprocessor;
+
+impl VariablesPreprocessor {
+    pub(crate) const NAME: &'static str = "variables";
+
+    /// Create a new `VariablesPreprocessor`.
+    pub fn new() -> Self {
+        VariablesPreprocessor
+    }
+}
+
+impl Preprocessor for VariablesPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let variables = match ctx.config.get("variables") {
+            Some(Value::Table(ref variables)) => Value::Table(variables.clone()),
+            Some(_) => bail!("`variables` must be a table"),
+            None => Value::Table(Default::default()),
+        };
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if let Some(ref path) = ch.path {
+                    ch.content = replace_variables(&ch.content, path, &variables);
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// Replaces the variables in the `content` of the chapter at `page`.
+fn replace_variables(content: &str, page: &Path, variables: &Value) -> String {
+    lazy_static! {
+        static ref VARIABLE: Regex = Regex::new(r"\{\{\s*#var\s+([A-Za-z0-9_.-]+)\s*\}\}").unwrap();
+    }
+
+    let code = code_ranges(content);
+    VARIABLE
+        .replace_all(content, |caps: &Captures<'_>| {
+            let whole = caps.get(0).unwrap();
+            if code.iter().any(|range| range.contains(&whole.start())) {
+                return whole.as_str().to_string();
+            }
+
+            let value = caps[1]
+                .split('.')
+                .try_fold(variables, |value, key| value.get(key));
+            match value {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Array(_)) | Some(Value::Table(_)) => {
+                    warn!(
+                        "The variable `{}` in {} is a list or table, not a single value",
+                        &caps[1],
+                        page.display()
+                    );
+                    whole.as_str().to_string()
+                }
+                Some(other) => other.to_string(),
+                None => {
+                    warn!(
+                        "The variable `{}` in {} isn't defined in `[variables]`",
+                        &caps[1],
+                        page.display()
+                    );
+                    whole.as_str().to_string()
+                }
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Value {
+        toml::from_str(
+            "product_name = \"mdBook\"\n\
+             max-depth = 10\n\
+             platforms = [\"linux\"]\n\
+             [versions]\n\
+             stable = \"0.4.5\"\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn defined_variables_are_substituted() {
+        let content = "Welcome to {{#var product_name}} {{ #var versions.stable }}, \
+                       nesting up to {{#var max-depth}} levels.";
+        assert_eq!(
+            replace_variables(content, Path::new("intro.md"), &variables()),
+            "Welcome to mdBook 0.4.5, nesting up to 10 levels."
+        );
+    }
+
+    #[test]
+    fn undefined_variables_stay_literal() {
+        let content = "{{#var product_name}} on {{#var platforms}} since {{#var date}} \
+                       via {{#var versions.nightly}}.";
+        assert_eq!(
+            replace_variables(content, Path::new("intro.md"), &variables()),
+            "mdBook on {{#var platforms}} since {{#var date}} via {{#var versions.nightly}}."
+        );
+    }
+
+    #[test]
+    fn variables_in_code_are_left_alone() {
+        let content = "`{{#var product_name}}`\n\n```\n{{#var product_name}}\n```\n";
+        assert_eq!(
+            replace_variables(content, Path::new("intro.md"), &variables()),
+            content
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::errors::*;
+use crate::utils;
+
+use super::{Preprocessor, PreprocessorContext};
+use crate::book::{Book, BookItem};
+
+/// A preprocessor for turning wiki style links, `[[Page Name]]` or
+/// `[[Page Name|Text]]`, into markdown links.
+///
+/// The page is found by comparing its name, normalized like an HTML id, with
+/// the names and file names of the chapters. `preprocessor.wikilinks.pages`
+/// maps names to the paths of chapters explicitly, relative to the source
+/// directory. Links which match no chapter, or more than one, are left as
+/// plain text with a warning. Links in code are left alone.
+#[derive(Default)]
+pub struct WikilinksPreprocessor;
+
+impl WikilinksPreprocessor {
+    pub(crate) const NAME: &'static str = "wikilinks";
+
+    /// Create a new `WikilinksPreprocessor`.
+    pub fn new() -> Self {
+        WikilinksPreprocessor
+    }
+}
+
+impl Preprocessor for WikilinksPreprocessor {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book> {
+        let explicit: HashMap<String, PathBuf> = ctx
+            .config
+            .get_preprocessor_option(Self::NAME, "pages")
+            .with_context(|| "`preprocessor.wikilinks.pages` must map names to paths")?
+            .unwrap_or_default();
+
+        let mut pages = Pages::default();
+        for item in book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                if let Some(ref path) = ch.path {
+                    pages.add(&ch.name, path);
+                    if let Some(stem) = path.file_stem() {
+                        pages.add(&stem.to_string_lossy(), path);
+                    }
+                }
+            }
+        }
+        for (name, path) in explicit {
+            pages.0.insert(utils::normalize_id(&name), vec![path]);
+        }
+
+        book.for_each_mut(|section: &mut BookItem| {
+            if let BookItem::Chapter(ref mut ch) = *section {
+                if let Some(ref path) = ch.path {
+                    ch.content = replace_wikilinks(&ch.content, path, &pages);
+                }
+            }
+        });
+
+        Ok(book)
+    }
+}
+
+/// The paths of the chapters, keyed by their normalized names.
+#[derive(Debug, Default)]
+struct Pages(HashMap<String, Vec<PathBuf>>);
+
+impl Pages {
+    fn add(&mut self, name: &str, path: &Path) {
+        let paths = self.0.entry(utils::normalize_id(name)).or_default();
+        if !paths.iter().any(|p| p == path) {
+            paths.push(path.to_path_buf());
+        }
+    }
+}
+
+/// Replaces the wikilinks in the `content` of the chapter at `page`.
+fn replace_wikilinks(content: &str, page: &Path, pages: &Pages) -> String {
+    lazy_static! {
+        static ref WIKILINK: Regex =
+            Regex::new(r"\[\[([^\[\]|#\n]+)(#[^\[\]|\n]*)?(?:\|([^\[\]\n]+))?\]\]").unwrap();
+    }
+
+    let code = utils::code_ranges(content);
+    WIKILINK
+        .replace_all(content, |caps: &Captures<'_>| {
+            let whole = caps.get(0).unwrap();
+            if code.iter().any(|range| range.contains(&whole.start())) {
+                return whole.as_str().to_string();
+            }
+
+            let target = caps[1].trim();
+            let anchor = caps.get(2).map_or("", |m| m.as_str());
+            let text = caps.get(3).map_or(target, |m| m.as_str().trim());
+
+            match pages.0.get(&utils::normalize_id(target)).map(Vec::as_slice) {
+                Some([path]) => format!(
+                    "[{}]({}{})",
+                    text,
+                    utils::fs::relative_link(page, path),
+                    anchor
+                ),
+                Some(paths) => {
+                    let paths: Vec<_> = paths.iter().map(|p| p.display().to_string()).collect();
+                    warn!(
+                        "The wikilink `{}` in {} is ambiguous, it could be any of: {}",
+                        whole.as_str(),
+                        page.display(),
+                        paths.join(", ")
+                    );
+                    text.to_string()
+                }
+                None => {
+                    warn!(
+                        "The wikilink `{}` in {} doesn't match any chapter",
+                        whole.as_str(),
+                        page.display()
+                    );
+                    text.to_string()
+                }
+            }
+        })
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages() -> Pages {
+        let mut pages = Pages::default();
+        pages.add("Getting Started", Path::new("guide/getting-started.md"));
+        pages.add("getting-started", Path::new("guide/getting-started.md"));
+        pages.add("Installation", Path::new("guide/install.md"));
+        pages.add("install", Path::new("guide/install.md"));
+        pages.add("Reference", Path::new("reference/index.md"));
+        pages.add("index", Path::new("reference/index.md"));
+        pages.add("Overview", Path::new("overview.md"));
+        pages.add("index", Path::new("index.md"));
+        pages
+    }
+
+    #[test]
+    fn wikilinks_become_markdown_links() {
+        let content = "See [[Getting Started]], [[install|the installation]] and \
+                       [[reference#options|the options]].";
+        assert_eq!(
+            replace_wikilinks(content, Path::new("guide/faq.md"), &pages()),
+            "See [Getting Started](getting-started.md), \
+             [the installation](install.md) and \
+             [the options](../reference/index.md#options)."
+        );
+    }
+
+    #[test]
+    fn unknown_and_ambiguous_wikilinks_stay_text() {
+        let content = "[[Missing Page]] and [[Index|the index]]";
+        assert_eq!(
+            replace_wikilinks(content, Path::new("overview.md"), &pages()),
+            "Missing Page and the index"
+        );
+    }
+
+    #[test]
+    fn wikilinks_in_code_are_left_alone() {
+        let content = "`[[Overview]]`\n\n```\nlet x = a[[0]];\n[[Overview]]\n```\n\n[[Overview]]\n";
+        assert_eq!(
+            replace_wikilinks(content, Path::new("guide/faq.md"), &pages()),
+            "`[[Overview]]`\n\n```\nlet x = a[[0]];\n[[Overview]]\n```\n\n[Overview](../overview.md)\n"
+        );
+    }
+}
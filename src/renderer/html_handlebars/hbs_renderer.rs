@@ -1,27 +1,51 @@
 use crate::book::{Book, BookItem};
-use crate::config::{Config, HtmlConfig, Playground, RustEdition};
+use crate::config::{
+    AdditionalJs, Config, Favicon, HtmlConfig, Playground, RedirectPattern, RustEdition,
+    ScriptPlacement, TextDirection,
+};
 use crate::errors::*;
 use crate::renderer::html_handlebars::helpers;
 use crate::renderer::{RenderContext, Renderer};
 use crate::theme::{self, playground_editor, Theme};
-use crate::utils;
+use crate::utils::{self, escape_html};
 
 use std::borrow::Cow;
-use std::collections::BTreeMap;
-use std::collections::HashMap;
-use std::fs::{self, File};
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
 
 use crate::utils::fs::get_404_output_file;
-use handlebars::Handlebars;
+use crate::utils::git;
+use handlebars::{Handlebars, HelperDef};
 use regex::{Captures, Regex};
 
-#[derive(Default)]
-pub struct HtmlHandlebars;
+/// The HTML renderer.
+///
+/// Clones of a renderer share its search index, which is updated with only the
+/// chapters which changed when the same renderer builds a book again, like
+/// `mdbook serve` does.
+#[derive(Default, Clone)]
+pub struct HtmlHandlebars {
+    helpers: Vec<(String, Arc<dyn HelperDef>)>,
+    #[cfg(feature = "search")]
+    search_index: Arc<std::sync::Mutex<super::search::SearchIndexCache>>,
+}
 
 impl HtmlHandlebars {
     pub fn new() -> Self {
-        HtmlHandlebars
+        HtmlHandlebars::default()
+    }
+
+    /// Register an additional Handlebars helper, which theme templates can
+    /// use like the built-in ones (e.g. `{{currentyear}}`).
+    ///
+    /// Helpers are registered after the built-in helpers, so a helper with
+    /// the name of a built-in one replaces it.
+    pub fn register_helper<H: HelperDef + 'static>(&mut self, name: &str, helper: H) -> &mut Self {
+        self.helpers.push((name.to_string(), Arc::new(helper)));
+        self
     }
 
     fn render_item(
@@ -37,15 +61,45 @@ impl HtmlHandlebars {
             _ => return Ok(()),
         };
 
-        let content = ch.content.clone();
-        let content = utils::render_markdown(&content, ctx.html_config.curly_quotes);
+        let options = utils::RenderOptions {
+            curly_quotes: ch.curly_quotes.unwrap_or(ctx.html_config.curly_quotes),
+            source_path: Some(path),
+            check_links: true,
+            renamed_chapters: Some(ctx.renamed_chapters),
+            ..render_options(&ctx.html_config)
+        };
 
-        let fixed_content = utils::render_markdown_with_path(
-            &ch.content,
-            ctx.html_config.curly_quotes,
-            Some(&path),
-        );
-        print_content.push_str(&fixed_content);
+        // With a `<base>` tag every relative link resolves against the book's
+        // root, so links in the content are rewritten just like on the print page.
+        let render = if is_html_chapter(path) {
+            utils::render_html_with_options
+        } else {
+            utils::render_markdown_with_options
+        };
+        let content = if ctx.html_config.use_base_tag {
+            render(&ch.content, Some(path), &options)
+        } else {
+            render(&ch.content, None, &options)
+        };
+
+        if ctx.html_config.print.includes(path) {
+            if !print_content.is_empty() {
+                if let Some(ref class) = ctx.html_config.print.page_break_class {
+                    print_content.push_str(&format!(r#"<div class="{}"></div>"#, class));
+                }
+            }
+            // The links were already checked when rendering the page itself
+            let options = utils::RenderOptions {
+                check_links: false,
+                ..options.clone()
+            };
+            let fixed_content = render(&ch.content, Some(path), &options);
+            if ctx.html_config.anchor_prefix {
+                print_content.push_str(&prefix_header_ids(&fixed_content, &anchor_prefix(path)));
+            } else {
+                print_content.push_str(&fixed_content);
+            }
+        }
 
         // Update the context with data for this file
         let ctx_path = path
@@ -64,19 +118,67 @@ impl HtmlHandlebars {
             .and_then(serde_json::Value::as_str)
             .unwrap_or("");
 
-        let title = match book_title {
-            "" => ch.name.clone(),
-            _ => ch.name.clone() + " - " + book_title,
+        let title = match (&ch.title, book_title) {
+            (Some(title), _) => title.clone(),
+            (None, "") => ch.name.clone(),
+            (None, _) => ch.name.clone() + " - " + book_title,
         };
 
         ctx.data.insert("path".to_owned(), json!(path));
+        if ctx.html_config.page_toc || ctx.html_config.page_outline_data {
+            let toc = page_toc(&content);
+            if ctx.html_config.page_outline_data {
+                ctx.data
+                    .insert("page_outline".to_owned(), json!(page_outline(&toc)));
+            }
+            if ctx.html_config.page_toc {
+                ctx.data.insert("page_toc".to_owned(), json!(toc));
+            }
+        }
         ctx.data.insert("content".to_owned(), json!(content));
         ctx.data.insert("chapter_title".to_owned(), json!(ch.name));
+        if let Some(hash) = ch.content_hash() {
+            ctx.data.insert("content_hash".to_owned(), json!(hash));
+        }
         ctx.data.insert("title".to_owned(), json!(title));
-        ctx.data.insert(
-            "path_to_root".to_owned(),
-            json!(utils::fs::path_to_root(&path)),
-        );
+        let path_to_root = if let Some(root) = links_root(&ctx.html_config) {
+            root
+        } else if ctx.html_config.use_base_tag {
+            ctx.data
+                .insert("base_url".to_owned(), json!(base_href(path)));
+            String::new()
+        } else {
+            utils::fs::path_to_root(path)
+        };
+        ctx.data
+            .insert("path_to_root".to_owned(), json!(path_to_root));
+        if !ch.tags.is_empty() {
+            let tags: Vec<_> = ch
+                .tags
+                .iter()
+                .map(|tag| {
+                    json!({
+                        "name": tag,
+                        "link": format!("{}tags.html#{}", path_to_root, utils::normalize_id(tag)),
+                    })
+                })
+                .collect();
+            ctx.data.insert("tags".to_owned(), json!(tags));
+        }
+        if let Some(ancestors) = ctx.breadcrumbs.get(path) {
+            let mut crumbs: Vec<_> = ancestors
+                .iter()
+                .map(|crumb| match crumb.link {
+                    Some(ref link) => json!({
+                        "name": crumb.name,
+                        "link": format!("{}{}", path_to_root, link),
+                    }),
+                    None => json!({ "name": crumb.name }),
+                })
+                .collect();
+            crumbs.push(json!({ "name": ch.name, "current": true }));
+            ctx.data.insert("breadcrumbs".to_owned(), json!(crumbs));
+        }
         if let Some(ref section) = ch.number {
             ctx.data
                 .insert("section".to_owned(), json!(section.to_string()));
@@ -86,7 +188,12 @@ impl HtmlHandlebars {
         debug!("Render template");
         let rendered = ctx.handlebars.render("index", &ctx.data)?;
 
-        let rendered = self.post_process(rendered, &ctx.html_config.playground, ctx.edition);
+        let rendered = self.post_process(rendered, &ctx.html_config, ctx.edition, &filepath);
+        let rendered = if ctx.html_config.use_base_tag {
+            fragment_links_to_page(&rendered, &filepath)
+        } else {
+            rendered
+        };
 
         // Write to file
         debug!("Creating {}", filepath.display());
@@ -94,11 +201,26 @@ impl HtmlHandlebars {
 
         if ctx.is_index {
             ctx.data.insert("path".to_owned(), json!("index.md"));
-            ctx.data.insert("path_to_root".to_owned(), json!(""));
+            ctx.data.insert(
+                "path_to_root".to_owned(),
+                json!(links_root(&ctx.html_config).unwrap_or_default()),
+            );
             ctx.data.insert("is_index".to_owned(), json!("true"));
+            if ctx.html_config.use_base_tag {
+                ctx.data.insert("base_url".to_owned(), json!("./"));
+            }
             let rendered_index = ctx.handlebars.render("index", &ctx.data)?;
-            let rendered_index =
-                self.post_process(rendered_index, &ctx.html_config.playground, ctx.edition);
+            let rendered_index = self.post_process(
+                rendered_index,
+                &ctx.html_config,
+                ctx.edition,
+                Path::new("index.html"),
+            );
+            let rendered_index = if ctx.html_config.use_base_tag {
+                fragment_links_to_page(&rendered_index, Path::new("index.html"))
+            } else {
+                rendered_index
+            };
             debug!("Creating index.html from {}", ctx_path);
             utils::fs::write_file(&ctx.destination, "index.html", rendered_index.as_bytes())?;
         }
@@ -132,7 +254,8 @@ impl HtmlHandlebars {
                     .to_string()
             }
         };
-        let html_content_404 = utils::render_markdown(&content_404, html_config.curly_quotes);
+        let html_content_404 =
+            utils::render_markdown_with_options(&content_404, None, &render_options(html_config));
 
         let mut data_404 = data.clone();
         let base_url = if let Some(site_url) = &html_config.site_url {
@@ -151,24 +274,192 @@ impl HtmlHandlebars {
         data_404.insert("content".to_owned(), json!(html_content_404));
         let rendered = handlebars.render("index", &data_404)?;
 
-        let rendered =
-            self.post_process(rendered, &html_config.playground, ctx.config.rust.edition);
         let output_file = get_404_output_file(&html_config.input_404);
+        let rendered = self.post_process(
+            rendered,
+            html_config,
+            ctx.config.rust.edition,
+            Path::new(&output_file),
+        );
         utils::fs::write_file(&destination, output_file, rendered.as_bytes())?;
         debug!("Creating 404.html ✓");
         Ok(())
     }
 
+    /// Renders `tags.html`, which lists the chapters of each tag used in the
+    /// book. Nothing is rendered if no chapter has tags, returns whether the
+    /// page was created.
+    fn render_tags(
+        &self,
+        ctx: &RenderContext,
+        html_config: &HtmlConfig,
+        handlebars: &Handlebars<'_>,
+        data: &serde_json::Map<String, serde_json::Value>,
+    ) -> Result<bool> {
+        // Tags are grouped by their anchor, so e.g. "Async" and "async" share one
+        let mut tags: BTreeMap<String, (&str, Vec<String>)> = BTreeMap::new();
+        for item in ctx.book.iter() {
+            let ch = match item {
+                BookItem::Chapter(ch) if !ch.is_draft_chapter() => ch,
+                _ => continue,
+            };
+            let path = ch.path.as_ref().expect("Checked that path exists above");
+            let link = utils::fs::normalize_path(
+                path.with_extension("html")
+                    .to_str()
+                    .with_context(|| "Could not convert path to str")?,
+            );
+            for tag in &ch.tags {
+                let (_, chapters) = tags
+                    .entry(utils::normalize_id(tag))
+                    .or_insert_with(|| (tag, Vec::new()));
+                let entry = format!(
+                    r#"<li><a href="{}">{}</a></li>"#,
+                    escape_html(&link),
+                    escape_html(&ch.name)
+                );
+                if chapters.last() != Some(&entry) {
+                    chapters.push(entry);
+                }
+            }
+        }
+        if tags.is_empty() {
+            return Ok(false);
+        }
+
+        if ctx.book.iter().any(|item| match item {
+            BookItem::Chapter(ch) => ch.path == Some(PathBuf::from("tags.md")),
+            _ => false,
+        }) {
+            bail!("tags.md is reserved for the tag index of books using tags");
+        }
+
+        let mut content = String::from("<h1>Tags</h1>\n");
+        for (id, (name, chapters)) in &tags {
+            content.push_str(&format!(
+                "<h2 id=\"{id}\"><a class=\"header\" href=\"#{id}\">{name}</a></h2>\n<ul>\n{chapters}\n</ul>\n",
+                id = id,
+                name = escape_html(name),
+                chapters = chapters.join("\n"),
+            ));
+        }
+
+        let mut data = data.clone();
+        data.insert("path".to_owned(), json!("tags.md"));
+        data.insert("path_to_root".to_owned(), json!(""));
+        data.insert("content".to_owned(), json!(content));
+        let title = match ctx.config.book.title {
+            Some(ref book_title) => format!("Tags - {}", book_title),
+            None => String::from("Tags"),
+        };
+        data.insert("title".to_owned(), json!(title));
+        let rendered = handlebars.render("index", &data)?;
+
+        let rendered = self.post_process(
+            rendered,
+            html_config,
+            ctx.config.rust.edition,
+            Path::new("tags.html"),
+        );
+        utils::fs::write_file(&ctx.destination, "tags.html", rendered.as_bytes())?;
+        debug!("Creating tags.html ✓");
+        Ok(true)
+    }
+
+    /// Writes `sitemap.xml`, pointing search engines at every page of the book.
+    fn emit_sitemap(&self, ctx: &RenderContext, site_url: &str, has_tags: bool) -> Result<()> {
+        if !(site_url.starts_with("http://") || site_url.starts_with("https://")) {
+            warn!(
+                "The sitemap needs absolute URLs, but `output.html.site-url` is \"{}\"",
+                site_url
+            );
+        }
+        let base = if site_url.ends_with('/') {
+            site_url.to_string()
+        } else {
+            format!("{}/", site_url)
+        };
+
+        // The book's index is listed as the site itself
+        let mut pages = vec![String::new()];
+        for item in ctx.book.iter() {
+            if let BookItem::Chapter(ch) = item {
+                if let Some(ref path) = ch.path {
+                    let page = path.with_extension("html");
+                    let page = page
+                        .to_str()
+                        .with_context(|| "Could not convert path to str")?;
+                    pages.push(utils::fs::normalize_path(page));
+                }
+            }
+        }
+        if has_tags {
+            pages.push(String::from("tags.html"));
+        }
+
+        let mut sitemap = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+        for page in pages {
+            let url = format!("{}{}", base, page.replace(' ', "%20"));
+            sitemap.push_str(&format!(
+                "  <url>\n    <loc>{}</loc>\n  </url>\n",
+                escape_html(&url)
+            ));
+        }
+        sitemap.push_str("</urlset>\n");
+
+        utils::fs::write_file(&ctx.destination, "sitemap.xml", sitemap.as_bytes())?;
+        debug!("Creating sitemap.xml ✓");
+        Ok(())
+    }
+
     #[cfg_attr(feature = "cargo-clippy", allow(clippy::let_and_return))]
     fn post_process(
         &self,
         rendered: String,
-        playground_config: &Playground,
+        html_config: &HtmlConfig,
         edition: Option<RustEdition>,
+        page: &Path,
     ) -> String {
-        let rendered = build_header_links(&rendered);
+        let permalinks = if html_config.heading_permalinks {
+            Some(page_url(html_config, page))
+        } else {
+            None
+        };
+        let rendered = build_header_links(&rendered, permalinks.as_ref().map(String::as_str));
         let rendered = fix_code_blocks(&rendered);
-        let rendered = add_playground_pre(&rendered, playground_config, edition);
+        let rendered = if html_config.code.wrap {
+            add_wrap_class(&rendered)
+        } else {
+            rendered
+        };
+        let rendered = if html_config.code.line_numbers {
+            add_line_numbers(&rendered)
+        } else {
+            rendered
+        };
+        let rendered = add_playground_pre(&rendered, &html_config.playground, edition);
+        let rendered = if html_config.code.hidelines.is_empty() {
+            rendered
+        } else {
+            hide_custom_lines(&rendered, &html_config.code.hidelines)
+        };
+        let rendered = if html_config.download_extensions.is_empty() {
+            rendered
+        } else {
+            add_download_attributes(
+                &rendered,
+                &html_config.download_extensions,
+                html_config.download_new_tab,
+            )
+        };
+        let rendered = if html_config.normalize_output {
+            normalize_output(&rendered)
+        } else {
+            rendered
+        };
 
         rendered
     }
@@ -192,65 +483,59 @@ impl HtmlHandlebars {
         write_file(destination, "css/chrome.css", &theme.chrome_css)?;
         write_file(destination, "css/print.css", &theme.print_css)?;
         write_file(destination, "css/variables.css", &theme.variables_css)?;
-        if let Some(contents) = &theme.favicon_png {
-            write_file(destination, "favicon.png", &contents)?;
-        }
-        if let Some(contents) = &theme.favicon_svg {
-            write_file(destination, "favicon.svg", &contents)?;
+        if html_config.favicon.is_none() {
+            if let Some(contents) = &theme.favicon_png {
+                write_file(destination, "favicon.png", contents)?;
+            }
+            if let Some(contents) = &theme.favicon_svg {
+                write_file(destination, "favicon.svg", contents)?;
+            }
         }
         write_file(destination, "highlight.css", &theme.highlight_css)?;
         write_file(destination, "tomorrow-night.css", &theme.tomorrow_night_css)?;
         write_file(destination, "ayu-highlight.css", &theme.ayu_highlight_css)?;
         write_file(destination, "highlight.js", &theme.highlight_js)?;
         write_file(destination, "clipboard.min.js", &theme.clipboard_js)?;
-        write_file(
-            destination,
-            "FontAwesome/css/font-awesome.css",
-            theme::FONT_AWESOME,
-        )?;
-        write_file(
-            destination,
-            "FontAwesome/fonts/fontawesome-webfont.eot",
-            theme::FONT_AWESOME_EOT,
-        )?;
-        write_file(
-            destination,
-            "FontAwesome/fonts/fontawesome-webfont.svg",
-            theme::FONT_AWESOME_SVG,
-        )?;
-        write_file(
-            destination,
-            "FontAwesome/fonts/fontawesome-webfont.ttf",
-            theme::FONT_AWESOME_TTF,
-        )?;
-        write_file(
-            destination,
-            "FontAwesome/fonts/fontawesome-webfont.woff",
-            theme::FONT_AWESOME_WOFF,
-        )?;
-        write_file(
-            destination,
-            "FontAwesome/fonts/fontawesome-webfont.woff2",
-            theme::FONT_AWESOME_WOFF2,
-        )?;
-        write_file(
-            destination,
-            "FontAwesome/fonts/FontAwesome.ttf",
-            theme::FONT_AWESOME_TTF,
-        )?;
+        let font_awesome = [
+            ("FontAwesome/css/font-awesome.css", theme::FONT_AWESOME),
+            (
+                "FontAwesome/fonts/fontawesome-webfont.eot",
+                theme::FONT_AWESOME_EOT,
+            ),
+            (
+                "FontAwesome/fonts/fontawesome-webfont.svg",
+                theme::FONT_AWESOME_SVG,
+            ),
+            (
+                "FontAwesome/fonts/fontawesome-webfont.ttf",
+                theme::FONT_AWESOME_TTF,
+            ),
+            (
+                "FontAwesome/fonts/fontawesome-webfont.woff",
+                theme::FONT_AWESOME_WOFF,
+            ),
+            (
+                "FontAwesome/fonts/fontawesome-webfont.woff2",
+                theme::FONT_AWESOME_WOFF2,
+            ),
+            ("FontAwesome/fonts/FontAwesome.ttf", theme::FONT_AWESOME_TTF),
+        ];
+        for (file_name, default) in &font_awesome {
+            write_file(destination, file_name, &theme.asset(file_name, default))?;
+        }
         if html_config.copy_fonts {
-            write_file(destination, "fonts/fonts.css", theme::fonts::CSS)?;
-            for (file_name, contents) in theme::fonts::LICENSES.iter() {
-                write_file(destination, file_name, contents)?;
-            }
-            for (file_name, contents) in theme::fonts::OPEN_SANS.iter() {
-                write_file(destination, file_name, contents)?;
-            }
             write_file(
                 destination,
-                theme::fonts::SOURCE_CODE_PRO.0,
-                theme::fonts::SOURCE_CODE_PRO.1,
+                "fonts/fonts.css",
+                &theme.asset("fonts/fonts.css", theme::fonts::CSS),
             )?;
+            let font_files = theme::fonts::LICENSES
+                .iter()
+                .chain(theme::fonts::OPEN_SANS.iter())
+                .chain(std::iter::once(&theme::fonts::SOURCE_CODE_PRO));
+            for (file_name, contents) in font_files {
+                write_file(destination, file_name, &theme.asset(file_name, contents))?;
+            }
         }
 
         let playground_config = &html_config.playground;
@@ -258,19 +543,19 @@ impl HtmlHandlebars {
         // Ace is a very large dependency, so only load it when requested
         if playground_config.editable && playground_config.copy_js {
             // Load the editor
-            write_file(destination, "editor.js", playground_editor::JS)?;
-            write_file(destination, "ace.js", playground_editor::ACE_JS)?;
-            write_file(destination, "mode-rust.js", playground_editor::MODE_RUST_JS)?;
-            write_file(
-                destination,
-                "theme-dawn.js",
-                playground_editor::THEME_DAWN_JS,
-            )?;
-            write_file(
-                destination,
-                "theme-tomorrow_night.js",
-                playground_editor::THEME_TOMORROW_NIGHT_JS,
-            )?;
+            let editor = [
+                ("editor.js", playground_editor::JS),
+                ("ace.js", playground_editor::ACE_JS),
+                ("mode-rust.js", playground_editor::MODE_RUST_JS),
+                ("theme-dawn.js", playground_editor::THEME_DAWN_JS),
+                (
+                    "theme-tomorrow_night.js",
+                    playground_editor::THEME_TOMORROW_NIGHT_JS,
+                ),
+            ];
+            for (file_name, default) in &editor {
+                write_file(destination, file_name, &theme.asset(file_name, default))?;
+            }
         }
 
         Ok(())
@@ -281,6 +566,7 @@ impl HtmlHandlebars {
         &self,
         data: &mut serde_json::Map<String, serde_json::Value>,
         print_content: &str,
+        html_config: &HtmlConfig,
     ) {
         // Make sure that the Print chapter does not display the title from
         // the last rendered chapter by removing it from its context
@@ -288,10 +574,9 @@ impl HtmlHandlebars {
         data.insert("is_print".to_owned(), json!(true));
         data.insert("path".to_owned(), json!("print.md"));
         data.insert("content".to_owned(), json!(print_content));
-        data.insert(
-            "path_to_root".to_owned(),
-            json!(utils::fs::path_to_root(Path::new("print.md"))),
-        );
+        let path_to_root = links_root(html_config)
+            .unwrap_or_else(|| utils::fs::path_to_root(Path::new("print.md")));
+        data.insert("path_to_root".to_owned(), json!(path_to_root));
     }
 
     fn register_hbs_helpers(&self, handlebars: &mut Handlebars<'_>, html_config: &HtmlConfig) {
@@ -304,6 +589,20 @@ impl HtmlHandlebars {
         handlebars.register_helper("previous", Box::new(helpers::navigation::previous));
         handlebars.register_helper("next", Box::new(helpers::navigation::next));
         handlebars.register_helper("theme_option", Box::new(helpers::theme::theme_option));
+        handlebars.register_helper(
+            "format_date",
+            Box::new(helpers::date::FormatDate {
+                format: html_config.date_format.clone(),
+            }),
+        );
+        handlebars.register_helper(
+            "inline_stylesheet",
+            Box::new(helpers::css::inline_stylesheet),
+        );
+
+        for (name, helper) in &self.helpers {
+            handlebars.register_helper(name, Box::new(SharedHelper(Arc::clone(helper))));
+        }
     }
 
     /// Copy across any additional CSS and JavaScript files which the book
@@ -314,7 +613,11 @@ impl HtmlHandlebars {
         root: &Path,
         destination: &Path,
     ) -> Result<()> {
-        let custom_files = html.additional_css.iter().chain(html.additional_js.iter());
+        let custom_files = html
+            .additional_css
+            .iter()
+            .map(PathBuf::as_path)
+            .chain(html.additional_js.iter().map(AdditionalJs::path));
 
         debug!("Copying additional CSS and JS");
 
@@ -343,11 +646,126 @@ impl HtmlHandlebars {
         Ok(())
     }
 
+    fn copy_extra_files(
+        &self,
+        extra_files: &[String],
+        root: &Path,
+        destination: &Path,
+        build_dir: &Path,
+    ) -> Result<()> {
+        if extra_files.is_empty() {
+            return Ok(());
+        }
+
+        debug!("Copying extra files");
+
+        for pattern in extra_files {
+            if !is_inside_of_book(Path::new(pattern)) {
+                bail!(
+                    "`{}` in `output.html.extra-files` has to be a relative path inside the book",
+                    pattern
+                );
+            }
+        }
+
+        let mut files = BTreeSet::new();
+        for raw in extra_files {
+            let pattern = utils::fs::GlobPattern::new(raw)
+                .with_context(|| "Invalid pattern in `output.html.extra-files`")?;
+
+            // Only the directory the pattern starts with is searched, and
+            // version control directories are left out unless it names them.
+            let start = literal_prefix(raw);
+            let skipped: Vec<&str> = VCS_DIRS
+                .iter()
+                .cloned()
+                .filter(|dir| !raw.split('/').any(|c| c == *dir))
+                .collect();
+            let mut found = Vec::new();
+            if root.join(&start).is_dir() {
+                find_files(root, &start, build_dir, &skipped, &mut found)?;
+            }
+            found.retain(|file| pattern.matches(file, false));
+
+            if found.is_empty() {
+                warn!(
+                    "`{}` in `output.html.extra-files` matches no files of the book",
+                    raw
+                );
+            }
+            files.extend(found);
+        }
+
+        for file in files {
+            let input_location = root.join(&file);
+            let output_location = destination.join(&file);
+            if let Some(parent) = output_location.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Unable to create {}", parent.display()))?;
+            }
+            debug!(
+                "Copying {} -> {}",
+                input_location.display(),
+                output_location.display()
+            );
+
+            fs::copy(&input_location, &output_location).with_context(|| {
+                format!(
+                    "Unable to copy {} to {}",
+                    input_location.display(),
+                    output_location.display()
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    fn copy_favicons(&self, html: &HtmlConfig, root: &Path, destination: &Path) -> Result<()> {
+        let favicon = match html.favicon {
+            Some(ref favicon) => favicon,
+            None => return Ok(()),
+        };
+
+        debug!("Copying favicons");
+
+        for (_, icon) in favicon.icons() {
+            if !is_inside_of_book(icon) {
+                bail!(
+                    "The favicon `{}` has to be a relative path inside the book",
+                    icon.display()
+                );
+            }
+            let input_location = root.join(icon);
+            let output_location = destination.join(icon);
+            if let Some(parent) = output_location.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Unable to create {}", parent.display()))?;
+            }
+            debug!(
+                "Copying {} -> {}",
+                input_location.display(),
+                output_location.display()
+            );
+
+            fs::copy(&input_location, &output_location).with_context(|| {
+                format!(
+                    "Unable to copy {} to {}",
+                    input_location.display(),
+                    output_location.display()
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
     fn emit_redirects(
         &self,
         root: &Path,
         handlebars: &Handlebars<'_>,
         redirects: &HashMap<String, String>,
+        normalize: bool,
     ) -> Result<()> {
         if redirects.is_empty() {
             return Ok(());
@@ -362,7 +780,7 @@ impl HtmlHandlebars {
             // up `root.join(original)`).
             let original = original.trim_start_matches("/");
             let filename = root.join(original);
-            self.emit_redirect(handlebars, &filename, new)?;
+            self.emit_redirect(handlebars, &filename, new, normalize)?;
         }
 
         Ok(())
@@ -373,6 +791,7 @@ impl HtmlHandlebars {
         handlebars: &Handlebars<'_>,
         original: &Path,
         destination: &str,
+        normalize: bool,
     ) -> Result<()> {
         if original.exists() {
             // sanity check to avoid accidentally overwriting a real file.
@@ -392,20 +811,55 @@ impl HtmlHandlebars {
         let ctx = json!({
             "url": destination,
         });
-        let f = File::create(original)?;
-        handlebars
-            .render_to_write("redirect", &ctx, f)
-            .with_context(|| {
-                format!(
-                    "Unable to create a redirect file at \"{}\"",
-                    original.display()
-                )
-            })?;
+        let rendered = handlebars.render("redirect", &ctx).with_context(|| {
+            format!(
+                "Unable to create a redirect file at \"{}\"",
+                original.display()
+            )
+        })?;
+        let rendered = if normalize {
+            normalize_output(&rendered)
+        } else {
+            rendered
+        };
+        fs::write(original, rendered).with_context(|| {
+            format!(
+                "Unable to create a redirect file at \"{}\"",
+                original.display()
+            )
+        })?;
 
         Ok(())
     }
 }
 
+/// Gives `html` LF line endings, no whitespace at the end of its lines and a
+/// single final newline for `output.html.normalize-output`. The contents of
+/// `<pre>`, `<code>` and `<textarea>` elements are left alone, apart from
+/// their line endings.
+fn normalize_output(html: &str) -> String {
+    lazy_static! {
+        static ref PRESERVED: Regex =
+            Regex::new(r"(?s)<pre\b.*?</pre>|<code\b.*?</code>|<textarea\b.*?</textarea>").unwrap();
+        static ref TRAILING_WHITESPACE: Regex = Regex::new(r"[ \t]+\n").unwrap();
+    }
+
+    let html = html.replace("\r\n", "\n").replace('\r', "\n");
+    let mut normalized = String::with_capacity(html.len());
+    let mut last = 0;
+    for preserved in PRESERVED.find_iter(&html) {
+        normalized.push_str(&TRAILING_WHITESPACE.replace_all(&html[last..preserved.start()], "\n"));
+        normalized.push_str(preserved.as_str());
+        last = preserved.end();
+    }
+    normalized.push_str(&TRAILING_WHITESPACE.replace_all(&html[last..], "\n"));
+
+    let len = normalized.trim_end().len();
+    normalized.truncate(len);
+    normalized.push('\n');
+    normalized
+}
+
 // TODO(mattico): Remove some time after the 0.1.8 release
 fn maybe_wrong_theme_dir(dir: &Path) -> Result<bool> {
     fn entry_is_maybe_book_file(entry: fs::DirEntry) -> Result<bool> {
@@ -437,6 +891,14 @@ impl Renderer for HtmlHandlebars {
         let book = &ctx.book;
         let build_dir = ctx.root.join(&ctx.config.build.build_dir);
 
+        if html_config.absolute_links && html_config.site_url.is_none() {
+            bail!("`output.html.absolute-links` needs the `output.html.site-url` to start from");
+        }
+        for pattern in &html_config.print.exclude {
+            utils::fs::GlobPattern::new(pattern)
+                .with_context(|| "Invalid pattern in `output.html.print.exclude`")?;
+        }
+
         if destination.exists() {
             utils::fs::remove_dir_content(destination)
                 .with_context(|| "Unable to remove stale HTML output")?;
@@ -476,9 +938,15 @@ impl Renderer for HtmlHandlebars {
         handlebars.register_partial("header", String::from_utf8(theme.header.clone())?)?;
 
         debug!("Register handlebars helpers");
+        helpers::date::check_date_format(&html_config.date_format)
+            .with_context(|| "Invalid `output.html.date-format`")?;
         self.register_hbs_helpers(&mut handlebars, &html_config);
 
         let mut data = make_data(&ctx.root, &book, &ctx.config, &html_config, &theme)?;
+        if html_config.inline_css {
+            let css = inline_css(&theme, &html_config, &ctx.root)?;
+            data.insert("inline_css".to_owned(), json!(css));
+        }
 
         // Print version
         let mut print_content = String::new();
@@ -486,10 +954,20 @@ impl Renderer for HtmlHandlebars {
         fs::create_dir_all(&destination)
             .with_context(|| "Unexpected error when constructing destination path")?;
 
+        let breadcrumbs = if html_config.breadcrumbs {
+            breadcrumbs(book)?
+        } else {
+            HashMap::new()
+        };
+
+        let renamed_chapters = renamed_chapters(book);
+
         let mut is_index = true;
         for item in book.iter() {
             let ctx = RenderItemContext {
                 handlebars: &handlebars,
+                breadcrumbs: &breadcrumbs,
+                renamed_chapters: &renamed_chapters,
                 destination: destination.to_path_buf(),
                 data: data.clone(),
                 is_index,
@@ -505,47 +983,531 @@ impl Renderer for HtmlHandlebars {
             self.render_404(ctx, &html_config, &src_dir, &mut handlebars, &mut data)?;
         }
 
-        // Print version
-        self.configure_print_version(&mut data, &print_content);
-        if let Some(ref title) = ctx.config.book.title {
-            data.insert("title".to_owned(), json!(title));
+        let has_tags = self.render_tags(ctx, &html_config, &handlebars, &data)?;
+
+        if html_config.sitemap {
+            match html_config.site_url {
+                Some(ref site_url) => self.emit_sitemap(ctx, site_url, has_tags)?,
+                None => warn!("Not creating sitemap.xml because `output.html.site-url` isn't set"),
+            }
         }
 
-        // Render the handlebars template with the data
-        debug!("Render template");
-        let rendered = handlebars.render("index", &data)?;
+        // Print version
+        if html_config.print.enable {
+            if html_config.anchor_prefix {
+                print_content = link_print_anchors(&print_content);
+            }
+            self.configure_print_version(&mut data, &print_content, &html_config);
+            if let Some(ref title) = ctx.config.book.title {
+                data.insert("title".to_owned(), json!(title));
+            }
+            if html_config.use_base_tag {
+                data.insert("base_url".to_owned(), json!("./"));
+            }
 
-        let rendered =
-            self.post_process(rendered, &html_config.playground, ctx.config.rust.edition);
+            // Render the handlebars template with the data
+            debug!("Render template");
+            let rendered = handlebars.render("index", &data)?;
 
-        utils::fs::write_file(&destination, "print.html", rendered.as_bytes())?;
-        debug!("Creating print.html ✓");
+            let rendered = self.post_process(
+                rendered,
+                &html_config,
+                ctx.config.rust.edition,
+                Path::new("print.html"),
+            );
+            let rendered = if html_config.use_base_tag {
+                fragment_links_to_page(&rendered, Path::new("print.html"))
+            } else {
+                rendered
+            };
+
+            utils::fs::write_file(destination, "print.html", rendered.as_bytes())?;
+            debug!("Creating print.html ✓");
+        }
 
         debug!("Copy static files");
         self.copy_static_files(&destination, &theme, &html_config)
             .with_context(|| "Unable to copy across static files")?;
         self.copy_additional_css_and_js(&html_config, &ctx.root, &destination)
             .with_context(|| "Unable to copy across additional CSS and JS")?;
+        self.copy_favicons(&html_config, &ctx.root, destination)
+            .with_context(|| "Unable to copy across favicons")?;
 
         // Render search index
         #[cfg(feature = "search")]
         {
             let search = html_config.search.unwrap_or_default();
             if search.enable {
-                super::search::create_files(&search, &destination, &book)?;
+                // An update which failed part way leaves no index behind, so a
+                // poisoned lock is fine: the next build makes a new one.
+                let mut search_index = self
+                    .search_index
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                super::search::write_files(&search, destination, book, &theme, &mut search_index)?;
             }
         }
 
-        self.emit_redirects(&ctx.destination, &handlebars, &html_config.redirect)
-            .context("Unable to emit redirects")?;
-
-        // Copy all remaining files, avoid a recursive copy from/to the book build dir
-        utils::fs::copy_files_except_ext(&src_dir, &destination, true, Some(&build_dir), &["md"])?;
-
-        Ok(())
+        // The exact redirects win over the patterns.
+        let mut redirects =
+            pattern_redirects(&html_config.redirect_patterns, &html_config.redirect_paths)?;
+        redirects.extend(html_config.redirect.clone());
+        self.emit_redirects(
+            &ctx.destination,
+            &handlebars,
+            &redirects,
+            html_config.normalize_output,
+        )
+        .context("Unable to emit redirects")?;
+
+        // Copy all remaining files, avoid a recursive copy from/to the book build dir.
+        // Books loaded from memory may not have a source directory at all.
+        // The HTML chapters were already written with the theme around them.
+        if src_dir.exists() {
+            let html_chapters: Vec<_> = book
+                .iter()
+                .filter_map(|item| match *item {
+                    BookItem::Chapter(ref ch) => ch.source_path.as_ref(),
+                    _ => None,
+                })
+                .filter(|path| is_html_chapter(path))
+                .map(|path| src_dir.join(path))
+                .collect();
+            utils::fs::copy_files_except(
+                &src_dir,
+                destination,
+                true,
+                Some(&build_dir),
+                &["md"],
+                &html_chapters,
+            )?;
+        }
+
+        self.copy_extra_files(&html_config.extra_files, &ctx.root, destination, &build_dir)
+            .with_context(|| "Unable to copy across extra files")?;
+
+        Ok(())
+    }
+}
+
+/// Whether the relative `path` stays inside of the book. The extra files and
+/// favicons are copied to the same place relative to the output directory, so
+/// they can't be outside of it.
+fn is_inside_of_book(path: &Path) -> bool {
+    path.components().all(|c| match c {
+        Component::Normal(_) => true,
+        _ => false,
+    })
+}
+
+/// The directories of version control systems, which `output.html.extra-files`
+/// only looks into when a pattern names them.
+const VCS_DIRS: &[&str] = &[".git", ".hg", ".svn"];
+
+/// The directory a pattern of `output.html.extra-files` starts with, before
+/// any of its wildcards, relative to the book's root. Patterns without a `/`
+/// before their end match in every directory, so they start at the root.
+fn literal_prefix(pattern: &str) -> PathBuf {
+    let pattern = pattern.trim_end_matches('/');
+    let dir = match pattern.rfind('/') {
+        Some(i) => &pattern[..i],
+        None => return PathBuf::new(),
+    };
+    dir.split('/')
+        .take_while(|c| !c.contains(|ch| ch == '*' || ch == '?' || ch == '['))
+        .collect()
+}
+
+/// Adds the files in the directory `dir` of the book at `root`, and in its
+/// subdirectories except for the `build_dir` and the ones named like one of
+/// `skipped`, to `files`, relative to `root`.
+fn find_files(
+    root: &Path,
+    dir: &Path,
+    build_dir: &Path,
+    skipped: &[&str],
+    files: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let path = root.join(dir);
+    for entry in
+        fs::read_dir(&path).with_context(|| format!("Unable to read {}", path.display()))?
+    {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let file = dir.join(entry.file_name());
+        if file_type.is_dir() {
+            let name = entry.file_name();
+            if entry.path() != build_dir && !skipped.iter().any(|s| name == **s) {
+                find_files(root, &file, build_dir, skipped, files)?;
+            }
+        } else if file_type.is_file() {
+            files.push(file);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the chapter at `path` is written in HTML instead of markdown, which
+/// is put into the theme as it is.
+fn is_html_chapter(path: &Path) -> bool {
+    path.extension().map_or(false, |ext| ext == "html")
+}
+
+/// A helper registered through [`HtmlHandlebars::register_helper`], which has
+/// to be handed to the `Handlebars` registry of every build.
+struct SharedHelper(Arc<dyn HelperDef>);
+
+impl HelperDef for SharedHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &handlebars::Helper<'reg, 'rc>,
+        r: &'reg Handlebars<'_>,
+        ctx: &'rc handlebars::Context,
+        rc: &mut handlebars::RenderContext<'reg, 'rc>,
+    ) -> std::result::Result<Option<handlebars::ScopedJson<'reg, 'rc>>, handlebars::RenderError>
+    {
+        self.0.call_inner(h, r, ctx, rc)
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &handlebars::Helper<'reg, 'rc>,
+        r: &'reg Handlebars<'_>,
+        ctx: &'rc handlebars::Context,
+        rc: &mut handlebars::RenderContext<'reg, 'rc>,
+        out: &mut dyn handlebars::Output,
+    ) -> handlebars::HelperResult {
+        self.0.call(h, r, ctx, rc, out)
+    }
+}
+
+/// The `href` of the `<base>` tag for the page at `path`, pointing at the
+/// book's root.
+fn base_href(path: &Path) -> String {
+    let path_to_root = utils::fs::path_to_root(path);
+    if path_to_root.is_empty() {
+        "./".to_string()
+    } else {
+        path_to_root
+    }
+}
+
+/// Point fragment-only links at `page` itself. A `<base>` tag would otherwise
+/// make `#anchor` links resolve against the book's root.
+fn fragment_links_to_page(html: &str, page: &Path) -> String {
+    let page = utils::fs::normalize_path(&page.to_string_lossy());
+    html.replace("href=\"#", &format!("href=\"{}#", page))
+}
+
+/// Build the `<link>` tag attributes for the configured favicons, making sure
+/// that every icon actually exists.
+fn favicon_links(root: &Path, favicon: &Favicon) -> Result<serde_json::Value> {
+    let mut links = Vec::new();
+
+    for (size, icon) in favicon.icons() {
+        if !root.join(icon).is_file() {
+            bail!(
+                "Favicon {} doesn't exist (looked in {})",
+                icon.display(),
+                root.join(icon).display()
+            );
+        }
+
+        let mut link = BTreeMap::new();
+        match size {
+            Some("apple-touch-icon") => {
+                link.insert("rel", "apple-touch-icon".to_string());
+            }
+            Some(size) => {
+                link.insert("rel", "icon".to_string());
+                link.insert("sizes", size.to_string());
+            }
+            None => {
+                link.insert("rel", "icon".to_string());
+            }
+        }
+        let mime = match icon.extension().and_then(|ext| ext.to_str()) {
+            Some("ico") => Some("image/x-icon"),
+            Some("png") => Some("image/png"),
+            Some("svg") => Some("image/svg+xml"),
+            _ => None,
+        };
+        if let Some(mime) = mime {
+            link.insert("type", mime.to_string());
+        }
+        link.insert(
+            "href",
+            icon.to_str()
+                .with_context(|| "Favicon path is not valid UTF-8")?
+                .replace("\\", "/"),
+        );
+        links.push(link);
+    }
+
+    Ok(json!(links))
+}
+
+/// Writes `value` as JSON which can be put into a `<script>` as is, without
+/// closing it early with a `</script>`.
+fn script_value(value: &serde_json::Value) -> String {
+    value.to_string().replace('<', "\\u003c")
+}
+
+/// Checks the properties of `output.html.theme-vars` and gives them all their
+/// `--` prefix.
+fn theme_vars(vars: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+    let mut props = BTreeMap::new();
+    for (key, value) in vars {
+        let name = if key.starts_with("--") {
+            key.clone()
+        } else {
+            format!("--{}", key)
+        };
+        if name.len() == 2
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!(
+                "`output.html.theme-vars` has an invalid property name `{}`",
+                name
+            );
+        }
+        if value.contains(|c| c == ';' || c == '{' || c == '}' || c == '<') {
+            bail!(
+                "The value of `{}` in `output.html.theme-vars` can't contain `;`, `{{`, `}}` or `<`",
+                name
+            );
+        }
+        if props
+            .insert(name.clone(), value.trim().to_owned())
+            .is_some()
+        {
+            bail!(
+                "`output.html.theme-vars` sets `{}` more than once, with and without `--`",
+                name
+            );
+        }
+    }
+    Ok(props)
+}
+
+/// Turns the theme's CSS custom properties into a `:root` rule, in the order
+/// of their names.
+fn root_rule(props: &BTreeMap<String, String>) -> String {
+    let mut rule = String::from(":root {\n");
+    for (name, value) in props {
+        rule.push_str(&format!("    {}: {};\n", name, value));
+    }
+    rule.push('}');
+    rule
+}
+
+/// The redirects of the old pages in `output.html.redirect-paths`, each to
+/// the destination of the first of `output.html.redirect-patterns` matching
+/// its whole path, with the groups the pattern captured filled in.
+fn pattern_redirects(
+    patterns: &[RedirectPattern],
+    paths: &[String],
+) -> Result<HashMap<String, String>> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| {
+            let regex = Regex::new(&format!("^(?:{})$", pattern.from)).with_context(|| {
+                format!(
+                    "`{}` in `output.html.redirect-patterns` is not a valid regex",
+                    pattern.from
+                )
+            })?;
+            Ok((regex, &pattern.to))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut redirects = HashMap::new();
+    for path in paths {
+        let redirect = patterns
+            .iter()
+            .filter_map(|&(ref regex, to)| regex.captures(path).map(|caps| (caps, to)))
+            .next();
+        match redirect {
+            Some((caps, to)) => {
+                let mut destination = String::new();
+                caps.expand(to, &mut destination);
+                redirects.insert(path.clone(), destination);
+            }
+            None => warn!(
+                "`{}` in `output.html.redirect-paths` matches none of the redirect patterns",
+                path
+            ),
+        }
+    }
+    Ok(redirects)
+}
+
+/// Concatenates the theme's and the additional stylesheets for
+/// `output.html.inline-css`, with their relative `url()`s and `@import`s
+/// pointing from the book's root behind [`helpers::css::PATH_TO_ROOT`].
+fn inline_css(theme: &Theme, html_config: &HtmlConfig, root: &Path) -> Result<String> {
+    let mut sheets: Vec<(String, Cow<'_, [u8]>)> = vec![
+        (
+            "css/variables.css".into(),
+            Cow::Borrowed(&theme.variables_css),
+        ),
+        ("css/general.css".into(), Cow::Borrowed(&theme.general_css)),
+        ("css/chrome.css".into(), Cow::Borrowed(&theme.chrome_css)),
+        (
+            "FontAwesome/css/font-awesome.css".into(),
+            theme.asset("FontAwesome/css/font-awesome.css", theme::FONT_AWESOME),
+        ),
+    ];
+    if html_config.copy_fonts {
+        sheets.push((
+            "fonts/fonts.css".into(),
+            theme.asset("fonts/fonts.css", theme::fonts::CSS),
+        ));
+    }
+    sheets.push(("highlight.css".into(), Cow::Borrowed(&theme.highlight_css)));
+    sheets.push((
+        "tomorrow-night.css".into(),
+        Cow::Borrowed(&theme.tomorrow_night_css),
+    ));
+    sheets.push((
+        "ayu-highlight.css".into(),
+        Cow::Borrowed(&theme.ayu_highlight_css),
+    ));
+    for style in &html_config.additional_css {
+        let path = style.strip_prefix(root).unwrap_or(style);
+        let contents = fs::read(root.join(path))
+            .with_context(|| format!("Unable to read the stylesheet {}", path.display()))?;
+        let path = path
+            .to_str()
+            .expect("Could not convert to str")
+            .replace('\\', "/");
+        sheets.push((path, Cow::Owned(contents)));
+    }
+
+    let mut imports = String::new();
+    let mut body = String::new();
+    for (path, contents) in &sheets {
+        let (sheet_imports, css) = rebase_css(&String::from_utf8_lossy(contents), path);
+        imports.push_str(&sheet_imports);
+        body.push_str(&format!("/* {} */\n{}\n", path, css));
+    }
+    // The print stylesheet is linked with `media="print"`
+    let (print_imports, print_css) =
+        rebase_css(&String::from_utf8_lossy(&theme.print_css), "css/print.css");
+    imports.push_str(&print_imports);
+    body.push_str(&format!("@media print {{\n{}\n}}\n", print_css));
+
+    Ok(imports + &body)
+}
+
+/// Makes the relative `url()`s and `@import`s of the stylesheet at `path`
+/// start from the book's root, returning the `@import` rules, which have to
+/// come first in the bundle, and the rest of the stylesheet.
+fn rebase_css(css: &str, path: &str) -> (String, String) {
+    lazy_static! {
+        static ref IMPORT: Regex =
+            Regex::new(r#"@import\s+(?:url\(\s*)?['"]?([^'"()\s;]+)['"]?\s*\)?([^;]*);"#).unwrap();
+        static ref URL: Regex = Regex::new(r#"url\(\s*(['"]?)([^'"()]+)(['"]?)\s*\)"#).unwrap();
+        static ref ABSOLUTE: Regex = Regex::new(r"^(?i:[a-z][a-z0-9+.-]*:|/|#)").unwrap();
+    }
+
+    let rebase = |url: &str| {
+        if ABSOLUTE.is_match(url) {
+            url.to_string()
+        } else {
+            format!(
+                "{}{}",
+                helpers::css::PATH_TO_ROOT,
+                utils::resolve_in_book(Some(Path::new(path)), url)
+            )
+        }
+    };
+
+    let mut imports = String::new();
+    let css = IMPORT.replace_all(css, |caps: &Captures<'_>| {
+        let media = caps[2].trim();
+        let separator = if media.is_empty() { "" } else { " " };
+        imports.push_str(&format!(
+            "@import url(\"{}\"){}{};\n",
+            rebase(&caps[1]),
+            separator,
+            media
+        ));
+        String::new()
+    });
+    let css = URL.replace_all(&css, |caps: &Captures<'_>| {
+        format!("url({}{}{})", &caps[1], rebase(&caps[2]), &caps[3])
+    });
+
+    (imports, css.into_owned())
+}
+
+/// The URL all links start with when they are absolute, ending with a `/`.
+fn links_root(html_config: &HtmlConfig) -> Option<String> {
+    match html_config.site_url {
+        Some(ref site_url) if html_config.absolute_links => {
+            Some(format!("{}/", site_url.trim_end_matches('/')))
+        }
+        _ => None,
+    }
+}
+
+/// How the markdown of the book's pages is rendered, according to `html_config`.
+fn render_options(html_config: &HtmlConfig) -> utils::RenderOptions<'_> {
+    utils::RenderOptions {
+        curly_quotes: html_config.curly_quotes,
+        default_language: html_config.default_language.as_ref().map(String::as_str),
+        definition_lists: html_config.definition_lists,
+        asset_url_prefix: html_config.asset_url_prefix.as_ref().map(String::as_str),
+        source_path: None,
+        check_links: false,
+        links_root: if html_config.absolute_links {
+            html_config.site_url.as_ref().map(String::as_str)
+        } else {
+            None
+        },
+        renamed_chapters: None,
     }
 }
 
+/// Finds the chapters a preprocessor moved, like the `README.md` files which
+/// become `index.md`, mapping their source files to their new paths.
+fn renamed_chapters(book: &Book) -> HashMap<PathBuf, PathBuf> {
+    book.iter()
+        .filter_map(|item| match *item {
+            BookItem::Chapter(ref ch) => match (&ch.source_path, &ch.path) {
+                (Some(source_path), Some(path)) if source_path != path => {
+                    Some((source_path.clone(), path.clone()))
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// A token for the URLs of the theme's stylesheets and scripts, which changes
+/// whenever mdBook or the theme's files do, so browsers don't keep using
+/// cached copies of the old ones.
+fn asset_version(theme: &Theme) -> String {
+    let version = utils::stable_hash(&[
+        crate::MDBOOK_VERSION.as_bytes(),
+        &theme.js,
+        &theme.chrome_css,
+        &theme.general_css,
+        &theme.print_css,
+        &theme.variables_css,
+        &theme.highlight_css,
+        &theme.tomorrow_night_css,
+        &theme.ayu_highlight_css,
+        &theme.highlight_js,
+        &theme.clipboard_js,
+    ]);
+    version[..8].to_string()
+}
+
 fn make_data(
     root: &Path,
     book: &Book,
@@ -560,6 +1522,10 @@ fn make_data(
         "language".to_owned(),
         json!(config.book.language.clone().unwrap_or_default()),
     );
+    let text_direction = html_config.text_direction.unwrap_or_else(|| {
+        TextDirection::from_language(config.book.language.as_ref().map_or("", String::as_str))
+    });
+    data.insert("text_direction".to_owned(), json!(text_direction.as_str()));
     data.insert(
         "book_title".to_owned(),
         json!(config.book.title.clone().unwrap_or_default()),
@@ -568,11 +1534,22 @@ fn make_data(
         "description".to_owned(),
         json!(config.book.description.clone().unwrap_or_default()),
     );
-    if theme.favicon_png.is_some() {
-        data.insert("favicon_png".to_owned(), json!("favicon.png"));
+    data.insert("asset_version".to_owned(), json!(asset_version(theme)));
+    if let Some(ref favicon) = html_config.favicon {
+        data.insert("favicons".to_owned(), favicon_links(root, favicon)?);
+    } else {
+        if theme.favicon_png.is_some() {
+            data.insert("favicon_png".to_owned(), json!("favicon.png"));
+        }
+        if theme.favicon_svg.is_some() {
+            data.insert("favicon_svg".to_owned(), json!("favicon.svg"));
+        }
+    }
+    if html_config.print.enable {
+        data.insert("print_enable".to_owned(), json!(true));
     }
-    if theme.favicon_svg.is_some() {
-        data.insert("favicon_svg".to_owned(), json!("favicon.svg"));
+    if html_config.a11y {
+        data.insert("a11y".to_owned(), json!(true));
     }
     if let Some(ref livereload) = html_config.livereload_url {
         data.insert("livereload".to_owned(), json!(livereload));
@@ -618,16 +1595,59 @@ fn make_data(
         data.insert("additional_css".to_owned(), json!(css));
     }
 
+    let mut vars = theme_vars(&html_config.theme_vars)?;
+    if let Some(ref width) = html_config.max_content_width {
+        if width.trim().is_empty()
+            || width.contains(|c| c == ';' || c == '{' || c == '}' || c == '<')
+        {
+            bail!("`output.html.max-content-width` must be a CSS length, like `80ch`");
+        }
+        vars.entry("--content-max-width".to_owned())
+            .or_insert_with(|| width.trim().to_owned());
+    }
+    if !vars.is_empty() {
+        data.insert("theme_vars".to_owned(), json!(root_rule(&vars)));
+    }
+
     // Add check to see if there is an additional script
     if !html_config.additional_js.is_empty() {
         let mut js = Vec::new();
+        let mut head_scripts = Vec::new();
+        let mut scripts = Vec::new();
         for script in &html_config.additional_js {
-            match script.strip_prefix(root) {
-                Ok(p) => js.push(p.to_str().expect("Could not convert to str")),
-                Err(_) => js.push(script.to_str().expect("Could not convert to str")),
+            let path = script.path();
+            let src = match path.strip_prefix(root) {
+                Ok(p) => p.to_str().expect("Could not convert to str"),
+                Err(_) => path.to_str().expect("Could not convert to str"),
+            };
+            let mut tag = serde_json::Map::new();
+            tag.insert("src".to_owned(), json!(src));
+            let mut placement = ScriptPlacement::default();
+            if let AdditionalJs::Script(ref script) = *script {
+                placement = script.placement;
+                if let Some(ref script_type) = script.script_type {
+                    tag.insert("type".to_owned(), json!(script_type));
+                }
+                if script.defer {
+                    tag.insert("defer".to_owned(), json!(true));
+                }
+                if script.is_async {
+                    tag.insert("async".to_owned(), json!(true));
+                }
+            }
+            match placement {
+                ScriptPlacement::Head => head_scripts.push(tag),
+                ScriptPlacement::BodyEnd => {
+                    js.push(src);
+                    scripts.push(tag);
+                }
             }
         }
+        // `additional_js` only has the paths, for themes written before
+        // scripts could have attributes.
         data.insert("additional_js".to_owned(), json!(js));
+        data.insert("additional_head_scripts".to_owned(), json!(head_scripts));
+        data.insert("additional_scripts".to_owned(), json!(scripts));
     }
 
     if html_config.playground.editable && html_config.playground.copy_js {
@@ -636,10 +1656,43 @@ fn make_data(
             data.insert("playground_line_numbers".to_owned(), json!(true));
         }
     }
-    if html_config.playground.copyable {
+    if html_config.playground.copyable && html_config.code.copy_button {
         data.insert("playground_copyable".to_owned(), json!(true));
+        data.insert(
+            "copy_button_label".to_owned(),
+            json!(script_value(&json!(html_config.code.copy_button_label))),
+        );
+        data.insert(
+            "copy_success_label".to_owned(),
+            json!(script_value(&json!(html_config.code.copy_success_label))),
+        );
+    }
+    if html_config.code.wrap_button {
+        data.insert("code_wrap_button".to_owned(), json!(true));
+    }
+    let runner_url = html_config.playground.runner_url.trim_end_matches('/');
+    if !runner_url.is_empty() {
+        data.insert(
+            "playground_runner_url".to_owned(),
+            json!(script_value(&json!(runner_url))),
+        );
     }
 
+    // Read by the theme's scripts, `false` when the shortcuts are disabled.
+    let keyboard_shortcuts = if html_config.keyboard_shortcuts {
+        data.insert(
+            "search_shortcut".to_owned(),
+            json!(html_config.shortcut_keys.search),
+        );
+        json!(html_config.shortcut_keys)
+    } else {
+        json!(false)
+    };
+    data.insert(
+        "keyboard_shortcuts".to_owned(),
+        json!(script_value(&keyboard_shortcuts)),
+    );
+
     data.insert("fold_enable".to_owned(), json!((html_config.fold.enable)));
     data.insert("fold_level".to_owned(), json!((html_config.fold.level)));
 
@@ -707,13 +1760,245 @@ fn make_data(
 
     data.insert("chapters".to_owned(), json!(chapters));
 
+    if html_config.expose_chapters {
+        let mut all_chapters = vec![];
+        for item in book.iter() {
+            if let BookItem::Chapter(ref ch) = *item {
+                let mut chapter = serde_json::Map::new();
+                chapter.insert("title".to_owned(), json!(ch.name));
+                if let Some(ref path) = ch.path {
+                    let p = path
+                        .to_str()
+                        .with_context(|| "Could not convert path to str")?;
+                    chapter.insert("path".to_owned(), json!(p));
+                }
+                if let Some(ref section) = ch.number {
+                    chapter.insert("section_number".to_owned(), json!(section.to_string()));
+                }
+                chapter.insert("level".to_owned(), json!(ch.parent_names.len()));
+                all_chapters.push(chapter);
+            }
+        }
+        data.insert("all_chapters".to_owned(), json!(all_chapters));
+    }
+
+    if let Some(ref changes) = html_config.recent_changes {
+        let src_dir = root.join(&config.book.src);
+        data.insert(
+            "recent_changes".to_owned(),
+            json!(recent_changes(&src_dir, book, changes.count)?),
+        );
+    }
+
     debug!("[*]: JSON constructed");
     Ok(data)
 }
 
+/// The latest `count` commits changing the chapters of the book in `src_dir`,
+/// with the chapters each of them changed.
+fn recent_changes(src_dir: &Path, book: &Book, count: usize) -> Result<Vec<serde_json::Value>> {
+    let mut chapters = HashMap::new();
+    for item in book.iter() {
+        if let BookItem::Chapter(ref ch) = *item {
+            if let Some(ref path) = ch.path {
+                let link = path
+                    .with_extension("html")
+                    .to_str()
+                    .with_context(|| "Could not convert path to str")?
+                    .replace("\\", "/");
+                // git knows chapters by their source file, like `README.md`.
+                let source = ch.source_path.as_ref().unwrap_or(path);
+                chapters.insert(source.clone(), json!({ "name": ch.name, "link": link }));
+            }
+        }
+    }
+
+    let changes = git::log(src_dir)
+        .into_iter()
+        .filter_map(|commit| {
+            let changed: Vec<_> = commit
+                .files
+                .iter()
+                .filter_map(|file| chapters.get(file))
+                .collect();
+            if changed.is_empty() {
+                return None;
+            }
+            Some(json!({
+                "message": commit.message,
+                "date": commit.date,
+                "chapters": changed,
+            }))
+        })
+        .take(count)
+        .collect();
+    Ok(changes)
+}
+
+/// One step on the way from the book's home page to a chapter.
+#[derive(Clone, Debug, PartialEq)]
+struct Breadcrumb {
+    name: String,
+    /// The page to link to, relative to the book's root. Part titles and
+    /// draft chapters don't have one.
+    link: Option<String>,
+}
+
+/// Finds the breadcrumbs leading to every chapter of the book, that is the
+/// home page, the part the chapter is in and its parent chapters.
+fn breadcrumbs(book: &Book) -> Result<HashMap<PathBuf, Vec<Breadcrumb>>> {
+    fn walk(
+        items: &[BookItem],
+        ancestors: &mut Vec<Breadcrumb>,
+        breadcrumbs: &mut HashMap<PathBuf, Vec<Breadcrumb>>,
+    ) -> Result<()> {
+        for item in items {
+            let ch = match *item {
+                BookItem::Chapter(ref ch) => ch,
+                _ => continue,
+            };
+            let link = match ch.path {
+                Some(ref path) => {
+                    breadcrumbs.insert(path.clone(), ancestors.clone());
+                    let link = path
+                        .with_extension("html")
+                        .to_str()
+                        .with_context(|| "Could not convert path to str")?
+                        .replace("\\", "/");
+                    Some(link)
+                }
+                None => None,
+            };
+
+            ancestors.push(Breadcrumb {
+                name: ch.name.clone(),
+                link,
+            });
+            walk(&ch.sub_items, ancestors, breadcrumbs)?;
+            ancestors.pop();
+        }
+        Ok(())
+    }
+
+    let home = Breadcrumb {
+        name: "Home".to_string(),
+        link: Some("index.html".to_string()),
+    };
+    let mut breadcrumbs = HashMap::new();
+    let mut ancestors = vec![home.clone()];
+
+    // A part contains all the top-level chapters up to the next part title.
+    for item in &book.sections {
+        match *item {
+            BookItem::PartTitle(ref title) => {
+                ancestors = vec![
+                    home.clone(),
+                    Breadcrumb {
+                        name: title.clone(),
+                        link: None,
+                    },
+                ];
+            }
+            _ => walk(std::slice::from_ref(item), &mut ancestors, &mut breadcrumbs)?,
+        }
+    }
+
+    Ok(breadcrumbs)
+}
+
+/// The URL of the output file `page`, which is absolute when `site_url` is
+/// set and otherwise left empty, to stand for the current page.
+fn page_url(html_config: &HtmlConfig, page: &Path) -> String {
+    match html_config.site_url {
+        Some(ref site_url) => format!(
+            "{}/{}",
+            site_url.trim_end_matches('/'),
+            page.to_str()
+                .expect("Could not convert to str")
+                .replace('\\', "/")
+        ),
+        None => String::new(),
+    }
+}
+
+/// Lists the headings of a chapter's rendered `content` for
+/// `output.html.page-toc`, with the same ids [`build_header_links`] gives them.
+fn page_toc(content: &str) -> Vec<serde_json::Value> {
+    lazy_static! {
+        static ref HEADER: Regex = Regex::new(r"<h(\d)>(.*?)</h\d>").unwrap();
+        static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+    }
+    let mut id_counter = HashMap::new();
+
+    HEADER
+        .captures_iter(content)
+        .map(|caps| {
+            let level: usize = caps[1]
+                .parse()
+                .expect("Regex should ensure we only ever get numbers here");
+            let text = TAG
+                .replace_all(&caps[2], "")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&#39;", "'")
+                .replace("&amp;", "&");
+            json!({
+                "level": level,
+                "id": unique_header_id(&caps[2], &mut id_counter),
+                "text": text,
+            })
+        })
+        .collect()
+}
+
+/// Serializes the headings found by [`page_toc`] for the `page-outline`
+/// script of `output.html.page-outline-data`. Characters HTML would give a
+/// meaning to inside a `<script>` are escaped, so a heading can't end it.
+fn page_outline(toc: &[serde_json::Value]) -> String {
+    serde_json::to_string(toc)
+        .expect("The headings can always be serialized")
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+}
+
 /// Goes through the rendered HTML, making sure all header tags have
 /// an anchor respectively so people can link to sections directly.
-fn build_header_links(html: &str) -> String {
+///
+/// With a `permalink` URL of the page, every header also gets a button
+/// copying the URL of its anchor.
+///
+/// Headers which already have a link, like those [`prefix_header_ids`] gives
+/// the chapters of the print page, keep their ids.
+fn build_header_links(html: &str, permalink: Option<&str>) -> String {
+    lazy_static! {
+        static ref LINKED: Regex =
+            Regex::new(r##"^<a class="header" href="#([^"]*)" id="[^"]*">(.*)</a>$"##).unwrap();
+    }
+    let regex = Regex::new(r"<h(\d)>(.*?)</h\d>").unwrap();
+    let mut id_counter = HashMap::new();
+
+    regex
+        .replace_all(html, |caps: &Captures<'_>| {
+            let level = caps[1]
+                .parse()
+                .expect("Regex should ensure we only ever get numbers here");
+
+            match LINKED.captures(&caps[2]) {
+                Some(linked) => insert_link_into_header(level, &linked[1], &linked[2], permalink),
+                None => {
+                    let id = unique_header_id(&caps[2], &mut id_counter);
+                    insert_link_into_header(level, &id, &caps[2], permalink)
+                }
+            }
+        })
+        .into_owned()
+}
+
+/// Links the headers of a print page chapter's rendered `html` to their ids,
+/// with the chapter's `prefix` in front, like `guide-intro-installation`.
+fn prefix_header_ids(html: &str, prefix: &str) -> String {
     let regex = Regex::new(r"<h(\d)>(.*?)</h\d>").unwrap();
     let mut id_counter = HashMap::new();
 
@@ -722,19 +2007,73 @@ fn build_header_links(html: &str) -> String {
             let level = caps[1]
                 .parse()
                 .expect("Regex should ensure we only ever get numbers here");
+            let id = format!("{}-{}", prefix, unique_header_id(&caps[2], &mut id_counter));
 
-            insert_link_into_header(level, &caps[2], &mut id_counter)
+            insert_link_into_header(level, &id, &caps[2], None)
         })
         .into_owned()
 }
 
-/// Insert a sinle link into a header, making sure each link gets its own
-/// unique ID by appending an auto-incremented number (if necessary).
+/// The prefix of the heading ids of the chapter at `path` on the print page,
+/// e.g. `guide-intro` for `guide/intro.md`.
+fn anchor_prefix(path: &Path) -> String {
+    let path = utils::fs::normalize_path(&path.with_extension("").to_string_lossy());
+    utils::normalize_id(&path.replace('/', "-"))
+}
+
+/// Points the links of the print page's `html` to the headings of its own
+/// chapters, like `guide/intro.html#installation`, at the prefixed headings
+/// on the print page. Other links, e.g. to footnotes, are left alone.
+fn link_print_anchors(html: &str) -> String {
+    lazy_static! {
+        static ref ID: Regex = Regex::new(r##"<a class="header" href="#([^"]*)""##).unwrap();
+        static ref LINK: Regex = Regex::new(r##"href="([^"#:]+)\.html#([^"]+)""##).unwrap();
+    }
+
+    let ids: HashSet<_> = ID
+        .captures_iter(html)
+        .map(|caps| caps[1].to_string())
+        .collect();
+    LINK.replace_all(html, |caps: &Captures<'_>| {
+        let page = utils::resolve_in_book(None, &caps[1]);
+        let id = format!("{}-{}", anchor_prefix(Path::new(&page)), &caps[2]);
+        if ids.contains(&id) {
+            format!("href=\"#{}\"", id)
+        } else {
+            caps[0].to_string()
+        }
+    })
+    .into_owned()
+}
+
+/// Insert a sinle link into a header with the given `id`.
 fn insert_link_into_header(
     level: usize,
+    id: &str,
     content: &str,
-    id_counter: &mut HashMap<String, usize>,
+    permalink: Option<&str>,
 ) -> String {
+    let button = match permalink {
+        Some(url) => format!(
+            r##"<button class="fa fa-link permalink-button" data-permalink="{url}#{id}" title="Copy link to this section" aria-label="Copy link to this section"><i class="tooltiptext"></i></button>"##,
+            url = escape_html(url),
+            id = id
+        ),
+        None => String::new(),
+    };
+
+    format!(
+        r##"<h{level}><a class="header" href="#{id}" id="{id}">{text}</a>{button}</h{level}>"##,
+        level = level,
+        id = id,
+        text = content,
+        button = button
+    )
+}
+
+/// The id of the header with the HTML `content`, made unique by appending
+/// how often the same id was already used.
+fn unique_header_id(content: &str, id_counter: &mut HashMap<String, usize>) -> String {
     let raw_id = utils::id_from_content(content);
 
     let id_count = id_counter.entry(raw_id.clone()).or_insert(0);
@@ -746,12 +2085,7 @@ fn insert_link_into_header(
 
     *id_count += 1;
 
-    format!(
-        r##"<h{level}><a class="header" href="#{id}" id="{id}">{text}</a></h{level}>"##,
-        level = level,
-        id = id,
-        text = content
-    )
+    id
 }
 
 // The rust book uses annotations for rustdoc to test code snippets,
@@ -780,17 +2114,101 @@ fn fix_code_blocks(html: &str) -> String {
         .into_owned()
 }
 
+// Marks every code block with the `wrap` class, which makes the theme soft-wrap
+// its long lines.
+fn add_wrap_class(html: &str) -> String {
+    let regex = Regex::new(r##"<pre><code(?: class="([^"]*)")?>"##).unwrap();
+    regex
+        .replace_all(html, |caps: &Captures<'_>| match caps.get(1) {
+            Some(classes) if classes.as_str().split_whitespace().any(|c| c == "wrap") => {
+                caps[0].to_string()
+            }
+            Some(classes) => format!(r#"<pre><code class="{} wrap">"#, classes.as_str()),
+            None => String::from(r#"<pre><code class="wrap">"#),
+        })
+        .into_owned()
+}
+
+// Marks every code block with the number of its first line so that the theme
+// can render a line number gutter. The starting line defaults to 1 and can be
+// changed with a `line-start=N` token in the code block's info string, e.g.
+// ```rust,line-start=10
+fn add_line_numbers(html: &str) -> String {
+    let regex = Regex::new(r##"<pre><code(?: class="([^"]*)")?>"##).unwrap();
+    regex
+        .replace_all(html, |caps: &Captures<'_>| {
+            let mut line_start = 1;
+            let classes = caps
+                .get(1)
+                .map(|classes| classes.as_str())
+                .unwrap_or("")
+                .split_whitespace()
+                .filter(|class| {
+                    if class.starts_with("line-start=") {
+                        line_start = class["line-start=".len()..].parse().unwrap_or(1);
+                        false
+                    } else {
+                        true
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if classes.is_empty() {
+                format!(r#"<pre><code data-line-start="{}">"#, line_start)
+            } else {
+                format!(
+                    r#"<pre><code class="{}" data-line-start="{}">"#,
+                    classes, line_start
+                )
+            }
+        })
+        .into_owned()
+}
+
+// Adds the `download` attribute to the links to files with one of the
+// `extensions`, like `report.pdf`, so they are downloaded instead of opened.
+// With `new_tab` they also get `target="_blank"`.
+fn add_download_attributes(html: &str, extensions: &[String], new_tab: bool) -> String {
+    let regex = Regex::new(r##"<a ([^>]*?)href="([^"]+)"([^>]*)>"##).unwrap();
+    regex
+        .replace_all(html, |caps: &Captures<'_>| {
+            let target = caps[2].split(|c| c == '#' || c == '?').next().unwrap();
+            let file = target.rsplit('/').next().unwrap();
+            let matches = match file.rfind('.') {
+                Some(dot) => extensions.iter().any(|ext| {
+                    ext.trim_start_matches('.')
+                        .eq_ignore_ascii_case(&file[dot + 1..])
+                }),
+                None => false,
+            };
+            if !matches || caps[0].contains(" download") {
+                return caps[0].to_string();
+            }
+
+            let mut link = caps[0][..caps[0].len() - 1].to_string();
+            link.push_str(" download");
+            if new_tab && !caps[0].contains(" target=") {
+                link.push_str(r#" target="_blank" rel="noopener""#);
+            }
+            link.push('>');
+            link
+        })
+        .into_owned()
+}
+
 fn add_playground_pre(
     html: &str,
     playground_config: &Playground,
     edition: Option<RustEdition>,
 ) -> String {
-    let regex = Regex::new(r##"((?s)<code[^>]?class="([^"]+)".*?>(.*?)</code>)"##).unwrap();
+    let regex = Regex::new(r##"((?s)<code[^>]?class="([^"]+)"(.*?)>(.*?)</code>)"##).unwrap();
     regex
         .replace_all(html, |caps: &Captures<'_>| {
             let text = &caps[1];
             let classes = &caps[2];
-            let code = &caps[3];
+            let attrs = &caps[3];
+            let code = &caps[4];
 
             if classes.contains("language-rust") {
                 if (!classes.contains("ignore")
@@ -813,9 +2231,10 @@ fn add_playground_pre(
 
                     // wrap the contents in an external pre block
                     format!(
-                        "<pre class=\"playground\"><code class=\"{}{}\">{}</code></pre>",
+                        "<pre class=\"playground\"><code class=\"{}{}\"{}>{}</code></pre>",
                         classes,
                         edition_class,
+                        attrs,
                         {
                             let content: Cow<'_, str> = if playground_config.editable
                                 && classes.contains("editable")
@@ -837,7 +2256,12 @@ fn add_playground_pre(
                         }
                     )
                 } else {
-                    format!("<code class=\"{}\">{}</code>", classes, hide_lines(code))
+                    format!(
+                        "<code class=\"{}\"{}>{}</code>",
+                        classes,
+                        attrs,
+                        hide_lines(code)
+                    )
                 }
             } else {
                 // not language-rust, so no-op
@@ -879,6 +2303,63 @@ fn hide_lines(content: &str) -> String {
     result
 }
 
+// Hides the lines of non-Rust code blocks which start with the prefix
+// configured for their language in `output.html.code.hidelines`.
+fn hide_custom_lines(html: &str, hidelines: &HashMap<String, String>) -> String {
+    let regex = Regex::new(r##"((?s)<code class="([^"]+)"([^>]*)>(.*?)</code>)"##).unwrap();
+    regex
+        .replace_all(html, |caps: &Captures<'_>| {
+            let text = &caps[1];
+            let classes = &caps[2];
+            let attrs = &caps[3];
+            let code = &caps[4];
+
+            let prefix = classes
+                .split_whitespace()
+                .filter(|class| class.starts_with("language-"))
+                .map(|class| &class["language-".len()..])
+                .filter(|language| *language != "rust")
+                .find_map(|language| hidelines.get(language));
+
+            match prefix {
+                Some(prefix) if !prefix.is_empty() => format!(
+                    "<code class=\"{}\"{}>{}</code>",
+                    classes,
+                    attrs,
+                    // The code is already HTML-escaped, so the prefix has to be as well
+                    hide_lines_with_prefix(code, &escape_html(prefix))
+                ),
+                _ => text.to_owned(),
+            }
+        })
+        .into_owned()
+}
+
+fn hide_lines_with_prefix(content: &str, prefix: &str) -> String {
+    let mut result = String::with_capacity(content.len());
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with(prefix) {
+            let indent = &line[..line.len() - trimmed.len()];
+            let rest = &trimmed[prefix.len()..];
+            let rest = if rest.starts_with(' ') {
+                &rest[1..]
+            } else {
+                rest
+            };
+            result += "<span class=\"boring\">";
+            result += indent;
+            result += rest;
+            result += "\n";
+            result += "</span>";
+        } else {
+            result += line;
+            result += "\n";
+        }
+    }
+    result
+}
+
 fn partition_source(s: &str) -> (String, String) {
     let mut after_header = false;
     let mut before = String::new();
@@ -902,6 +2383,8 @@ fn partition_source(s: &str) -> (String, String) {
 
 struct RenderItemContext<'a> {
     handlebars: &'a Handlebars<'a>,
+    breadcrumbs: &'a HashMap<PathBuf, Vec<Breadcrumb>>,
+    renamed_chapters: &'a HashMap<PathBuf, PathBuf>,
     destination: PathBuf,
     data: serde_json::Map<String, serde_json::Value>,
     is_index: bool,
@@ -943,11 +2426,176 @@ mod tests {
         ];
 
         for (src, should_be) in inputs {
-            let got = build_header_links(&src);
+            let got = build_header_links(src, None);
             assert_eq!(got, should_be);
         }
     }
 
+    #[test]
+    fn header_links_can_get_permalink_buttons() {
+        let got = build_header_links(
+            "<h1>Foo</h1><h2>Foo</h2>",
+            Some("https://example.com/book/guide/intro.html"),
+        );
+        assert_eq!(
+            got,
+            r##"<h1><a class="header" href="#foo" id="foo">Foo</a><button class="fa fa-link permalink-button" data-permalink="https://example.com/book/guide/intro.html#foo" title="Copy link to this section" aria-label="Copy link to this section"><i class="tooltiptext"></i></button></h1><h2><a class="header" href="#foo-1" id="foo-1">Foo</a><button class="fa fa-link permalink-button" data-permalink="https://example.com/book/guide/intro.html#foo-1" title="Copy link to this section" aria-label="Copy link to this section"><i class="tooltiptext"></i></button></h2>"##
+        );
+
+        let mut html_config = HtmlConfig::default();
+        assert_eq!(page_url(&html_config, Path::new("guide/intro.html")), "");
+        html_config.site_url = Some("https://example.com/book/".to_string());
+        assert_eq!(
+            page_url(&html_config, Path::new("guide/intro.html")),
+            "https://example.com/book/guide/intro.html"
+        );
+    }
+
+    #[test]
+    fn page_toc_lists_the_headings() {
+        let content = "<h1>Intro</h1>\n<p>Text</p>\n<h2><code>Foo</code> &amp; bar</h2>\n\
+                       <h3>Intro</h3>\n";
+        assert_eq!(
+            page_toc(content),
+            vec![
+                json!({"level": 1, "id": "intro", "text": "Intro"}),
+                json!({"level": 2, "id": "foo--bar", "text": "Foo & bar"}),
+                json!({"level": 3, "id": "intro-1", "text": "Intro"}),
+            ]
+        );
+        assert_eq!(
+            build_header_links(content, None),
+            "<h1><a class=\"header\" href=\"#intro\" id=\"intro\">Intro</a></h1>\n<p>Text</p>\n\
+             <h2><a class=\"header\" href=\"#foo--bar\" id=\"foo--bar\"><code>Foo</code> &amp; bar</a></h2>\n\
+             <h3><a class=\"header\" href=\"#intro-1\" id=\"intro-1\">Intro</a></h3>\n"
+        );
+    }
+
+    #[test]
+    fn page_outline_cant_end_its_script() {
+        let toc = page_toc("<h2>Ending &lt;/script&gt; &amp; more</h2>");
+        assert_eq!(
+            page_outline(&toc),
+            r#"[{"id":"ending-script--more","level":2,"text":"Ending \u003c/script\u003e \u0026 more"}]"#
+        );
+    }
+
+    #[test]
+    fn add_wrap_class_to_code_blocks() {
+        let inputs = [
+            (
+                "<pre><code>x</code></pre>",
+                "<pre><code class=\"wrap\">x</code></pre>",
+            ),
+            (
+                "<pre><code class=\"language-rust\">x</code></pre>",
+                "<pre><code class=\"language-rust wrap\">x</code></pre>",
+            ),
+            (
+                "<pre><code class=\"language-text wrap\">x</code></pre>",
+                "<pre><code class=\"language-text wrap\">x</code></pre>",
+            ),
+            ("<p><code>inline</code></p>", "<p><code>inline</code></p>"),
+        ];
+        for (src, should_be) in &inputs {
+            let got = add_wrap_class(src);
+            assert_eq!(&*got, *should_be);
+        }
+    }
+
+    #[test]
+    fn add_line_numbers_to_code_blocks() {
+        let inputs = [
+            (
+                "<pre><code>x</code></pre>",
+                "<pre><code data-line-start=\"1\">x</code></pre>",
+            ),
+            (
+                "<pre><code class=\"language-rust\">x</code></pre>",
+                "<pre><code class=\"language-rust\" data-line-start=\"1\">x</code></pre>",
+            ),
+            (
+                "<pre><code class=\"language-rust line-start=10 ignore\">x</code></pre>",
+                "<pre><code class=\"language-rust ignore\" data-line-start=\"10\">x</code></pre>",
+            ),
+            (
+                "<pre><code class=\"line-start=5\">x</code></pre>",
+                "<pre><code data-line-start=\"5\">x</code></pre>",
+            ),
+            ("<p><code>inline</code></p>", "<p><code>inline</code></p>"),
+        ];
+        for (src, should_be) in &inputs {
+            let got = add_line_numbers(src);
+            assert_eq!(&*got, *should_be);
+        }
+    }
+
+    #[test]
+    fn add_download_attributes_to_file_links() {
+        let extensions = vec![String::from("pdf"), String::from(".ZIP")];
+        let inputs = [
+            (
+                r#"<a href="report.pdf">x</a>"#,
+                r#"<a href="report.pdf" download>x</a>"#,
+            ),
+            (
+                r#"<a href="../files/All.Zip#page=2" title="t">x</a>"#,
+                r#"<a href="../files/All.Zip#page=2" title="t" download>x</a>"#,
+            ),
+            (
+                r#"<a href="report.pdf" download="r.pdf">x</a>"#,
+                r#"<a href="report.pdf" download="r.pdf">x</a>"#,
+            ),
+            (
+                r#"<a href="report.html">x</a> <a href="pdf/">y</a>"#,
+                r#"<a href="report.html">x</a> <a href="pdf/">y</a>"#,
+            ),
+        ];
+        for (src, should_be) in &inputs {
+            assert_eq!(add_download_attributes(src, &extensions, false), *should_be);
+        }
+
+        assert_eq!(
+            add_download_attributes(r#"<a href="a.pdf">x</a>"#, &extensions, true),
+            r#"<a href="a.pdf" download target="_blank" rel="noopener">x</a>"#
+        );
+    }
+
+    #[test]
+    fn hide_lines_in_non_rust_code_blocks() {
+        let mut hidelines = HashMap::new();
+        hidelines.insert("python".to_string(), "###".to_string());
+        hidelines.insert("html".to_string(), "<!-- hide -->".to_string());
+
+        let inputs = [
+          ("<code class=\"language-python\">### import os\nprint(1)\n    ###x = 2</code>",
+           "<code class=\"language-python\"><span class=\"boring\">import os\n</span>print(1)\n<span class=\"boring\">    x = 2\n</span></code>"),
+          ("<code class=\"language-html\">&lt;!-- hide --&gt; &lt;head&gt;\n&lt;p&gt;</code>",
+           "<code class=\"language-html\"><span class=\"boring\">&lt;head&gt;\n</span>&lt;p&gt;\n</code>"),
+          ("<code class=\"language-bash\">### echo</code>",
+           "<code class=\"language-bash\">### echo</code>"),
+          ("<code class=\"language-python nocopy\" data-line-start=\"1\">###a</code>",
+           "<code class=\"language-python nocopy\" data-line-start=\"1\"><span class=\"boring\">a\n</span></code>"),
+        ];
+        for (src, should_be) in &inputs {
+            let got = hide_custom_lines(src, &hidelines);
+            assert_eq!(&*got, *should_be);
+        }
+    }
+
+    #[test]
+    fn add_playground_keeps_line_numbers() {
+        let got = add_playground_pre(
+            "<code class=\"language-rust\" data-line-start=\"3\">fn main() {}</code>",
+            &Playground::default(),
+            None,
+        );
+        assert_eq!(
+            got,
+            "<pre class=\"playground\"><code class=\"language-rust\" data-line-start=\"3\">fn main() {}\n</code></pre>"
+        );
+    }
+
     #[test]
     fn add_playground() {
         let inputs = [
@@ -1026,4 +2674,213 @@ mod tests {
             assert_eq!(&*got, *should_be);
         }
     }
+
+    #[test]
+    fn breadcrumbs_follow_parts_and_parent_chapters() {
+        use crate::book::Chapter;
+
+        let crumb = |name: &str, link: Option<&str>| Breadcrumb {
+            name: name.to_string(),
+            link: link.map(String::from),
+        };
+
+        let mut nested = Chapter::new("Nested", String::new(), "first/nested.md", vec![]);
+        nested
+            .sub_items
+            .push(BookItem::Chapter(Chapter::new_draft("Draft", vec![])));
+        nested.sub_items.push(BookItem::Chapter(Chapter::new(
+            "Deep",
+            String::new(),
+            "first/deep.md",
+            vec![],
+        )));
+        let mut first = Chapter::new("First", String::new(), "first/index.md", vec![]);
+        first.sub_items.push(BookItem::Chapter(nested));
+
+        let mut book = Book::new();
+        book.push_item(Chapter::new("Intro", String::new(), "intro.md", vec![]))
+            .push_item(BookItem::PartTitle("Part".to_string()))
+            .push_item(first);
+
+        let got = breadcrumbs(&book).unwrap();
+
+        let home = crumb("Home", Some("index.html"));
+        assert_eq!(got.len(), 4);
+        assert_eq!(got[Path::new("intro.md")], vec![home.clone()]);
+        assert_eq!(
+            got[Path::new("first/index.md")],
+            vec![home.clone(), crumb("Part", None)]
+        );
+        assert_eq!(
+            got[Path::new("first/deep.md")],
+            vec![
+                home,
+                crumb("Part", None),
+                crumb("First", Some("first/index.html")),
+                crumb("Nested", Some("first/nested.html")),
+            ]
+        );
+    }
+
+    #[test]
+    fn stylesheets_are_rebased_on_the_root() {
+        let css = "@import \"base.css\" screen;\n\
+                   @import url('https://example.com/x.css');\n\
+                   a { background: url(../img/a.png); }\n\
+                   b { background: url( \"b.svg#icon\" ); }\n\
+                   c { background: url(data:image/png;base64,AAAA) url(/d.png) url(#e); }\n";
+        let (imports, css) = rebase_css(css, "theme/custom.css");
+
+        let root = helpers::css::PATH_TO_ROOT;
+        assert_eq!(
+            imports,
+            format!(
+                "@import url(\"{}theme/base.css\") screen;\n\
+                 @import url(\"https://example.com/x.css\");\n",
+                root
+            )
+        );
+        assert_eq!(
+            css,
+            format!(
+                "\n\n\
+                 a {{ background: url({0}img/a.png); }}\n\
+                 b {{ background: url(\"{0}theme/b.svg#icon\"); }}\n\
+                 c {{ background: url(data:image/png;base64,AAAA) url(/d.png) url(#e); }}\n",
+                root
+            )
+        );
+    }
+
+    #[test]
+    fn theme_vars_make_a_sorted_root_rule() {
+        let mut vars = BTreeMap::new();
+        vars.insert("--sidebar-bg".to_string(), "#fff".to_string());
+        vars.insert("links".to_string(), " rebeccapurple ".to_string());
+        vars.insert("--bg".to_string(), "hsl(0, 0%, 98%)".to_string());
+        assert_eq!(
+            root_rule(&theme_vars(&vars).unwrap()),
+            ":root {\n    --bg: hsl(0, 0%, 98%);\n    --links: rebeccapurple;\n    --sidebar-bg: #fff;\n}"
+        );
+
+        let mut vars = BTreeMap::new();
+        vars.insert("links".to_string(), "red".to_string());
+        vars.insert("--links".to_string(), "blue".to_string());
+        assert!(theme_vars(&vars).is_err());
+
+        let mut vars = BTreeMap::new();
+        vars.insert(
+            "--fg".to_string(),
+            "red; } body { display: none".to_string(),
+        );
+        assert!(theme_vars(&vars).is_err());
+        let mut vars = BTreeMap::new();
+        vars.insert("--a b".to_string(), "red".to_string());
+        assert!(theme_vars(&vars).is_err());
+    }
+
+    #[test]
+    fn redirect_patterns_are_expanded_for_the_old_paths() {
+        let patterns = vec![
+            RedirectPattern {
+                from: r"/old/(.*)\.html".to_string(),
+                to: "/new/$1.html".to_string(),
+            },
+            RedirectPattern {
+                from: r"/(?P<page>[^/]+)\.html".to_string(),
+                to: "../guide/${page}.html".to_string(),
+            },
+            RedirectPattern {
+                from: r"/old/.*".to_string(),
+                to: "/unused.html".to_string(),
+            },
+        ];
+        let paths = vec![
+            "/old/intro.html".to_string(),
+            "/old/guide/setup.html".to_string(),
+            "/faq.html".to_string(),
+            "/nowhere/faq.html".to_string(),
+        ];
+        let mut got: Vec<_> = pattern_redirects(&patterns, &paths)
+            .unwrap()
+            .into_iter()
+            .collect();
+        got.sort();
+        assert_eq!(
+            got,
+            [
+                ("/faq.html".to_string(), "../guide/faq.html".to_string()),
+                (
+                    "/old/guide/setup.html".to_string(),
+                    "/new/guide/setup.html".to_string()
+                ),
+                ("/old/intro.html".to_string(), "/new/intro.html".to_string()),
+            ]
+        );
+
+        let invalid = vec![RedirectPattern {
+            from: "/old/(".to_string(),
+            to: "/new".to_string(),
+        }];
+        assert!(pattern_redirects(&invalid, &paths).is_err());
+    }
+
+    #[test]
+    fn print_page_anchors_are_prefixed_and_linked() {
+        assert_eq!(
+            anchor_prefix(Path::new("guide/Getting Started.md")),
+            "guide-getting-started"
+        );
+
+        let chapters = format!(
+            "{}{}",
+            prefix_header_ids(
+                "<h1>Intro</h1>\n<p><a href=\"guide/setup.html#install\">a</a></p>\n",
+                "intro"
+            ),
+            prefix_header_ids(
+                "<h1>Setup</h1>\n<h2>Install</h2>\n<h2>Install</h2>\n\
+                 <p><a href=\"guide/setup.html#install-1\">b</a> \
+                 <a href=\"guide/../intro.html#intro\">c</a> \
+                 <a href=\"guide/setup.html#1\">d</a> \
+                 <a href=\"https://example.com/x.html#intro\">e</a></p>\n",
+                "guide-setup"
+            )
+        );
+        assert_eq!(
+            build_header_links(&link_print_anchors(&chapters), None),
+            "<h1><a class=\"header\" href=\"#intro-intro\" id=\"intro-intro\">Intro</a></h1>\n\
+             <p><a href=\"#guide-setup-install\">a</a></p>\n\
+             <h1><a class=\"header\" href=\"#guide-setup-setup\" id=\"guide-setup-setup\">Setup</a></h1>\n\
+             <h2><a class=\"header\" href=\"#guide-setup-install\" id=\"guide-setup-install\">Install</a></h2>\n\
+             <h2><a class=\"header\" href=\"#guide-setup-install-1\" id=\"guide-setup-install-1\">Install</a></h2>\n\
+             <p><a href=\"#guide-setup-install-1\">b</a> \
+             <a href=\"#intro-intro\">c</a> \
+             <a href=\"guide/setup.html#1\">d</a> \
+             <a href=\"https://example.com/x.html#intro\">e</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn normalized_output_has_no_trailing_whitespace_outside_of_code() {
+        let html = "<p>Text  \r\n<pre><code>let x = 1;  \nlet y = 2;\t\n</code></pre>\r\
+                    <p>More\t \n\n\n";
+        assert_eq!(
+            normalize_output(html),
+            "<p>Text\n<pre><code>let x = 1;  \nlet y = 2;\t\n</code></pre>\n<p>More\n"
+        );
+    }
+
+    #[test]
+    fn extra_files_are_searched_from_the_literal_prefix_of_their_pattern() {
+        assert_eq!(literal_prefix("robots.txt"), PathBuf::new());
+        assert_eq!(literal_prefix("assets/"), PathBuf::new());
+        assert_eq!(
+            literal_prefix(".well-known/**"),
+            PathBuf::from(".well-known")
+        );
+        assert_eq!(literal_prefix("samples/data.csv"), PathBuf::from("samples"));
+        assert_eq!(literal_prefix("a/b*/c/*.zip"), PathBuf::from("a"));
+        assert_eq!(literal_prefix("**/*.pdf"), PathBuf::new());
+    }
 }
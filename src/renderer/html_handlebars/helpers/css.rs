@@ -0,0 +1,33 @@
+use handlebars::{Context, Handlebars, Helper, Output, RenderContext, RenderError};
+
+/// Stands in for the page's `path_to_root` in the `url()`s of the inlined
+/// stylesheets, which differs between the pages.
+pub const PATH_TO_ROOT: &str = "\u{1}path_to_root\u{1}";
+
+/// Writes the stylesheets bundled for `output.html.inline-css` into a
+/// `<style>` element, with their `url()`s pointing from the current page.
+pub fn inline_stylesheet(
+    _h: &Helper<'_, '_>,
+    _r: &Handlebars<'_>,
+    ctx: &Context,
+    rc: &mut RenderContext<'_, '_>,
+    out: &mut dyn Output,
+) -> Result<(), RenderError> {
+    trace!("inline_stylesheet (handlebars helper)");
+
+    let css = rc.evaluate(ctx, "@root/inline_css")?;
+    let css = css
+        .as_json()
+        .as_str()
+        .ok_or_else(|| RenderError::new("Type error for `inline_css`, string expected"))?;
+    let path_to_root = rc.evaluate(ctx, "@root/path_to_root")?;
+    let path_to_root = path_to_root.as_json().as_str().unwrap_or("");
+
+    out.write("<style>\n")?;
+    out.write(
+        &css.replace(PATH_TO_ROOT, path_to_root)
+            .replace("</style", "<\\/style"),
+    )?;
+    out.write("</style>")?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone};
+use handlebars::{Context, Handlebars, Helper, HelperDef, Output, RenderContext, RenderError};
+use serde_json::Value;
+
+use crate::errors::*;
+
+/// The named date formats, besides strftime-style ones.
+const NAMED_FORMATS: &[&str] = &["iso8601", "rfc2822", "rfc3339"];
+
+/// Handlebars helper formatting dates with `output.html.date-format`, like
+/// `{{format_date date}}`, or with another format given after the date, like
+/// `{{format_date date "rfc2822"}}`.
+///
+/// Dates are either RFC 3339 strings, like `2020-05-17T09:30:00+02:00`, plain
+/// dates like `2020-05-17`, or Unix timestamps in seconds.
+#[derive(Clone)]
+pub struct FormatDate {
+    pub format: String,
+}
+
+impl HelperDef for FormatDate {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _r: &'reg Handlebars<'_>,
+        _ctx: &'rc Context,
+        _rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> Result<(), RenderError> {
+        trace!("format_date (handlebars helper)");
+
+        let value = h
+            .param(0)
+            .ok_or_else(|| RenderError::new("Param 0 with a date is required for format_date."))?
+            .value();
+        let date = parse_date(value).ok_or_else(|| {
+            RenderError::new(format!("format_date can't read {} as a date", value))
+        })?;
+        let format = match h.param(1) {
+            Some(format) => {
+                let format = format
+                    .value()
+                    .as_str()
+                    .ok_or_else(|| RenderError::new("Param 1 of format_date must be a string."))?;
+                check_date_format(format).map_err(|e| RenderError::new(e.to_string()))?;
+                format
+            }
+            None => &self.format,
+        };
+
+        out.write(&format_date(&date, format))?;
+        Ok(())
+    }
+}
+
+/// Makes sure `format` is one of the named formats, or a valid strftime-style
+/// format, which chrono panics on otherwise.
+pub fn check_date_format(format: &str) -> Result<()> {
+    if NAMED_FORMATS.contains(&format) {
+        return Ok(());
+    }
+    for item in StrftimeItems::new(format) {
+        if let Item::Error = item {
+            bail!("`{}` is not a valid date format", format);
+        }
+    }
+    Ok(())
+}
+
+/// Formats `date` with a format accepted by [`check_date_format`].
+pub fn format_date(date: &DateTime<FixedOffset>, format: &str) -> String {
+    match format {
+        "iso8601" => date.format("%Y-%m-%d").to_string(),
+        "rfc2822" => date.to_rfc2822(),
+        "rfc3339" => date.to_rfc3339(),
+        format => date.format(format).to_string(),
+    }
+}
+
+fn parse_date(value: &Value) -> Option<DateTime<FixedOffset>> {
+    let utc = FixedOffset::east(0);
+    match *value {
+        Value::Number(ref secs) => secs
+            .as_i64()
+            .and_then(|secs| NaiveDateTime::from_timestamp_opt(secs, 0))
+            .map(|time| utc.from_utc_datetime(&time)),
+        Value::String(ref date) => DateTime::parse_from_rfc3339(date).ok().or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .map(|date| utc.from_utc_datetime(&date.and_hms(0, 0, 0)))
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(format: &str, template: &str) -> Option<String> {
+        let mut h = Handlebars::new();
+        h.register_helper(
+            "format_date",
+            Box::new(FormatDate {
+                format: format.to_string(),
+            }),
+        );
+        h.render_template(
+            template,
+            &json!({
+                "updated": "2020-05-17T09:30:00+02:00",
+                "released": "2020-05-17",
+                "built": 1_589_700_600,
+            }),
+        )
+        .ok()
+    }
+
+    #[test]
+    fn dates_are_formatted_with_the_configured_format() {
+        assert_eq!(
+            render("%B %-d, %Y at %H:%M", "{{format_date updated}}").unwrap(),
+            "May 17, 2020 at 09:30"
+        );
+        assert_eq!(
+            render(
+                "iso8601",
+                "{{format_date updated}} {{format_date released}} {{format_date built}}"
+            )
+            .unwrap(),
+            "2020-05-17 2020-05-17 2020-05-17"
+        );
+    }
+
+    #[test]
+    fn the_format_can_be_given_with_the_date() {
+        assert_eq!(
+            render("iso8601", r#"{{format_date updated "rfc2822"}}"#).unwrap(),
+            "Sun, 17 May 2020 09:30:00 +0200"
+        );
+        assert_eq!(
+            render("iso8601", r#"{{format_date released "rfc3339"}}"#).unwrap(),
+            "2020-05-17T00:00:00+00:00"
+        );
+        assert!(render("iso8601", r#"{{format_date updated "%Q"}}"#).is_none());
+        assert!(render("iso8601", "{{format_date book}}").is_none());
+    }
+
+    #[test]
+    fn invalid_formats_are_rejected() {
+        assert!(check_date_format("rfc2822").is_ok());
+        assert!(check_date_format("%d.%m.%Y").is_ok());
+        assert!(check_date_format("%Y-%").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+pub mod css;
+pub mod date;
 pub mod navigation;
 pub mod theme;
 pub mod toc;
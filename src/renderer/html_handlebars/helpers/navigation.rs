@@ -123,10 +123,13 @@ fn render(
         .ok_or_else(|| RenderError::new("Type error for `path`, string expected"))?
         .replace("\"", "");
 
-    context.insert(
-        "path_to_root".to_owned(),
-        json!(utils::fs::path_to_root(&base_path)),
-    );
+    // Links are relative to the root when the page has a `<base>` tag.
+    let path_to_root = if rc.evaluate(ctx, "@root/base_url")?.as_json().is_string() {
+        String::new()
+    } else {
+        utils::fs::path_to_root(&base_path)
+    };
+    context.insert("path_to_root".to_owned(), json!(path_to_root));
 
     chapter
         .get("name")
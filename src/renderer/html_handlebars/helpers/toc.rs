@@ -35,6 +35,13 @@ impl HelperDef for RenderToc {
             .ok_or_else(|| RenderError::new("Type error for `path`, string expected"))?
             .replace("\"", "");
 
+        // Links are relative to the root when the page has a `<base>` tag.
+        let path_to_root = if rc.evaluate(ctx, "@root/base_url")?.as_json().is_string() {
+            String::new()
+        } else {
+            utils::fs::path_to_root(&current_path)
+        };
+
         let current_section = rc
             .evaluate(ctx, "@root/section")?
             .as_json()
@@ -81,22 +88,34 @@ impl HelperDef for RenderToc {
                     level - 1 < fold_level as usize
                 };
 
+            // Foldable sections get an id, so the theme can remember whether
+            // the reader expanded or collapsed them.
+            let has_sub_items = item
+                .get("has_sub_items")
+                .and_then(|flag| flag.parse::<bool>().ok())
+                .unwrap_or_default();
+            let fold_id = if fold_enable && has_sub_items {
+                item.get("section").or_else(|| item.get("name"))
+            } else {
+                None
+            };
+
             if level > current_level {
                 while level > current_level {
                     out.write("<li>")?;
                     out.write("<ol class=\"section\">")?;
                     current_level += 1;
                 }
-                write_li_open_tag(out, is_expanded, false)?;
+                write_li_open_tag(out, is_expanded, false, fold_id)?;
             } else if level < current_level {
                 while level < current_level {
                     out.write("</ol>")?;
                     out.write("</li>")?;
                     current_level -= 1;
                 }
-                write_li_open_tag(out, is_expanded, false)?;
+                write_li_open_tag(out, is_expanded, false, fold_id)?;
             } else {
-                write_li_open_tag(out, is_expanded, item.get("section").is_none())?;
+                write_li_open_tag(out, is_expanded, !item.contains_key("section"), fold_id)?;
             }
 
             // Part title
@@ -120,12 +139,12 @@ impl HelperDef for RenderToc {
                         .replace("\\", "/");
 
                     // Add link
-                    out.write(&utils::fs::path_to_root(&current_path))?;
+                    out.write(&path_to_root)?;
                     out.write(&tmp)?;
                     out.write("\"")?;
 
                     if path == &current_path {
-                        out.write(" class=\"active\"")?;
+                        out.write(" class=\"active\" aria-current=\"page\"")?;
                     }
 
                     out.write(">")?;
@@ -168,11 +187,8 @@ impl HelperDef for RenderToc {
             }
 
             // Render expand/collapse toggle
-            if let Some(flag) = item.get("has_sub_items") {
-                let has_sub_items = flag.parse::<bool>().unwrap_or_default();
-                if fold_enable && has_sub_items {
-                    out.write("<a class=\"toggle\"><div>❱</div></a>")?;
-                }
+            if fold_id.is_some() {
+                out.write("<a class=\"toggle\"><div>❱</div></a>")?;
             }
             out.write("</li>")?;
         }
@@ -191,6 +207,7 @@ fn write_li_open_tag(
     out: &mut dyn Output,
     is_expanded: bool,
     is_affix: bool,
+    fold_id: Option<&String>,
 ) -> Result<(), std::io::Error> {
     let mut li = String::from("<li class=\"chapter-item ");
     if is_expanded {
@@ -199,6 +216,12 @@ fn write_li_open_tag(
     if is_affix {
         li.push_str("affix ");
     }
-    li.push_str("\">");
+    li.push('"');
+    if let Some(fold_id) = fold_id {
+        li.push_str(" data-fold-id=\"");
+        li.push_str(&fold_id.replace('&', "&amp;").replace('"', "&quot;"));
+        li.push('"');
+    }
+    li.push('>');
     out.write(&li)
 }
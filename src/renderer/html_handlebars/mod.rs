@@ -6,4 +6,4 @@ mod hbs_renderer;
 mod helpers;
 
 #[cfg(feature = "search")]
-mod search;
+pub mod search;
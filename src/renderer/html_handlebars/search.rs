@@ -1,26 +1,108 @@
+//! Creation of the search index used by the HTML renderer's search bar.
+//!
+//! Alternative backends can use [`create_index`] to emit a `searchindex.json`
+//! which works with the bundled `searcher.js`.
+
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use elasticlunr::Index;
+use elasticlunr::document_store::DocumentStore;
+use elasticlunr::inverted_index::InvertedIndex;
+use elasticlunr::pipeline::{self, PipelineFn};
+use elasticlunr::Language;
 use pulldown_cmark::*;
 
-use crate::book::{Book, BookItem};
+use crate::book::{Book, BookItem, Chapter};
 use crate::config::Search;
 use crate::errors::*;
-use crate::theme::searcher;
+use crate::theme::{searcher, Theme};
 use crate::utils;
 
-/// Creates all files required for search.
-pub fn create_files(search_config: &Search, destination: &Path, book: &Book) -> Result<()> {
-    let mut index = Index::new(&["title", "body", "breadcrumbs"]);
-    let mut doc_urls = Vec::with_capacity(book.sections.len());
+/// A section of a chapter as it is added to the search index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchDocument {
+    /// The URL of the section, relative to the book's root (e.g.
+    /// `first/index.html#some-section`).
+    pub url: String,
+    /// The section's heading.
+    pub title: String,
+    /// The section's text, without any markup.
+    pub body: String,
+    /// The headings of the chapters and sections containing the section,
+    /// separated by ` » `.
+    pub breadcrumbs: String,
+    /// The tags of the chapter containing the section.
+    pub tags: Vec<String>,
+}
 
-    for item in book.iter() {
-        render_item(&mut index, &search_config, &mut doc_urls, item)?;
-    }
+/// The search index of a book, kept between builds, so that only the
+/// documents of the chapters which changed since the last one are removed from
+/// it and added again. The whole index is rebuilt when the summary or the
+/// search config changed.
+#[derive(Default)]
+pub(crate) struct SearchIndexCache {
+    state: Option<IndexState>,
+}
+
+struct IndexState {
+    search_config: Search,
+    /// The paths and parent names of the chapters, in the order of the book.
+    summary: Vec<(PathBuf, Vec<String>)>,
+    stopwords: Vec<String>,
+    has_tags: bool,
+    index: SearchIndex,
+    chapters: HashMap<PathBuf, IndexedChapter>,
+    /// The documents in the index, by their ref. The refs of removed
+    /// documents are free until the next documents added get them.
+    docs: Vec<Option<IndexedDoc>>,
+}
+
+/// A chapter in the index, with what its documents were made of.
+struct IndexedChapter {
+    content: String,
+    tags: Vec<String>,
+    refs: Vec<usize>,
+}
+
+struct IndexedDoc {
+    url: String,
+    /// The terms added to the index for each field.
+    terms: Vec<Vec<String>>,
+    teaser_terms: Option<TermPositions>,
+}
 
-    let index = write_to_json(index, &search_config, doc_urls)?;
+/// How much more the results of the first chapter are boosted than the ones of
+/// the last chapter with `prefer-order`.
+const ORDER_BOOST: f64 = 0.1;
+
+/// Creates all files required for search.
+pub fn create_files(
+    search_config: &Search,
+    destination: &Path,
+    book: &Book,
+    theme: &Theme,
+) -> Result<()> {
+    write_files(
+        search_config,
+        destination,
+        book,
+        theme,
+        &mut SearchIndexCache::default(),
+    )
+}
+
+/// Like [`create_files`], but updating the search index of the previous
+/// build in `cache`.
+pub(crate) fn write_files(
+    search_config: &Search,
+    destination: &Path,
+    book: &Book,
+    theme: &Theme,
+    cache: &mut SearchIndexCache,
+) -> Result<()> {
+    cache.update(book, search_config)?;
+    let index = cache.to_json()?;
     debug!("Writing search index ✓");
     if index.len() > 10_000_000 {
         warn!("searchindex.json is very large ({} bytes)", index.len());
@@ -33,48 +115,541 @@ pub fn create_files(search_config: &Search, destination: &Path, book: &Book) ->
             "searchindex.js",
             format!("Object.assign(window.search, {});", index).as_bytes(),
         )?;
-        utils::fs::write_file(destination, "searcher.js", searcher::JS)?;
-        utils::fs::write_file(destination, "mark.min.js", searcher::MARK_JS)?;
-        utils::fs::write_file(destination, "elasticlunr.min.js", searcher::ELASTICLUNR_JS)?;
+        for (file_name, default) in &[
+            ("searcher.js", searcher::JS),
+            ("mark.min.js", searcher::MARK_JS),
+            ("elasticlunr.min.js", searcher::ELASTICLUNR_JS),
+        ] {
+            utils::fs::write_file(destination, file_name, &theme.asset(file_name, default))?;
+        }
         debug!("Copying search files ✓");
     }
 
     Ok(())
 }
 
-/// Uses the given arguments to construct a search document, then inserts it to the given index.
-fn add_doc(
-    index: &mut Index,
-    doc_urls: &mut Vec<String>,
+/// Creates the contents of `searchindex.json` for the given book.
+pub fn create_index(book: &Book, search_config: &Search) -> Result<String> {
+    let mut cache = SearchIndexCache::default();
+    cache.update(book, search_config)?;
+    cache.to_json()
+}
+
+impl SearchIndexCache {
+    /// Brings the index up to date with `book`, returning the paths of the
+    /// chapters whose documents were added to it.
+    pub(crate) fn update(&mut self, book: &Book, search_config: &Search) -> Result<Vec<PathBuf>> {
+        let chapters = book_chapters(book);
+        let summary: Vec<_> = chapters
+            .iter()
+            .map(|ch| (chapter_path(ch).to_path_buf(), ch.parent_names.clone()))
+            .collect();
+        // Only books using tags get a field for them, keeping the index small otherwise
+        let has_tags = chapters.iter().any(|ch| !ch.tags.is_empty());
+
+        let mut state = match self.state.take() {
+            Some(state)
+                if state.search_config == *search_config
+                    && state.summary == summary
+                    && state.has_tags == has_tags =>
+            {
+                state
+            }
+            previous => {
+                if previous.is_some() {
+                    debug!("The book's structure changed, rebuilding the whole search index");
+                }
+                IndexState::new(search_config, summary, has_tags)
+            }
+        };
+
+        let mut free_refs = Vec::new();
+        let mut added = Vec::new();
+        for ch in &chapters {
+            let path = chapter_path(ch);
+            match state.chapters.get(path) {
+                Some(indexed) if indexed.content == ch.content && indexed.tags == ch.tags => {
+                    continue
+                }
+                Some(_) => {
+                    for doc_ref in state.chapters.remove(path).unwrap().refs {
+                        state.remove_doc(doc_ref);
+                        free_refs.push(doc_ref);
+                    }
+                }
+                None => {}
+            }
+            added.push((ch, render_chapter(search_config, ch)?));
+        }
+        debug!(
+            "Adding the search documents of {} of {} chapters",
+            added.len(),
+            chapters.len()
+        );
+
+        // The documents get the lowest free refs, so a chapter keeps its refs
+        // if the number of its documents stays the same.
+        free_refs.sort_unstable_by(|a, b| b.cmp(a));
+        let mut paths = Vec::with_capacity(added.len());
+        for (ch, documents) in added {
+            let mut refs = Vec::with_capacity(documents.len());
+            for doc in documents {
+                let doc_ref = free_refs.pop().unwrap_or_else(|| {
+                    state.docs.push(None);
+                    state.docs.len() - 1
+                });
+                state.add_doc(doc_ref, doc);
+                refs.push(doc_ref);
+            }
+            state.chapters.insert(
+                chapter_path(ch).to_path_buf(),
+                IndexedChapter {
+                    content: ch.content.clone(),
+                    tags: ch.tags.clone(),
+                    refs,
+                },
+            );
+            paths.push(chapter_path(ch).to_path_buf());
+        }
+        while let Some(&None) = state.docs.last() {
+            state.docs.pop();
+        }
+
+        self.state = Some(state);
+        Ok(paths)
+    }
+
+    /// The contents of `searchindex.json` for the index.
+    pub(crate) fn to_json(&self) -> Result<String> {
+        let state = self
+            .state
+            .as_ref()
+            .expect("The index is updated before it is written");
+        let search_config = &state.search_config;
+
+        let doc_urls = state
+            .docs
+            .iter()
+            .map(|doc| doc.as_ref().map_or("", |doc| &doc.url).to_string())
+            .collect();
+        let teaser_terms = if search_config.teaser_highlight {
+            Some(
+                state
+                    .docs
+                    .iter()
+                    .map(|doc| {
+                        doc.as_ref()
+                            .and_then(|doc| doc.teaser_terms.clone())
+                            .unwrap_or_default()
+                    })
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        let doc_boosts = if search_config.prefer_order {
+            let refs: Vec<usize> = state
+                .summary
+                .iter()
+                .flat_map(|(path, _)| state.chapters[path].refs.iter().cloned())
+                .collect();
+            let urls: Vec<&str> = refs
+                .iter()
+                .map(|&doc_ref| state.docs[doc_ref].as_ref().map_or("", |doc| &doc.url))
+                .collect();
+            let mut doc_boosts = vec![1.0; state.docs.len()];
+            for (doc_ref, boost) in refs.into_iter().zip(order_boosts(&urls)) {
+                doc_boosts[doc_ref] = boost;
+            }
+            Some(doc_boosts)
+        } else {
+            None
+        };
+
+        write_to_json(
+            &state.index,
+            search_config,
+            doc_urls,
+            state.stopwords.clone(),
+            teaser_terms,
+            doc_boosts,
+            state.has_tags,
+        )
+    }
+}
+
+impl IndexState {
+    fn new(search_config: &Search, summary: Vec<(PathBuf, Vec<String>)>, has_tags: bool) -> Self {
+        let lang = Language::from_code(&search_config.lang).unwrap_or_else(|| {
+            warn!(
+                "The search index doesn't support the language \"{}\", falling back to English",
+                search_config.lang
+            );
+            Language::English
+        });
+        let mut fields = vec!["title", "body", "breadcrumbs"];
+        if has_tags {
+            fields.push("tags");
+        }
+        let stopwords = custom_stopwords(search_config);
+
+        IndexState {
+            search_config: search_config.clone(),
+            summary,
+            index: SearchIndex::new(lang, &fields, SearchPipeline::new(lang, &stopwords)),
+            stopwords,
+            has_tags,
+            chapters: HashMap::new(),
+            docs: Vec::new(),
+        }
+    }
+
+    fn add_doc(&mut self, doc_ref: usize, doc: SearchDocument) {
+        let key = doc_ref.to_string();
+        let terms = if self.has_tags {
+            let tags = doc.tags.join(" ");
+            self.index
+                .add_doc(&key, &[&doc.title, &doc.body, &doc.breadcrumbs, &tags])
+        } else {
+            self.index
+                .add_doc(&key, &[&doc.title, &doc.body, &doc.breadcrumbs])
+        };
+        let teaser_terms = if self.search_config.teaser_highlight {
+            Some(term_positions(&self.index.pipeline, &doc.body))
+        } else {
+            None
+        };
+        self.docs[doc_ref] = Some(IndexedDoc {
+            url: doc.url,
+            terms,
+            teaser_terms,
+        });
+    }
+
+    fn remove_doc(&mut self, doc_ref: usize) {
+        if let Some(doc) = self.docs[doc_ref].take() {
+            self.index.remove_doc(&doc_ref.to_string(), &doc.terms);
+        }
+    }
+}
+
+/// A step of the pipeline the words of the search documents go through
+/// before they are added to the index.
+enum PipelineStep {
+    /// A function of the language's pipeline, named like the elasticlunr.js
+    /// function doing the same to the search queries, e.g. `stemmer-de`.
+    Language(String, PipelineFn),
+    /// Leaves out the configured stop words.
+    Stopwords(HashSet<String>),
+}
+
+/// The pipeline of a search index: the one of its language, with the
+/// configured stop words left out right after the language's own ones.
+struct SearchPipeline {
+    steps: Vec<PipelineStep>,
+}
+
+impl SearchPipeline {
+    fn new(lang: Language, stopwords: &[String]) -> SearchPipeline {
+        let mut steps: Vec<_> = lang
+            .make_pipeline()
+            .queue
+            .into_iter()
+            .map(|(name, function)| PipelineStep::Language(name, function))
+            .collect();
+
+        if !stopwords.is_empty() {
+            let position = steps.iter().position(|step| match *step {
+                PipelineStep::Language(ref name, _) => name.starts_with("stopWordFilter"),
+                PipelineStep::Stopwords(_) => false,
+            });
+            match position {
+                Some(position) => steps.insert(
+                    position + 1,
+                    PipelineStep::Stopwords(stopwords.iter().cloned().collect()),
+                ),
+                None => warn!("The search language has no stop word filter, ignoring `stopwords`"),
+            }
+        }
+
+        SearchPipeline { steps }
+    }
+
+    /// Runs the pipeline on `tokens`, leaving out the ones a step filters out.
+    fn run(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .filter_map(|token| {
+                self.steps
+                    .iter()
+                    .try_fold(token, |token, step| match *step {
+                        PipelineStep::Language(_, function) => function(token),
+                        PipelineStep::Stopwords(ref words) if words.contains(&token) => None,
+                        PipelineStep::Stopwords(_) => Some(token),
+                    })
+            })
+            .collect()
+    }
+
+    /// The names of the functions elasticlunr.js runs on the search queries.
+    /// The stop words are passed to it separately, so they aren't a step of
+    /// the serialized pipeline.
+    fn names(&self) -> Vec<&str> {
+        self.steps
+            .iter()
+            .filter_map(|step| match *step {
+                PipelineStep::Language(ref name, _) => Some(name.as_str()),
+                PipelineStep::Stopwords(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// A search index, serialized like an `elasticlunr::Index` for elasticlunr.js,
+/// whose words go through a [`SearchPipeline`].
+struct SearchIndex {
+    lang: Language,
+    fields: Vec<String>,
+    pipeline: SearchPipeline,
+    index: BTreeMap<String, InvertedIndex>,
+    document_store: DocumentStore,
+}
+
+impl SearchIndex {
+    fn new(lang: Language, fields: &[&str], pipeline: SearchPipeline) -> SearchIndex {
+        SearchIndex {
+            lang,
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            pipeline,
+            index: fields
+                .iter()
+                .map(|field| (field.to_string(), InvertedIndex::new()))
+                .collect(),
+            document_store: DocumentStore::new(true),
+        }
+    }
+
+    /// Adds the document `doc_ref` with the `data` of each of the fields,
+    /// returning the terms added to the index of each field.
+    fn add_doc(&mut self, doc_ref: &str, data: &[&str]) -> Vec<Vec<String>> {
+        let mut doc = BTreeMap::new();
+        doc.insert(String::from("id"), doc_ref.to_string());
+        // Like elasticlunr-rs does, the frequencies of the terms add up over
+        // the fields, so every field's terms are also added to the following
+        // fields.
+        let mut token_freq = BTreeMap::new();
+        let mut terms = Vec::with_capacity(data.len());
+
+        for (field, value) in self.fields.iter().zip(data) {
+            doc.insert(field.clone(), value.to_string());
+
+            let tokens = self.pipeline.run(pipeline::tokenize(value));
+            self.document_store
+                .add_field_length(doc_ref, field, tokens.len());
+            for token in tokens {
+                *token_freq.entry(token).or_insert(0u64) += 1;
+            }
+
+            let index = self.index.get_mut(field).expect("Every field has an index");
+            for (token, count) in &token_freq {
+                index.add_token(doc_ref, token, (*count as f64).sqrt());
+            }
+            terms.push(token_freq.keys().cloned().collect());
+        }
+
+        self.document_store.add_doc(doc_ref, doc);
+        terms
+    }
+
+    /// Removes the document `doc_ref`, whose `terms` were returned by
+    /// [`SearchIndex::add_doc`].
+    fn remove_doc(&mut self, doc_ref: &str, terms: &[Vec<String>]) {
+        for (field, terms) in self.fields.iter().zip(terms) {
+            let index = self.index.get_mut(field).expect("Every field has an index");
+            for term in terms {
+                index.remove_token(doc_ref, term);
+            }
+        }
+        self.document_store.remove_doc(doc_ref);
+        self.document_store.doc_info.remove(doc_ref);
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut index = json!(self.index);
+        // Removing documents leaves the nodes of their terms behind
+        if let serde_json::Value::Object(ref mut fields) = index {
+            for field in fields.values_mut() {
+                if let Some(root) = field.get_mut("root") {
+                    prune_index_node(root);
+                }
+            }
+        }
+
+        json!({
+            "fields": self.fields,
+            "pipeline": self.pipeline.names(),
+            "ref": "id",
+            "version": elasticlunr::ELASTICLUNR_VERSION,
+            "index": index,
+            "documentStore": self.document_store,
+            "lang": self.lang,
+        })
+    }
+}
+
+/// Removes the children of a serialized inverted index node which have no
+/// documents, neither themselves nor in any of their children, returning
+/// whether `node` is left without any.
+fn prune_index_node(node: &mut serde_json::Value) -> bool {
+    let node = match *node {
+        serde_json::Value::Object(ref mut node) => node,
+        _ => return false,
+    };
+    let children: Vec<String> = node
+        .keys()
+        .filter(|key| *key != "docs" && *key != "df")
+        .cloned()
+        .collect();
+    for child in children {
+        if prune_index_node(&mut node[&child]) {
+            node.remove(&child);
+        }
+    }
+
+    let has_docs = node
+        .get("docs")
+        .and_then(|docs| docs.as_object())
+        .map_or(false, |docs| !docs.is_empty());
+    !has_docs && node.len() <= 2
+}
+
+/// The boost of every document for `prefer-order`, by the `urls` of the
+/// documents in the order of the book, going down evenly from
+/// `1 + ORDER_BOOST` for the sections of the first chapter to `1` for the ones
+/// of the last.
+fn order_boosts(urls: &[&str]) -> Vec<f64> {
+    let mut pages = Vec::new();
+    let positions: Vec<usize> = urls
+        .iter()
+        .map(|url| {
+            let page = url.split('#').next().unwrap_or_default();
+            if pages.last() != Some(&page) {
+                pages.push(page);
+            }
+            pages.len() - 1
+        })
+        .collect();
+
+    let last = std::cmp::max(pages.len(), 2) as f64 - 1.0;
+    positions
+        .into_iter()
+        .map(|position| {
+            let boost = 1.0 + ORDER_BOOST * (last - position as f64) / last;
+            // A few decimals are plenty, and keep the index small.
+            (boost * 1000.0).round() / 1000.0
+        })
+        .collect()
+}
+
+/// Maps each term of a document to the `[start, end)` ranges of the words it
+/// was created from.
+type TermPositions = BTreeMap<String, Vec<(usize, usize)>>;
+
+/// The positions of the index terms within a document's body.
+///
+/// Words are split like elasticlunr.js tokenizes text, and the offsets count
+/// UTF-16 code units, so they can be used with JavaScript strings directly.
+fn term_positions(pipeline: &SearchPipeline, body: &str) -> TermPositions {
+    let mut positions = TermPositions::new();
+    let mut word = String::new();
+    let mut word_start = 0;
+    let mut offset = 0;
+
+    for c in body.chars().chain(std::iter::once(' ')) {
+        if c.is_whitespace() || c == '-' {
+            if !word.is_empty() {
+                for term in pipeline.run(vec![word.to_lowercase()]) {
+                    positions
+                        .entry(term)
+                        .or_default()
+                        .push((word_start, offset));
+                }
+                word.clear();
+            }
+            word_start = offset + c.len_utf16();
+        } else {
+            word.push(c);
+        }
+        offset += c.len_utf16();
+    }
+
+    positions
+}
+
+/// The configured stop words, normalized the same way as the tokens they are
+/// compared with.
+fn custom_stopwords(search_config: &Search) -> Vec<String> {
+    let mut stopwords: Vec<String> = search_config
+        .stopwords
+        .iter()
+        .map(|word| word.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect();
+    stopwords.sort();
+    stopwords.dedup();
+    stopwords
+}
+
+/// Splits the book's chapters into the documents which are added to the
+/// search index, in the order of the book.
+pub fn create_documents(book: &Book, search_config: &Search) -> Result<Vec<SearchDocument>> {
+    let mut documents = Vec::new();
+    for ch in book_chapters(book) {
+        documents.extend(render_chapter(search_config, ch)?);
+    }
+    Ok(documents)
+}
+
+/// The chapters of the book which are searched, in the order of the book.
+fn book_chapters(book: &Book) -> Vec<&Chapter> {
+    book.iter()
+        .filter_map(|item| match *item {
+            BookItem::Chapter(ref ch) if !ch.is_draft_chapter() => Some(ch),
+            _ => None,
+        })
+        .collect()
+}
+
+fn chapter_path(ch: &Chapter) -> &Path {
+    ch.path.as_ref().expect("Checked that path exists above")
+}
+
+/// Uses the given arguments to construct a search document.
+fn make_doc(
     anchor_base: &str,
     section_id: &Option<String>,
-    items: &[&str],
-) {
+    title: &str,
+    body: &str,
+    breadcrumbs: &str,
+) -> SearchDocument {
     let url = if let Some(ref id) = *section_id {
         Cow::Owned(format!("{}#{}", anchor_base, id))
     } else {
         Cow::Borrowed(anchor_base)
     };
-    let url = utils::collapse_whitespace(url.trim());
-    let doc_ref = doc_urls.len().to_string();
-    doc_urls.push(url.into());
 
-    let items = items.iter().map(|&x| utils::collapse_whitespace(x.trim()));
-    index.add_doc(&doc_ref, items);
+    SearchDocument {
+        url: utils::collapse_whitespace(url.trim()).into(),
+        title: utils::collapse_whitespace(title.trim()).into(),
+        body: utils::collapse_whitespace(body.trim()).into(),
+        breadcrumbs: utils::collapse_whitespace(breadcrumbs.trim()).into(),
+        tags: Vec::new(),
+    }
 }
 
-/// Renders markdown into flat unformatted text and adds it to the search index.
-fn render_item(
-    index: &mut Index,
-    search_config: &Search,
-    doc_urls: &mut Vec<String>,
-    item: &BookItem,
-) -> Result<()> {
-    let chapter = match *item {
-        BookItem::Chapter(ref ch) if !ch.is_draft_chapter() => ch,
-        _ => return Ok(()),
-    };
-
+/// Renders markdown into flat unformatted text, split into one search document
+/// per section.
+fn render_chapter(search_config: &Search, chapter: &Chapter) -> Result<Vec<SearchDocument>> {
     let chapter_path = chapter
         .path
         .as_ref()
@@ -94,20 +669,21 @@ fn render_item(
     let mut body = String::new();
     let mut breadcrumbs = chapter.parent_names.clone();
     let mut footnote_numbers = HashMap::new();
+    let mut documents = Vec::new();
 
     while let Some(event) = p.next() {
         match event {
             Event::Start(Tag::Heading(i)) if i <= max_section_depth => {
                 if !heading.is_empty() {
                     // Section finished, the next heading is following now
-                    // Write the data to the index, and clear it for the next section
-                    add_doc(
-                        index,
-                        doc_urls,
+                    // Turn the data into a document, and clear it for the next section
+                    documents.push(make_doc(
                         &anchor_base,
                         &section_id,
-                        &[&heading, &body, &breadcrumbs.join(" » ")],
-                    );
+                        &heading,
+                        &body,
+                        &breadcrumbs.join(" » "),
+                    ));
                     section_id = None;
                     heading.clear();
                     body.clear();
@@ -165,21 +741,32 @@ fn render_item(
 
     if !heading.is_empty() {
         // Make sure the last section is added to the index
-        add_doc(
-            index,
-            doc_urls,
+        documents.push(make_doc(
             &anchor_base,
             &section_id,
-            &[&heading, &body, &breadcrumbs.join(" » ")],
-        );
+            &heading,
+            &body,
+            &breadcrumbs.join(" » "),
+        ));
     }
 
-    Ok(())
+    for doc in &mut documents {
+        doc.tags = chapter.tags.clone();
+    }
+
+    Ok(documents)
 }
 
-fn write_to_json(index: Index, search_config: &Search, doc_urls: Vec<String>) -> Result<String> {
+fn write_to_json(
+    index: &SearchIndex,
+    search_config: &Search,
+    doc_urls: Vec<String>,
+    stopwords: Vec<String>,
+    teaser_terms: Option<Vec<TermPositions>>,
+    doc_boosts: Option<Vec<f64>>,
+    has_tags: bool,
+) -> Result<String> {
     use elasticlunr::config::{SearchBool, SearchOptions, SearchOptionsField};
-    use std::collections::BTreeMap;
 
     #[derive(Serialize)]
     struct ResultsOptions {
@@ -195,8 +782,19 @@ fn write_to_json(index: Index, search_config: &Search, doc_urls: Vec<String>) ->
         search_options: SearchOptions,
         /// Used to lookup a document's URL from an integer document ref.
         doc_urls: Vec<String>,
+        /// Additional stop words for elasticlunr.js
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        stopwords: Vec<String>,
+        /// The positions of each document's terms, used to highlight matches in teasers
+        #[serde(skip_serializing_if = "Option::is_none")]
+        teaser_terms: Option<Vec<TermPositions>>,
+        /// The factor each document's score is multiplied with, favoring earlier chapters
+        #[serde(skip_serializing_if = "Option::is_none")]
+        doc_boosts: Option<Vec<f64>>,
+        /// The language of the index, whose pipeline elasticlunr.js runs on the queries
+        lang: &'static str,
         /// The index for elasticlunr.js
-        index: elasticlunr::Index,
+        index: serde_json::Value,
     }
 
     let mut fields = BTreeMap::new();
@@ -207,6 +805,11 @@ fn write_to_json(index: Index, search_config: &Search, doc_urls: Vec<String>) ->
     fields.insert("body".into(), opt);
     opt.boost = Some(search_config.boost_hierarchy);
     fields.insert("breadcrumbs".into(), opt);
+    if has_tags {
+        // Tags describe the whole chapter, like its title does
+        opt.boost = Some(search_config.boost_title);
+        fields.insert("tags".into(), opt);
+    }
 
     let search_options = SearchOptions {
         bool: if search_config.use_boolean_and {
@@ -227,7 +830,11 @@ fn write_to_json(index: Index, search_config: &Search, doc_urls: Vec<String>) ->
         results_options,
         search_options,
         doc_urls,
-        index,
+        stopwords,
+        teaser_terms,
+        doc_boosts,
+        lang: index.lang.to_code(),
+        index: index.to_json(),
     };
 
     // By converting to serde_json::Value as an intermediary, we use a
@@ -257,3 +864,87 @@ fn clean_html(html: &str) -> String {
     }
     AMMONIA.clean(html).to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(chapters: &[(&str, &str)]) -> Book {
+        let mut book = Book::new();
+        for &(path, content) in chapters {
+            book.push_item(Chapter::new(path, content.to_string(), path, Vec::new()));
+        }
+        book
+    }
+
+    #[test]
+    fn only_the_documents_of_changed_chapters_are_added_again() {
+        let search_config = Search {
+            teaser_highlight: true,
+            prefer_order: true,
+            ..Default::default()
+        };
+        let mut cache = SearchIndexCache::default();
+
+        let first = book(&[
+            ("a.md", "# A\n\nApples and pears."),
+            ("b.md", "# B\n\nBananas."),
+            ("c.md", "# C\n\nCherries."),
+        ]);
+        let added = cache.update(&first, &search_config).unwrap();
+        let paths = vec![
+            PathBuf::from("a.md"),
+            PathBuf::from("b.md"),
+            PathBuf::from("c.md"),
+        ];
+        assert_eq!(added, paths);
+
+        // The changed chapter loses a term
+        let second = book(&[
+            ("a.md", "# A\n\nApples and pears."),
+            ("b.md", "# B\n\nBlueberries."),
+            ("c.md", "# C\n\nCherries."),
+        ]);
+        let added = cache.update(&second, &search_config).unwrap();
+        assert_eq!(added, vec![PathBuf::from("b.md")]);
+        assert_eq!(
+            cache.to_json().unwrap(),
+            create_index(&second, &search_config).unwrap()
+        );
+
+        assert!(cache.update(&second, &search_config).unwrap().is_empty());
+
+        // The new documents of a chapter are added after the others
+        let third = book(&[
+            ("a.md", "# A\n\nApples and pears."),
+            ("b.md", "# B\n\nBlueberries.\n\n## More\n\nBlackberries."),
+            ("c.md", "# C\n\nCherries."),
+        ]);
+        let added = cache.update(&third, &search_config).unwrap();
+        assert_eq!(added, vec![PathBuf::from("b.md")]);
+        let index: serde_json::Value = serde_json::from_str(&cache.to_json().unwrap()).unwrap();
+        assert_eq!(
+            index["doc_urls"],
+            json!(["a.html#a", "b.html#b", "c.html#c", "b.html#more"])
+        );
+        assert_eq!(index["doc_boosts"], json!([1.1, 1.05, 1.0, 1.05]));
+        assert_eq!(
+            index["index"]["documentStore"]["docs"]["3"]["title"],
+            "More"
+        );
+
+        // A new chapter changes the summary
+        let fourth = book(&[
+            ("a.md", "# A\n\nApples and pears."),
+            ("d.md", "# D\n\nDates."),
+            ("b.md", "# B\n\nBlueberries.\n\n## More\n\nBlackberries."),
+            ("c.md", "# C\n\nCherries."),
+        ]);
+        let added = cache.update(&fourth, &search_config).unwrap();
+        assert_eq!(added.len(), 4);
+        assert_eq!(
+            cache.to_json().unwrap(),
+            create_index(&fourth, &search_config).unwrap()
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use crate::errors::*;
+use crate::renderer::{RenderContext, Renderer};
+use crate::utils;
+
+#[derive(Default)]
+/// A renderer to output the whole book, after the preprocessors have run, as
+/// a single `book.json`. Useful for tools which process the book further
+/// without parsing the `SUMMARY.md` themselves.
+pub struct JsonRenderer;
+
+impl JsonRenderer {
+    /// Create a new `JsonRenderer` instance.
+    pub fn new() -> Self {
+        JsonRenderer
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn render(&self, ctx: &RenderContext) -> Result<()> {
+        let destination = &ctx.destination;
+
+        if destination.exists() {
+            utils::fs::remove_dir_content(destination)
+                .with_context(|| "Unable to remove stale JSON output")?;
+        }
+
+        trace!("json render");
+        let json = serde_json::to_string_pretty(&ctx.book)
+            .with_context(|| "Unable to serialize the book")?;
+        utils::fs::write_file(destination, "book.json", json.as_bytes())?;
+
+        Ok(())
+    }
+}
@@ -1,13 +1,21 @@
-use crate::book::BookItem;
+use crate::book::{self, BookItem, Chapter};
+use crate::config::MarkdownConfig;
 use crate::errors::*;
 use crate::renderer::{RenderContext, Renderer};
 use crate::utils;
 
+use pulldown_cmark::{Event, Tag};
+use regex::Regex;
 use std::fs;
+use std::path::Path;
 
 #[derive(Default)]
 /// A renderer to output the Markdown after the preprocessors have run. Mostly useful
 /// when debugging preprocessors.
+///
+/// With `[output.markdown]` it can also write a faithful copy of the sources
+/// instead, keeping the includes and front matter, or point the links at the
+/// pages of the HTML renderer, see [`MarkdownConfig`].
 pub struct MarkdownRenderer;
 
 impl MarkdownRenderer {
@@ -25,6 +33,12 @@ impl Renderer for MarkdownRenderer {
     fn render(&self, ctx: &RenderContext) -> Result<()> {
         let destination = &ctx.destination;
         let book = &ctx.book;
+        let config: MarkdownConfig = ctx
+            .config
+            .get_deserialized_opt("output.markdown")
+            .with_context(|| "Parsing configuration [output.markdown]")?
+            .unwrap_or_default();
+        let src_dir = ctx.root.join(&ctx.config.book.src);
 
         if destination.exists() {
             utils::fs::remove_dir_content(destination)
@@ -38,7 +52,7 @@ impl Renderer for MarkdownRenderer {
                     utils::fs::write_file(
                         &ctx.destination,
                         &ch.path.as_ref().expect("Checked path exists before"),
-                        ch.content.as_bytes(),
+                        chapter_markdown(ch, &src_dir, &config).as_bytes(),
                     )?;
                 }
             }
@@ -50,3 +64,86 @@ impl Renderer for MarkdownRenderer {
         Ok(())
     }
 }
+
+/// The markdown written for the chapter `ch` of the book in `src_dir`.
+fn chapter_markdown(ch: &Chapter, src_dir: &Path, config: &MarkdownConfig) -> String {
+    let content = if config.rewrite_links {
+        rewrite_links(&ch.content)
+    } else {
+        ch.content.clone()
+    };
+
+    // The front matter is only kept in the chapter's source file.
+    let source = match ch.source_path {
+        Some(ref source_path) if config.front_matter => {
+            fs::read_to_string(src_dir.join(source_path)).ok()
+        }
+        _ => None,
+    };
+    match source {
+        Some(ref source) => match book::split_front_matter(source) {
+            Some((_, rest)) => format!("{}{}", &source[..source.len() - rest.len()], content),
+            None => content,
+        },
+        None => content,
+    }
+}
+
+/// Points the relative links to `.md` files in `content` at the `.html`
+/// pages made of them, like `intro.html#usage` for `intro.md#usage`.
+fn rewrite_links(content: &str) -> String {
+    lazy_static! {
+        static ref SCHEME_LINK: Regex = Regex::new(r"^[a-z][a-z0-9+.-]*:").unwrap();
+        static ref MD_LINK: Regex = Regex::new(r"^(?P<link>.*)\.md(?P<anchor>#.*)?$").unwrap();
+    }
+
+    let mut rewritten = String::with_capacity(content.len());
+    let mut last = 0;
+    for (event, range) in utils::new_cmark_parser(content).into_offset_iter() {
+        let dest = match event {
+            Event::Start(Tag::Link(_, dest, _)) => dest,
+            _ => continue,
+        };
+        let caps = match MD_LINK.captures(&dest) {
+            Some(caps) if !SCHEME_LINK.is_match(&dest) && !dest.starts_with("//") => caps,
+            _ => continue,
+        };
+
+        // The destination of a reference link is in its definition instead.
+        let link = &content[range.clone()];
+        let start = match link
+            .rfind("](")
+            .and_then(|i| Some(i + link[i..].find(&*dest)?))
+        {
+            Some(i) if range.start + i >= last => range.start + i,
+            _ => continue,
+        };
+        rewritten.push_str(&content[last..start]);
+        rewritten.push_str(&caps["link"]);
+        rewritten.push_str(".html");
+        if let Some(anchor) = caps.name("anchor") {
+            rewritten.push_str(anchor.as_str());
+        }
+        last = start + dest.len();
+    }
+    rewritten.push_str(&content[last..]);
+    rewritten
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_to_chapters_are_pointed_at_their_pages() {
+        let content = "[Intro](intro.md) and [usage](../guide/setup.md#usage \"Setup.md\"),\n\
+                       not [the site](https://example.com/README.md), [code](main.rs) \
+                       or [ref][r].\n\n[r]: other.md\n";
+        assert_eq!(
+            rewrite_links(content),
+            "[Intro](intro.html) and [usage](../guide/setup.html#usage \"Setup.md\"),\n\
+             not [the site](https://example.com/README.md), [code](main.rs) \
+             or [ref][r].\n\n[r]: other.md\n"
+        );
+    }
+}
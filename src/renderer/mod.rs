@@ -11,10 +11,14 @@
 //! [For Developers]: https://rust-lang.github.io/mdBook/for_developers/index.html
 //! [RenderContext]: struct.RenderContext.html
 
+#[cfg(feature = "search")]
+pub use self::html_handlebars::search;
 pub use self::html_handlebars::HtmlHandlebars;
+pub use self::json_renderer::JsonRenderer;
 pub use self::markdown_renderer::MarkdownRenderer;
 
 mod html_handlebars;
+mod json_renderer;
 mod markdown_renderer;
 
 use shlex::Shlex;
@@ -26,6 +30,7 @@ use std::process::{Command, Stdio};
 use crate::book::Book;
 use crate::config::Config;
 use crate::errors::*;
+use crate::utils::process;
 use toml::Value;
 
 /// An arbitrary `mdbook` backend.
@@ -111,10 +116,11 @@ impl RenderContext {
 /// > in command line arguments, so there's no reason why it couldn't be
 /// > `python /path/to/renderer --from mdbook --to epub`.
 ///
-/// Anything the subprocess writes to `stdin` or `stdout` will be passed through
-/// to the user. While this gives the renderer maximum flexibility to output
-/// whatever it wants, to avoid spamming users it is recommended to avoid
-/// unnecessary output.
+/// Anything the subprocess writes to `stdout` will be passed through to the
+/// user. What it writes to `stderr` is logged at the debug level once it has
+/// finished, or shown as part of the error if rendering failed. While this gives the
+/// renderer maximum flexibility to output whatever it wants, to avoid spamming
+/// users it is recommended to avoid unnecessary output.
 ///
 /// To help choose the appropriate output level, the `RUST_LOG` environment
 /// variable will be passed through to the subprocess, if set.
@@ -193,12 +199,13 @@ impl Renderer for CmdRenderer {
         info!("Invoking the \"{}\" renderer", self.name);
 
         let _ = fs::create_dir_all(&ctx.destination);
+        let timeout = process::timeout(&ctx.config, &format!("output.{}.timeout", self.name))?;
 
         let mut child = match self
             .compose_command()?
             .stdin(Stdio::piped())
             .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit())
+            .stderr(Stdio::piped())
             .current_dir(&ctx.destination)
             .spawn()
         {
@@ -206,26 +213,36 @@ impl Renderer for CmdRenderer {
             Err(e) => return self.handle_render_command_error(ctx, e),
         };
 
-        let mut stdin = child.stdin.take().expect("Child has stdin");
-        if let Err(e) = serde_json::to_writer(&mut stdin, &ctx) {
-            // Looks like the backend hung up before we could finish
-            // sending it the render context. Log the error and keep going
-            warn!("Error writing the RenderContext to the backend, {}", e);
-        }
+        let input = serde_json::to_vec(&ctx)?;
+        process::write_stdin(&mut child, input);
+        let stderr = process::read_to_end(child.stderr.take().expect("Child has stderr"));
 
-        // explicitly close the `stdin` file handle
-        drop(stdin);
+        let status = match process::wait_timeout(&mut child, timeout)
+            .with_context(|| "Error waiting for the backend to complete")?
+        {
+            Some(status) => status,
+            None => bail!(
+                "The \"{}\" renderer (`{}`) didn't finish within {} seconds and was killed",
+                self.name,
+                self.cmd,
+                timeout.unwrap_or_default().as_secs_f64()
+            ),
+        };
 
-        let status = child
-            .wait()
-            .with_context(|| "Error waiting for the backend to complete")?;
+        let stderr =
+            process::collect(stderr).with_context(|| "Unable to read the backend's stderr")?;
 
         trace!("{} exited with output: {:?}", self.cmd, status);
 
         if !status.success() {
             error!("Renderer exited with non-zero return code.");
-            bail!("The \"{}\" renderer failed", self.name);
+            Err(process::failure(
+                &format!("The \"{}\" renderer", self.name),
+                format!("The \"{}\" renderer failed", self.name),
+                &stderr,
+            ))
         } else {
+            process::log_stderr(&format!("The \"{}\" renderer", self.name), &stderr);
             Ok(())
         }
     }
@@ -23,9 +23,11 @@ function playground_text(playground) {
         ]);
     }
 
+    var playground_url = window.playground_runner_url || "https://play.rust-lang.org";
+
     var playgrounds = Array.from(document.querySelectorAll(".playground"));
     if (playgrounds.length > 0) {
-        fetch_with_timeout("https://play.rust-lang.org/meta/crates", {
+        fetch_with_timeout(playground_url + "/meta/crates", {
             headers: {
                 'Content-Type': "application/json",
             },
@@ -66,7 +68,7 @@ function playground_text(playground) {
     }
 
     // updates the visibility of play button based on `no_run` class and
-    // used crates vs ones available on http://play.rust-lang.org
+    // used crates vs ones available on the playground
     function update_play_button(pre_block, playground_crates) {
         var play_button = pre_block.querySelector(".play-button");
 
@@ -85,7 +87,7 @@ function playground_text(playground) {
             snippet_crates.push(item[1]);
         }
 
-        // check if all used crates are available on play.rust-lang.org
+        // check if all used crates are available on the playground
         var all_available = snippet_crates.every(function (elem) {
             return playground_crates.indexOf(elem) > -1;
         });
@@ -124,7 +126,7 @@ function playground_text(playground) {
 
         result_block.innerText = "Running...";
 
-        fetch_with_timeout("https://play.rust-lang.org/evaluate.json", {
+        fetch_with_timeout(playground_url + "/evaluate.json", {
             headers: {
                 'Content-Type': "application/json",
             },
@@ -166,7 +168,7 @@ function playground_text(playground) {
     // even if highlighting doesn't apply
     code_nodes.forEach(function (block) { block.classList.add('hljs'); });
 
-    Array.from(document.querySelectorAll("code.language-rust")).forEach(function (block) {
+    Array.from(document.querySelectorAll("pre code")).forEach(function (block) {
 
         var lines = Array.from(block.querySelectorAll('.boring'));
         // If no lines were hidden, return
@@ -200,9 +202,85 @@ function playground_text(playground) {
         });
     });
 
+    // Render a line number gutter for code blocks marked by the renderer.
+    // Hidden (boring) lines don't get a number, so that `rustdoc_include`d
+    // snippets are numbered like the lines that are actually shown.
+    Array.from(document.querySelectorAll('pre code[data-line-start]:not(.editable)')).forEach(function (block) {
+        var line_number = parseInt(block.getAttribute('data-line-start'), 10) || 1;
+        var lines = [];
+        var at_line_start = true;
+
+        var walker = document.createTreeWalker(block, NodeFilter.SHOW_TEXT, null, false);
+        while (walker.nextNode()) {
+            var node = walker.currentNode;
+            var boring = node.parentElement.closest('.boring') !== null;
+            for (var i = 0; i < node.data.length; i++) {
+                if (at_line_start) {
+                    lines.push({ boring: boring, empty: node.data[i] === '\n' });
+                    at_line_start = false;
+                }
+                if (node.data[i] === '\n') {
+                    at_line_start = true;
+                }
+            }
+        }
+
+        // Skip the newline injected in front of the generated `fn main`
+        // for playground snippets.
+        if (lines.length && lines[0].empty && lines.length > 1 && lines[1].boring) {
+            lines[0].unnumbered = true;
+        }
+
+        var gutter = document.createElement('span');
+        gutter.className = 'line-numbers';
+        gutter.setAttribute('aria-hidden', 'true');
+        lines.forEach(function (line) {
+            var entry = document.createElement('span');
+            if (line.boring || line.unnumbered) {
+                entry.className = line.boring ? 'boring' : '';
+                entry.textContent = '\n';
+            } else {
+                entry.textContent = line_number + '\n';
+                line_number += 1;
+            }
+            gutter.appendChild(entry);
+        });
+
+        var pre_block = block.parentNode;
+        pre_block.classList.add('line-numbered');
+        pre_block.appendChild(gutter);
+    });
+
+    if (window.code_wrap_button) {
+        Array.from(document.querySelectorAll('pre code')).forEach(function (block) {
+            var pre_block = block.parentNode;
+            var buttons = pre_block.querySelector(".buttons");
+            if (!buttons) {
+                buttons = document.createElement('div');
+                buttons.className = 'buttons';
+                pre_block.insertBefore(buttons, pre_block.firstChild);
+            }
+
+            var wrapButton = document.createElement('button');
+            wrapButton.className = 'fa fa-text-width wrap-button';
+            wrapButton.title = 'Toggle line wrapping';
+            wrapButton.setAttribute('aria-label', wrapButton.title);
+            wrapButton.addEventListener('click', function () {
+                block.classList.toggle('wrap');
+            });
+
+            buttons.appendChild(wrapButton);
+        });
+    }
+
+    var copy_button_label = window.copy_button_label || 'Copy to clipboard';
+
     if (window.playground_copyable) {
         Array.from(document.querySelectorAll('pre code')).forEach(function (block) {
             var pre_block = block.parentNode;
+            if (block.classList.contains('nocopy')) {
+                return;
+            }
             if (!pre_block.classList.contains('playground')) {
                 var buttons = pre_block.querySelector(".buttons");
                 if (!buttons) {
@@ -213,7 +291,7 @@ function playground_text(playground) {
 
                 var clipButton = document.createElement('button');
                 clipButton.className = 'fa fa-copy clip-button';
-                clipButton.title = 'Copy to clipboard';
+                clipButton.title = copy_button_label;
                 clipButton.setAttribute('aria-label', clipButton.title);
                 clipButton.innerHTML = '<i class=\"tooltiptext\"></i>';
 
@@ -243,11 +321,11 @@ function playground_text(playground) {
             run_rust_code(pre_block);
         });
 
-        if (window.playground_copyable) {
+        if (window.playground_copyable && !pre_block.querySelector('code').classList.contains('nocopy')) {
             var copyCodeClipboardButton = document.createElement('button');
             copyCodeClipboardButton.className = 'fa fa-copy clip-button';
             copyCodeClipboardButton.innerHTML = '<i class="tooltiptext"></i>';
-            copyCodeClipboardButton.title = 'Copy to clipboard';
+            copyCodeClipboardButton.title = copy_button_label;
             copyCodeClipboardButton.setAttribute('aria-label', copyCodeClipboardButton.title);
 
             buttons.insertBefore(copyCodeClipboardButton, buttons.firstChild);
@@ -433,9 +511,28 @@ function playground_text(playground) {
 
 
     var sidebarAnchorToggles = document.querySelectorAll('#sidebar a.toggle');
+    var foldState = {};
+    try { foldState = JSON.parse(localStorage.getItem('mdbook-sidebar-folds')) || {}; } catch (e) { }
+
+    // Restore the sections the reader expanded or collapsed on other pages,
+    // but keep the current chapter visible.
+    Array.from(sidebar.querySelectorAll('li[data-fold-id]')).forEach(function (li) {
+        var expanded = foldState[li.dataset.foldId];
+        var sectionItems = li.nextElementSibling;
+        var isCurrent = li.querySelector('.active') ||
+            (sectionItems && sectionItems.querySelector('.active'));
+        if (expanded === true) {
+            li.classList.add('expanded');
+        } else if (expanded === false && !isCurrent) {
+            li.classList.remove('expanded');
+        }
+    });
 
     function toggleSection(ev) {
-        ev.currentTarget.parentElement.classList.toggle('expanded');
+        var li = ev.currentTarget.parentElement;
+        li.classList.toggle('expanded');
+        foldState[li.dataset.foldId] = li.classList.contains('expanded');
+        try { localStorage.setItem('mdbook-sidebar-folds', JSON.stringify(foldState)); } catch (e) { }
     }
 
     Array.from(sidebarAnchorToggles).forEach(function (el) {
@@ -481,7 +578,9 @@ function playground_text(playground) {
         html.classList.add('sidebar-resizing');
     }
     function resize(e) {
-        var pos = (e.clientX - sidebar.offsetLeft);
+        var pos = html.dir === 'rtl'
+            ? (sidebar.offsetLeft + sidebar.offsetWidth - e.clientX)
+            : (e.clientX - sidebar.offsetLeft);
         if (pos < 20) {
             hideSidebar();
         } else {
@@ -533,19 +632,37 @@ function playground_text(playground) {
 })();
 
 (function chapterNavigation() {
+    // Themes which don't set the shortcuts keep the default keys.
+    var shortcuts = window.keyboard_shortcuts === undefined
+        ? { previous: 'ArrowLeft', next: 'ArrowRight' }
+        : window.keyboard_shortcuts;
+
+    if (window.keyboard_shortcuts !== undefined) {
+        var links = document.querySelectorAll('.nav-chapters, .mobile-nav-chapters');
+        for (var i = 0; i < links.length; i++) {
+            if (!shortcuts) {
+                links[i].removeAttribute('aria-keyshortcuts');
+            } else {
+                var key = links[i].classList.contains('next') ? shortcuts.next : shortcuts.previous;
+                links[i].setAttribute('aria-keyshortcuts', key);
+            }
+        }
+    }
+    if (!shortcuts) { return; }
+
     document.addEventListener('keydown', function (e) {
         if (e.altKey || e.ctrlKey || e.metaKey || e.shiftKey) { return; }
         if (window.search && window.search.hasFocus()) { return; }
 
         switch (e.key) {
-            case 'ArrowRight':
+            case shortcuts.next:
                 e.preventDefault();
                 var nextButton = document.querySelector('.nav-chapters.next');
                 if (nextButton) {
                     window.location.href = nextButton.href;
                 }
                 break;
-            case 'ArrowLeft':
+            case shortcuts.previous:
                 e.preventDefault();
                 var previousButton = document.querySelector('.nav-chapters.previous');
                 if (previousButton) {
@@ -585,12 +702,38 @@ function playground_text(playground) {
 
     clipboardSnippets.on('success', function (e) {
         e.clearSelection();
-        showTooltip(e.trigger, "Copied!");
+        showTooltip(e.trigger, window.copy_success_label || "Copied!");
     });
 
     clipboardSnippets.on('error', function (e) {
         showTooltip(e.trigger, "Clipboard error!");
     });
+
+    var permalinkButtons = document.querySelectorAll('.permalink-button');
+
+    var clipboardPermalinks = new ClipboardJS('.permalink-button', {
+        text: function (trigger) {
+            trigger.classList.remove('tooltipped');
+            // Without a `site-url` only the anchor is known
+            return new URL(trigger.dataset.permalink, window.location.href).href;
+        }
+    });
+
+    Array.from(permalinkButtons).forEach(function (button) {
+        button.addEventListener('mouseout', function (e) {
+            e.currentTarget.classList.remove('tooltipped');
+        });
+    });
+
+    clipboardPermalinks.on('success', function (e) {
+        e.trigger.firstChild.innerText = window.copy_success_label || "Copied!";
+        e.trigger.classList.add('tooltipped');
+    });
+
+    clipboardPermalinks.on('error', function (e) {
+        e.trigger.firstChild.innerText = "Clipboard error!";
+        e.trigger.classList.add('tooltipped');
+    });
 })();
 
 (function scrollToTop () {
@@ -7,9 +7,10 @@ pub mod fonts;
 #[cfg(feature = "search")]
 pub mod searcher;
 
+use std::borrow::Cow;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::errors::*;
 
@@ -62,6 +63,9 @@ pub struct Theme {
     pub ayu_highlight_css: Vec<u8>,
     pub highlight_js: Vec<u8>,
     pub clipboard_js: Vec<u8>,
+    /// The theme directory the files were loaded from, if it exists. Other
+    /// theme files are looked up here by [`Theme::asset`].
+    pub theme_dir: Option<PathBuf>,
 }
 
 impl Theme {
@@ -76,6 +80,8 @@ impl Theme {
             return theme;
         }
 
+        theme.theme_dir = Some(theme_dir.to_path_buf());
+
         // Check for individual files, if they exist copy them across
         {
             let files = vec![
@@ -140,6 +146,27 @@ impl Theme {
 
         theme
     }
+
+    /// The contents of the theme file at `path`, relative to the theme
+    /// directory, falling back to the built-in `default` if the theme doesn't
+    /// override it.
+    ///
+    /// This layers the theme directory over the built-in theme for the files
+    /// that are only copied to the output, like fonts and scripts.
+    pub fn asset<'a>(&self, path: &str, default: &'a [u8]) -> Cow<'a, [u8]> {
+        if let Some(ref theme_dir) = self.theme_dir {
+            let filename = theme_dir.join(path);
+            if filename.exists() {
+                let mut contents = Vec::new();
+                match load_file_contents(&filename, &mut contents) {
+                    Ok(()) => return Cow::Owned(contents),
+                    Err(e) => warn!("Couldn't load custom file, {}: {}", filename.display(), e),
+                }
+            }
+        }
+
+        Cow::Borrowed(default)
+    }
 }
 
 impl Default for Theme {
@@ -161,6 +188,7 @@ impl Default for Theme {
             ayu_highlight_css: AYU_HIGHLIGHT_CSS.to_owned(),
             highlight_js: HIGHLIGHT_JS.to_owned(),
             clipboard_js: CLIPBOARD_JS.to_owned(),
+            theme_dir: None,
         }
     }
 }
@@ -248,6 +276,7 @@ mod tests {
             ayu_highlight_css: Vec::new(),
             highlight_js: Vec::new(),
             clipboard_js: Vec::new(),
+            theme_dir: Some(temp.path().to_path_buf()),
         };
 
         assert_eq!(got, empty);
@@ -267,4 +296,22 @@ mod tests {
         assert_eq!(got.favicon_png, None);
         assert_eq!(got.favicon_svg.as_ref().unwrap(), b"4567");
     }
+
+    #[test]
+    fn assets_fall_back_to_the_defaults() {
+        let temp = TempFileBuilder::new().prefix("mdbook-").tempdir().unwrap();
+        fs::create_dir(temp.path().join("fonts")).unwrap();
+        fs::write(temp.path().join("fonts/fonts.css"), "custom").unwrap();
+        let got = Theme::new(temp.path());
+
+        assert_eq!(&*got.asset("fonts/fonts.css", fonts::CSS), b"custom");
+        assert_eq!(
+            &*got.asset("FontAwesome/css/font-awesome.css", FONT_AWESOME),
+            FONT_AWESOME
+        );
+        assert_eq!(
+            &*Theme::default().asset("fonts/fonts.css", fonts::CSS),
+            fonts::CSS
+        );
+    }
 }
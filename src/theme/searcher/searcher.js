@@ -27,7 +27,10 @@ window.search = window.search || {};
         content = document.getElementById('content'),
 
         searchindex = null,
+        stemmer = elasticlunr.stemmer,
         doc_urls = [],
+        teaser_terms = null,
+        doc_boosts = null,
         results_options = {
             teaser_word_count: 30,
             limit_results: 30,
@@ -48,7 +51,8 @@ window.search = window.search || {};
         URL_MARK_PARAM = 'highlight',
         teaser_count = 0,
 
-        SEARCH_HOTKEY_KEYCODE = 83,
+        // Themes which don't set the shortcuts keep the default key.
+        shortcuts = window.keyboard_shortcuts === undefined ? { search: 's' } : window.keyboard_shortcuts,
         ESCAPE_KEYCODE = 27,
         DOWN_KEYCODE = 40,
         UP_KEYCODE = 38,
@@ -136,7 +140,7 @@ window.search = window.search || {};
     }
     
     function formatSearchResult(result, searchterms) {
-        var teaser = makeTeaser(escapeHTML(result.doc.body), searchterms);
+        var teaser = makeTeaser(result.doc.body, searchterms, teaserTermRanges(result.ref, searchterms));
         teaser_count++;
 
         // The ?URL_MARK_PARAM= parameter belongs inbetween the page and the #heading-anchor
@@ -150,8 +154,29 @@ window.search = window.search || {};
             + '<span class="teaser" id="teaser_' + teaser_count + '" aria-label="Search Result Teaser">' 
             + teaser + '</span>';
     }
-    
-    function makeTeaser(body, searchterms) {
+
+    // Looks up the ranges of the document's body which matched the search terms
+    // in `teaser_terms`. Returns null if the index doesn't contain term positions.
+    function teaserTermRanges(ref, searchterms) {
+        if (!teaser_terms || !teaser_terms[ref]) {
+            return null;
+        }
+        var terms = teaser_terms[ref];
+        var query = searchindex.pipeline.run(elasticlunr.tokenizer(searchterms.join(' ')));
+        var ranges = [];
+        for (var term in terms) {
+            if (!terms.hasOwnProperty(term)) { continue; }
+            var matches = query.some(function(q) {
+                return term === q || (search_options.expand && term.startsWith(q));
+            });
+            if (matches) {
+                ranges = ranges.concat(terms[term]);
+            }
+        }
+        return ranges;
+    }
+
+    function makeTeaser(body, searchterms, ranges) {
         // The strategy is as follows:
         // First, assign a value to each word in the document:
         //  Words that correspond to search terms (stemmer aware): 40
@@ -161,9 +186,24 @@ window.search = window.search || {};
         // sum of the values of the words within the window. Then use the window that got the
         // maximum sum. If there are multiple maximas, then get the last one.
         // Enclose the terms in <em>.
+        // If the index stores the positions of the terms, they decide which
+        // words matched. Otherwise the words are compared with the stemmed
+        // search terms.
         var stemmed_searchterms = searchterms.map(function(w) {
-            return elasticlunr.stemmer(w.toLowerCase());
+            return stemmer(w.toLowerCase());
         });
+        var isMatch = function(word, start) {
+            if (ranges) {
+                var end = start + word.length;
+                return ranges.some(function(range) {
+                    return range[0] < end && start < range[1];
+                });
+            }
+            var stemmed = stemmer(word);
+            return stemmed_searchterms.some(function(term) {
+                return stemmed.startsWith(term);
+            });
+        };
         var searchterm_weight = 40;
         var weighted = []; // contains elements of ["word", weight, index_in_document]
         // split in sentences, then words
@@ -177,12 +217,10 @@ window.search = window.search || {};
             for (var wordindex in words) {
                 var word = words[wordindex];
                 if (word.length > 0) {
-                    for (var searchtermindex in stemmed_searchterms) {
-                        if (elasticlunr.stemmer(word).startsWith(stemmed_searchterms[searchtermindex])) {
-                            value = searchterm_weight;
-                            searchterm_found = true;
-                        }
-                    };
+                    if (isMatch(word, index)) {
+                        value = searchterm_weight;
+                        searchterm_found = true;
+                    }
                     weighted.push([word, value, index]);
                     value = 2;
                 }
@@ -193,7 +231,7 @@ window.search = window.search || {};
         };
 
         if (weighted.length == 0) {
-            return body;
+            return escapeHTML(body);
         }
 
         var window_weight = [];
@@ -231,14 +269,14 @@ window.search = window.search || {};
             var word = weighted[i];
             if (index < word[2]) {
                 // missing text from index to start of `word`
-                teaser_split.push(body.substring(index, word[2]));
+                teaser_split.push(escapeHTML(body.substring(index, word[2])));
                 index = word[2];
             }
             if (word[1] == searchterm_weight) {
                 teaser_split.push("<em>")
             }
             index = word[2] + word[0].length;
-            teaser_split.push(body.substring(word[2], index));
+            teaser_split.push(escapeHTML(body.substring(word[2], index)));
             if (word[1] == searchterm_weight) {
                 teaser_split.push("</em>")
             }
@@ -247,11 +285,36 @@ window.search = window.search || {};
         return teaser_split.join('');
     }
 
+    // The pipeline functions of other languages than English, like
+    // `stemmer-de`, are registered by the lunr-languages scripts of the
+    // language, which the book has to add. Without them, the queries are
+    // processed like English ones.
+    function loadLanguage(lang, index) {
+        var missing = index.pipeline.filter(function(name) {
+            return !elasticlunr.Pipeline.getRegisteredFunction(name);
+        });
+        if (missing.length > 0) {
+            console.warn('The search pipeline of the language "' + lang + '" is missing '
+                + missing.join(', ') + ', processing the search like English');
+            index.pipeline = ['trimmer', 'stopWordFilter', 'stemmer'];
+        }
+        var name = index.pipeline.filter(function(name) {
+            return name.indexOf('stemmer') === 0;
+        })[0];
+        stemmer = (name && elasticlunr.Pipeline.getRegisteredFunction(name)) || elasticlunr.stemmer;
+    }
+
     function init(config) {
         results_options = config.results_options;
         search_options = config.search_options;
         searchbar_outer = config.searchbar_outer;
         doc_urls = config.doc_urls;
+        teaser_terms = config.teaser_terms || null;
+        doc_boosts = config.doc_boosts || null;
+        if (config.stopwords) {
+            elasticlunr.addStopWords(config.stopwords);
+        }
+        loadLanguage(config.lang || 'en', config.index);
         searchindex = elasticlunr.Index.load(config.index);
 
         // Set up events
@@ -323,7 +386,7 @@ window.search = window.search || {};
             }
             showSearch(false);
             marker.unmark();
-        } else if (!hasFocus() && e.keyCode === SEARCH_HOTKEY_KEYCODE) {
+        } else if (!hasFocus() && shortcuts && e.key === shortcuts.search) {
             e.preventDefault();
             showSearch(true);
             window.scrollTo(0, 0);
@@ -444,6 +507,11 @@ window.search = window.search || {};
 
         // Do the actual search
         var results = searchindex.search(searchterm, search_options);
+        if (doc_boosts) {
+            // Rank earlier chapters higher among results which match about as well
+            results.forEach(function (result) { result.score *= doc_boosts[result.ref]; });
+            results.sort(function (a, b) { return b.score - a.score; });
+        }
         var resultcount = Math.min(results.length, results_options.limit_results);
 
         // Display search metrics
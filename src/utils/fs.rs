@@ -55,6 +55,25 @@ pub fn path_to_root<P: Into<PathBuf>>(path: P) -> String {
         })
 }
 
+/// The link from the page `from` to the page `to`, both relative to the
+/// book's source directory.
+pub(crate) fn relative_link(from: &Path, to: &Path) -> String {
+    let from_dir: Vec<_> = from
+        .parent()
+        .map(|dir| dir.components().collect())
+        .unwrap_or_default();
+    let to: Vec<_> = to.components().collect();
+    let common = from_dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut link = "../".repeat(from_dir.len() - common);
+    let rest: Vec<_> = to[common..]
+        .iter()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect();
+    link.push_str(&rest.join("/"));
+    link
+}
+
 /// This function creates a file and returns it. But before creating the file
 /// it checks every directory in the path to see if it exists,
 /// and if it does not it will be created.
@@ -94,6 +113,19 @@ pub fn copy_files_except_ext(
     recursive: bool,
     avoid_dir: Option<&PathBuf>,
     ext_blacklist: &[&str],
+) -> Result<()> {
+    copy_files_except(from, to, recursive, avoid_dir, ext_blacklist, &[])
+}
+
+/// Like [`copy_files_except_ext`], but also skips the `skipped` files, like
+/// the HTML chapters a renderer writes itself.
+pub(crate) fn copy_files_except(
+    from: &Path,
+    to: &Path,
+    recursive: bool,
+    avoid_dir: Option<&PathBuf>,
+    ext_blacklist: &[&str],
+    skipped: &[PathBuf],
 ) -> Result<()> {
     debug!(
         "Copying all files from {} to {} (blacklist: {:?}), avoiding {:?}",
@@ -129,12 +161,13 @@ pub fn copy_files_except_ext(
                 fs::create_dir(&to.join(entry.file_name()))?;
             }
 
-            copy_files_except_ext(
+            copy_files_except(
                 &from.join(entry.file_name()),
                 &to.join(entry.file_name()),
                 true,
                 avoid_dir,
                 ext_blacklist,
+                skipped,
             )?;
         } else if metadata.is_file() {
             // Check if it is in the blacklist
@@ -143,6 +176,9 @@ pub fn copy_files_except_ext(
                     continue;
                 }
             }
+            if skipped.contains(&entry.path()) {
+                continue;
+            }
             debug!(
                 "creating path for file: {:?}",
                 &to.join(
@@ -184,10 +220,51 @@ pub fn get_404_output_file(input_404: &Option<String>) -> String {
         .replace(".md", ".html")
 }
 
+/// A pattern in `.gitignore` syntax, matching paths relative to some
+/// directory, like the ones of `build.watch-ignore` or `output.html.extra-files`.
+///
+/// Unlike in `.gitignore` files, negated patterns (`!pattern`) match the paths
+/// which the rest of the pattern doesn't.
+#[derive(Debug)]
+pub struct GlobPattern {
+    pattern: gitignore::Pattern<'static>,
+}
+
+impl GlobPattern {
+    pub fn new(raw: &str) -> Result<GlobPattern> {
+        // Patterns containing a slash need to start with one to be anchored to
+        // the root by `gitignore::Pattern`, which matches absolute paths.
+        let anchored = raw.trim_end_matches('/').contains('/') && !raw.starts_with('/');
+        let raw_pattern = if anchored {
+            format!("/{}", raw)
+        } else {
+            raw.to_string()
+        };
+
+        match gitignore::Pattern::new(&raw_pattern, Path::new("/")) {
+            Ok(pattern) => Ok(GlobPattern { pattern }),
+            Err(e) => bail!("Invalid pattern `{}`: {}", raw, e),
+        }
+    }
+
+    /// Whether the relative `path`, or one of the directories it is in,
+    /// matches the pattern. `is_dir` tells whether `path` is a directory.
+    pub fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        let path = Path::new("/").join(normalize_path(&path.to_string_lossy()));
+        path.ancestors()
+            .take_while(|p| p.parent().is_some())
+            .any(|p| {
+                let directory = p != path || is_dir;
+                self.pattern.is_excluded(p, directory)
+            })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::copy_files_except_ext;
+    use super::{copy_files_except_ext, GlobPattern};
     use std::fs;
+    use std::path::Path;
 
     #[test]
     fn copy_files_except_ext_test() {
@@ -250,4 +327,27 @@ mod tests {
             panic!("output/sub_dir/file.png should exist")
         }
     }
+
+    #[test]
+    fn glob_patterns_match_paths() {
+        let matches = |pattern: &str, path: &str| {
+            GlobPattern::new(pattern)
+                .unwrap()
+                .matches(Path::new(path), false)
+        };
+        assert!(matches("chapter_1.md", "chapter_1.md"));
+        assert!(matches("appendix/*", "appendix/a.md"));
+        assert!(!matches("appendix/*.md", "appendix/nested/a.md"));
+        assert!(matches("appendix/**", "appendix/nested/a.md"));
+        assert!(matches("appendix/", "appendix/nested/a.md"));
+        assert!(!matches("appendix/", "appendix"));
+        assert!(matches("**/index.md", "index.md"));
+        assert!(matches("**/index.md", "first/second/index.md"));
+        assert!(matches("chapter_?.md", "chapter_2.md"));
+        assert!(!matches("chapter_?.md", "chapter_10.md"));
+        // Patterns without a slash match in every directory, like in `.gitignore`
+        assert!(matches("*.md", "first/nested.md"));
+        assert!(!matches("/*.md", "first/nested.md"));
+        assert!(!matches("first/*.md", "second/first/nested.md"));
+    }
 }
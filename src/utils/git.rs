@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A commit of the git repository a book is in.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Commit {
+    /// The first line of the commit message.
+    pub message: String,
+    /// The date of the commit, in RFC 3339.
+    pub date: String,
+    /// The files changed by the commit, relative to the directory the log was
+    /// read for.
+    pub files: Vec<PathBuf>,
+}
+
+/// Runs `git` with `args` in `dir`, returning what it printed, or `None` if git
+/// isn't installed or fails, like it does outside of a git repository.
+pub(crate) fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = match Command::new("git").args(args).current_dir(dir).output() {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("Unable to run git: {}", e);
+            return None;
+        }
+    };
+
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        debug!(
+            "git {} failed in {}: {}",
+            args.join(" "),
+            dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+        None
+    }
+}
+
+/// The commits changing the files in `dir`, newest first. Empty if `dir` isn't
+/// in a git repository.
+pub(crate) fn log(dir: &Path) -> Vec<Commit> {
+    // Every commit starts with a NUL, and its date and message are separated
+    // by a unit separator, neither of which can be in a message.
+    let output = match git(
+        dir,
+        &[
+            "log",
+            "--format=%x00%cI%x1f%s",
+            "--name-only",
+            "--relative",
+            "--",
+            ".",
+        ],
+    ) {
+        Some(output) => output,
+        None => return Vec::new(),
+    };
+
+    output
+        .split('\0')
+        .filter_map(|commit| {
+            let mut lines = commit.lines();
+            let mut header = lines.next()?.splitn(2, '\x1f');
+            let date = header.next()?.to_string();
+            let message = header.next()?.to_string();
+            let files = lines
+                .filter(|line| !line.is_empty())
+                .map(PathBuf::from)
+                .collect();
+            Some(Commit {
+                message,
+                date,
+                files,
+            })
+        })
+        .collect()
+}
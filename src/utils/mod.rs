@@ -1,7 +1,10 @@
 #![allow(missing_docs)] // FIXME: Document this
 
 pub mod fs;
+pub(crate) mod git;
+pub(crate) mod process;
 mod string;
+pub(crate) mod table;
 pub(crate) mod toml_ext;
 use crate::errors::Error;
 use regex::Regex;
@@ -9,12 +12,14 @@ use regex::Regex;
 use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag};
 
 use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Write;
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 pub use self::string::{
-    take_anchored_lines, take_lines, take_rustdoc_include_anchored_lines,
-    take_rustdoc_include_lines,
+    anchor_start_line, dedent, take_anchored_lines, take_lines,
+    take_rustdoc_include_anchored_lines, take_rustdoc_include_lines,
 };
 
 /// Replaces multiple consecutive whitespace characters with a single space character.
@@ -25,6 +30,30 @@ pub fn collapse_whitespace(text: &str) -> Cow<'_, str> {
     RE.replace_all(text, " ")
 }
 
+/// Escapes the characters with a meaning in HTML attributes and text.
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Hashes the concatenated `parts` with the 64 bit FNV-1a hash, which, unlike
+/// the hashers of the standard library, stays the same across Rust versions
+/// and platforms.
+pub(crate) fn stable_hash(parts: &[&[u8]]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(OFFSET_BASIS, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        });
+    format!("{:016x}", hash)
+}
+
 /// Convert the given string to a valid HTML element ID.
 /// The only restriction is that the ID must not contain any ASCII whitespace.
 pub fn normalize_id(content: &str) -> String {
@@ -80,13 +109,25 @@ pub fn id_from_content(content: &str) -> String {
 /// page go to the original location. Normal page rendering sets `path` to
 /// None. Ideally, print page links would link to anchors on the print page,
 /// but that is very difficult.
-fn adjust_links<'a>(event: Event<'a>, path: Option<&Path>) -> Event<'a> {
+///
+/// Links starting with `/`, like `/guide/intro.md`, are relative to the root
+/// of the book, and are made relative to the page using `source_path`, the
+/// chapter's location relative to the book's source directory.
+///
+/// With an `asset_url_prefix`, relative image sources are made absolute
+/// instead, by resolving them against `source_path` and putting the prefix in
+/// front.
+fn adjust_links<'a>(
+    event: Event<'a>,
+    path: Option<&Path>,
+    options: &RenderOptions<'_>,
+) -> Event<'a> {
     lazy_static! {
         static ref SCHEME_LINK: Regex = Regex::new(r"^[a-z][a-z0-9+.-]*:").unwrap();
         static ref MD_LINK: Regex = Regex::new(r"(?P<link>.*)\.md(?P<anchor>#.*)?").unwrap();
     }
 
-    fn fix<'a>(dest: CowStr<'a>, path: Option<&Path>) -> CowStr<'a> {
+    fn fix<'a>(dest: CowStr<'a>, path: Option<&Path>, options: &RenderOptions<'_>) -> CowStr<'a> {
         if dest.starts_with('#') {
             // Fragment-only link.
             if let Some(path) = path {
@@ -94,16 +135,54 @@ fn adjust_links<'a>(event: Event<'a>, path: Option<&Path>) -> Event<'a> {
                 if base.ends_with(".md") {
                     base.replace_range(base.len() - 3.., ".html");
                 }
+                if let Some(root) = options.links_root {
+                    base.insert_str(0, &format!("{}/", root.trim_end_matches('/')));
+                }
                 return format!("{}{}", base, dest).into();
             } else {
                 return dest;
             }
         }
         // Don't modify links with schemes like `https`.
-        if !SCHEME_LINK.is_match(&dest) {
-            // This is a relative link, adjust it as necessary.
+        if !SCHEME_LINK.is_match(&dest) && !dest.starts_with("//") {
+            if let (true, Some(source_path)) = (options.check_links, options.source_path) {
+                if leads_outside_of_book(source_path, &dest) {
+                    warn!(
+                        "The link to `{}` in {} leads outside of the book",
+                        dest,
+                        source_path.display()
+                    );
+                }
+            }
+
             let mut fixed_link = String::new();
-            if let Some(path) = path {
+            let mut link = &*dest;
+            let renamed;
+            if let (Some(chapters), Some(source_path)) =
+                (options.renamed_chapters, options.source_path)
+            {
+                if let Some(new_link) = follow_renamed_chapter(source_path, link, chapters) {
+                    renamed = new_link;
+                    link = &renamed;
+                }
+            }
+            let resolved;
+            if let (Some(root), Some(source_path)) = (options.links_root, options.source_path) {
+                // The link is made absolute, starting at the book's root.
+                resolved = resolve_in_book(Some(source_path), link);
+                link = &resolved;
+                fixed_link.push_str(root.trim_end_matches('/'));
+                fixed_link.push('/');
+            } else if link.starts_with('/') {
+                // This link is relative to the book's root, like
+                // `/guide/intro.md`. Links on the print page, or with `path`
+                // in general, already resolve against the root.
+                link = &link[1..];
+                if let (None, Some(source_path)) = (path, options.source_path) {
+                    fixed_link.push_str(&fs::path_to_root(source_path));
+                }
+            } else if let Some(path) = path {
+                // This is a relative link, adjust it as necessary.
                 let base = path
                     .parent()
                     .expect("path can't be empty")
@@ -114,21 +193,39 @@ fn adjust_links<'a>(event: Event<'a>, path: Option<&Path>) -> Event<'a> {
                 }
             }
 
-            if let Some(caps) = MD_LINK.captures(&dest) {
+            if let Some(caps) = MD_LINK.captures(link) {
                 fixed_link.push_str(&caps["link"]);
                 fixed_link.push_str(".html");
                 if let Some(anchor) = caps.name("anchor") {
                     fixed_link.push_str(anchor.as_str());
                 }
             } else {
-                fixed_link.push_str(&dest);
+                fixed_link.push_str(link);
             };
             return CowStr::from(fixed_link);
         }
         dest
     }
 
-    fn fix_html<'a>(html: CowStr<'a>, path: Option<&Path>) -> CowStr<'a> {
+    fn fix_image<'a>(
+        dest: CowStr<'a>,
+        path: Option<&Path>,
+        options: &RenderOptions<'_>,
+    ) -> CowStr<'a> {
+        let prefix = match options.asset_url_prefix {
+            Some(prefix) if !SCHEME_LINK.is_match(&dest) && !dest.starts_with("//") => prefix,
+            _ => return fix(dest, path, options),
+        };
+
+        let target = resolve_in_book(options.source_path, &dest);
+        format!("{}/{}", prefix.trim_end_matches('/'), target).into()
+    }
+
+    fn fix_html<'a>(
+        html: CowStr<'a>,
+        path: Option<&Path>,
+        options: &RenderOptions<'_>,
+    ) -> CowStr<'a> {
         // This is a terrible hack, but should be reasonably reliable. Nobody
         // should ever parse a tag with a regex. However, there isn't anything
         // in Rust that I know of that is suitable for handling partial html
@@ -139,12 +236,16 @@ fn adjust_links<'a>(event: Event<'a>, path: Option<&Path>) -> Event<'a> {
         // care about right now.
         lazy_static! {
             static ref HTML_LINK: Regex =
-                Regex::new(r#"(<(?:a|img) [^>]*?(?:src|href)=")([^"]+?)""#).unwrap();
+                Regex::new(r#"(<(a|img) [^>]*?(?:src|href)=")([^"]+?)""#).unwrap();
         }
 
         HTML_LINK
             .replace_all(&html, |caps: &regex::Captures<'_>| {
-                let fixed = fix(caps[2].into(), path);
+                let fixed = if &caps[2] == "img" {
+                    fix_image(caps[3].into(), path, options)
+                } else {
+                    fix(caps[3].into(), path, options)
+                };
                 format!("{}{}\"", &caps[1], fixed)
             })
             .into_owned()
@@ -153,16 +254,97 @@ fn adjust_links<'a>(event: Event<'a>, path: Option<&Path>) -> Event<'a> {
 
     match event {
         Event::Start(Tag::Link(link_type, dest, title)) => {
-            Event::Start(Tag::Link(link_type, fix(dest, path), title))
+            Event::Start(Tag::Link(link_type, fix(dest, path, options), title))
         }
         Event::Start(Tag::Image(link_type, dest, title)) => {
-            Event::Start(Tag::Image(link_type, fix(dest, path), title))
+            Event::Start(Tag::Image(link_type, fix_image(dest, path, options), title))
         }
-        Event::Html(html) => Event::Html(fix_html(html, path)),
+        Event::Html(html) => Event::Html(fix_html(html, path, options)),
         _ => event,
     }
 }
 
+/// Resolves the relative `link` of the chapter at `source_path`, returning
+/// the link's target relative to the book's root, e.g. `images/a.png` for
+/// `../images/a.png` in `guide/intro.md`. Links starting with `/` are already
+/// relative to the root.
+pub(crate) fn resolve_in_book(source_path: Option<&Path>, link: &str) -> String {
+    let mut segments: Vec<_> = match source_path.and_then(Path::parent) {
+        Some(dir) if !link.starts_with('/') => dir
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect(),
+        _ => Vec::new(),
+    };
+    for segment in link.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment.to_string()),
+        }
+    }
+
+    let mut target = segments.join("/");
+    if link.ends_with('/') && !target.is_empty() {
+        target.push('/');
+    }
+    target
+}
+
+/// Points the `link` of the chapter at `source_path` to where the chapter it
+/// links to ended up, if that is one of the `renamed` chapters, e.g.
+/// `../index.md` for `../README.md`.
+fn follow_renamed_chapter(
+    source_path: &Path,
+    link: &str,
+    renamed: &HashMap<PathBuf, PathBuf>,
+) -> Option<String> {
+    let (target, anchor) = match link.find('#') {
+        Some(i) => link.split_at(i),
+        None => (link, ""),
+    };
+    let original = PathBuf::from(resolve_in_book(Some(source_path), target));
+    let new_path = renamed.get(&original)?;
+
+    let mut new_link = if original.parent() == new_path.parent() {
+        // Only the file name changed, which keeps the link as it is otherwise
+        let dir_len = target.rfind('/').map_or(0, |i| i + 1);
+        let file_name = new_path.file_name()?.to_str()?;
+        format!("{}{}", &target[..dir_len], file_name)
+    } else {
+        let new_path = new_path.to_str()?.replace('\\', "/");
+        if target.starts_with('/') {
+            format!("/{}", new_path)
+        } else {
+            format!("{}{}", fs::path_to_root(source_path), new_path)
+        }
+    };
+    new_link.push_str(anchor);
+    Some(new_link)
+}
+
+/// Whether the `link` of the chapter at `source_path` leads outside of the
+/// book's source directory, like `../../README.md` in `guide/intro.md`.
+pub(crate) fn leads_outside_of_book(source_path: &Path, link: &str) -> bool {
+    let link = link.split(|c| c == '#' || c == '?').next().unwrap();
+    let mut depth = match source_path.parent() {
+        Some(dir) if !link.starts_with('/') => dir.components().count(),
+        _ => 0,
+    };
+
+    for segment in link.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." if depth == 0 => return true,
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+    }
+    false
+}
+
 /// Wrapper around the pulldown-cmark parser for rendering markdown to HTML.
 pub fn render_markdown(text: &str, curly_quotes: bool) -> String {
     render_markdown_with_path(text, curly_quotes, None)
@@ -177,17 +359,232 @@ pub fn new_cmark_parser(text: &str) -> Parser<'_> {
     Parser::new_ext(text, opts)
 }
 
+/// Where the inline code and code blocks are in `content`, which the
+/// preprocessors leave alone.
+pub(crate) fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    new_cmark_parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
 pub fn render_markdown_with_path(text: &str, curly_quotes: bool, path: Option<&Path>) -> String {
+    let options = RenderOptions {
+        curly_quotes,
+        ..Default::default()
+    };
+    render_markdown_with_options(text, path, &options)
+}
+
+/// How markdown is rendered by [`render_markdown_with_options`].
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderOptions<'a> {
+    /// Convert straight quotes to curly quotes, except in code.
+    pub curly_quotes: bool,
+    /// The language of fenced code blocks without an info string.
+    pub default_language: Option<&'a str>,
+    /// Turn paragraphs like `Term\n: Definition` into definition lists.
+    pub definition_lists: bool,
+    /// The URL relative image sources are resolved against, e.g. a CDN.
+    pub asset_url_prefix: Option<&'a str>,
+    /// The location of the markdown relative to the book's source directory,
+    /// which relative image sources and links from the root are resolved
+    /// against.
+    pub source_path: Option<&'a Path>,
+    /// Warn about links leading outside of the book, which needs the
+    /// `source_path`.
+    pub check_links: bool,
+    /// Make the links to the book's pages and files absolute, starting at
+    /// this URL, which needs the `source_path`.
+    pub links_root: Option<&'a str>,
+    /// The new paths of the chapters a preprocessor moved, keyed by their
+    /// source files, which links to those files are pointed at. This needs
+    /// the `source_path`.
+    pub renamed_chapters: Option<&'a HashMap<PathBuf, PathBuf>>,
+}
+
+/// Like [`render_markdown_with_path`], with all the [`RenderOptions`].
+pub fn render_markdown_with_options(
+    text: &str,
+    path: Option<&Path>,
+    options: &RenderOptions<'_>,
+) -> String {
     let mut s = String::with_capacity(text.len() * 3 / 2);
+    html::push_html(&mut s, markdown_events(text, path, options));
+    s
+}
+
+/// Like [`render_markdown_with_options`], but for a chapter written in HTML,
+/// which is left as it is besides fixing its links like the HTML in markdown.
+pub fn render_html_with_options(
+    html: &str,
+    path: Option<&Path>,
+    options: &RenderOptions<'_>,
+) -> String {
+    match adjust_links(Event::Html(html.into()), path, options) {
+        Event::Html(html) => html.into_string(),
+        _ => unreachable!("links are fixed in place"),
+    }
+}
+
+/// The markdown events [`render_markdown_with_options`] turns into HTML, with
+/// the code blocks cleaned up, the links fixed and the quotes converted the
+/// same way, for tools inspecting a chapter without rendering it.
+pub fn markdown_events<'a>(
+    text: &'a str,
+    path: Option<&'a Path>,
+    options: &'a RenderOptions<'a>,
+) -> impl Iterator<Item = Event<'a>> + 'a {
     let p = new_cmark_parser(text);
-    let mut converter = EventQuoteConverter::new(curly_quotes);
-    let events = p
-        .map(clean_codeblock_headers)
-        .map(|event| adjust_links(event, path))
-        .map(|event| converter.convert(event));
+    let mut converter = EventQuoteConverter::new(options.curly_quotes);
+    let events: Box<dyn Iterator<Item = Event<'a>>> = if options.definition_lists {
+        Box::new(DefinitionLists::new(p))
+    } else {
+        Box::new(p)
+    };
+    events
+        .map(move |event| clean_codeblock_headers(event, options.default_language))
+        .map(move |event| adjust_links(event, path, options))
+        .map(move |event| converter.convert(event))
+}
 
-    html::push_html(&mut s, events);
-    s
+/// Turns paragraphs made of terms followed by definitions starting with `: `
+/// into definition lists, merging adjacent ones:
+///
+/// ```text
+/// Term
+/// : Definition
+/// ```
+///
+/// A definition continues on the following lines until the next `: `.
+/// pulldown-cmark doesn't support definition lists itself.
+struct DefinitionLists<'a, I> {
+    events: I,
+    queue: VecDeque<Event<'a>>,
+    in_list: bool,
+}
+
+impl<'a, I: Iterator<Item = Event<'a>>> DefinitionLists<'a, I> {
+    fn new(events: I) -> Self {
+        DefinitionLists {
+            events,
+            queue: VecDeque::new(),
+            in_list: false,
+        }
+    }
+
+    fn close_list(&mut self) {
+        if self.in_list {
+            self.in_list = false;
+            self.queue
+                .push_back(Event::Html(CowStr::Borrowed("</dl>\n")));
+        }
+    }
+}
+
+impl<'a, I: Iterator<Item = Event<'a>>> Iterator for DefinitionLists<'a, I> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        loop {
+            if let Some(event) = self.queue.pop_front() {
+                return Some(event);
+            }
+
+            match self.events.next() {
+                Some(Event::Start(Tag::Paragraph)) => {
+                    let mut inline = Vec::new();
+                    for event in &mut self.events {
+                        if let Event::End(Tag::Paragraph) = event {
+                            break;
+                        }
+                        inline.push(event);
+                    }
+
+                    match definition_list_items(inline) {
+                        Ok(items) => {
+                            if !self.in_list {
+                                self.in_list = true;
+                                self.queue
+                                    .push_back(Event::Html(CowStr::Borrowed("<dl>\n")));
+                            }
+                            for (is_term, events) in items {
+                                let (start, end) = if is_term {
+                                    ("<dt>", "</dt>\n")
+                                } else {
+                                    ("<dd>", "</dd>\n")
+                                };
+                                self.queue.push_back(Event::Html(CowStr::Borrowed(start)));
+                                self.queue.extend(events);
+                                self.queue.push_back(Event::Html(CowStr::Borrowed(end)));
+                            }
+                        }
+                        Err(inline) => {
+                            self.close_list();
+                            self.queue.push_back(Event::Start(Tag::Paragraph));
+                            self.queue.extend(inline);
+                            self.queue.push_back(Event::End(Tag::Paragraph));
+                        }
+                    }
+                }
+                Some(event) => {
+                    self.close_list();
+                    self.queue.push_back(event);
+                }
+                None => {
+                    self.close_list();
+                    return self.queue.pop_front();
+                }
+            }
+        }
+    }
+}
+
+/// Splits the content of a paragraph into terms (`true`) and definitions
+/// (`false`), or gives it back if it isn't a definition list.
+fn definition_list_items(
+    inline: Vec<Event<'_>>,
+) -> Result<Vec<(bool, Vec<Event<'_>>)>, Vec<Event<'_>>> {
+    fn is_definition(line: &[Event<'_>]) -> bool {
+        match line.first() {
+            Some(Event::Text(text)) => text.starts_with(": ") || &**text == ":",
+            _ => false,
+        }
+    }
+
+    let mut lines = vec![Vec::new()];
+    for event in &inline {
+        match event {
+            Event::SoftBreak => lines.push(Vec::new()),
+            _ => lines.last_mut().unwrap().push(event.clone()),
+        }
+    }
+    if is_definition(&lines[0]) || !lines.iter().any(|line| is_definition(line)) {
+        return Err(inline);
+    }
+
+    let mut items: Vec<(bool, Vec<Event<'_>>)> = Vec::new();
+    for mut line in lines {
+        if is_definition(&line) {
+            if let Event::Text(text) = &line[0] {
+                line[0] = Event::Text(CowStr::from(text[1..].trim_start().to_string()));
+            }
+            items.push((false, line));
+        } else {
+            match items.last_mut() {
+                // Lazy continuation of a definition
+                Some((false, definition)) => {
+                    definition.push(Event::SoftBreak);
+                    definition.extend(line);
+                }
+                _ => items.push((true, line)),
+            }
+        }
+    }
+    Ok(items)
 }
 
 struct EventQuoteConverter {
@@ -225,10 +622,30 @@ impl EventQuoteConverter {
     }
 }
 
-fn clean_codeblock_headers(event: Event<'_>) -> Event<'_> {
+/// The attribute of code blocks which `mdbook test` leaves out.
+pub(crate) const TEST_SKIP_ATTRIBUTE: &str = "mdbook-test-skip";
+
+/// Whether the `info` string of a fenced code block has the
+/// [`TEST_SKIP_ATTRIBUTE`].
+pub(crate) fn skips_tests(info: &str) -> bool {
+    info.split(|c: char| c == ',' || c.is_whitespace())
+        .any(|attribute| attribute == TEST_SKIP_ATTRIBUTE)
+}
+
+fn clean_codeblock_headers<'a>(event: Event<'a>, default_language: Option<&str>) -> Event<'a> {
     match event {
         Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => {
-            let info: String = info.chars().filter(|ch| !ch.is_whitespace()).collect();
+            let mut info: String = if skips_tests(info) {
+                info.split(|c: char| c == ',' || c.is_whitespace())
+                    .filter(|attribute| !attribute.is_empty() && *attribute != TEST_SKIP_ATTRIBUTE)
+                    .collect::<Vec<_>>()
+                    .join(",")
+            } else {
+                info.chars().filter(|ch| !ch.is_whitespace()).collect()
+            };
+            if info.is_empty() {
+                info.extend(default_language);
+            }
 
             Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(CowStr::from(info))))
         }
@@ -280,7 +697,30 @@ pub fn log_backtrace(e: &Error) {
 #[cfg(test)]
 mod tests {
     mod render_markdown {
-        use super::super::render_markdown;
+        use super::super::{
+            markdown_events, render_markdown, render_markdown_with_options, RenderOptions,
+        };
+        use pulldown_cmark::{Event, Tag};
+        use std::collections::HashMap;
+        use std::path::{Path, PathBuf};
+
+        #[test]
+        fn events_are_transformed_like_the_rendered_html() {
+            let options = RenderOptions {
+                curly_quotes: true,
+                ..Default::default()
+            };
+            let events: Vec<_> = markdown_events("[\"a\"](b.md)", None, &options).collect();
+
+            match events[1] {
+                Event::Start(Tag::Link(_, ref dest, _)) => assert_eq!(dest.as_ref(), "b.html"),
+                ref other => panic!("Expected a link, got {:?}", other),
+            }
+            match events[2] {
+                Event::Text(ref text) => assert_eq!(text.as_ref(), "“a”"),
+                ref other => panic!("Expected text, got {:?}", other),
+            }
+        }
 
         #[test]
         fn preserves_external_links() {
@@ -308,6 +748,128 @@ mod tests {
             );
         }
 
+        #[test]
+        fn local_images_can_get_an_asset_url_prefix() {
+            let options = RenderOptions {
+                asset_url_prefix: Some("https://cdn.example.com"),
+                source_path: Some(Path::new("guide/setup.md")),
+                ..Default::default()
+            };
+            let render = |text| render_markdown_with_options(text, None, &options);
+
+            assert_eq!(
+                render("![a](img/a.png) ![b](../b.png) ![c](https://example.com/c.png)"),
+                "<p><img src=\"https://cdn.example.com/guide/img/a.png\" alt=\"a\" /> \
+                 <img src=\"https://cdn.example.com/b.png\" alt=\"b\" /> \
+                 <img src=\"https://example.com/c.png\" alt=\"c\" /></p>\n"
+            );
+            assert_eq!(
+                render("<img src=\"./a.png\"> <a href=\"a.md\">A</a>"),
+                "<p><img src=\"https://cdn.example.com/guide/a.png\"> <a href=\"a.html\">A</a></p>\n"
+            );
+            assert_eq!(render("[a](a.png)"), "<p><a href=\"a.png\">a</a></p>\n");
+        }
+
+        #[test]
+        fn links_from_the_root_are_made_relative() {
+            let options = RenderOptions {
+                source_path: Some(Path::new("guide/setup/linux.md")),
+                ..Default::default()
+            };
+            assert_eq!(
+                render_markdown_with_options("[a](/guide/intro.md#b)", None, &options),
+                "<p><a href=\"../../guide/intro.html#b\">a</a></p>\n"
+            );
+            assert_eq!(
+                render_markdown_with_options("<a href=\"/img/x.png\">x</a>", None, &options),
+                "<p><a href=\"../../img/x.png\">x</a></p>\n"
+            );
+            assert_eq!(
+                render_markdown_with_options("[a](//example.com/a.md)", None, &options),
+                "<p><a href=\"//example.com/a.md\">a</a></p>\n"
+            );
+
+            // The print page is at the root
+            let path = Some(Path::new("guide/setup/linux.md"));
+            assert_eq!(
+                render_markdown_with_options("[a](/guide/intro.md)", path, &options),
+                "<p><a href=\"guide/intro.html\">a</a></p>\n"
+            );
+        }
+
+        #[test]
+        fn links_follow_renamed_chapters() {
+            let mut renamed = HashMap::new();
+            renamed.insert(PathBuf::from("README.md"), PathBuf::from("index.md"));
+            renamed.insert(
+                PathBuf::from("guide/README.md"),
+                PathBuf::from("guide/index.md"),
+            );
+            renamed.insert(PathBuf::from("old.md"), PathBuf::from("archive/old.md"));
+            let options = RenderOptions {
+                source_path: Some(Path::new("guide/setup.md")),
+                renamed_chapters: Some(&renamed),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                render_markdown_with_options(
+                    "[a](../README.md) [b](README.md#usage) [c](./README.md) [d](/README.md) \
+                     [e](../old.md) [f](setup.md)",
+                    None,
+                    &options
+                ),
+                "<p><a href=\"../index.html\">a</a> \
+                 <a href=\"index.html#usage\">b</a> \
+                 <a href=\"./index.html\">c</a> \
+                 <a href=\"../index.html\">d</a> \
+                 <a href=\"../archive/old.html\">e</a> \
+                 <a href=\"setup.html\">f</a></p>\n"
+            );
+
+            // The print page
+            assert_eq!(
+                render_markdown_with_options(
+                    "[a](../README.md)",
+                    Some(Path::new("guide/setup.md")),
+                    &options
+                ),
+                "<p><a href=\"guide/../index.html\">a</a></p>\n"
+            );
+        }
+
+        #[test]
+        fn links_can_be_made_absolute() {
+            let options = RenderOptions {
+                source_path: Some(Path::new("guide/intro.md")),
+                links_root: Some("/docs"),
+                ..Default::default()
+            };
+            assert_eq!(
+                render_markdown_with_options(
+                    "[a](setup.md#linux) [b](../README.md) [c](/api/) [d](#e) [f](https://a.b/)",
+                    None,
+                    &options
+                ),
+                "<p><a href=\"/docs/guide/setup.html#linux\">a</a> \
+                 <a href=\"/docs/README.html\">b</a> \
+                 <a href=\"/docs/api/\">c</a> \
+                 <a href=\"#e\">d</a> \
+                 <a href=\"https://a.b/\">f</a></p>\n"
+            );
+
+            // The print page
+            assert_eq!(
+                render_markdown_with_options(
+                    "[a](setup.md) [d](#e)",
+                    Some(Path::new("guide/intro.md")),
+                    &options
+                ),
+                "<p><a href=\"/docs/guide/setup.html\">a</a> \
+                 <a href=\"/docs/guide/intro.html#e\">d</a></p>\n"
+            );
+        }
+
         #[test]
         fn it_can_keep_quotes_straight() {
             assert_eq!(render_markdown("'one'", false), "<p>'one'</p>\n");
@@ -329,6 +891,44 @@ mod tests {
             assert_eq!(render_markdown(input, true), expected);
         }
 
+        #[test]
+        fn quotes_in_indented_code_blocks_stay_straight() {
+            let input = "'one'\n\n    let s = \"two\";\n    'three'\n\n'four'\n\n\
+                         - 'five'\n\n      \"six\"\n";
+            let expected = "<p>‘one’</p>\n\
+                            <pre><code>let s = &quot;two&quot;;\n'three'\n</code></pre>\n\
+                            <p>‘four’</p>\n\
+                            <ul>\n<li>\n<p>‘five’</p>\n<pre><code>&quot;six&quot;\n</code></pre>\n</li>\n</ul>\n";
+            assert_eq!(render_markdown(input, true), expected);
+        }
+
+        #[test]
+        fn quotes_in_fenced_code_blocks_of_any_language_stay_straight() {
+            let input = "~~~ {.custom, x=\"y\"}\n'one'\n~~~\n'two'\n\n```mermaid\n\"three\"\n```\n";
+            let expected = "<pre><code class=\"language-{.custom,x=&quot;y&quot;}\">'one'\n</code></pre>\n\
+                            <p>‘two’</p>\n\
+                            <pre><code class=\"language-mermaid\">&quot;three&quot;\n</code></pre>\n";
+            assert_eq!(render_markdown(input, true), expected);
+        }
+
+        #[test]
+        fn quotes_in_code_blocks_of_definitions_stay_straight() {
+            let options = RenderOptions {
+                curly_quotes: true,
+                definition_lists: true,
+                ..Default::default()
+            };
+            let input = "'Term'\n: 'Definition'\n\n    'code'\n\n'after'\n";
+            let html = render_markdown_with_options(input, None, &options);
+            assert!(
+                html.contains("<pre><code>'code'\n</code></pre>"),
+                "{}",
+                html
+            );
+            assert!(html.contains("‘Term’"), "{}", html);
+            assert!(html.contains("<p>‘after’</p>"), "{}", html);
+        }
+
         #[test]
         fn whitespace_outside_of_codeblock_header_is_preserved() {
             let input = r#"
@@ -378,6 +978,76 @@ more text with spaces
             assert_eq!(render_markdown(input, true), expected);
         }
 
+        #[test]
+        fn the_test_skip_attribute_is_removed_from_the_class() {
+            let input = "```rust,mdbook-test-skip,no_run\n```\n\n```mdbook-test-skip rust\n```\n";
+
+            let expected = r#"<pre><code class="language-rust,no_run"></code></pre>
+<pre><code class="language-rust"></code></pre>
+"#;
+            assert_eq!(render_markdown(input, false), expected);
+        }
+
+        #[test]
+        fn code_blocks_without_a_language_get_the_default_one() {
+            let input = "```\necho hi\n```\n\n```text\nplain\n```\n\n    indented\n";
+
+            let expected = r#"<pre><code class="language-bash">echo hi
+</code></pre>
+<pre><code class="language-text">plain
+</code></pre>
+<pre><code>indented
+</code></pre>
+"#;
+            let options = RenderOptions {
+                default_language: Some("bash"),
+                ..Default::default()
+            };
+            assert_eq!(
+                render_markdown_with_options(input, None, &options),
+                expected
+            );
+            assert!(render_markdown(input, false).starts_with("<pre><code>echo hi"));
+        }
+
+        #[test]
+        fn definition_lists_are_rendered_when_enabled() {
+            let input =
+                "Term\n: The *definition*\ncontinued\n\nOther term\nSynonym\n: A\n: B\n\nText\n";
+
+            let expected = r#"<dl>
+<dt>Term</dt>
+<dd>The <em>definition</em>
+continued</dd>
+<dt>Other term</dt>
+<dt>Synonym</dt>
+<dd>A</dd>
+<dd>B</dd>
+</dl>
+<p>Text</p>
+"#;
+            let options = RenderOptions {
+                definition_lists: true,
+                ..Default::default()
+            };
+            assert_eq!(
+                render_markdown_with_options(input, None, &options),
+                expected
+            );
+            assert_eq!(
+                render_markdown(input, false),
+                "<p>Term\n: The <em>definition</em>\ncontinued</p>\n\
+                 <p>Other term\nSynonym\n: A\n: B</p>\n<p>Text</p>\n"
+            );
+
+            // Paragraphs starting with a colon, or without one, are no lists
+            let input = ": Not a term\nline\n";
+            assert_eq!(
+                render_markdown_with_options(input, None, &options),
+                "<p>: Not a term\nline</p>\n"
+            );
+        }
+
         #[test]
         fn rust_code_block_without_properties_has_proper_html_class() {
             let input = r#"
@@ -399,6 +1069,24 @@ more text with spaces
         }
     }
 
+    mod leads_outside_of_book {
+        use super::super::leads_outside_of_book;
+        use std::path::Path;
+
+        #[test]
+        fn escaping_links_are_detected() {
+            let chapter = Path::new("guide/intro.md");
+            assert!(leads_outside_of_book(chapter, "../../README.md"));
+            assert!(leads_outside_of_book(chapter, "../a/../../b.md#x"));
+            assert!(leads_outside_of_book(chapter, "/../b.md"));
+            assert!(leads_outside_of_book(Path::new("intro.md"), "../b.md"));
+
+            assert!(!leads_outside_of_book(chapter, "../README.md"));
+            assert!(!leads_outside_of_book(chapter, "./setup/../../b.md"));
+            assert!(!leads_outside_of_book(chapter, "/guide/b.md"));
+        }
+    }
+
     mod html_munging {
         use super::super::{id_from_content, normalize_id};
 
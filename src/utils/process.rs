@@ -0,0 +1,160 @@
+use crate::config::Config;
+use crate::errors::*;
+use std::cmp;
+use std::io::{self, Read, Write};
+use std::process::{Child, ExitStatus};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use toml::Value;
+
+/// Reads the timeout of an external command, given in seconds by the config
+/// option `key`.
+pub(crate) fn timeout(config: &Config, key: &str) -> Result<Option<Duration>> {
+    let seconds = match config.get(key) {
+        None => return Ok(None),
+        Some(Value::Integer(seconds)) => *seconds as f64,
+        Some(Value::Float(seconds)) => *seconds,
+        Some(_) => bail!("`{}` must be a number of seconds", key),
+    };
+    ensure!(
+        seconds > 0.0 && seconds < std::u64::MAX as f64,
+        "`{}` must be a positive number of seconds",
+        key
+    );
+
+    Ok(Some(Duration::from_secs_f64(seconds)))
+}
+
+/// Writes `input` to the stdin of `child` on a separate thread, so a child
+/// which doesn't read its input can't block mdBook.
+pub(crate) fn write_stdin(child: &mut Child, input: Vec<u8>) {
+    let mut stdin = child.stdin.take().expect("Child has stdin");
+
+    thread::spawn(move || {
+        if let Err(e) = stdin.write_all(&input) {
+            // Looks like the child hung up before we could finish
+            // sending it its input. Log the error and keep going
+            warn!("Error writing the input to the child process, {}", e);
+        }
+    });
+}
+
+/// Reads everything from `reader`, usually the stdout or stderr of a child,
+/// on a separate thread, so the child can't get stuck on a full pipe.
+pub(crate) fn read_to_end<R: Read + Send + 'static>(
+    mut reader: R,
+) -> JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        reader.read_to_end(&mut output).map(|_| output)
+    })
+}
+
+/// Collects the output of a [`read_to_end`] thread.
+pub(crate) fn collect(output: JoinHandle<io::Result<Vec<u8>>>) -> io::Result<Vec<u8>> {
+    output.join().expect("Reading the output doesn't panic")
+}
+
+/// Creates the error for a child which exited unsuccessfully, with `message`
+/// followed by the block of what the child, described by `who`, wrote to its
+/// stderr.
+pub(crate) fn failure(who: &str, message: String, stderr: &[u8]) -> Error {
+    const RULE: &str = "----------";
+
+    let stderr = String::from_utf8_lossy(stderr);
+    let stderr = stderr.trim_end();
+    if stderr.is_empty() {
+        return Error::msg(message);
+    }
+
+    Error::msg(format!(
+        "{} wrote to stderr:\n{}\n{}\n{}",
+        who, RULE, stderr, RULE
+    ))
+    .context(message)
+}
+
+/// Logs what a successful child, described by `who`, wrote to its stderr at
+/// the debug level.
+pub(crate) fn log_stderr(who: &str, stderr: &[u8]) {
+    let stderr = String::from_utf8_lossy(stderr);
+    for line in stderr.trim_end().lines() {
+        debug!("{}: {}", who, line);
+    }
+}
+
+/// Waits for `child` to exit, killing it once it has run for longer than
+/// `timeout`.
+///
+/// Returns `None` if the child had to be killed.
+pub(crate) fn wait_timeout(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> io::Result<Option<ExitStatus>> {
+    let timeout = match timeout {
+        Some(timeout) => timeout,
+        None => return child.wait().map(Some),
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            // The child may have exited in the meantime, which is fine
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(cmp::min(deadline - now, Duration::from_millis(10)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failures_show_the_stderr_in_a_block() {
+        let err = failure("The \"x\" renderer", "It failed".to_string(), b"oops\n\n");
+        let causes: Vec<_> = err.chain().map(ToString::to_string).collect();
+        assert_eq!(
+            causes,
+            vec![
+                "It failed",
+                "The \"x\" renderer wrote to stderr:\n----------\noops\n----------",
+            ]
+        );
+
+        let err = failure("The \"x\" renderer", "It failed".to_string(), b" \n");
+        assert_eq!(err.chain().count(), 1);
+    }
+
+    #[test]
+    fn timeouts_are_in_seconds() {
+        let mut config = Config::default();
+        assert_eq!(timeout(&config, "output.x.timeout").unwrap(), None);
+
+        config.set("output.x.timeout", 2).unwrap();
+        assert_eq!(
+            timeout(&config, "output.x.timeout").unwrap(),
+            Some(Duration::from_secs(2))
+        );
+
+        config.set("output.x.timeout", 0.5).unwrap();
+        assert_eq!(
+            timeout(&config, "output.x.timeout").unwrap(),
+            Some(Duration::from_millis(500))
+        );
+
+        config.set("output.x.timeout", 0).unwrap();
+        assert!(timeout(&config, "output.x.timeout").is_err());
+        config.set("output.x.timeout", 1e300).unwrap();
+        assert!(timeout(&config, "output.x.timeout").is_err());
+        config.set("output.x.timeout", "forever").unwrap();
+        assert!(timeout(&config, "output.x.timeout").is_err());
+    }
+}
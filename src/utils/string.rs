@@ -58,6 +58,51 @@ pub fn take_anchored_lines(s: &str, anchor: &str) -> String {
     retained.join("\n")
 }
 
+/// The index of the line starting the section of `s` marked with `anchor`, if
+/// there is one.
+pub fn anchor_start_line(s: &str, anchor: &str) -> Option<usize> {
+    s.lines().position(|l| match ANCHOR_START.captures(l) {
+        Some(cap) => &cap["anchor_name"] == anchor,
+        None => false,
+    })
+}
+
+/// Removes the leading whitespace all lines of a string have in common.
+///
+/// Spaces and tabs are never considered the same, so only the indentation
+/// which is exactly the same on every line is removed. Lines which are only
+/// whitespace don't count and are left empty.
+pub fn dedent(s: &str) -> String {
+    let indent = s
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| &l[..l.len() - l.trim_start().len()])
+        .fold(None, |common: Option<&str>, indent| match common {
+            None => Some(indent),
+            Some(common) => {
+                let len = common
+                    .char_indices()
+                    .zip(indent.chars())
+                    .take_while(|&((_, a), b)| a == b)
+                    .last()
+                    .map_or(0, |((i, c), _)| i + c.len_utf8());
+                Some(&common[..len])
+            }
+        })
+        .unwrap_or("");
+
+    s.lines()
+        .map(|l| {
+            if l.trim().is_empty() {
+                ""
+            } else {
+                &l[indent.len()..]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 /// Keep lines contained within the range specified as-is.
 /// For any lines not in the range, include them but use `#` at the beginning. This will hide the
 /// lines from initial display but include them when expanding the code snippet or testing with
@@ -117,7 +162,7 @@ pub fn take_rustdoc_include_anchored_lines(s: &str, anchor: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::{
-        take_anchored_lines, take_lines, take_rustdoc_include_anchored_lines,
+        dedent, take_anchored_lines, take_lines, take_rustdoc_include_anchored_lines,
         take_rustdoc_include_lines,
     };
 
@@ -133,6 +178,23 @@ mod tests {
         assert_eq!(take_lines(s, ..100), s);
     }
 
+    #[test]
+    fn dedent_test() {
+        let s = "    fn main() {\n        println!();\n\n    }";
+        assert_eq!(dedent(s), "fn main() {\n    println!();\n\n}");
+
+        // tabs and spaces are different indentation
+        let s = "\t  Lorem\n\t\tipsum\n  \n\t  dolor";
+        assert_eq!(dedent(s), "  Lorem\n\tipsum\n\n  dolor");
+        let s = "    Lorem\n\tipsum";
+        assert_eq!(dedent(s), s);
+
+        // corner cases
+        assert_eq!(dedent(""), "");
+        assert_eq!(dedent("  \n   "), "\n");
+        assert_eq!(dedent("Lorem\n  ipsum"), "Lorem\n  ipsum");
+    }
+
     #[test]
     fn take_anchored_lines_test() {
         let s = "Lorem\nipsum\ndolor\nsit\namet";
@@ -0,0 +1,251 @@
+//! Rendering data files as markdown tables.
+
+use crate::errors::*;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Renders `data`, read from the file at `path`, as a markdown table.
+///
+/// CSV files are read as a header row followed by the rows of the table. JSON
+/// files must hold an array of objects, with a row for each object and a
+/// column for each key, in sorted order.
+pub(crate) fn data_table(data: &str, path: &Path) -> Result<String> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase);
+    let (header, rows) = match extension.as_ref().map(String::as_str) {
+        Some("csv") => csv_table(data)?,
+        Some("json") => json_table(data)?,
+        _ => bail!("Only `.csv` and `.json` files can be included as a table"),
+    };
+
+    let mut table = table_row(&header);
+    table.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+    for row in &rows {
+        table.push_str(&table_row(row));
+    }
+    Ok(table)
+}
+
+fn table_row(cells: &[String]) -> String {
+    let cells: Vec<_> = cells
+        .iter()
+        .map(|cell| {
+            cell.trim()
+                .replace('|', "\\|")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        })
+        .collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+type Table = (Vec<String>, Vec<Vec<String>>);
+
+fn csv_table(data: &str) -> Result<Table> {
+    let mut records = parse_csv(data)?.into_iter();
+    let header = match records.next() {
+        Some((_, header)) => header,
+        None => bail!("The CSV data has no header row"),
+    };
+
+    let mut rows = Vec::new();
+    for (line, mut row) in records {
+        if row.len() > header.len() {
+            bail!(
+                "The row on line {} has {} fields, but the header only has {}",
+                line,
+                row.len(),
+                header.len()
+            );
+        }
+        row.resize(header.len(), String::new());
+        rows.push(row);
+    }
+    Ok((header, rows))
+}
+
+/// Splits CSV `data` into its records, along with the lines they start on.
+/// Fields may be quoted, with `""` standing for a quote within them. Blank
+/// lines are skipped.
+fn parse_csv(data: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut line = 1;
+    let mut record_line = 1;
+    // Where the quoted field currently being read started, and whether the
+    // last field was quoted.
+    let mut quoted = None;
+    let mut closed = false;
+
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted.is_some() {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => {
+                    quoted = None;
+                    closed = true;
+                }
+                c => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    field.push(c);
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() && !closed => quoted = Some(line),
+            ',' => {
+                record.push(std::mem::replace(&mut field, String::new()));
+                closed = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::replace(&mut field, String::new()));
+                if record.len() > 1 || !record[0].is_empty() || closed {
+                    records.push((record_line, std::mem::replace(&mut record, Vec::new())));
+                } else {
+                    record.clear();
+                }
+                closed = false;
+                line += 1;
+                record_line = line;
+            }
+            _ if closed => bail!(
+                "Unexpected `{}` after a quoted field on line {}",
+                c.escape_default(),
+                line
+            ),
+            c => field.push(c),
+        }
+    }
+
+    if let Some(start) = quoted {
+        bail!(
+            "The quoted field starting on line {} is never closed",
+            start
+        );
+    }
+    if !record.is_empty() || !field.is_empty() || closed {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+fn json_table(data: &str) -> Result<Table> {
+    let value: Value = serde_json::from_str(data).with_context(|| "The JSON data is invalid")?;
+    let objects = match value {
+        Value::Array(items) => items,
+        _ => bail!("The JSON data must be an array of objects"),
+    };
+
+    let mut columns = BTreeSet::new();
+    for (i, object) in objects.iter().enumerate() {
+        match *object {
+            Value::Object(ref map) => columns.extend(map.keys().cloned()),
+            _ => bail!("Item {} of the JSON array isn't an object", i + 1),
+        }
+    }
+    if columns.is_empty() {
+        bail!("The JSON objects have no keys to use as columns");
+    }
+
+    let rows = objects
+        .iter()
+        .map(|object| {
+            columns
+                .iter()
+                .map(|column| match object.get(column) {
+                    None | Some(Value::Null) => String::new(),
+                    Some(Value::String(s)) => s.clone(),
+                    Some(other) => other.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    Ok((columns.into_iter().collect(), rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_files_become_tables() {
+        let data = "Name,Description,Default\r\n\
+                    title,\"The book's title, if any\",\n\
+                    \n\
+                    src,\"Where the \"\"chapters\"\" are\",src\n\
+                    multilingual,a | b\n";
+        assert_eq!(
+            data_table(data, Path::new("options.csv")).unwrap(),
+            "| Name | Description | Default |\n\
+             |---|---|---|\n\
+             | title | The book's title, if any |  |\n\
+             | src | Where the \"chapters\" are | src |\n\
+             | multilingual | a \\| b |  |\n"
+        );
+    }
+
+    #[test]
+    fn malformed_csv_is_an_error() {
+        let cases = [
+            ("", "no header row"),
+            ("a,b\n1,2,3\n", "line 2 has 3 fields"),
+            ("a,b\n\"1,2\n", "starting on line 2 is never closed"),
+            (
+                "a,b\n\"1\"2,3\n",
+                "Unexpected `2` after a quoted field on line 2",
+            ),
+        ];
+        for &(data, message) in &cases {
+            let err = data_table(data, Path::new("data.csv")).unwrap_err();
+            assert!(err.to_string().contains(message), "{:?}: {}", data, err);
+        }
+    }
+
+    #[test]
+    fn json_arrays_of_objects_become_tables() {
+        let data = r#"[
+            {"name": "mdbook", "version": "0.4", "stable": true},
+            {"version": null, "name": "multi\nline", "tags": ["a", "b"]}
+        ]"#;
+        assert_eq!(
+            data_table(data, Path::new("crates.JSON")).unwrap(),
+            "| name | stable | tags | version |\n\
+             |---|---|---|---|\n\
+             | mdbook | true |  | 0.4 |\n\
+             | multi<br>line |  | [\"a\",\"b\"] |  |\n"
+        );
+    }
+
+    #[test]
+    fn unsupported_data_is_an_error() {
+        let cases = [
+            (
+                "[1, 2]",
+                "data.json",
+                "Item 1 of the JSON array isn't an object",
+            ),
+            ("{\"a\": 1}", "data.json", "must be an array of objects"),
+            ("[{}]", "data.json", "no keys"),
+            ("[", "data.json", "invalid"),
+            ("a,b", "data.txt", "Only `.csv` and `.json` files"),
+        ];
+        for &(data, path, message) in &cases {
+            let err = data_table(data, Path::new(path)).unwrap_err();
+            assert!(err.to_string().contains(message), "{:?}: {}", data, err);
+        }
+    }
+}
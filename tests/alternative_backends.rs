@@ -32,6 +32,49 @@ fn missing_optional_backends_are_not_fatal() {
     assert!(md.build().is_ok());
 }
 
+#[test]
+fn failing_optional_backends_are_not_fatal() {
+    let (md, _temp) = dummy_book_with_backend("failing", fail_cmd(), true);
+    assert!(md.build().is_ok());
+}
+
+#[test]
+fn other_backends_still_run_when_one_fails() {
+    use mdbook::renderer::CmdRenderer;
+
+    let (mut md, temp) = dummy_book_with_backend("failing", fail_cmd(), false);
+    md.with_renderer(CmdRenderer::new("passing".to_string(), touch_cmd("done")));
+
+    let err = md.build().unwrap_err();
+    assert_eq!(err.to_string(), "The \"failing\" backend failed");
+    assert!(temp.path().join("book/passing/done").exists());
+}
+
+#[test]
+#[cfg(not(windows))]
+fn backends_are_killed_after_their_timeout() {
+    let (mut md, _temp) = dummy_book_with_backend("slow", "sleep 10", false);
+    md.config.set("output.slow.timeout", 0.2).unwrap();
+
+    let err = md.build().unwrap_err();
+    assert!(err.chain().any(|cause| cause
+        .to_string()
+        .contains("The \"slow\" renderer (`sleep 10`) didn't finish within 0.2 seconds")));
+}
+
+#[test]
+#[cfg(not(windows))]
+fn the_stderr_of_failing_backends_is_part_of_the_error() {
+    let (md, _temp) =
+        dummy_book_with_backend("noisy", "sh -c 'echo Missing font >&2; exit 1'", false);
+
+    let err = md.build().unwrap_err();
+    let causes: Vec<_> = err.chain().map(ToString::to_string).collect();
+    assert!(causes.contains(&String::from(
+        "The \"noisy\" renderer wrote to stderr:\n----------\nMissing font\n----------"
+    )));
+}
+
 #[test]
 fn alternate_backend_with_arguments() {
     let (md, _temp) = dummy_book_with_backend("arguments", "echo Hello World!", false);
@@ -39,6 +82,92 @@ fn alternate_backend_with_arguments() {
     md.build().unwrap();
 }
 
+#[test]
+fn backends_with_the_same_name_are_an_error() {
+    use mdbook::renderer::CmdRenderer;
+
+    let (mut md, _temp) = dummy_book_with_backend("passing", success_cmd(), false);
+    md.with_renderer(CmdRenderer::new(
+        "passing".to_string(),
+        success_cmd().to_string(),
+    ));
+
+    let got = md.build().unwrap_err();
+    assert_eq!(got.to_string(), "More than one renderer is named `passing`");
+}
+
+#[test]
+fn backends_can_be_replaced() {
+    use mdbook::renderer::CmdRenderer;
+
+    let (mut md, _temp) = dummy_book_with_backend("failing", fail_cmd(), false);
+    md.replace_renderer(CmdRenderer::new(
+        "failing".to_string(),
+        success_cmd().to_string(),
+    ));
+
+    md.build().unwrap();
+}
+
+#[test]
+fn backends_with_overlapping_output_dirs_are_an_error() {
+    let temp = TempFileBuilder::new().prefix("mdbook").tempdir().unwrap();
+    let mut config = Config::default();
+    config.set("output.site.command", success_cmd()).unwrap();
+    config
+        .set("output.site/nested.command", success_cmd())
+        .unwrap();
+    let md = MDBook::init(temp.path())
+        .with_config(config)
+        .build()
+        .unwrap();
+
+    let got = md.build().unwrap_err().to_string();
+    assert!(
+        got.starts_with("The output directories of the `site` and `site/nested` renderers overlap"),
+        "{}",
+        got
+    );
+}
+
+#[test]
+fn some_backends_can_be_built() {
+    use mdbook::renderer::CmdRenderer;
+
+    let (mut md, temp) = dummy_book_with_backend("failing", fail_cmd(), false);
+    md.with_renderer(CmdRenderer::new("passing".to_string(), touch_cmd("done")));
+    md.with_renderer(CmdRenderer::new("other".to_string(), touch_cmd("done")));
+
+    md.build_renderer("passing").unwrap();
+    assert!(temp.path().join("book/passing/done").exists());
+    assert!(!temp.path().join("book/other/done").exists());
+
+    let err = md.build_renderers(&["failing", "other"]).unwrap_err();
+    assert_eq!(err.to_string(), "The \"failing\" backend failed");
+    assert!(temp.path().join("book/other/done").exists());
+
+    md.config.set("output.failing.optional", true).unwrap();
+    md.build_renderers(&["failing", "other"]).unwrap();
+}
+
+#[test]
+fn unknown_backends_are_an_error_before_anything_is_built() {
+    use mdbook::renderer::CmdRenderer;
+
+    let (mut md, temp) = dummy_book_with_backend("passing", &touch_cmd("done"), false);
+    md.with_renderer(CmdRenderer::new("other".to_string(), touch_cmd("done")));
+
+    let got = md
+        .build_renderers(&["passing", "pdf"])
+        .unwrap_err()
+        .to_string();
+    assert_eq!(
+        got,
+        "The book has no `pdf` renderer, its renderers are: passing, other"
+    );
+    assert!(!temp.path().join("book/passing").exists());
+}
+
 /// Get a command which will pipe `stdin` to the provided file.
 #[cfg(not(windows))]
 fn tee_command<P: AsRef<Path>>(out_file: P) -> String {
@@ -105,6 +234,15 @@ fn fail_cmd() -> &'static str {
     }
 }
 
+/// Get a command which creates the file `name` in the output directory.
+fn touch_cmd(name: &str) -> String {
+    if cfg!(windows) {
+        format!(r#"cmd.exe /c "type nul > {}""#, name)
+    } else {
+        format!("touch {}", name)
+    }
+}
+
 fn success_cmd() -> &'static str {
     if cfg!(windows) {
         r#"cmd.exe /c "exit 0""#
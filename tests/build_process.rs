@@ -1,7 +1,7 @@
 mod dummy_book;
 
 use crate::dummy_book::DummyBook;
-use mdbook::book::Book;
+use mdbook::book::{Book, BookItem};
 use mdbook::config::Config;
 use mdbook::errors::*;
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
@@ -76,3 +76,38 @@ fn mdbook_runs_renderers() {
     let inner = spy.lock().unwrap();
     assert_eq!(inner.run_count, 1);
 }
+
+#[test]
+fn books_can_be_preprocessed_without_rendering() {
+    let spy: Arc<Mutex<Inner>> = Default::default();
+
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.with_preprocessor(Spy(Arc::clone(&spy)));
+
+    let chapter = |book: &Book, name: &str| {
+        book.iter()
+            .filter_map(|item| match *item {
+                BookItem::Chapter(ref ch) if ch.name == name => Some(ch.clone()),
+                _ => None,
+            })
+            .next()
+            .unwrap()
+    };
+
+    let book = md.preprocess().unwrap();
+    let nested = chapter(&book, "Nested Chapter");
+    assert!(nested.content.contains("assert!(true);"));
+    assert!(!nested.content.contains("{{#include"));
+    // The index preprocessor only runs for HTML.
+    let index = chapter(&book, "Dummy Book");
+    assert_eq!(index.path.unwrap().to_str(), Some("README.md"));
+
+    let book = md.preprocess_for("html").unwrap();
+    let index = chapter(&book, "Dummy Book");
+    assert_eq!(index.path.unwrap().to_str(), Some("index.md"));
+
+    let inner = spy.lock().unwrap();
+    assert_eq!(inner.rendered_with, ["markdown", "html"]);
+    assert!(!temp.path().join("book").exists());
+}
@@ -0,0 +1,150 @@
+//! Integration tests running the `mdbook` binary itself.
+
+mod dummy_book;
+
+use crate::dummy_book::{assert_contains_strings, assert_doesnt_contain_strings, DummyBook};
+
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The `mdbook` binary, which cargo builds next to the directory of the
+/// test binaries.
+fn mdbook() -> Command {
+    let mut path = env::current_exe().unwrap();
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path.push(PathBuf::from("mdbook").with_extension(env::consts::EXE_EXTENSION));
+
+    let mut cmd = Command::new(path);
+    cmd.env_remove("RUST_LOG");
+    cmd
+}
+
+#[test]
+fn warnings_are_written_to_the_log_file() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/second.md"),
+        "---\nauthor: me\n---\n# Second Chapter\n",
+    )
+    .unwrap();
+    let log_file = temp.path().join("build.log");
+    fs::write(&log_file, "from an earlier build").unwrap();
+
+    let output = mdbook()
+        .arg("build")
+        .arg(temp.path())
+        .arg("--log-file")
+        .arg(&log_file)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let warning = "[WARN] (mdbook::book::book): Ignoring unknown front matter key \"author\"";
+    assert!(String::from_utf8_lossy(&output.stderr).contains(warning));
+    assert_contains_strings(&log_file, &[warning]);
+    assert_doesnt_contain_strings(&log_file, &["[INFO]", "from an earlier build"]);
+}
+
+/// A running `mdbook serve`, which is stopped when it is dropped.
+#[cfg(feature = "serve")]
+struct Server {
+    child: Child,
+    /// The address the server listens on, read from its `--port-file`.
+    address: String,
+}
+
+#[cfg(feature = "serve")]
+impl Server {
+    /// Serves the book at `root` on a free port, with the extra `args`.
+    fn start(root: &Path, args: &[&str]) -> Server {
+        let port_file = root.join("port");
+        let child = mdbook()
+            .arg("serve")
+            .arg(root)
+            .args(&["--hostname", "127.0.0.1", "--port", "0", "--port-file"])
+            .arg(&port_file)
+            .args(args)
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let mut server = Server {
+            child,
+            address: String::new(),
+        };
+
+        let started = Instant::now();
+        loop {
+            match fs::read_to_string(&port_file) {
+                Ok(ref address) if address.ends_with('\n') => {
+                    server.address = address.trim().to_string();
+                    return server;
+                }
+                _ if started.elapsed() < Duration::from_secs(60) => {
+                    thread::sleep(Duration::from_millis(100))
+                }
+                _ => panic!("mdbook serve didn't write the port file"),
+            }
+        }
+    }
+
+    /// The response to a GET request for `path`.
+    fn get(&self, path: &str) -> String {
+        let mut stream = TcpStream::connect(&self.address).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+}
+
+#[cfg(feature = "serve")]
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+#[test]
+#[cfg(feature = "serve")]
+fn serve_can_pick_a_free_port() {
+    let temp = DummyBook::new().build().unwrap();
+    let server = Server::start(temp.path(), &[]);
+    let response = server.get("/index.html");
+
+    let port = server.address.rsplit(':').next().unwrap();
+    assert_ne!(port, "0");
+    assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+    assert!(
+        response.contains(&format!("ws://127.0.0.1:{}/__livereload", port)),
+        "{}",
+        response
+    );
+}
+
+#[test]
+#[cfg(feature = "serve")]
+fn serve_mounts_static_dirs() {
+    let temp = DummyBook::new().build().unwrap();
+    let shared = temp.path().join("shared");
+    fs::create_dir_all(shared.join("icons")).unwrap();
+    fs::write(shared.join("icons/logo.svg"), "<svg></svg>").unwrap();
+    let mount = format!("/company/assets:{}", shared.display());
+    let server = Server::start(temp.path(), &["--static-dir", &mount]);
+
+    let response = server.get("/company/assets/icons/logo.svg");
+    assert!(response.starts_with("HTTP/1.0 200 OK"), "{}", response);
+    assert!(response.ends_with("<svg></svg>"), "{}", response);
+    // The book is still served, and the directory only at its prefix.
+    assert!(server.get("/index.html").starts_with("HTTP/1.0 200 OK"));
+    assert!(server.get("/icons/logo.svg").starts_with("HTTP/1.0 404"));
+    assert!(!temp.path().join("book/company").exists());
+}
@@ -1,6 +1,7 @@
 mod dummy_book;
 
 use crate::dummy_book::DummyBook;
+use mdbook::config::Config;
 use mdbook::preprocess::{CmdPreprocessor, Preprocessor};
 use mdbook::MDBook;
 
@@ -54,3 +55,34 @@ fn process_the_dummy_book() {
 
     md.build().unwrap();
 }
+
+#[test]
+#[cfg(not(windows))]
+fn preprocessors_are_killed_after_their_timeout() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut config = Config::default();
+    config.set("preprocessor.slow.command", "sleep 10").unwrap();
+    config
+        .set("preprocessor.slow.renderers", vec!["html"])
+        .unwrap();
+    config.set("preprocessor.slow.timeout", 0.2).unwrap();
+    let md = MDBook::load_with_config(temp.path(), config).unwrap();
+
+    let err = md.build().unwrap_err();
+    assert!(err.chain().any(|cause| cause
+        .to_string()
+        .contains("The \"slow\" preprocessor (`sleep 10`) didn't finish within 0.2 seconds")));
+}
+
+#[test]
+#[cfg(not(windows))]
+fn supports_checks_are_killed_after_the_timeout() {
+    use std::time::{Duration, Instant};
+
+    let cmd = CmdPreprocessor::new("slow".to_string(), "sh -c 'sleep 10'".to_string())
+        .with_timeout(Duration::from_millis(200));
+
+    let start = Instant::now();
+    assert_eq!(cmd.supports_renderer("html"), false);
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
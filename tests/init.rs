@@ -1,3 +1,4 @@
+use mdbook::book::Template;
 use mdbook::config::Config;
 use mdbook::MDBook;
 use std::fs;
@@ -102,3 +103,68 @@ fn book_toml_isnt_required() {
 
     md.build().unwrap();
 }
+
+#[test]
+fn init_from_a_builtin_template() {
+    let temp = TempFileBuilder::new().prefix("mdbook").tempdir().unwrap();
+    let template = Template::find("api-docs").unwrap();
+
+    MDBook::init(temp.path())
+        .with_template(template)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    for file in &[
+        "src/SUMMARY.md",
+        "src/overview.md",
+        "src/reference/types.md",
+    ] {
+        assert!(temp.path().join(file).exists(), "{} doesn't exist", file);
+    }
+    assert!(!temp.path().join("src/chapter_1.md").exists());
+
+    let contents = fs::read_to_string(temp.path().join("book.toml")).unwrap();
+    assert!(contents.contains("heading-split-level = 2"));
+}
+
+#[test]
+fn init_from_a_template_directory() {
+    let template_dir = TempFileBuilder::new().prefix("template").tempdir().unwrap();
+    fs::create_dir_all(template_dir.path().join("src/nested")).unwrap();
+    fs::write(
+        template_dir.path().join("book.toml"),
+        "[book]\ntitle = \"From a template\"\n",
+    )
+    .unwrap();
+    fs::write(
+        template_dir.path().join("src/SUMMARY.md"),
+        "# Summary\n\n- [Start](./start.md)\n- [Nested](./nested/page.md)\n",
+    )
+    .unwrap();
+    fs::write(template_dir.path().join("src/start.md"), "# Template start").unwrap();
+
+    let temp = TempFileBuilder::new().prefix("mdbook").tempdir().unwrap();
+    fs::create_dir_all(temp.path().join("src")).unwrap();
+    fs::write(temp.path().join("src/start.md"), "# Already there").unwrap();
+
+    let template = Template::find(template_dir.path().to_str().unwrap()).unwrap();
+    let md = MDBook::init(temp.path())
+        .with_template(template)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(md.config.book.title, Some(String::from("From a template")));
+    assert_eq!(
+        fs::read_to_string(temp.path().join("src/start.md")).unwrap(),
+        "# Already there"
+    );
+    // Missing chapters listed in the template's summary are still created
+    assert!(temp.path().join("src/nested/page.md").exists());
+}
+
+#[test]
+fn unknown_templates_are_an_error() {
+    assert!(Template::find("definitely-not-a-template").is_err());
+}
@@ -6,17 +6,19 @@ mod dummy_book;
 use crate::dummy_book::{assert_contains_strings, assert_doesnt_contain_strings, DummyBook};
 
 use anyhow::Context;
+use mdbook::book::BookItem;
 use mdbook::config::Config;
 use mdbook::errors::*;
 use mdbook::utils::fs::write_file;
 use mdbook::MDBook;
 use select::document::Document;
-use select::predicate::{Class, Name, Predicate};
+use select::predicate::{Attr, Class, Name, Predicate};
 use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs;
 use std::io::Write;
 use std::path::{Component, Path, PathBuf};
+use std::process::Command;
 use tempfile::Builder as TempFileBuilder;
 use walkdir::{DirEntry, WalkDir};
 
@@ -149,6 +151,114 @@ fn rendered_code_has_playground_stuff() {
     assert_contains_strings(book_js, &[".playground"]);
 }
 
+#[test]
+fn custom_playground_runner_url_is_passed_to_the_page() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set(
+            "output.html.playground.runner-url",
+            "https://play.example.com/",
+        )
+        .unwrap();
+    md.build().unwrap();
+
+    let nested = temp.path().join("book/first/nested.html");
+    assert_contains_strings(
+        &nested,
+        &[r#"window.playground_runner_url = "https://play.example.com";"#],
+    );
+
+    md.config
+        .set(
+            "output.html.playground.runner-url",
+            "https://play.example.com/?q=\"</script>",
+        )
+        .unwrap();
+    md.build().unwrap();
+    assert_contains_strings(
+        &nested,
+        &[r#"window.playground_runner_url = "https://play.example.com/?q=\"\u003c/script>";"#],
+    );
+}
+
+#[test]
+fn copy_button_can_be_customized_or_disabled() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.code.copy-button-label", "Kopieren")
+        .unwrap();
+    md.build().unwrap();
+
+    let nested = temp.path().join("book/first/nested.html");
+    assert_contains_strings(
+        &nested,
+        &[
+            "window.playground_copyable = true;",
+            r#"window.copy_button_label = "Kopieren";"#,
+        ],
+    );
+
+    md.config
+        .set("output.html.code.copy-button-label", "Copy \"</script>")
+        .unwrap();
+    md.build().unwrap();
+    assert_contains_strings(
+        &nested,
+        &[r#"window.copy_button_label = "Copy \"\u003c/script>";"#],
+    );
+
+    md.config
+        .set("output.html.code.copy-button", false)
+        .unwrap();
+    md.build().unwrap();
+    assert_doesnt_contain_strings(&nested, &["window.playground_copyable"]);
+}
+
+#[test]
+fn line_numbers_mark_the_first_line_of_code_blocks() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let nested = temp.path().join("book/first/nested.html");
+    assert_doesnt_contain_strings(&nested, &["data-line-start"]);
+
+    md.config
+        .set("output.html.code.line-numbers", true)
+        .unwrap();
+    md.build().unwrap();
+    assert_contains_strings(
+        &nested,
+        &[r#"<code class="language-rust" data-line-start="1">"#],
+    );
+}
+
+#[test]
+fn code_blocks_can_be_wrapped() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let nested = temp.path().join("book/first/nested.html");
+    assert_doesnt_contain_strings(
+        &nested,
+        &[r#"class="language-rust wrap""#, "code_wrap_button"],
+    );
+
+    md.config.set("output.html.code.wrap", true).unwrap();
+    md.config.set("output.html.code.wrap-button", true).unwrap();
+    md.build().unwrap();
+    assert_contains_strings(
+        &nested,
+        &[
+            r#"<code class="language-rust wrap">"#,
+            "window.code_wrap_button = true;",
+        ],
+    );
+}
+
 #[test]
 fn anchors_include_text_between_but_not_anchor_comments() {
     let temp = DummyBook::new().build().unwrap();
@@ -381,9 +491,9 @@ fn able_to_include_files_in_chapters() {
     assert_doesnt_contain_strings(&includes, &["{{#include ../SUMMARY.md::}}"]);
 }
 
-/// Ensure cyclic includes are capped so that no exceptions occur
+/// Ensure includes which include other files are expanded completely
 #[test]
-fn recursive_includes_are_capped() {
+fn nested_includes_are_expanded() {
     let temp = DummyBook::new().build().unwrap();
     let md = MDBook::load(temp.path()).unwrap();
     md.build().unwrap();
@@ -396,161 +506,1514 @@ Around the world, around the world"];
 }
 
 #[test]
-fn example_book_can_build() {
-    let example_book_dir = dummy_book::new_copy_of_example_book().unwrap();
+fn cyclic_includes_are_an_error() {
+    let temp = DummyBook::new().build().unwrap();
+    let first = temp.path().join("src/first");
+    fs::write(
+        first.join("recursive.md"),
+        "{{#include around-the-world.md}}",
+    )
+    .unwrap();
+    fs::write(
+        first.join("around-the-world.md"),
+        "{{#include recursive.md}}",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
 
-    let md = MDBook::load(example_book_dir.path()).unwrap();
+    let err = md.build().unwrap_err();
+    let message = format!("{:?}", err);
+    assert!(
+        message.contains(
+            "Cyclic include detected: first/recursive.md -> first/around-the-world.md -> first/recursive.md"
+        ),
+        "unexpected error: {}",
+        message
+    );
+}
+
+#[test]
+fn includes_deeper_than_max_depth_are_an_error() {
+    let temp = DummyBook::new().build().unwrap();
+    let first = temp.path().join("src/first");
+    fs::write(first.join("around-the-world.md"), "{{#include again.md}}").unwrap();
+    fs::write(first.join("again.md"), "Around the world, around the world").unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("preprocessor.links.max-depth", 1).unwrap();
+
+    let err = md.build().unwrap_err();
+    let message = format!("{:?}", err);
+    assert!(
+        message.contains(
+            "Maximum include depth of 1 exceeded: \
+             first/recursive.md -> first/around-the-world.md -> first/again.md"
+        ),
+        "unexpected error: {}",
+        message
+    );
+}
 
+#[test]
+fn title_directive_sets_the_page_title() {
+    let temp = DummyBook::new().build().unwrap();
+    let intro = temp.path().join("src/intro.md");
+    let content = fs::read_to_string(&intro).unwrap();
+    fs::write(
+        &intro,
+        format!("{{{{#title A custom title}}}}\n{}", content),
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
     md.build().unwrap();
+
+    let intro = temp.path().join("book/intro.html");
+    assert_contains_strings(
+        &intro,
+        &[
+            "<title>A custom title</title>",
+            r#"<meta property="og:title" content="A custom title">"#,
+            ">Introduction</a></h1>",
+        ],
+    );
+    assert_doesnt_contain_strings(&intro, &["#title"]);
+
+    let conclusion = temp.path().join("book/conclusion.html");
+    assert_contains_strings(&conclusion, &["<title>Conclusion</title>"]);
 }
 
 #[test]
-fn book_with_a_reserved_filename_does_not_build() {
-    let tmp_dir = TempFileBuilder::new().prefix("mdBook").tempdir().unwrap();
-    let src_path = tmp_dir.path().join("src");
-    fs::create_dir(&src_path).unwrap();
+fn chapter_tags_get_an_index_page() {
+    let temp = DummyBook::new().build().unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+    assert!(!temp.path().join("book/tags.html").exists());
 
-    let summary_path = src_path.join("SUMMARY.md");
-    let print_path = src_path.join("print.md");
+    for (file, tags) in &[("intro.md", "[async, Tokio]"), ("first/index.md", "async")] {
+        let path = temp.path().join("src").join(file);
+        let content = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("---\ntags: {}\n---\n{}", tags, content)).unwrap();
+    }
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
 
-    fs::File::create(print_path).unwrap();
-    let mut summary_file = fs::File::create(summary_path).unwrap();
-    writeln!(summary_file, "[print](print.md)").unwrap();
+    let tags = temp.path().join("book/tags.html");
+    assert_contains_strings(
+        &tags,
+        &[
+            r##"<h2 id="async"><a class="header" href="#async">async</a></h2>"##,
+            r#"<li><a href="intro.html">Introduction</a></li>"#,
+            r#"<li><a href="first/index.html">First Chapter</a></li>"#,
+            r##"<h2 id="tokio"><a class="header" href="#tokio">Tokio</a></h2>"##,
+        ],
+    );
 
-    let md = MDBook::load(tmp_dir.path()).unwrap();
-    let got = md.build();
-    assert!(got.is_err());
+    let first = temp.path().join("book/first/index.html");
+    assert_contains_strings(
+        &first,
+        &[r#"<a class="tag" href="../tags.html#async">async</a>"#],
+    );
+    assert_doesnt_contain_strings(&first, &["tags: async"]);
+
+    let index = fs::read_to_string(temp.path().join("book/searchindex.json")).unwrap();
+    let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+    assert_eq!(
+        index["index"]["fields"],
+        serde_json::json!(["title", "body", "breadcrumbs", "tags"])
+    );
 }
 
 #[test]
-fn by_default_mdbook_use_index_preprocessor_to_convert_readme_to_index() {
+fn sitemap_lists_every_page() {
     let temp = DummyBook::new().build().unwrap();
-    let mut cfg = Config::default();
-    cfg.set("book.src", "src2")
-        .expect("Couldn't set config.book.src to \"src2\".");
-    let md = MDBook::load_with_config(temp.path(), cfg).unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.site-url", "https://example.com/book")
+        .unwrap();
+    md.config.set("output.html.sitemap", true).unwrap();
     md.build().unwrap();
 
-    let first_index = temp.path().join("book").join("first").join("index.html");
-    let expected_strings = vec![
-        r#"href="../first/index.html""#,
-        r#"href="../second/index.html""#,
-        "First README",
-    ];
-    assert_contains_strings(&first_index, &expected_strings);
-    assert_doesnt_contain_strings(&first_index, &["README.html"]);
+    let sitemap = fs::read_to_string(temp.path().join("book/sitemap.xml")).unwrap();
+    let chapters = md
+        .book
+        .iter()
+        .filter(|item| match item {
+            BookItem::Chapter(ch) => !ch.is_draft_chapter(),
+            _ => false,
+        })
+        .count();
+    assert_eq!(sitemap.matches("<url>").count(), chapters + 1);
+    assert!(sitemap.contains("<loc>https://example.com/book/</loc>"));
+    assert!(sitemap.contains("<loc>https://example.com/book/first/nested.html</loc>"));
+    assert!(!sitemap.contains("print.html"));
+}
 
-    let second_index = temp.path().join("book").join("second").join("index.html");
-    let unexpected_strings = vec!["Second README"];
-    assert_doesnt_contain_strings(&second_index, &unexpected_strings);
+#[test]
+fn custom_favicons_replace_the_theme_ones() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::create_dir(temp.path().join("icons")).unwrap();
+    fs::write(temp.path().join("icons/small.png"), "small").unwrap();
+    fs::write(temp.path().join("icons/touch.png"), "touch").unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    let mut favicon = std::collections::BTreeMap::new();
+    favicon.insert("32x32", "icons/small.png");
+    favicon.insert("apple-touch-icon", "icons/touch.png");
+    md.config.set("output.html.favicon", favicon).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_eq!(
+        fs::read_to_string(book.join("icons/touch.png")).unwrap(),
+        "touch"
+    );
+    assert!(!book.join("favicon.png").exists());
+    assert!(!book.join("favicon.svg").exists());
+    assert_contains_strings(
+        book.join("first/nested.html"),
+        &[
+            r#"<link rel="icon" href="../icons/small.png" sizes="32x32" type="image/png">"#,
+            r#"<link rel="apple-touch-icon" href="../icons/touch.png" type="image/png">"#,
+        ],
+    );
+    assert_doesnt_contain_strings(book.join("first/nested.html"), &["favicon.svg"]);
 }
 
 #[test]
-fn theme_dir_overrides_work_correctly() {
-    let book_dir = dummy_book::new_copy_of_example_book().unwrap();
-    let book_dir = book_dir.path();
-    let theme_dir = book_dir.join("theme");
+fn missing_favicon_is_an_error() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.favicon", "icons/missing.png")
+        .unwrap();
+
+    let err = md.build().unwrap_err();
+    let got = format!("{:?}", err);
+    assert!(
+        got.contains("Favicon icons/missing.png doesn't exist"),
+        "{}",
+        got
+    );
+}
 
-    let mut index = mdbook::theme::INDEX.to_vec();
-    index.extend_from_slice(b"\n<!-- This is a modified index.hbs! -->");
+#[test]
+fn favicons_outside_of_the_book_are_an_error() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(temp.path(), "icons/icon.png", b"").unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.favicon", "icons/../icons/icon.png")
+        .unwrap();
+
+    let got = format!("{:?}", md.build().unwrap_err());
+    assert!(
+        got.contains(
+            "The favicon `icons/../icons/icon.png` has to be a relative path inside the book"
+        ),
+        "{}",
+        got
+    );
+}
 
-    write_file(&theme_dir, "index.hbs", &index).unwrap();
+#[test]
+fn print_page_leaves_out_excluded_chapters() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set(
+            "output.html.print.exclude",
+            vec!["first/u*.md", "**/conclusion.md"],
+        )
+        .unwrap();
+    md.config
+        .set("output.html.print.page-break-class", "page-break")
+        .unwrap();
+    md.build().unwrap();
 
-    let md = MDBook::load(book_dir).unwrap();
+    let print = temp.path().join("book/print.html");
+    assert_contains_strings(
+        &print,
+        &[
+            r##"<div class="page-break"></div><h1><a class="header" href="#first-chapter""##,
+            "Nested Chapter",
+        ],
+    );
+    assert_doesnt_contain_strings(&print, &["unicode-stress-tests", r#"id="conclusion""#]);
+    // Excluded chapters are still rendered on their own
+    assert!(temp.path().join("book/first/unicode.html").exists());
+}
+
+#[test]
+fn print_page_can_be_disabled() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.print.enable", false).unwrap();
     md.build().unwrap();
 
-    let built_index = book_dir.join("book").join("index.html");
-    dummy_book::assert_contains_strings(built_index, &["This is a modified index.hbs!"]);
+    assert!(!temp.path().join("book/print.html").exists());
+    assert_doesnt_contain_strings(temp.path().join("book/intro.html"), &["print.html"]);
 }
 
 #[test]
-fn no_index_for_print_html() {
+fn keyboard_shortcuts_can_be_remapped_or_disabled() {
     let temp = DummyBook::new().build().unwrap();
-    let md = MDBook::load(temp.path()).unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.shortcut-keys.search", "/")
+        .unwrap();
     md.build().unwrap();
 
-    let print_html = temp.path().join("book/print.html");
-    assert_contains_strings(print_html, &[r##"noindex"##]);
+    let intro = temp.path().join("book/intro.html");
+    assert_contains_strings(
+        &intro,
+        &[
+            r#"window.keyboard_shortcuts = {"next":"ArrowRight","previous":"ArrowLeft","search":"/"};"#,
+            r#"title="Search. (Shortkey: /)""#,
+            r#"aria-keyshortcuts="/""#,
+        ],
+    );
 
-    let index_html = temp.path().join("book/index.html");
-    assert_doesnt_contain_strings(index_html, &[r##"noindex"##]);
+    md.config
+        .set("output.html.keyboard-shortcuts", false)
+        .unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        &intro,
+        &["window.keyboard_shortcuts = false;", r#"title="Search." "#],
+    );
+    assert_doesnt_contain_strings(&intro, &["Shortkey", r#"aria-keyshortcuts="/""#]);
 }
 
 #[test]
-fn markdown_options() {
+fn print_page_anchors_can_be_prefixed_with_the_chapter() {
     let temp = DummyBook::new().build().unwrap();
-    let md = MDBook::load(temp.path()).unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.anchor-prefix", true).unwrap();
     md.build().unwrap();
 
-    let path = temp.path().join("book/first/markdown.html");
+    let print = temp.path().join("book/print.html");
     assert_contains_strings(
-        &path,
+        &print,
         &[
-            "<th>foo</th>",
-            "<th>bar</th>",
-            "<td>baz</td>",
-            "<td>bim</td>",
+            r##"<a class="header" href="#first-index-some-section" id="first-index-some-section">"##,
+            r##"<a class="header" href="#first-nested-some-section" id="first-nested-some-section">"##,
+            r##"<a href="#second-nested-some-section">fragment link</a>"##,
+            r##"<a href="second/../first/nested.html">the first section</a>"##,
         ],
     );
+    assert_doesnt_contain_strings(&print, &[r#"id="some-section"#]);
+    // The chapters' own pages keep their ids
     assert_contains_strings(
-        &path,
+        temp.path().join("book/first/nested.html"),
+        &[r##"href="#some-section" id="some-section""##],
+    );
+}
+
+#[test]
+fn base_tag_makes_links_relative_to_the_root() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.use-base-tag", true).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_contains_strings(
+        book.join("first/markdown.html"),
         &[
-            r##"<sup class="footnote-reference"><a href="#1">1</a></sup>"##,
-            r##"<sup class="footnote-reference"><a href="#word">2</a></sup>"##,
-            r##"<div class="footnote-definition" id="1"><sup class="footnote-definition-label">1</sup>"##,
-            r##"<div class="footnote-definition" id="word"><sup class="footnote-definition-label">2</sup>"##,
+            r#"<base href="../">"#,
+            r#"<link rel="stylesheet" href="css/general.css?v="#,
+            r#"<a href="intro.html">"#,
+            r##"<a class="header" href="first/markdown.html#tables" id="tables">"##,
+            r##"<a href="first/markdown.html#1">"##,
         ],
     );
-    assert_contains_strings(&path, &["<del>strikethrough example</del>"]);
+    assert_doesnt_contain_strings(
+        book.join("first/markdown.html"),
+        &[r##"href="#"##, "../css/", r#"<a href="../"#],
+    );
     assert_contains_strings(
-        &path,
+        book.join("index.html"),
         &[
-            "<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\nApples",
-            "<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\nBroccoli",
-            "<li><input disabled=\"\" type=\"checkbox\"/>\nCarrots",
+            r#"<base href="./">"#,
+            r##"<a class="header" href="index.html#dummy-book" id="dummy-book">"##,
         ],
     );
 }
 
 #[test]
-fn redirects_are_emitted_correctly() {
+fn additional_js_entries_can_have_attributes() {
     let temp = DummyBook::new().build().unwrap();
+    fs::write(temp.path().join("plain.js"), "").unwrap();
+    fs::write(temp.path().join("module.js"), "").unwrap();
+    fs::write(temp.path().join("early.js"), "").unwrap();
+    let table: toml::Value = toml::from_str(
+        r#"scripts = [
+            "plain.js",
+            { path = "module.js", type = "module", defer = true },
+            { path = "early.js", placement = "head", async = true },
+        ]"#,
+    )
+    .unwrap();
     let mut md = MDBook::load(temp.path()).unwrap();
-
-    // override the "outputs.html.redirect" table
-    let redirects: HashMap<PathBuf, String> = vec![
-        (PathBuf::from("/overview.html"), String::from("index.html")),
-        (
-            PathBuf::from("/nexted/page.md"),
-            String::from("https://rust-lang.org/"),
-        ),
-    ]
-    .into_iter()
-    .collect();
-    md.config.set("output.html.redirect", &redirects).unwrap();
-
+    md.config
+        .set("output.html.additional-js", &table["scripts"])
+        .unwrap();
     md.build().unwrap();
 
-    for (original, redirect) in &redirects {
-        let mut redirect_file = md.build_dir_for("html");
-        // append everything except the bits that make it absolute
-        // (e.g. "/" or "C:\")
-        redirect_file.extend(remove_absolute_components(&original));
-        let contents = fs::read_to_string(&redirect_file).unwrap();
-        assert!(contents.contains(redirect));
-    }
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[
+            r#"<script type="text/javascript" src="../plain.js"></script>"#,
+            r#"<script type="module" src="../module.js" defer></script>"#,
+        ],
+    );
+    let page = fs::read_to_string(temp.path().join("book/first/nested.html")).unwrap();
+    let early = page
+        .find(r#"<script type="text/javascript" src="../early.js" async></script>"#)
+        .unwrap();
+    assert!(early < page.find("</head>").unwrap());
+    assert!(page.find("../module.js").unwrap() > page.find("<body").unwrap());
 }
 
-fn remove_absolute_components(path: &Path) -> impl Iterator<Item = Component> + '_ {
-    path.components().skip_while(|c| match c {
-        Component::Prefix(_) | Component::RootDir => true,
-        _ => false,
-    })
+#[test]
+fn a11y_adds_a_skip_link_and_landmark_roles() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.a11y", true).unwrap();
+    md.build().unwrap();
+
+    let page = temp.path().join("book/first/nested.html");
+    assert_contains_strings(
+        &page,
+        &[
+            r##"<a class="skip-link" href="#content">Skip to content</a>"##,
+            r#"<nav id="sidebar" class="sidebar" aria-label="Table of contents" role="navigation">"#,
+            r#"<div id="content" class="content" tabindex="-1">"#,
+            r#"<main role="main">"#,
+        ],
+    );
+}
+
+#[test]
+fn the_active_sidebar_link_is_the_current_page() {
+    let temp = DummyBook::new().build().unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let page = temp.path().join("book/first/nested.html");
+    let doc = Document::from(fs::read_to_string(&page).unwrap().as_str());
+    let current: Vec<_> = doc
+        .find(Attr("aria-current", "page"))
+        .map(|link| link.attr("href").unwrap().to_string())
+        .collect();
+    assert_eq!(current, ["../first/nested.html"]);
+}
+
+#[test]
+fn foldable_sidebar_sections_are_marked() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.fold.enable", true).unwrap();
+    md.build().unwrap();
+
+    let intro = temp.path().join("book/intro.html");
+    assert_contains_strings(
+        &intro,
+        &[
+            r#"<li class="chapter-item " data-fold-id="1."><a href="first/index.html">"#,
+            r#"<li class="chapter-item " data-fold-id="2."><a href="second.html">"#,
+        ],
+    );
+    // The section of the current chapter is expanded
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[r#"<li class="chapter-item expanded " data-fold-id="1.">"#],
+    );
+
+    md.config.set("output.html.fold.enable", false).unwrap();
+    md.build().unwrap();
+    assert_doesnt_contain_strings(&intro, &["data-fold-id", r#"class="toggle""#]);
+}
+
+#[test]
+fn breadcrumbs_lead_to_the_current_chapter() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.breadcrumbs", true).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[
+            r#"<nav class="breadcrumbs" aria-label="Breadcrumbs">"#,
+            r#"<li><a href="../index.html">Home</a></li>"#,
+            r#"<li><a href="../first/index.html">First Chapter</a></li>"#,
+            r#"<li aria-current="page">Nested Chapter</li>"#,
+        ],
+    );
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &[r#"<li aria-current="page">Introduction</li>"#],
+    );
+
+    md.config.set("output.html.breadcrumbs", false).unwrap();
+    md.build().unwrap();
+    assert_doesnt_contain_strings(temp.path().join("book/first/nested.html"), &["breadcrumbs"]);
+}
+
+#[test]
+fn all_chapters_can_be_exposed_to_the_theme() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        &temp.path().join("theme"),
+        "head.hbs",
+        br#"{{#each all_chapters}}<meta name="chapter" content="{{level}} {{section_number}} {{title}} {{path}}">{{/each}}"#,
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let conclusion = temp.path().join("book/conclusion.html");
+    assert_doesnt_contain_strings(&conclusion, &[r#"<meta name="chapter""#]);
+
+    md.config.set("output.html.expose-chapters", true).unwrap();
+    md.build().unwrap();
+    assert_contains_strings(
+        &conclusion,
+        &[
+            r#"<meta name="chapter" content="0  Dummy Book index.md">"#,
+            r#"<meta name="chapter" content="0 1. First Chapter first/index.md">"#,
+            r#"<meta name="chapter" content="1 1.1. Nested Chapter first/nested.md">"#,
+            r#"<meta name="chapter" content="1 2.1. Nested Chapter second/nested.md">"#,
+        ],
+    );
+}
+
+#[test]
+fn recent_changes_are_read_from_git() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        temp.path(),
+        "book.toml",
+        b"[output.html.recent-changes]\ncount = 2\n",
+    )
+    .unwrap();
+    write_file(
+        &temp.path().join("theme"),
+        "head.hbs",
+        br#"{{#each recent_changes}}<meta name="change" content="{{date}} {{message}}:{{#each chapters}} {{name}} {{link}}{{/each}}">{{/each}}{{#unless recent_changes}}<meta name="no-changes">{{/unless}}"#,
+    )
+    .unwrap();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(temp.path())
+            .env("GIT_AUTHOR_NAME", "Author")
+            .env("GIT_AUTHOR_EMAIL", "author@example.com")
+            .env("GIT_COMMITTER_NAME", "Author")
+            .env("GIT_COMMITTER_EMAIL", "author@example.com")
+            .env("GIT_COMMITTER_DATE", "2020-05-17T09:30:00+02:00")
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+
+    // Outside of a git repository there are no changes.
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+    let index = temp.path().join("book/index.html");
+    assert_contains_strings(&index, &[r#"<meta name="no-changes">"#]);
+
+    git(&["init", "--quiet"]);
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "Write the book"]);
+    write_file(&temp.path().join("src"), "first/nested.md", b"# Nested\n").unwrap();
+    git(&["commit", "--quiet", "-am", "Expand the nested chapter"]);
+    write_file(&temp.path().join("src"), "notes.txt", b"Not a chapter").unwrap();
+    git(&["add", "."]);
+    git(&["commit", "--quiet", "-m", "Add notes"]);
+    md.build().unwrap();
+
+    assert_contains_strings(
+        &index,
+        &[
+            r#"<meta name="change" content="2020-05-17T09:30:00+02:00 Expand the nested chapter: Nested Chapter first/nested.html">"#,
+            r#"<meta name="change" content="2020-05-17T09:30:00+02:00 Write the book: Dummy Book index.html Conclusion conclusion.html"#,
+        ],
+    );
+    assert_doesnt_contain_strings(&index, &["Add notes", "no-changes"]);
+}
+
+#[test]
+fn html_element_has_the_language_and_text_direction() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let index = temp.path().join("book/index.html");
+    assert_contains_strings(&index, &[r#"<html lang="en" dir="ltr""#]);
+
+    md.config.set("book.language", "he").unwrap();
+    md.build().unwrap();
+    assert_contains_strings(&index, &[r#"<html lang="he" dir="rtl""#]);
+
+    md.config.set("output.html.text-direction", "ltr").unwrap();
+    md.build().unwrap();
+    assert_contains_strings(&index, &[r#"<html lang="he" dir="ltr""#]);
+}
+
+#[test]
+fn example_book_can_build() {
+    let example_book_dir = dummy_book::new_copy_of_example_book().unwrap();
+
+    let md = MDBook::load(example_book_dir.path()).unwrap();
+
+    md.build().unwrap();
+}
+
+#[test]
+fn book_with_a_reserved_filename_does_not_build() {
+    let tmp_dir = TempFileBuilder::new().prefix("mdBook").tempdir().unwrap();
+    let src_path = tmp_dir.path().join("src");
+    fs::create_dir(&src_path).unwrap();
+
+    let summary_path = src_path.join("SUMMARY.md");
+    let print_path = src_path.join("print.md");
+
+    fs::File::create(print_path).unwrap();
+    let mut summary_file = fs::File::create(summary_path).unwrap();
+    writeln!(summary_file, "[print](print.md)").unwrap();
+
+    let md = MDBook::load(tmp_dir.path()).unwrap();
+    let got = md.build();
+    assert!(got.is_err());
+}
+
+#[test]
+fn by_default_mdbook_use_index_preprocessor_to_convert_readme_to_index() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut cfg = Config::default();
+    cfg.set("book.src", "src2")
+        .expect("Couldn't set config.book.src to \"src2\".");
+    let md = MDBook::load_with_config(temp.path(), cfg).unwrap();
+    md.build().unwrap();
+
+    let first_index = temp.path().join("book").join("first").join("index.html");
+    let expected_strings = vec![
+        r#"href="../first/index.html""#,
+        r#"href="../second/index.html""#,
+        "First README",
+    ];
+    assert_contains_strings(&first_index, &expected_strings);
+    assert_doesnt_contain_strings(&first_index, &["README.html"]);
+
+    let second_index = temp.path().join("book").join("second").join("index.html");
+    let unexpected_strings = vec!["Second README"];
+    assert_doesnt_contain_strings(&second_index, &unexpected_strings);
+}
+
+#[test]
+fn theme_dir_overrides_work_correctly() {
+    let book_dir = dummy_book::new_copy_of_example_book().unwrap();
+    let book_dir = book_dir.path();
+    let theme_dir = book_dir.join("theme");
+
+    let mut index = mdbook::theme::INDEX.to_vec();
+    index.extend_from_slice(b"\n<!-- This is a modified index.hbs! -->");
+
+    write_file(&theme_dir, "index.hbs", &index).unwrap();
+
+    let md = MDBook::load(book_dir).unwrap();
+    md.build().unwrap();
+
+    let built_index = book_dir.join("book").join("index.html");
+    dummy_book::assert_contains_strings(built_index, &["This is a modified index.hbs!"]);
+}
+
+#[test]
+fn theme_files_can_be_overridden_one_by_one() {
+    let temp = DummyBook::new().build().unwrap();
+    let theme_dir = temp.path().join("theme");
+    write_file(&theme_dir, "css/general.css", b"/* custom general.css */").unwrap();
+    write_file(&theme_dir, "fonts/fonts.css", b"/* custom fonts.css */").unwrap();
+
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_eq!(
+        fs::read(book.join("css/general.css")).unwrap(),
+        b"/* custom general.css */"
+    );
+    assert_eq!(
+        fs::read(book.join("fonts/fonts.css")).unwrap(),
+        b"/* custom fonts.css */"
+    );
+    assert_eq!(
+        fs::read(book.join("css/chrome.css")).unwrap(),
+        mdbook::theme::CHROME_CSS
+    );
+    assert_eq!(
+        fs::read(book.join("FontAwesome/css/font-awesome.css")).unwrap(),
+        mdbook::theme::FONT_AWESOME
+    );
+    assert_contains_strings(
+        book.join("index.html"),
+        &[r#"<link rel="stylesheet" href="css/general.css?v="#],
+    );
+}
+
+#[test]
+fn theme_assets_are_versioned() {
+    fn book_js_version(temp: &Path) -> String {
+        let md = MDBook::load(temp).unwrap();
+        md.build().unwrap();
+        let intro = fs::read_to_string(temp.join("book/intro.html")).unwrap();
+        let start = intro
+            .find(r#"src="book.js?v="#)
+            .expect("a versioned book.js")
+            + 15;
+        intro[start..start + 8].to_string()
+    }
+
+    let temp = DummyBook::new().build().unwrap();
+    let version = book_js_version(temp.path());
+    assert!(version.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[&format!(r#"src="../searcher.js?v={}""#, version)],
+    );
+
+    write_file(&temp.path().join("theme"), "book.js", b"// custom book.js").unwrap();
+    assert_ne!(book_js_version(temp.path()), version);
+}
+
+#[test]
+fn custom_handlebars_helpers_can_be_used_in_the_theme() {
+    use handlebars::{Context, Handlebars, Helper, HelperResult, Output, RenderContext};
+    use mdbook::renderer::HtmlHandlebars;
+
+    fn shout(
+        h: &Helper<'_, '_>,
+        _: &Handlebars<'_>,
+        _: &Context,
+        _: &mut RenderContext<'_, '_>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let text = h.param(0).and_then(|v| v.value().as_str()).unwrap_or("");
+        out.write(&text.to_uppercase())?;
+        Ok(())
+    }
+
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        &temp.path().join("theme"),
+        "head.hbs",
+        br#"<meta name="shout" content="{{shout "hello"}}">"#,
+    )
+    .unwrap();
+
+    let mut html = HtmlHandlebars::new();
+    html.register_helper("shout", shout);
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.replace_renderer(html);
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/index.html"),
+        &[r#"<meta name="shout" content="HELLO">"#],
+    );
+}
+
+#[test]
+fn no_index_for_print_html() {
+    let temp = DummyBook::new().build().unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let print_html = temp.path().join("book/print.html");
+    assert_contains_strings(print_html, &[r##"noindex"##]);
+
+    let index_html = temp.path().join("book/index.html");
+    assert_doesnt_contain_strings(index_html, &[r##"noindex"##]);
+}
+
+#[test]
+fn markdown_options() {
+    let temp = DummyBook::new().build().unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let path = temp.path().join("book/first/markdown.html");
+    assert_contains_strings(
+        &path,
+        &[
+            "<th>foo</th>",
+            "<th>bar</th>",
+            "<td>baz</td>",
+            "<td>bim</td>",
+        ],
+    );
+    assert_contains_strings(
+        &path,
+        &[
+            r##"<sup class="footnote-reference"><a href="#1">1</a></sup>"##,
+            r##"<sup class="footnote-reference"><a href="#word">2</a></sup>"##,
+            r##"<div class="footnote-definition" id="1"><sup class="footnote-definition-label">1</sup>"##,
+            r##"<div class="footnote-definition" id="word"><sup class="footnote-definition-label">2</sup>"##,
+        ],
+    );
+    assert_contains_strings(&path, &["<del>strikethrough example</del>"]);
+    assert_contains_strings(
+        &path,
+        &[
+            "<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\nApples",
+            "<li><input disabled=\"\" type=\"checkbox\" checked=\"\"/>\nBroccoli",
+            "<li><input disabled=\"\" type=\"checkbox\"/>\nCarrots",
+        ],
+    );
+}
+
+#[test]
+fn redirects_are_emitted_correctly() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+
+    // override the "outputs.html.redirect" table
+    let redirects: HashMap<PathBuf, String> = vec![
+        (PathBuf::from("/overview.html"), String::from("index.html")),
+        (
+            PathBuf::from("/nexted/page.md"),
+            String::from("https://rust-lang.org/"),
+        ),
+    ]
+    .into_iter()
+    .collect();
+    md.config.set("output.html.redirect", &redirects).unwrap();
+
+    md.build().unwrap();
+
+    for (original, redirect) in &redirects {
+        let mut redirect_file = md.build_dir_for("html");
+        // append everything except the bits that make it absolute
+        // (e.g. "/" or "C:\")
+        redirect_file.extend(remove_absolute_components(&original));
+        let contents = fs::read_to_string(&redirect_file).unwrap();
+        assert!(contents.contains(redirect));
+        assert!(contents.contains("target += window.location.hash;"));
+    }
+}
+
+#[test]
+fn redirect_patterns_are_expanded_for_the_listed_paths() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set(
+            "output.html.redirect-patterns",
+            vec![serde_json::json!({"from": "/old/(.*)", "to": "/new/$1"})],
+        )
+        .unwrap();
+    md.config
+        .set(
+            "output.html.redirect-paths",
+            vec!["/old/intro.html", "/old/nested/page.html"],
+        )
+        .unwrap();
+    md.config
+        .set(
+            "output.html.redirect",
+            serde_json::json!({"/old/intro.html": "intro.html"}),
+        )
+        .unwrap();
+
+    md.build().unwrap();
+
+    let build_dir = md.build_dir_for("html");
+    assert_contains_strings(
+        build_dir.join("old/nested/page.html"),
+        &["<meta http-equiv=\"refresh\" content=\"0;URL='/new/nested/page.html'\">"],
+    );
+    assert_contains_strings(build_dir.join("old/intro.html"), &["URL='intro.html'"]);
+}
+
+#[test]
+fn chapters_can_override_curly_quotes_in_their_front_matter() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/intro.md"),
+        "---\ncurly-quotes: false\n---\n# Introduction\n\n\"Straight\" quotes\n",
+    )
+    .unwrap();
+    fs::write(
+        temp.path().join("src/second.md"),
+        "# Second Chapter\n\n\"Curly\" quotes\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.curly-quotes", true).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_contains_strings(
+        book.join("intro.html"),
+        &["<p>&quot;Straight&quot; quotes</p>"],
+    );
+    assert_contains_strings(book.join("second.html"), &["<p>“Curly” quotes</p>"]);
+    assert_contains_strings(
+        book.join("print.html"),
+        &[
+            "<p>&quot;Straight&quot; quotes</p>",
+            "<p>“Curly” quotes</p>",
+        ],
+    );
+}
+
+#[test]
+fn images_can_be_served_from_an_asset_url_prefix() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set(
+            "output.html.asset-url-prefix",
+            "https://cdn.example.com/book/",
+        )
+        .unwrap();
+    md.build().unwrap();
+
+    let images = &[
+        r##"<img src="https://cdn.example.com/book/images/picture.png" alt="Some image" />"##,
+        r##"<img src="https://cdn.example.com/book/images/picture.png" alt="raw html">"##,
+    ];
+    let book = temp.path().join("book");
+    assert_contains_strings(book.join("second/nested.html"), images);
+    assert_contains_strings(book.join("print.html"), images);
+
+    // Links aren't images
+    assert_contains_strings(
+        book.join("second/nested.html"),
+        &[
+            r##"<a href="../first/nested.html">the first section</a>"##,
+            r##"<a href="../first/markdown.html">HTML Link</a>"##,
+            r##"<a href="../../std/foo/bar.html">outside</a>"##,
+        ],
+    );
+}
+
+#[test]
+fn links_to_downloads_get_the_download_attribute() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/intro.md"),
+        "# Introduction\n\nGet the [report](files/report.pdf) or read [more](first/index.md).\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.download-extensions", vec!["pdf", "zip"])
+        .unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &[
+            r#"<a href="files/report.pdf" download>report</a>"#,
+            r#"<a href="first/index.html">more</a>"#,
+        ],
+    );
+}
+
+#[test]
+fn links_from_the_book_root_are_relative_to_the_page() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/second/nested.md"),
+        "# Nested\n\nBack to [the first chapter](/first/index.md#first-chapter).\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_contains_strings(
+        book.join("second/nested.html"),
+        &[r##"<a href="../first/index.html#first-chapter">the first chapter</a>"##],
+    );
+    assert_contains_strings(
+        book.join("print.html"),
+        &[r##"<a href="first/index.html#first-chapter">the first chapter</a>"##],
+    );
+}
+
+#[test]
+fn links_can_start_from_the_site_url() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/second/nested.md"),
+        "# Nested\n\nBack to [the first chapter](../first/index.md#first-chapter).\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.site-url", "/docs/").unwrap();
+    md.config.set("output.html.absolute-links", true).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_contains_strings(
+        book.join("second/nested.html"),
+        &[
+            r##"<a href="/docs/first/index.html#first-chapter">the first chapter</a>"##,
+            r#"var path_to_root = "/docs/";"#,
+        ],
+    );
+    assert_contains_strings(
+        book.join("print.html"),
+        &[r##"<a href="/docs/first/index.html#first-chapter">the first chapter</a>"##],
+    );
+}
+
+#[test]
+fn absolute_links_need_a_site_url() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.absolute-links", true).unwrap();
+
+    assert!(md.build().is_err());
+}
+
+#[test]
+fn books_can_be_built_from_memory() {
+    let temp = TempFileBuilder::new().prefix("book").tempdir().unwrap();
+    let mut chapters = HashMap::new();
+    chapters.insert(
+        PathBuf::from("intro.md"),
+        String::from("# Introduction\n\nSee [the guide](guide.md)."),
+    );
+    chapters.insert(PathBuf::from("guide.md"), String::from("# Guide\n"));
+
+    let md = MDBook::from_summary_str(
+        temp.path(),
+        "[Introduction](./intro.md)\n\n- [Guide](./guide.md)\n",
+        chapters,
+        Config::default(),
+    )
+    .unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_contains_strings(
+        book.join("intro.html"),
+        &[r#"<a href="guide.html">the guide</a>"#],
+    );
+    assert!(book.join("guide.html").exists());
+    assert!(!temp.path().join("src").exists());
+}
+
+#[test]
+fn links_to_a_readme_point_to_the_index_page() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/second/nested.md"),
+        "# Nested\n\nBack to [the start](../README.md#dummy-book).\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert_contains_strings(
+        book.join("second/nested.html"),
+        &[r##"<a href="../index.html#dummy-book">the start</a>"##],
+    );
+    assert_contains_strings(
+        book.join("print.html"),
+        &[r##"<a href="second/../index.html#dummy-book">the start</a>"##],
+    );
+}
+
+#[test]
+fn stylesheets_can_be_inlined() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::create_dir_all(temp.path().join("theme/extra")).unwrap();
+    fs::write(temp.path().join("first.css"), ".first { color: red; }\n").unwrap();
+    fs::write(
+        temp.path().join("theme/extra/second.css"),
+        ".second { background: url(../img/bg.png); }\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.inline-css", true).unwrap();
+    md.config
+        .set(
+            "output.html.additional-css",
+            vec!["first.css", "theme/extra/second.css"],
+        )
+        .unwrap();
+    md.build().unwrap();
+
+    let nested = temp.path().join("book/first/nested.html");
+    let html = fs::read_to_string(&nested).unwrap();
+    assert_eq!(html.matches("<style>").count(), 1);
+    assert_contains_strings(
+        &nested,
+        &[
+            ".first { color: red; }",
+            ".second { background: url(../theme/img/bg.png); }",
+            "url('../fonts/open-sans-v17-all-charsets-300.woff2')",
+        ],
+    );
+    assert_doesnt_contain_strings(
+        &nested,
+        &[r#"href="../css/general.css""#, r#"href="../first.css""#],
+    );
+}
+
+#[test]
+fn theme_vars_are_injected_into_the_head() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.theme-vars.--sidebar-bg", "#fff")
+        .unwrap();
+    md.config
+        .set("output.html.theme-vars.--links", "rebeccapurple")
+        .unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[":root {\n    --links: rebeccapurple;\n    --sidebar-bg: #fff;\n}"],
+    );
+}
+
+#[test]
+fn the_content_width_can_be_configured() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.max-content-width", "80ch")
+        .unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[":root {\n    --content-max-width: 80ch;\n}"],
+    );
+
+    md.config
+        .set("output.html.theme-vars.content-max-width", "60ch")
+        .unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[":root {\n    --content-max-width: 60ch;\n}"],
+    );
+}
+
+#[test]
+fn headings_can_get_permalink_buttons() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set("output.html.heading-permalinks", true)
+        .unwrap();
+    md.config
+        .set("output.html.site-url", "https://example.com/book/")
+        .unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[
+            r##"<h1><a class="header" href="#nested-chapter" id="nested-chapter">Nested Chapter</a><button class="fa fa-link permalink-button" data-permalink="https://example.com/book/first/nested.html#nested-chapter""##,
+        ],
+    );
+    assert_doesnt_contain_strings(
+        temp.path().join("book/first/nested.html"),
+        &["data-permalink=\"#"],
+    );
+}
+
+#[test]
+fn the_book_can_be_rendered_as_json() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(temp.path().join("book.toml"), "[output.json]\n").unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let json = fs::read_to_string(temp.path().join("book/book.json")).unwrap();
+    let book: mdbook::book::Book = serde_json::from_str(&json).unwrap();
+
+    let nested = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(ch) => Some(ch),
+            _ => None,
+        })
+        .find(|ch| ch.name == "Nested Chapter")
+        .unwrap();
+    assert_eq!(nested.number.as_ref().unwrap().to_string(), "1.1.");
+    assert_eq!(
+        nested.source_path.as_ref().unwrap(),
+        Path::new("first/nested.md")
+    );
+    assert!(nested.content.starts_with("# Nested Chapter"));
+}
+
+#[test]
+fn templates_can_render_the_headings_of_the_page() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        &temp.path().join("theme"),
+        "index.hbs",
+        b"<nav>{{#each page_toc}}<a href=\"#{{id}}\" class=\"level-{{level}}\">{{text}}</a>{{/each}}</nav>\n{{{content}}}\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.page-toc", true).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/first/nested.html"),
+        &[concat!(
+            r##"<nav><a href="#nested-chapter" class="level-1">Nested Chapter</a>"##,
+            r##"<a href="#some-section" class="level-2">Some Section</a>"##,
+            r##"<a href="#anchors-include-the-part-of-a-file-between-special-comments" class="level-2">"##,
+        )],
+    );
+}
+
+#[test]
+fn data_files_can_be_included_as_tables() {
+    let temp = DummyBook::new().build().unwrap();
+    let src = temp.path().join("src");
+    write_file(&src, "crates.csv", b"Name,Version\nmdbook,\"0.4\"\n").unwrap();
+    write_file(
+        &src,
+        "intro.md",
+        b"# Intro\n\n{{#include crates.csv table}}\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &[
+            "<th>Name</th><th>Version</th>",
+            "<td>mdbook</td><td>0.4</td>",
+        ],
+    );
+}
+
+#[test]
+fn the_page_outline_can_be_embedded_as_json() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+    let nested = temp.path().join("book/first/nested.html");
+    assert_doesnt_contain_strings(&nested, &["id=\"page-outline\""]);
+
+    md.config
+        .set("output.html.page-outline-data", true)
+        .unwrap();
+    md.build().unwrap();
+    assert_contains_strings(
+        &nested,
+        &[concat!(
+            r#"<script type="application/json" id="page-outline">"#,
+            r#"[{"id":"nested-chapter","level":1,"text":"Nested Chapter"},"#,
+            r#"{"id":"some-section","level":2,"text":"Some Section"},"#,
+        )],
+    );
+}
+
+#[test]
+fn variables_from_the_config_are_substituted() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        temp.path(),
+        "book.toml",
+        b"[preprocessor.variables]\n\n[variables]\nproduct = \"Gizmo\"\n",
+    )
+    .unwrap();
+    write_file(
+        &temp.path().join("src"),
+        "intro.md",
+        b"# Intro\n\nUsing {{#var product}} and {{#var undefined}}.\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &["Using Gizmo and {{#var undefined}}."],
+    );
+}
+
+#[test]
+fn details_blocks_become_collapsible() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(temp.path(), "book.toml", b"[preprocessor.details]\n").unwrap();
+    write_file(
+        &temp.path().join("src"),
+        "intro.md",
+        b"# Intro\n\n````details\nShow the `main` function\nIt is *short*:\n\n\
+          ```rust\nfn main() {}\n```\n````\n\nAfter the details.\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &[
+            "<details>\n<summary>Show the <code>main</code> function</summary>\n\
+             <p>It is <em>short</em>:</p>\n\
+             <pre><pre class=\"playground\"><code class=\"language-rust\">fn main() {}\n</code></pre></pre>\n\
+             </details>\n<p>After the details.</p>",
+        ],
+    );
+}
+
+#[test]
+fn mismatched_anchors_can_be_normalized() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        temp.path(),
+        "book.toml",
+        b"[preprocessor.anchors]\nnormalize = true\n",
+    )
+    .unwrap();
+    write_file(
+        &temp.path().join("src"),
+        "intro.md",
+        b"# Intro\n\nSee [the section](first/nested.md#Some-Section), \
+          [another one](first/nested.md#some-sektion) and [the top](#INTRO).\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &[
+            r##"<a href="first/nested.html#some-section">the section</a>"##,
+            r##"<a href="first/nested.html#some-sektion">another one</a>"##,
+            r##"<a href="#intro">the top</a>"##,
+        ],
+    );
+}
+
+#[test]
+fn the_version_is_read_from_cargo_toml() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        temp.path(),
+        "Cargo.toml",
+        b"[package]\nname = \"fixture\"\nversion = \"3.1.4\"\n",
+    )
+    .unwrap();
+    write_file(
+        &temp.path().join("src"),
+        "intro.md",
+        b"# Intro\n\nVersion {{#version ../Cargo.toml}}, not {{#version}}.\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &["Version 3.1.4, not {{#version}}."],
+    );
+}
+
+#[test]
+fn output_can_be_normalized() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config.set("output.html.normalize-output", true).unwrap();
+    md.build().unwrap();
+
+    let nested = fs::read_to_string(temp.path().join("book/first/nested.html")).unwrap();
+    assert!(!nested.contains('\r'));
+    assert!(nested.ends_with("</html>\n"));
+    for line in nested.lines() {
+        assert_eq!(line, line.trim_end());
+    }
+}
+
+#[test]
+fn only_a_subtree_of_the_book_can_be_built() {
+    let temp = DummyBook::new().build().unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.only("./second/").unwrap();
+    md.build().unwrap();
+
+    let book = temp.path().join("book");
+    assert!(book.join("second.html").exists());
+    assert!(book.join("second/nested.html").exists());
+    assert!(!book.join("intro.html").exists());
+    assert!(!book.join("first/nested.html").exists());
+    assert_contains_strings(
+        book.join("second/nested.html"),
+        &[
+            r#"<a href="../second.html">"#,
+            r#"<a href="../second/nested.html" class="active""#,
+        ],
+    );
+    assert_doesnt_contain_strings(
+        book.join("second/nested.html"),
+        &["first/index.html", "intro.html"],
+    );
+
+    let mut md = MDBook::load(temp.path()).unwrap();
+    let got = md.only("third").unwrap_err().to_string();
+    assert_eq!(got, "The book has no chapters in `third`");
+}
+
+#[test]
+fn the_summary_can_have_another_name() {
+    let temp = DummyBook::new().build().unwrap();
+    let src = temp.path().join("src");
+    fs::rename(src.join("SUMMARY.md"), src.join("TOC.md")).unwrap();
+
+    let got = MDBook::load(temp.path()).map(|_| ()).unwrap_err();
+    assert_eq!(got.to_string(), "Couldn't open SUMMARY.md");
+
+    write_file(temp.path(), "book.toml", b"[book]\nsummary = \"TOC.md\"\n").unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    assert!(temp.path().join("book/second/nested.html").exists());
+    assert_contains_strings(
+        temp.path().join("book/intro.html"),
+        &[r#"<a href="second/nested.html">"#],
+    );
+}
+
+#[test]
+fn html_chapters_arent_rendered_as_markdown() {
+    let temp = DummyBook::new().build().unwrap();
+    let src = temp.path().join("src");
+    let summary = fs::read_to_string(src.join("SUMMARY.md")).unwrap();
+    let summary = summary.replace(
+        "- [Second Chapter](second.md)",
+        "- [Widget](first/widget.html)\n- [Second Chapter](second.md)",
+    );
+    write_file(&src, "SUMMARY.md", summary.as_bytes()).unwrap();
+    write_file(
+        &src,
+        "first/widget.html",
+        b"<div id=\"widget\">\n\n# Not a *heading*\n\n<a href=\"nested.md#some-section\">Back</a>\n</div>\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let widget = temp.path().join("book/first/widget.html");
+    assert_contains_strings(
+        &widget,
+        &[
+            "<title>Widget</title>",
+            "<div id=\"widget\">\n\n# Not a *heading*\n\n",
+            r#"<a href="nested.html#some-section">Back</a>"#,
+            r#"<a href="../first/widget.html" class="active""#,
+        ],
+    );
+    assert_doesnt_contain_strings(&widget, &["id=\"not-a-heading\"", "<em>heading</em>"]);
+    assert_contains_strings(
+        temp.path().join("book/first/unicode.html"),
+        &[r#"<a rel="next" href="../first/widget.html""#],
+    );
+}
+
+#[test]
+fn the_markdown_can_be_expanded_or_copied_faithfully() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(
+        &temp.path().join("src"),
+        "intro.md",
+        b"---\ntags: [setup]\n---\n# Intro\n\n{{#include ../LICENSE}}\n\nSee [the setup](first/index.md#setup).\n",
+    )
+    .unwrap();
+    write_file(temp.path(), "LICENSE", b"Licensed under MPL-2.0.").unwrap();
+    let intro = temp.path().join("book/intro.md");
+
+    write_file(temp.path(), "book.toml", b"[output.markdown]\n").unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let got = fs::read_to_string(&intro).unwrap();
+    assert_eq!(
+        got,
+        "# Intro\n\nLicensed under MPL-2.0.\n\nSee [the setup](first/index.md#setup).\n"
+    );
+
+    write_file(
+        temp.path(),
+        "book.toml",
+        b"[output.markdown]\nexpand-includes = false\nrewrite-links = true\nfront-matter = true\n",
+    )
+    .unwrap();
+    let md = MDBook::load(temp.path()).unwrap();
+    md.build().unwrap();
+
+    let got = fs::read_to_string(&intro).unwrap();
+    assert_eq!(
+        got,
+        "---\ntags: [setup]\n---\n# Intro\n\n{{#include ../LICENSE}}\n\n\
+         See [the setup](first/index.html#setup).\n"
+    );
+}
+
+#[test]
+fn extra_files_are_copied_into_the_output() {
+    let temp = DummyBook::new().build().unwrap();
+    write_file(temp.path(), "robots.txt", b"User-agent: *\nDisallow:\n").unwrap();
+    write_file(temp.path(), ".well-known/security.txt", b"Contact: me\n").unwrap();
+    write_file(temp.path(), "samples/data.csv", b"a,b\n").unwrap();
+    write_file(temp.path(), ".git/robots.txt", b"").unwrap();
+    write_file(temp.path(), ".git/HEAD", b"ref: refs/heads/main\n").unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.config
+        .set(
+            "output.html.extra-files",
+            vec!["robots.txt", ".well-known/**", "missing/*", ".git/HEAD"],
+        )
+        .unwrap();
+    md.build().unwrap();
+
+    let build_dir = md.build_dir_for("html");
+    assert_eq!(
+        fs::read_to_string(build_dir.join("robots.txt")).unwrap(),
+        "User-agent: *\nDisallow:\n"
+    );
+    assert!(build_dir.join(".well-known/security.txt").exists());
+    assert!(!build_dir.join("samples/data.csv").exists());
+    // Version control directories are only searched by the patterns naming them
+    assert!(!build_dir.join(".git/robots.txt").exists());
+    assert!(build_dir.join(".git/HEAD").exists());
+
+    md.config
+        .set("output.html.extra-files", vec!["../outside.txt"])
+        .unwrap();
+    assert!(md.build().is_err());
+}
+
+fn remove_absolute_components(path: &Path) -> impl Iterator<Item = Component> + '_ {
+    path.components().skip_while(|c| match c {
+        Component::Prefix(_) | Component::RootDir => true,
+        _ => false,
+    })
 }
 
 #[cfg(feature = "search")]
 mod search {
     use crate::dummy_book::DummyBook;
+    use mdbook::renderer::{search, HtmlHandlebars};
     use mdbook::MDBook;
     use std::fs::{self, File};
     use std::path::Path;
@@ -599,6 +2062,213 @@ mod search {
         assert_eq!(docs[&conclusion]["body"], "I put &lt;HTML&gt; in here!");
     }
 
+    #[test]
+    fn rebuilding_updates_the_search_index_of_changed_chapters() {
+        let temp = DummyBook::new().build().unwrap();
+        let html = HtmlHandlebars::new();
+        let mut md = MDBook::load(temp.path()).unwrap();
+        md.replace_renderer(html.clone());
+        md.build().unwrap();
+        let before = read_book_index(temp.path());
+
+        let intro = temp.path().join("src/intro.md");
+        let content = fs::read_to_string(&intro).unwrap();
+        fs::write(&intro, content.replace("interesting", "fascinating")).unwrap();
+
+        // Rebuild with the same renderer, like `mdbook serve` does
+        let mut md = MDBook::load(temp.path()).unwrap();
+        md.replace_renderer(html.clone());
+        md.build().unwrap();
+        let after = read_book_index(temp.path());
+
+        assert_eq!(before["doc_urls"], after["doc_urls"]);
+        let doc_urls = after["doc_urls"].as_array().unwrap();
+        let get_doc_ref =
+            |url: &str| -> String { doc_urls.iter().position(|s| s == url).unwrap().to_string() };
+        let introduction = get_doc_ref("intro.html#introduction");
+        let conclusion = get_doc_ref("conclusion.html#conclusion");
+
+        let docs_before = &before["index"]["documentStore"]["docs"];
+        let docs_after = &after["index"]["documentStore"]["docs"];
+        assert_eq!(
+            docs_before[&introduction]["body"],
+            "Here's some interesting text..."
+        );
+        assert_eq!(
+            docs_after[&introduction]["body"],
+            "Here's some fascinating text..."
+        );
+        assert_eq!(docs_after[&conclusion], docs_before[&conclusion]);
+
+        let interest = |index: &serde_json::Value| {
+            index["index"]["index"]["body"]["root"]["i"]["n"]["t"]["e"]["r"]["e"]["s"]["t"]["docs"]
+                .get(&introduction)
+                .is_some()
+        };
+        assert!(interest(&before));
+        assert!(!interest(&after));
+
+        // The updated index is the same as a new one
+        MDBook::load(temp.path()).unwrap().build().unwrap();
+        assert_eq!(read_book_index(temp.path()), after);
+    }
+
+    #[test]
+    fn search_index_can_be_created_by_other_backends() {
+        let temp = DummyBook::new().build().unwrap();
+        let md = MDBook::load(temp.path()).unwrap();
+        md.build().unwrap();
+
+        let search_config = Default::default();
+        let index = search::create_index(&md.book, &search_config).unwrap();
+        let index: serde_json::Value = serde_json::from_str(&index).unwrap();
+        let written = read_book_index(temp.path());
+        assert_eq!(index["results_options"], written["results_options"]);
+        assert_eq!(index["search_options"], written["search_options"]);
+
+        let documents = search::create_documents(&md.book, &search_config).unwrap();
+        let intro = documents
+            .iter()
+            .find(|doc| doc.url == "intro.html#introduction")
+            .unwrap();
+        assert_eq!(intro.title, "Introduction");
+        assert_eq!(intro.body, "Here's some interesting text...");
+    }
+
+    #[test]
+    fn custom_stopwords_are_left_out_of_the_search_index() {
+        let temp = DummyBook::new().build().unwrap();
+        let mut md = MDBook::load(temp.path()).unwrap();
+        md.config
+            .set("output.html.search.stopwords", vec!["Interesting"])
+            .unwrap();
+        md.build().unwrap();
+
+        let index = read_book_index(temp.path());
+        assert_eq!(index["stopwords"], serde_json::json!(["interesting"]));
+        assert_eq!(
+            index["index"]["pipeline"],
+            serde_json::json!(["trimmer", "stopWordFilter", "stemmer"])
+        );
+
+        let bodyidx = &index["index"]["index"]["body"]["root"];
+        assert!(bodyidx["i"]["n"]["t"]["e"]["r"]["e"]["s"]["t"]
+            .get("docs")
+            .is_none());
+        assert!(bodyidx["t"]["e"]["x"]["t"]["docs"].is_object());
+    }
+
+    #[test]
+    fn the_search_index_uses_the_pipeline_of_its_language() {
+        let temp = DummyBook::new().build().unwrap();
+        fs::write(
+            temp.path().join("src/intro.md"),
+            "# Einleitung\n\nDie Häuser sind schön und höher als alle anderen.\n",
+        )
+        .unwrap();
+        let mut md = MDBook::load(temp.path()).unwrap();
+        md.config.set("output.html.search.lang", "de").unwrap();
+        md.config
+            .set("output.html.search.stopwords", vec!["höher"])
+            .unwrap();
+        md.build().unwrap();
+
+        let index = read_book_index(temp.path());
+        assert_eq!(index["lang"], "de");
+        assert_eq!(index["index"]["lang"], "German");
+        assert_eq!(
+            index["index"]["pipeline"],
+            serde_json::json!(["trimmer-de", "stopWordFilter-de", "stemmer-de"])
+        );
+
+        let introduction = index["doc_urls"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .position(|s| s == "intro.html#einleitung")
+            .unwrap()
+            .to_string();
+        let bodyidx = &index["index"]["index"]["body"]["root"];
+        let docs = |word: &str| {
+            let mut node = bodyidx;
+            for c in word.chars() {
+                node = &node[c.to_string()];
+            }
+            node["docs"].get(&introduction).is_some()
+        };
+        // "Häuser" is stemmed to "haus", "die" and "und" are German stop words
+        assert!(docs("haus"));
+        assert!(!docs("häuser"));
+        assert!(!docs("die"));
+        assert!(!docs("und"));
+        assert!(!docs("hoh"));
+        assert!(!docs("höher"));
+        assert!(docs("schon"));
+    }
+
+    #[test]
+    fn teaser_highlight_stores_term_positions() {
+        let temp = DummyBook::new().build().unwrap();
+        let mut md = MDBook::load(temp.path()).unwrap();
+        let index = read_book_index_with(&mut md, false);
+        assert!(index.get("teaser_terms").is_none());
+
+        let index = read_book_index_with(&mut md, true);
+        let doc_urls = index["doc_urls"].as_array().unwrap();
+        let introduction = doc_urls
+            .iter()
+            .position(|s| s == "intro.html#introduction")
+            .unwrap();
+
+        // "Here's some interesting text..."
+        let terms = &index["teaser_terms"][introduction];
+        assert_eq!(terms["interest"], serde_json::json!([[12, 23]]));
+        assert_eq!(terms["text"], serde_json::json!([[24, 31]]));
+        assert!(terms.get("some").is_none());
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn prefer_order_boosts_earlier_chapters() {
+        let temp = DummyBook::new().build().unwrap();
+        let mut md = MDBook::load(temp.path()).unwrap();
+        md.build().unwrap();
+        assert!(read_book_index(temp.path()).get("doc_boosts").is_none());
+
+        md.config
+            .set("output.html.search.prefer-order", true)
+            .unwrap();
+        md.build().unwrap();
+        let index = read_book_index(temp.path());
+        let doc_urls = index["doc_urls"].as_array().unwrap();
+        let boosts = index["doc_boosts"].as_array().unwrap();
+        assert_eq!(boosts.len(), doc_urls.len());
+        let boost = |url: &str| {
+            let doc = doc_urls.iter().position(|s| s == url).unwrap();
+            boosts[doc].as_f64().unwrap()
+        };
+
+        assert_eq!(boost("index.html#dummy-book"), 1.1);
+        assert_eq!(
+            boost("first/index.html#first-chapter"),
+            boost("first/index.html#some-section")
+        );
+        assert!(boost("intro.html#introduction") > boost("first/index.html#first-chapter"));
+        assert!(
+            boost("first/index.html#first-chapter")
+                > boost("second/nested.html#testing-relative-links-for-the-print-page")
+        );
+        assert_eq!(boost("conclusion.html#conclusion"), 1.0);
+    }
+
+    fn read_book_index_with(md: &mut MDBook, teaser_highlight: bool) -> serde_json::Value {
+        md.config
+            .set("output.html.search.teaser-highlight", teaser_highlight)
+            .unwrap();
+        md.build().unwrap();
+        read_book_index(&md.root)
+    }
+
     // Setting this to `true` may cause issues with `cargo watch`,
     // since it may not finish writing the fixture before the tests
     // are run again.
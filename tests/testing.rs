@@ -2,7 +2,10 @@ mod dummy_book;
 
 use crate::dummy_book::DummyBook;
 
+use mdbook::book::TestOutcome;
 use mdbook::MDBook;
+use std::fs;
+use std::path::Path;
 
 #[test]
 fn mdbook_can_correctly_test_a_passing_book() {
@@ -24,3 +27,201 @@ fn mdbook_detects_book_with_failing_tests() {
 
     assert!(md.test(vec![]).is_err());
 }
+
+#[test]
+fn html_chapters_are_not_tested() {
+    let temp = DummyBook::new().with_passing_test(true).build().unwrap();
+    let summary = temp.path().join("src/SUMMARY.md");
+    let content = fs::read_to_string(&summary).unwrap();
+    let content = content.replace(
+        "- [Second Chapter](second.md)",
+        "- [Widget](widget.html)\n- [Second Chapter](second.md)",
+    );
+    fs::write(&summary, content).unwrap();
+    fs::write(
+        temp.path().join("src/widget.html"),
+        "<div id=\"widget\"></div>\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+
+    let report = md.test_all(vec![], false).unwrap();
+    assert!(report.passed(), "{:?}", report.chapters);
+    assert!(report
+        .chapters
+        .iter()
+        .all(|ch| ch.path != Path::new("widget.html")));
+}
+
+/// Tests a dummy book whose introduction is `intro`.
+fn test_intro(intro: &str) -> mdbook::errors::Result<()> {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(temp.path().join("src/intro.md"), intro).unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.test(vec![])
+}
+
+/// Like [`test_intro`], but only compiles the code blocks.
+fn test_intro_no_run(intro: &str) -> mdbook::errors::Result<()> {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(temp.path().join("src/intro.md"), intro).unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+    md.test_no_run(vec![])
+}
+
+#[test]
+fn code_blocks_can_be_skipped_by_mdbook_test() {
+    let result =
+        test_intro("# Introduction\n\n```rust,mdbook-test-skip\nthis doesn't compile\n```\n");
+    assert!(
+        result.is_ok(),
+        "Tests failed with {}",
+        result.err().unwrap()
+    );
+}
+
+#[test]
+fn compile_fail_blocks_must_not_compile() {
+    let result = test_intro("# Introduction\n\n```rust, compile_fail\nlet x: u32 = \"no\";\n```\n");
+    assert!(
+        result.is_ok(),
+        "Tests failed with {}",
+        result.err().unwrap()
+    );
+
+    let err = test_intro(
+        "# Introduction\n\n```rust\nlet _ = 1;\n```\n\n```rust,compile_fail\nlet x: u32 = 2;\n```\n",
+    )
+    .unwrap_err();
+    let message = err.to_string();
+    assert!(message.starts_with("rustdoc returned an error for intro.md:\n"));
+    assert!(message.contains(
+        "code block 2 (line 7, `rust,compile_fail`) failed: \
+         Test compiled successfully, but it's marked `compile_fail`."
+    ));
+}
+
+#[test]
+fn should_panic_blocks_must_panic() {
+    let result = test_intro("# Introduction\n\n```rust,should_panic\npanic!(\"expected\");\n```\n");
+    assert!(
+        result.is_ok(),
+        "Tests failed with {}",
+        result.err().unwrap()
+    );
+
+    let err = test_intro("# Introduction\n\n```rust,should_panic\nlet _ = 1;\n```\n").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("code block 1 (line 3, `rust,should_panic`) failed"));
+}
+
+#[test]
+fn no_run_blocks_are_compiled_but_not_run() {
+    let result = test_intro("# Introduction\n\n```rust,no_run\nloop {}\n```\n");
+    assert!(
+        result.is_ok(),
+        "Tests failed with {}",
+        result.err().unwrap()
+    );
+
+    let err =
+        test_intro("# Introduction\n\n```rust,no_run\nlet x: u32 = \"no\";\n```\n").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("code block 1 (line 3, `rust,no_run`) failed: error[E0308]"));
+}
+
+#[test]
+fn no_run_only_compiles_every_code_block() {
+    let intro = "# Introduction\n\n```rust\npanic!(\"ran\");\n```\n\n\
+                 ```rust,should_panic\nlet _ = 1;\n```\n\n    panic!(\"ran\");\n";
+    assert!(test_intro(intro).is_err());
+    let result = test_intro_no_run(intro);
+    assert!(
+        result.is_ok(),
+        "Tests failed with {}",
+        result.err().unwrap()
+    );
+
+    let err =
+        test_intro_no_run("# Introduction\n\n```rust\nlet x: u32 = \"no\";\n```\n").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("code block 1 (line 3, `rust,no_run`) failed: error[E0308]"));
+}
+
+#[test]
+fn test_results_are_reported_per_code_block() {
+    let temp = DummyBook::new().build().unwrap();
+    fs::write(
+        temp.path().join("src/intro.md"),
+        "# Introduction\n\n```rust\nassert!(true);\n```\n\n```rust,no_run\nloop {}\n```\n\n\
+         ```rust,ignore\nnope\n```\n\n```rust\nassert!(false);\n```\n\n```text\nnot tested\n```\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+
+    let report = md.test_all(vec![], false).unwrap();
+    assert!(!report.passed());
+    assert!(report.check().is_err());
+
+    let intro = report
+        .chapters
+        .iter()
+        .find(|chapter| chapter.path == Path::new("intro.md"))
+        .unwrap();
+    assert_eq!(intro.name, "Introduction");
+    assert!(!intro.passed());
+    let outcomes: Vec<_> = intro
+        .blocks
+        .iter()
+        .map(|block| (block.index, block.attributes.as_str(), &block.outcome))
+        .collect();
+    assert_eq!(outcomes.len(), 4);
+    assert_eq!(outcomes[0], (Some(1), "rust", &TestOutcome::Ran));
+    assert_eq!(
+        outcomes[1],
+        (Some(2), "rust,no_run", &TestOutcome::Compiled)
+    );
+    assert_eq!(outcomes[2], (Some(3), "rust,ignore", &TestOutcome::Ignored));
+    match *outcomes[3].2 {
+        TestOutcome::Failed { ref output } => assert!(output.contains("assertion failed")),
+        ref other => panic!("Expected a failure, got {:?}", other),
+    }
+
+    let nested = report
+        .chapters
+        .iter()
+        .find(|chapter| chapter.path == Path::new("first/nested.md"))
+        .unwrap();
+    assert!(nested.passed());
+}
+
+#[test]
+#[cfg(not(windows))]
+fn the_configured_rustdoc_is_used() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp = DummyBook::new().build().unwrap();
+    let rustdoc = temp.path().join("fake-rustdoc");
+    fs::write(
+        &rustdoc,
+        "#!/bin/sh\necho \"$@\" >> \"$(dirname \"$0\")/rustdoc-args\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&rustdoc, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(
+        temp.path().join("book.toml"),
+        "[rust]\nrustdoc = \"./fake-rustdoc\"\n",
+    )
+    .unwrap();
+    let mut md = MDBook::load(temp.path()).unwrap();
+
+    let report = md.test_all(vec!["deps"], false).unwrap();
+    assert!(report.passed());
+
+    let args = fs::read_to_string(temp.path().join("rustdoc-args")).unwrap();
+    assert_eq!(args.lines().count(), report.chapters.len());
+    assert!(args.lines().all(|line| line.contains(" --test -L deps")));
+}